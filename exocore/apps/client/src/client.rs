@@ -0,0 +1,710 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use exocore_core::{
+    cell::{Cell, CellNodeRole, CellNodes, Node},
+    futures::interval,
+    time::{Clock, ConsistentTimestamp, Instant},
+    utils::handle_set::{Handle, HandleSet},
+};
+use exocore_protos::{
+    apps::{ClientMessage, ClientRequest, ClientResponse, ClientSubscribe},
+    prost::Message,
+};
+use exocore_transport::{
+    InEvent, InMessage, OutEvent, OutMessage, ServiceType, TransportServiceHandle,
+};
+use futures::{
+    channel::{mpsc, oneshot},
+    prelude::*,
+};
+
+use crate::{message_types, Error};
+
+/// Client exchanging messages with the applications running on the
+/// application host nodes of a cell.
+///
+/// Applications publish messages on topics to which clients subscribe, and
+/// answer requests sent by clients. Messages and requests are only sent to the
+/// application host node known to run the application, which is learned from
+/// the messages and responses it sends. Until it's known, or once a request to
+/// it times out (ex: another node took over the application), they are sent to
+/// all application host nodes and only the node running the application
+/// handles them.
+///
+/// Subscriptions expire on application hosts if they aren't renewed, which the
+/// client does periodically. This also registers them on a node taking over an
+/// application.
+pub struct Client<T>
+where
+    T: TransportServiceHandle,
+{
+    config: ClientConfig,
+    inner: Arc<Mutex<Inner>>,
+    transport_handle: T,
+    handles: HandleSet,
+}
+
+impl<T> Client<T>
+where
+    T: TransportServiceHandle,
+{
+    pub fn new(config: ClientConfig, cell: Cell, clock: Clock, transport_handle: T) -> Client<T> {
+        let inner = Arc::new(Mutex::new(Inner {
+            config,
+            cell,
+            clock,
+            transport_out: None,
+            subscriptions: HashMap::new(),
+            pending_requests: HashMap::new(),
+            app_hosts: HashMap::new(),
+        }));
+
+        Client {
+            config,
+            inner,
+            transport_handle,
+            handles: HandleSet::new(),
+        }
+    }
+
+    pub fn get_handle(&self) -> ClientHandle {
+        ClientHandle {
+            inner: Arc::downgrade(&self.inner),
+            handle: self.handles.get_handle(),
+        }
+    }
+
+    pub async fn run(mut self) -> Result<(), Error> {
+        // create a channel through which we will receive message from our handles to be
+        // sent to transport
+        let out_receiver = {
+            let mut inner = self.inner.lock()?;
+            let (out_sender, out_receiver) = mpsc::unbounded();
+            inner.transport_out = Some(out_sender);
+            out_receiver
+        };
+
+        // send outgoing messages to transport
+        let mut transport_sink = self.transport_handle.get_sink();
+        let transport_sender = async move {
+            let mut receiver = out_receiver;
+
+            while let Some(item) = receiver.next().await {
+                transport_sink.send(item).await?;
+            }
+
+            Ok::<(), Error>(())
+        };
+
+        // handle incoming messages from transport
+        let weak_inner = Arc::downgrade(&self.inner);
+        let mut transport_stream = self.transport_handle.get_stream();
+        let transport_receiver = async move {
+            while let Some(event) = transport_stream.next().await {
+                if let InEvent::Message(msg) = event {
+                    if let Err(err) = Inner::handle_incoming_message(&weak_inner, msg) {
+                        error!("Couldn't process incoming apps message: {}", err);
+                    }
+                }
+            }
+
+            Ok::<(), Error>(())
+        };
+
+        // management timer that checks for timed out requests & renews subscriptions
+        let weak_inner = Arc::downgrade(&self.inner);
+        let management_interval = self.config.management_interval;
+        let management_timer = async move {
+            let mut timer = interval(management_interval);
+
+            loop {
+                timer.tick().await;
+                Inner::management_timer_process(&weak_inner)?;
+            }
+
+            // types the async block
+            #[allow(unreachable_code)]
+            Ok::<(), Error>(())
+        };
+
+        futures::select! {
+            _ = transport_sender.fuse() => {},
+            _ = transport_receiver.fuse() => {},
+            _ = management_timer.fuse() => {},
+            _ = self.transport_handle.fuse() => {},
+            _ = self.handles.on_handles_dropped().fuse() => {},
+        };
+
+        info!("Apps client dropped");
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ClientConfig {
+    /// Duration after which a request that didn't get a response fails.
+    pub request_timeout: Duration,
+
+    /// Interval at which subscriptions are renewed on application hosts. Needs
+    /// to be lower than the subscriptions lease of the hosts.
+    pub subscription_renew_interval: Duration,
+
+    /// Number of messages buffered per subscription. Once the buffer is full,
+    /// the subscription is closed so that the subscriber knows that it missed
+    /// messages and can subscribe again.
+    pub subscription_channel_size: usize,
+
+    /// Interval at which timed out requests are failed and subscriptions are
+    /// renewed.
+    pub management_interval: Duration,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            request_timeout: Duration::from_secs(10),
+            subscription_renew_interval: Duration::from_secs(10),
+            subscription_channel_size: 1000,
+            management_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+struct Inner {
+    config: ClientConfig,
+    cell: Cell,
+    clock: Clock,
+    transport_out: Option<mpsc::UnboundedSender<OutEvent>>,
+    subscriptions: HashMap<String, AppSubscriptions>,
+    pending_requests: HashMap<ConsistentTimestamp, PendingRequest>,
+    app_hosts: HashMap<String, Node>,
+}
+
+impl Inner {
+    fn subscribe(&mut self, app_id: &str, topic: &str) -> Result<Subscription, Error> {
+        let (sender, receiver) = mpsc::channel(self.config.subscription_channel_size);
+
+        let app_subscriptions = self.subscriptions.entry(app_id.to_string()).or_default();
+        let senders = app_subscriptions
+            .topics
+            .entry(topic.to_string())
+            .or_default();
+        let new_topic = senders.is_empty();
+        senders.push(sender);
+
+        if new_topic {
+            self.register_subscriptions(app_id)?;
+        }
+
+        Ok(Subscription { receiver })
+    }
+
+    /// Sends the topics to which the client is subscribed for an application to
+    /// the application hosts.
+    fn register_subscriptions(&mut self, app_id: &str) -> Result<(), Error> {
+        let topics = self
+            .subscriptions
+            .get_mut(app_id)
+            .map(|app_subscriptions| {
+                app_subscriptions.last_register = Some(Instant::now());
+                app_subscriptions.topics.keys().cloned().collect()
+            })
+            .unwrap_or_default();
+
+        let subscribe = ClientSubscribe {
+            app_id: app_id.to_string(),
+            topics,
+        };
+        // subscriptions are sent to all nodes so that a node taking over the application knows
+        // them
+        let app_hosts = self.app_hosts(None)?;
+        self.send_to_app_hosts(
+            app_hosts,
+            message_types::SUBSCRIBE,
+            &subscribe.encode_to_vec(),
+            None,
+        )
+    }
+
+    fn publish(&self, app_id: &str, topic: &str, data: Vec<u8>) -> Result<(), Error> {
+        let message = ClientMessage {
+            app_id: app_id.to_string(),
+            topic: topic.to_string(),
+            data,
+        };
+        let app_hosts = self.app_hosts(Some(app_id))?;
+        self.send_to_app_hosts(
+            app_hosts,
+            message_types::MESSAGE,
+            &message.encode_to_vec(),
+            None,
+        )
+    }
+
+    fn send_request(
+        &mut self,
+        app_id: &str,
+        method: &str,
+        data: Vec<u8>,
+    ) -> Result<oneshot::Receiver<Result<Vec<u8>, Error>>, Error> {
+        let request_id = self.clock.consistent_time(self.cell.local_node());
+        let request = ClientRequest {
+            app_id: app_id.to_string(),
+            method: method.to_string(),
+            data,
+        };
+        let app_hosts = self.app_hosts(Some(app_id))?;
+        self.send_to_app_hosts(
+            app_hosts,
+            message_types::REQUEST,
+            &request.encode_to_vec(),
+            Some(request_id),
+        )?;
+
+        let (sender, receiver) = oneshot::channel();
+        self.pending_requests.insert(
+            request_id,
+            PendingRequest {
+                app_id: app_id.to_string(),
+                sender,
+                send_time: Instant::now(),
+            },
+        );
+
+        Ok(receiver)
+    }
+
+    /// Returns the application host nodes to send to. If an application is
+    /// given and the node running it is known, only this node is returned.
+    fn app_hosts(&self, app_id: Option<&str>) -> Result<Vec<Node>, Error> {
+        let nodes = self.cell.nodes().to_owned();
+        let app_hosts = nodes
+            .iter()
+            .with_role(CellNodeRole::AppHost)
+            .filter(|cell_node| cell_node.node().id() != self.cell.local_node().id())
+            .map(|cell_node| cell_node.node().clone())
+            .collect::<Vec<_>>();
+        if app_hosts.is_empty() {
+            return Err(Error::NoAppHost);
+        }
+
+        // the known node may not be an application host anymore
+        let known_host = app_id
+            .and_then(|app_id| self.app_hosts.get(app_id))
+            .filter(|known| app_hosts.iter().any(|node| node.id() == known.id()));
+        if let Some(known_host) = known_host {
+            return Ok(vec![known_host.clone()]);
+        }
+
+        Ok(app_hosts)
+    }
+
+    fn send_to_app_hosts(
+        &self,
+        app_hosts: Vec<Node>,
+        message_type: u16,
+        data: &[u8],
+        request_id: Option<ConsistentTimestamp>,
+    ) -> Result<(), Error> {
+        let transport = self.transport_out.as_ref().ok_or_else(|| {
+            Error::Other(anyhow!("Tried to send message, but transport_out was none"))
+        })?;
+
+        for node in app_hosts {
+            let mut message =
+                OutMessage::from_data(&self.cell, ServiceType::Client, message_type, data)
+                    .with_destination(node)
+                    .with_expiration(Some(Instant::now() + self.config.request_timeout));
+            if let Some(request_id) = request_id {
+                message = message.with_rdv(request_id);
+            }
+
+            transport
+                .unbounded_send(OutEvent::Message(message))
+                .map_err(|_err| {
+                    Error::Other(anyhow!(
+                        "Tried to send message, but transport_out channel is closed"
+                    ))
+                })?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_incoming_message(
+        weak_inner: &Weak<Mutex<Inner>>,
+        msg: InMessage,
+    ) -> Result<(), Error> {
+        let inner = weak_inner.upgrade().ok_or(Error::Dropped)?;
+        let mut inner = inner.lock()?;
+
+        match msg.typ {
+            message_types::MESSAGE => {
+                let message = ClientMessage::decode(msg.get_data()?)?;
+                inner
+                    .app_hosts
+                    .insert(message.app_id.clone(), msg.source.clone());
+                inner.dispatch_message(message);
+            }
+            message_types::RESPONSE => {
+                let request_id = msg
+                    .rendez_vous_id
+                    .ok_or_else(|| anyhow!("Got a response without rendez-vous id"))?;
+
+                // when the request was sent to all application hosts, only the one running the
+                // application responds, unless multiple nodes run it while they can't reach each
+                // other, in which case the first one to respond wins
+                let Some(pending_request) = inner.pending_requests.remove(&request_id) else {
+                    return Ok(());
+                };
+                inner
+                    .app_hosts
+                    .insert(pending_request.app_id.clone(), msg.source.clone());
+
+                let response = ClientResponse::decode(msg.get_data()?)?;
+                let result = if response.error.is_empty() {
+                    Ok(response.data)
+                } else {
+                    Err(Error::Remote(response.error))
+                };
+                let _ = pending_request.sender.send(result);
+            }
+            other => {
+                return Err(anyhow!("Got an unknown message type {}", other).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn dispatch_message(&mut self, message: ClientMessage) {
+        let Some(senders) = self
+            .subscriptions
+            .get_mut(&message.app_id)
+            .and_then(|app_subscriptions| app_subscriptions.topics.get_mut(&message.topic))
+        else {
+            return;
+        };
+
+        senders.retain_mut(|sender| match sender.try_send(message.data.clone()) {
+            Ok(()) => true,
+            Err(err) if err.is_full() => {
+                error!(
+                    "Subscription to topic '{}' of app {} is full. Closing it since it would miss messages",
+                    message.topic, message.app_id
+                );
+                false
+            }
+            Err(_) => false,
+        });
+    }
+
+    fn management_timer_process(weak_inner: &Weak<Mutex<Inner>>) -> Result<(), Error> {
+        let inner = weak_inner.upgrade().ok_or(Error::Dropped)?;
+        let mut inner = inner.lock()?;
+
+        let request_timeout = inner.config.request_timeout;
+        let timed_out_requests = inner
+            .pending_requests
+            .iter()
+            .filter(|(_id, request)| request.send_time.elapsed() > request_timeout)
+            .map(|(id, _request)| *id)
+            .collect::<Vec<_>>();
+        for request_id in timed_out_requests {
+            if let Some(request) = inner.pending_requests.remove(&request_id) {
+                // the node may not be running the application anymore, next requests are sent to
+                // all application hosts to find the one running it
+                inner.app_hosts.remove(&request.app_id);
+                let _ = request.sender.send(Err(Error::Timeout(request_timeout)));
+            }
+        }
+
+        let renew_interval = inner.config.subscription_renew_interval;
+        let app_ids = inner.subscriptions.keys().cloned().collect::<Vec<_>>();
+        for app_id in app_ids {
+            let Some(app_subscriptions) = inner.subscriptions.get_mut(&app_id) else {
+                continue;
+            };
+
+            let topics_count = app_subscriptions.topics.len();
+            app_subscriptions.topics.retain(|_topic, senders| {
+                senders.retain(|sender| !sender.is_closed());
+                !senders.is_empty()
+            });
+            let topics_dropped = app_subscriptions.topics.len() != topics_count;
+
+            let renew_due = app_subscriptions
+                .last_register
+                .is_none_or(|last| last.elapsed() > renew_interval);
+            let no_topics = app_subscriptions.topics.is_empty();
+
+            if topics_dropped || renew_due {
+                if let Err(err) = inner.register_subscriptions(&app_id) {
+                    error!("Couldn't register subscriptions of app {}: {}", app_id, err);
+                }
+            }
+
+            if no_topics {
+                inner.subscriptions.remove(&app_id);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct AppSubscriptions {
+    topics: HashMap<String, Vec<mpsc::Sender<Vec<u8>>>>,
+    last_register: Option<Instant>,
+}
+
+struct PendingRequest {
+    app_id: String,
+    sender: oneshot::Sender<Result<Vec<u8>, Error>>,
+    send_time: Instant,
+}
+
+/// Async handle to the applications client.
+#[derive(Clone)]
+pub struct ClientHandle {
+    inner: Weak<Mutex<Inner>>,
+    handle: Handle,
+}
+
+impl ClientHandle {
+    pub async fn on_start(&self) {
+        self.handle.on_set_started().await;
+    }
+
+    /// Subscribes to messages published by an application on a topic. The
+    /// subscription is removed once the returned stream is dropped.
+    pub fn subscribe(&self, app_id: &str, topic: &str) -> Result<Subscription, Error> {
+        let inner = self.inner.upgrade().ok_or(Error::Dropped)?;
+        let mut inner = inner.lock()?;
+        inner.subscribe(app_id, topic)
+    }
+
+    /// Publishes a message on a topic to an application, without waiting for
+    /// it to be handled.
+    pub fn publish(&self, app_id: &str, topic: &str, data: Vec<u8>) -> Result<(), Error> {
+        let inner = self.inner.upgrade().ok_or(Error::Dropped)?;
+        let inner = inner.lock()?;
+        inner.publish(app_id, topic, data)
+    }
+
+    /// Sends a request to a method of an application and returns its response.
+    pub async fn request(
+        &self,
+        app_id: &str,
+        method: &str,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, Error> {
+        let receiver = {
+            let inner = self.inner.upgrade().ok_or(Error::Dropped)?;
+            let mut inner = inner.lock()?;
+            inner.send_request(app_id, method, data)?
+        };
+
+        receiver.await.map_err(|_| Error::Dropped)?
+    }
+}
+
+/// Stream of the messages published by an application on a subscribed topic.
+pub struct Subscription {
+    receiver: mpsc::Receiver<Vec<u8>>,
+}
+
+impl Stream for Subscription {
+    type Item = Vec<u8>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exocore_core::{
+        cell::{CellNode, FullCell, LocalNode},
+        futures::owned_spawn,
+    };
+    use exocore_transport::testing::MockTransport;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn request_and_subscribe() -> anyhow::Result<()> {
+        let hub = MockTransport::default();
+        let clock = Clock::new();
+
+        let client_node = LocalNode::generate();
+        let host_node = LocalNode::generate();
+        let client_cell = FullCell::generate(client_node.clone())?;
+        {
+            let mut nodes = client_cell.cell().nodes_mut();
+            let mut cell_node = CellNode::new(host_node.node().clone());
+            cell_node.add_role(CellNodeRole::AppHost);
+            nodes.add_cell_node(cell_node);
+        }
+        let host_cell = client_cell.clone().with_local_node(host_node.clone());
+
+        let client_transport = hub.get_transport(client_node.clone(), ServiceType::Client);
+        let client = Client::new(
+            ClientConfig::default(),
+            client_cell.cell().clone(),
+            clock,
+            client_transport,
+        );
+        let handle = client.get_handle();
+        let _client_spawn = owned_spawn(client.run());
+        handle.on_start().await;
+
+        let mut host_transport = hub.get_transport(host_node.clone(), ServiceType::Client);
+        let mut host_stream = host_transport.get_stream();
+        let mut host_sink = host_transport.get_sink();
+        let _host_transport_spawn = owned_spawn(host_transport);
+
+        // subscribing registers the topic on the application host
+        let mut subscription = handle.subscribe("app", "notifications")?;
+        let msg = next_message(&mut host_stream).await;
+        assert_eq!(msg.typ, message_types::SUBSCRIBE);
+        let subscribe = ClientSubscribe::decode(msg.get_data()?)?;
+        assert_eq!(subscribe.app_id, "app");
+        assert_eq!(subscribe.topics, vec!["notifications"]);
+
+        let message = ClientMessage {
+            app_id: "app".to_string(),
+            topic: "notifications".to_string(),
+            data: b"hello".to_vec(),
+        };
+        let out = OutMessage::from_data(
+            host_cell.cell(),
+            ServiceType::Client,
+            message_types::MESSAGE,
+            &message.encode_to_vec(),
+        )
+        .with_destination(client_node.node().clone());
+        host_sink.send(OutEvent::Message(out)).await?;
+        assert_eq!(subscription.next().await.unwrap(), b"hello");
+
+        // requests are answered by the application host with the same rendez-vous id
+        let request_handle = handle.clone();
+        let response = owned_spawn(async move {
+            request_handle
+                .request("app", "ping", b"ping".to_vec())
+                .await
+        });
+        let msg = next_message(&mut host_stream).await;
+        assert_eq!(msg.typ, message_types::REQUEST);
+        let request = ClientRequest::decode(msg.get_data()?)?;
+        assert_eq!(request.method, "ping");
+
+        let response_data = ClientResponse {
+            data: b"pong".to_vec(),
+            ..Default::default()
+        };
+        let out = OutMessage::from_data(
+            host_cell.cell(),
+            ServiceType::Client,
+            message_types::RESPONSE,
+            &response_data.encode_to_vec(),
+        )
+        .with_destination(client_node.node().clone())
+        .with_rdv(msg.rendez_vous_id.unwrap());
+        host_sink.send(OutEvent::Message(out)).await?;
+        assert_eq!(response.await??, b"pong");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn requests_routed_to_known_app_host() -> anyhow::Result<()> {
+        let hub = MockTransport::default();
+
+        let client_node = LocalNode::generate();
+        let host_nodes = [LocalNode::generate(), LocalNode::generate()];
+        let client_cell = FullCell::generate(client_node.clone())?;
+        {
+            let mut nodes = client_cell.cell().nodes_mut();
+            for host_node in &host_nodes {
+                let mut cell_node = CellNode::new(host_node.node().clone());
+                cell_node.add_role(CellNodeRole::AppHost);
+                nodes.add_cell_node(cell_node);
+            }
+        }
+        let host_cell = client_cell.clone().with_local_node(host_nodes[0].clone());
+
+        let config = ClientConfig {
+            request_timeout: Duration::from_millis(200),
+            management_interval: Duration::from_millis(10),
+            ..Default::default()
+        };
+        let client_transport = hub.get_transport(client_node.clone(), ServiceType::Client);
+        let client = Client::new(
+            config,
+            client_cell.cell().clone(),
+            Clock::new(),
+            client_transport,
+        );
+        let inner = client.inner.clone();
+        let handle = client.get_handle();
+        let _client_spawn = owned_spawn(client.run());
+        handle.on_start().await;
+
+        let mut host_transport = hub.get_transport(host_nodes[0].clone(), ServiceType::Client);
+        let mut host_stream = host_transport.get_stream();
+        let mut host_sink = host_transport.get_sink();
+        let _host_transport_spawn = owned_spawn(host_transport);
+
+        // node running the app isn't known, so request is sent to all app hosts
+        let app_hosts = |inner: &Arc<Mutex<Inner>>| {
+            let inner = inner.lock().unwrap();
+            inner.app_hosts(Some("app")).unwrap().len()
+        };
+        assert_eq!(app_hosts(&inner), 2);
+
+        let request_handle = handle.clone();
+        let response = owned_spawn(async move {
+            request_handle
+                .request("app", "ping", b"ping".to_vec())
+                .await
+        });
+        let msg = next_message(&mut host_stream).await;
+        let out = OutMessage::from_data(
+            host_cell.cell(),
+            ServiceType::Client,
+            message_types::RESPONSE,
+            &ClientResponse::default().encode_to_vec(),
+        )
+        .with_destination(client_node.node().clone())
+        .with_rdv(msg.rendez_vous_id.unwrap());
+        host_sink.send(OutEvent::Message(out)).await?;
+        response.await??;
+
+        // once known, requests are only sent to it
+        assert_eq!(app_hosts(&inner), 1);
+
+        // until a request to it times out, in case it doesn't run the app anymore
+        let res = handle.request("app", "ping", b"ping".to_vec()).await;
+        assert!(matches!(res, Err(Error::Timeout(_))));
+        assert_eq!(app_hosts(&inner), 2);
+
+        Ok(())
+    }
+
+    async fn next_message<S: Stream<Item = InEvent> + Unpin>(stream: &mut S) -> InMessage {
+        loop {
+            if let InEvent::Message(msg) = stream.next().await.unwrap() {
+                return msg;
+            }
+        }
+    }
+}
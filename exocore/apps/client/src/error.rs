@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Transport error: {0}")]
+    Transport(#[from] exocore_transport::Error),
+
+    #[error("Protobuf error: {0}")]
+    Proto(#[from] exocore_protos::Error),
+
+    #[error("Error from application: {0}")]
+    Remote(String),
+
+    #[error("Request timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("Cell doesn't have any application host node")]
+    NoAppHost,
+
+    #[error("Try to lock a mutex that was poisoned")]
+    Poisoned,
+
+    #[error("Dropped or couldn't get locked")]
+    Dropped,
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<exocore_protos::prost::DecodeError> for Error {
+    fn from(err: exocore_protos::prost::DecodeError) -> Self {
+        Error::Proto(err.into())
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for Error {
+    fn from(_err: std::sync::PoisonError<T>) -> Self {
+        Error::Poisoned
+    }
+}
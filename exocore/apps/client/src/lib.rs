@@ -0,0 +1,27 @@
+#[macro_use]
+extern crate log;
+
+mod client;
+mod error;
+
+pub use client::{Client, ClientConfig, ClientHandle, Subscription};
+pub use error::Error;
+
+/// Types of the messages exchanged over the transport between clients and
+/// application host nodes. Messages are protobuf encoded instead of being
+/// capnp frames (see `exocore/apps/messaging.proto`).
+pub mod message_types {
+    /// `ClientSubscribe` sent by a client to application hosts.
+    pub const SUBSCRIBE: u16 = 610;
+
+    /// `ClientMessage` published by a client to an application, or by an
+    /// application to its subscribed clients.
+    pub const MESSAGE: u16 = 611;
+
+    /// `ClientRequest` sent by a client to an application.
+    pub const REQUEST: u16 = 612;
+
+    /// `ClientResponse` sent back by an application host to the client that
+    /// sent a request, with the same rendez-vous id.
+    pub const RESPONSE: u16 = 613;
+}
@@ -6,6 +6,12 @@ use exocore_core::utils::backoff::BackoffConfig;
 #[derive(Clone, Copy)]
 pub struct Config {
     pub restart_backoff: BackoffConfig,
+
+    /// Validates the signature of applications' manifests before starting
+    /// them. Modules are always validated against the multihash of their
+    /// manifest. Only disabled for local development, where manifests are
+    /// modified without being signed again.
+    pub validate_signatures: bool,
 }
 
 impl Default for Config {
@@ -18,6 +24,7 @@ impl Default for Config {
                 failure_exp_multiplier: Duration::from_secs(5),
                 failure_maximum: Duration::from_secs(30),
             },
+            validate_signatures: true,
         }
     }
 }
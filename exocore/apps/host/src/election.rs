@@ -0,0 +1,497 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use exocore_core::{
+    cell::{Cell, CellNodeRole, CellNodes, NodeId},
+    futures::interval,
+    sec::hash::{Hasher, Sha3_256},
+    time::{Clock, Instant},
+};
+use exocore_protos::{apps::AppHostHeartbeat, prost::Message};
+use exocore_transport::{
+    InEvent, InMessage, OutEvent, OutMessage, ServiceType, TransportServiceHandle,
+};
+use futures::{channel::mpsc, FutureExt, SinkExt, StreamExt};
+
+use crate::Error;
+
+/// Type of the heartbeat messages sent over the transport. Heartbeats are
+/// protobuf encoded instead of being capnp frames.
+const HEARTBEAT_MESSAGE_TYPE: u16 = 600;
+
+/// Configuration of the election of application host nodes.
+#[derive(Clone, Copy)]
+pub struct ElectionConfig {
+    /// Interval at which heartbeats are sent to the other application host
+    /// nodes.
+    pub heartbeat_interval: Duration,
+
+    /// Duration after which a node that didn't send any heartbeat is
+    /// considered down. A node that just started also waits for this duration
+    /// before running applications, to hear from the other nodes first.
+    pub lease_duration: Duration,
+}
+
+impl Default for ElectionConfig {
+    fn default() -> Self {
+        ElectionConfig {
+            heartbeat_interval: Duration::from_secs(2),
+            lease_duration: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Elects the node running each application among the nodes of a cell having
+/// the application host role.
+///
+/// Application host nodes periodically send heartbeats to each other with the
+/// applications they are running. A node that didn't send a heartbeat for the
+/// lease duration is considered down. Among the nodes that are up, each
+/// application is assigned to a node using rendezvous hashing of the
+/// application and node ids, which spreads applications over nodes and only
+/// moves the applications of a node that goes down or comes back up.
+///
+/// A node only starts an application assigned to it once no other node
+/// reports running it, which gives the previous node time to stop it. There is
+/// no consensus involved: nodes that can't reach each other will both run
+/// the same applications until they can.
+///
+/// The state kept by the host for applications (last runs of scheduled jobs,
+/// changes handled by triggers) is stored in the cell's store (see
+/// `AppStateStore`), and is therefore carried over to the node taking over
+/// once replicated to it. Applications' key-value storage is kept locally on
+/// the node running them and isn't carried over. Since there is no fencing, a node that
+/// keeps running an application while cut off from the others may overwrite
+/// the state written by the node that took over, and state that wasn't
+/// replicated yet when a node goes down is lost, in which case jobs may run
+/// again and changes may be delivered again to triggers.
+pub struct AppsElection<T: TransportServiceHandle> {
+    config: ElectionConfig,
+    cell: Cell,
+    transport: T,
+    state: Arc<Mutex<ElectionState>>,
+}
+
+impl<T: TransportServiceHandle> AppsElection<T> {
+    pub fn new(config: ElectionConfig, clock: Clock, cell: Cell, transport: T) -> AppsElection<T> {
+        let state = ElectionState::new(config, clock, cell.local_node().id().clone());
+
+        AppsElection {
+            config,
+            cell,
+            transport,
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Handle used by the applications runtime to know which applications to
+    /// run on this node.
+    pub fn handle(&self) -> ElectionHandle {
+        ElectionHandle {
+            state: Some(self.state.clone()),
+        }
+    }
+
+    pub async fn run(self) -> Result<(), Error> {
+        let mut transport = self.transport;
+
+        let (out_sender, mut out_receiver) = mpsc::unbounded();
+        let mut transport_sink = transport.get_sink();
+        let transport_sender = async move {
+            while let Some(event) = out_receiver.next().await {
+                transport_sink.send(event).await?;
+            }
+            Ok::<(), Error>(())
+        };
+
+        let mut transport_stream = transport.get_stream();
+        let receiver_state = self.state.clone();
+        let receiver_cell = self.cell.clone();
+        let transport_receiver = async move {
+            while let Some(event) = transport_stream.next().await {
+                if let InEvent::Message(msg) = event {
+                    if let Err(err) = handle_heartbeat(&receiver_cell, &receiver_state, msg) {
+                        error!(
+                            "{}: Couldn't handle app host heartbeat: {}",
+                            receiver_cell, err
+                        );
+                    }
+                }
+            }
+            Ok::<(), Error>(())
+        };
+
+        let heartbeat_interval = self.config.heartbeat_interval;
+        let heartbeat_state = self.state.clone();
+        let heartbeat_cell = self.cell.clone();
+        let heartbeat_sender = async move {
+            let mut interval = interval(heartbeat_interval);
+            loop {
+                interval.tick().await;
+
+                let heartbeat = {
+                    let state = heartbeat_state.lock().unwrap();
+                    state.heartbeat()
+                };
+                let data = heartbeat.encode_to_vec();
+
+                let nodes = heartbeat_cell.nodes().to_owned();
+                let local_node_id = heartbeat_cell.local_node().id();
+                for cell_node in nodes.iter().all() {
+                    let node = cell_node.node();
+                    if !cell_node.has_role(CellNodeRole::AppHost) || node.id() == local_node_id {
+                        continue;
+                    }
+
+                    let msg = OutMessage::from_data(
+                        &heartbeat_cell,
+                        ServiceType::AppHost,
+                        HEARTBEAT_MESSAGE_TYPE,
+                        &data,
+                    )
+                    .with_destination(node.clone())
+                    .with_expiration(Some(Instant::now() + heartbeat_interval));
+                    if out_sender.unbounded_send(OutEvent::Message(msg)).is_err() {
+                        return Ok::<(), Error>(());
+                    }
+                }
+            }
+        };
+
+        info!("{}: Application hosts election started", self.cell);
+
+        futures::select! {
+            res = transport_sender.fuse() => res?,
+            res = transport_receiver.fuse() => res?,
+            res = heartbeat_sender.fuse() => res?,
+            _ = transport.fuse() => {},
+        };
+
+        Ok(())
+    }
+}
+
+fn handle_heartbeat(
+    cell: &Cell,
+    state: &Mutex<ElectionState>,
+    msg: InMessage,
+) -> Result<(), Error> {
+    if msg.typ != HEARTBEAT_MESSAGE_TYPE {
+        return Err(anyhow!("unknown message type {}", msg.typ).into());
+    }
+
+    let is_app_host = cell
+        .nodes()
+        .get(msg.source.id())
+        .is_some_and(|node| node.has_role(CellNodeRole::AppHost));
+    if !is_app_host {
+        return Err(anyhow!(
+            "got a heartbeat from node {} that isn't an app host",
+            msg.source.id()
+        )
+        .into());
+    }
+
+    let heartbeat = AppHostHeartbeat::decode(msg.get_data()?)?;
+
+    let mut state = state.lock().unwrap();
+    state.handle_heartbeat(msg.source.id().clone(), heartbeat);
+
+    Ok(())
+}
+
+/// Handle to the election, telling if an application should run on this node.
+#[derive(Clone)]
+pub struct ElectionHandle {
+    state: Option<Arc<Mutex<ElectionState>>>,
+}
+
+impl ElectionHandle {
+    /// Handle for a node that is the only application host of its cell, and
+    /// therefore runs all applications.
+    pub fn single() -> ElectionHandle {
+        ElectionHandle { state: None }
+    }
+
+    /// Returns if the application with the given id should be running on this
+    /// node.
+    pub fn is_leader(&self, app_id: &str) -> bool {
+        let Some(state) = &self.state else {
+            return true;
+        };
+
+        let state = state.lock().unwrap();
+        state.is_leader(app_id)
+    }
+
+    /// Marks the application as running or not on this node, which is
+    /// reported to other nodes in heartbeats.
+    pub fn set_running(&self, app_id: &str, running: bool) {
+        let Some(state) = &self.state else {
+            return;
+        };
+
+        let mut state = state.lock().unwrap();
+        if running {
+            state.running.insert(app_id.to_string());
+        } else {
+            state.running.remove(app_id);
+        }
+    }
+}
+
+struct ElectionState {
+    config: ElectionConfig,
+    clock: Clock,
+    local_node: NodeId,
+    started: Instant,
+    nodes: HashMap<NodeId, RemoteNode>,
+    running: HashSet<String>,
+}
+
+struct RemoteNode {
+    last_heartbeat: Instant,
+    running: HashSet<String>,
+}
+
+impl ElectionState {
+    fn new(config: ElectionConfig, clock: Clock, local_node: NodeId) -> ElectionState {
+        ElectionState {
+            config,
+            started: clock.instant(),
+            clock,
+            local_node,
+            nodes: HashMap::new(),
+            running: HashSet::new(),
+        }
+    }
+
+    fn heartbeat(&self) -> AppHostHeartbeat {
+        let mut running_apps: Vec<String> = self.running.iter().cloned().collect();
+        running_apps.sort();
+
+        AppHostHeartbeat { running_apps }
+    }
+
+    fn handle_heartbeat(&mut self, node: NodeId, heartbeat: AppHostHeartbeat) {
+        self.nodes.insert(
+            node,
+            RemoteNode {
+                last_heartbeat: self.clock.instant(),
+                running: heartbeat.running_apps.into_iter().collect(),
+            },
+        );
+    }
+
+    fn is_leader(&self, app_id: &str) -> bool {
+        let now = self.clock.instant();
+        let local_score = node_score(app_id, &self.local_node);
+
+        let mut running_elsewhere = false;
+        for (node_id, node) in &self.nodes {
+            if now.duration_since(node.last_heartbeat) >= self.config.lease_duration {
+                continue;
+            }
+
+            if node_score(app_id, node_id) > local_score {
+                return false;
+            }
+
+            running_elsewhere |= node.running.contains(app_id);
+        }
+
+        if self.running.contains(app_id) {
+            return true;
+        }
+
+        let lease_waited = now.duration_since(self.started) >= self.config.lease_duration;
+        lease_waited && !running_elsewhere
+    }
+}
+
+/// Rendezvous hashing score of a node for an application. The node with the
+/// highest score runs the application. The hash needs to be stable across
+/// nodes, hence the use of SHA3.
+fn node_score(app_id: &str, node_id: &NodeId) -> (u64, String) {
+    let node_id = node_id.to_string();
+
+    let mut hasher = Sha3_256::default();
+    hasher.update(app_id.as_bytes());
+    hasher.update(node_id.as_bytes());
+    let digest = hasher.finalize();
+
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    (u64::from_be_bytes(bytes), node_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use exocore_core::{
+        cell::{CellNode, FullCell, LocalNode},
+        futures::{owned_spawn, sleep},
+    };
+    use exocore_transport::testing::MockTransport;
+
+    use super::*;
+
+    #[test]
+    fn single_node() {
+        let clock = Clock::new_fixed_mocked(Instant::now());
+        let node = LocalNode::generate();
+        let state = ElectionState::new(config(), clock.clone(), node.id().clone());
+
+        // waits for the lease before running applications
+        assert!(!state.is_leader("app"));
+
+        clock.add_fixed_instant_duration(Duration::from_secs(10));
+        assert!(state.is_leader("app"));
+
+        assert!(ElectionHandle::single().is_leader("app"));
+    }
+
+    #[test]
+    fn apps_assigned_to_a_single_node() {
+        let clock = Clock::new_fixed_mocked(Instant::now());
+        let mut states = create_states(&clock, 3);
+        clock.add_fixed_instant_duration(Duration::from_secs(10));
+        exchange_heartbeats(&mut states);
+
+        for i in 0..20 {
+            let app = format!("app{}", i);
+            let leaders = states.iter().filter(|s| s.is_leader(&app)).count();
+            assert_eq!(leaders, 1);
+        }
+    }
+
+    #[test]
+    fn take_over_when_node_down() {
+        let clock = Clock::new_fixed_mocked(Instant::now());
+        let mut states = create_states(&clock, 2);
+        clock.add_fixed_instant_duration(Duration::from_secs(10));
+        exchange_heartbeats(&mut states);
+
+        let app = "app";
+        let (leader, follower) = if states[0].is_leader(app) {
+            (0, 1)
+        } else {
+            (1, 0)
+        };
+        assert!(!states[follower].is_leader(app));
+        states[leader].running.insert(app.to_string());
+        exchange_heartbeats(&mut states);
+
+        // leader stops sending heartbeats, follower takes over once its lease expired
+        clock.add_fixed_instant_duration(Duration::from_secs(5));
+        assert!(!states[follower].is_leader(app));
+
+        clock.add_fixed_instant_duration(Duration::from_secs(5));
+        assert!(states[follower].is_leader(app));
+        states[follower].running.insert(app.to_string());
+
+        // leader comes back, but waits for the follower to stop the app
+        let heartbeat = states[follower].heartbeat();
+        let follower_id = states[follower].local_node.clone();
+        states[leader].running.clear();
+        states[leader].handle_heartbeat(follower_id, heartbeat);
+        let heartbeat = states[leader].heartbeat();
+        let leader_id = states[leader].local_node.clone();
+        states[follower].handle_heartbeat(leader_id, heartbeat);
+
+        assert!(!states[follower].is_leader(app));
+        assert!(!states[leader].is_leader(app));
+
+        states[follower].running.clear();
+        exchange_heartbeats(&mut states);
+        assert!(states[leader].is_leader(app));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn elect_over_transport() -> anyhow::Result<()> {
+        let hub = MockTransport::default();
+        let clock = Clock::new();
+
+        let node0 = LocalNode::generate();
+        let node1 = LocalNode::generate();
+        let cell0 = FullCell::generate(node0.clone())?;
+        let cell1 = cell0.clone().with_local_node(node1.clone());
+        for cell in [&cell0, &cell1] {
+            let mut nodes = cell.cell().nodes_mut();
+            for node in [&node0, &node1] {
+                if node.id() == cell.cell().local_node().id() {
+                    nodes.local_cell_node_mut().add_role(CellNodeRole::AppHost);
+                } else {
+                    let mut cell_node = CellNode::new(node.node().clone());
+                    cell_node.add_role(CellNodeRole::AppHost);
+                    nodes.add_cell_node(cell_node);
+                }
+            }
+        }
+
+        let config = ElectionConfig {
+            heartbeat_interval: Duration::from_millis(10),
+            lease_duration: Duration::from_millis(200),
+        };
+
+        let mut handles = Vec::new();
+        let mut spawns = Vec::new();
+        for (node, cell) in [(&node0, &cell0), (&node1, &cell1)] {
+            let transport = hub.get_transport(node.clone(), ServiceType::AppHost);
+            let election = AppsElection::new(config, clock.clone(), cell.cell().clone(), transport);
+            handles.push(election.handle());
+            spawns.push(owned_spawn(election.run()));
+        }
+
+        sleep(Duration::from_millis(500)).await;
+        for i in 0..10 {
+            let app = format!("app{}", i);
+            let leaders = handles.iter().filter(|h| h.is_leader(&app)).count();
+            assert_eq!(leaders, 1);
+        }
+
+        // once the other node stops, this node takes over all applications
+        let leader = handles.iter().position(|h| h.is_leader("app0")).unwrap();
+        drop(spawns.remove(leader));
+        let other = handles.remove(1 - leader);
+
+        sleep(Duration::from_millis(500)).await;
+        assert!(other.is_leader("app0"));
+
+        Ok(())
+    }
+
+    fn config() -> ElectionConfig {
+        ElectionConfig {
+            heartbeat_interval: Duration::from_secs(1),
+            lease_duration: Duration::from_secs(10),
+        }
+    }
+
+    fn create_states(clock: &Clock, count: usize) -> Vec<ElectionState> {
+        (0..count)
+            .map(|_| {
+                let node = LocalNode::generate();
+                ElectionState::new(config(), clock.clone(), node.id().clone())
+            })
+            .collect()
+    }
+
+    fn exchange_heartbeats(states: &mut [ElectionState]) {
+        let heartbeats: Vec<(NodeId, AppHostHeartbeat)> = states
+            .iter()
+            .map(|state| (state.local_node.clone(), state.heartbeat()))
+            .collect();
+
+        for state in states.iter_mut() {
+            for (node_id, heartbeat) in &heartbeats {
+                if *node_id != state.local_node {
+                    state.handle_heartbeat(node_id.clone(), heartbeat.clone());
+                }
+            }
+        }
+    }
+}
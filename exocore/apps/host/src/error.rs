@@ -28,6 +28,9 @@ pub enum Error {
     #[error("Entity store error: {0}")]
     Store(#[from] exocore_store::error::Error),
 
+    #[error("Transport error: {0}")]
+    Transport(#[from] exocore_transport::Error),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -0,0 +1,285 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::anyhow;
+use exocore_protos::apps::{HttpHeader, HttpRequest, HttpResponse};
+use reqwest::{redirect, Method, Url};
+
+use crate::Error;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_REDIRECTS: usize = 10;
+
+/// Maximum size of a response's body, after which the request fails.
+const MAX_RESPONSE_SIZE: usize = 10 * 1024 * 1024;
+
+/// Executes HTTP requests on behalf of an application, restricted to the
+/// domains declared in its manifest.
+///
+/// Redirects are followed as long as they stay within the allowed domains.
+#[derive(Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    allowed_domains: Arc<AllowedDomains>,
+}
+
+impl HttpClient {
+    pub fn new(allowed_domains: &[String]) -> Result<HttpClient, Error> {
+        let allowed_domains = Arc::new(AllowedDomains::new(allowed_domains));
+
+        let redirect_domains = allowed_domains.clone();
+        let redirect_policy = redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if !redirect_domains.is_allowed(attempt.url()) {
+                let err = format!("redirect to a domain not allowed: {}", attempt.url());
+                attempt.error(err)
+            } else {
+                attempt.follow()
+            }
+        });
+
+        let client = reqwest::Client::builder()
+            .redirect(redirect_policy)
+            .build()
+            .map_err(|err| anyhow!("Couldn't create HTTP client: {}", err))?;
+
+        Ok(HttpClient {
+            client,
+            allowed_domains,
+        })
+    }
+
+    pub async fn execute(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+        let url = Url::parse(&request.url)
+            .map_err(|err| anyhow!("Invalid URL '{}': {}", request.url, err))?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(anyhow!("Unsupported URL scheme '{}'", url.scheme()).into());
+        }
+        if !self.allowed_domains.is_allowed(&url) {
+            return Err(anyhow!(
+                "Domain of URL '{}' is not allowed by the application's manifest",
+                url
+            )
+            .into());
+        }
+
+        let method = if request.method.is_empty() {
+            Method::GET
+        } else {
+            Method::from_bytes(request.method.to_uppercase().as_bytes())
+                .map_err(|err| anyhow!("Invalid method '{}': {}", request.method, err))?
+        };
+
+        let timeout = match request.timeout_secs {
+            0 => DEFAULT_TIMEOUT,
+            secs => Duration::from_secs(secs as u64).min(MAX_TIMEOUT),
+        };
+
+        let mut builder = self.client.request(method, url).timeout(timeout);
+        for header in request.headers {
+            builder = builder.header(header.name, header.value);
+        }
+        if !request.body.is_empty() {
+            builder = builder.body(request.body);
+        }
+
+        let mut response = builder
+            .send()
+            .await
+            .map_err(|err| anyhow!("HTTP request failed: {}", err))?;
+
+        let status = response.status().as_u16() as u32;
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                Some(HttpHeader {
+                    name: name.to_string(),
+                    value: value.to_str().ok()?.to_string(),
+                })
+            })
+            .collect();
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|err| anyhow!("Couldn't read HTTP response: {}", err))?
+        {
+            if body.len() + chunk.len() > MAX_RESPONSE_SIZE {
+                return Err(
+                    anyhow!("HTTP response is bigger than {} bytes", MAX_RESPONSE_SIZE).into(),
+                );
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+struct AllowedDomains {
+    domains: Vec<String>,
+}
+
+impl AllowedDomains {
+    fn new(domains: &[String]) -> AllowedDomains {
+        AllowedDomains {
+            domains: domains
+                .iter()
+                .map(|domain| domain.trim_matches('.').to_lowercase())
+                .filter(|domain| !domain.is_empty())
+                .collect(),
+        }
+    }
+
+    fn is_allowed(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_lowercase();
+
+        self.domains.iter().any(|domain| {
+            host == *domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::Infallible, net::SocketAddr};
+
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Response,
+    };
+
+    use super::*;
+
+    #[test]
+    fn allowed_domains() {
+        let domains = AllowedDomains::new(&["example.com".to_string(), "127.0.0.1".to_string()]);
+
+        let allowed = |url: &str| domains.is_allowed(&Url::parse(url).unwrap());
+        assert!(allowed("https://example.com/path"));
+        assert!(allowed("https://www.EXAMPLE.com"));
+        assert!(allowed("http://127.0.0.1:8080/"));
+        assert!(!allowed("https://notexample.com"));
+        assert!(!allowed("https://example.com.evil.com"));
+        assert!(!allowed("https://other.com"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn execute_request() -> anyhow::Result<()> {
+        let addr = start_stub_server().await;
+        let client = HttpClient::new(&["127.0.0.1".to_string()])?;
+
+        let response = client
+            .execute(HttpRequest {
+                method: "post".to_string(),
+                url: format!("http://{}/echo", addr),
+                headers: vec![HttpHeader {
+                    name: "x-test".to_string(),
+                    value: "value".to_string(),
+                }],
+                body: b"hello".to_vec(),
+                ..Default::default()
+            })
+            .await?;
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"POST /echo value hello");
+        assert!(response
+            .headers
+            .iter()
+            .any(|header| header.name == "x-stub" && header.value == "true"));
+
+        // redirect within allowed domains are followed
+        let response = client
+            .execute(HttpRequest {
+                url: format!("http://{}/redirect", addr),
+                ..Default::default()
+            })
+            .await?;
+        assert_eq!(response.body, b"GET /echo  ");
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn domain_not_allowed() -> anyhow::Result<()> {
+        let addr = start_stub_server().await;
+
+        let client = HttpClient::new(&["example.com".to_string()])?;
+        let res = client
+            .execute(HttpRequest {
+                url: format!("http://{}/echo", addr),
+                ..Default::default()
+            })
+            .await;
+        assert!(res.is_err());
+
+        // redirects to a domain not allowed fail
+        let client = HttpClient::new(&["127.0.0.1".to_string()])?;
+        let res = client
+            .execute(HttpRequest {
+                url: format!("http://{}/redirect_away", addr),
+                ..Default::default()
+            })
+            .await;
+        assert!(res.is_err());
+
+        Ok(())
+    }
+
+    /// Starts a server replying with the method, path, `x-test` header and body
+    /// of requests, and redirecting some paths.
+    async fn start_stub_server() -> SocketAddr {
+        let make_svc = make_service_fn(|_conn| async {
+            Ok::<_, Infallible>(service_fn(|req: hyper::Request<Body>| async move {
+                let path = req.uri().path().to_string();
+                let resp = match path.as_str() {
+                    "/redirect" => Response::builder()
+                        .status(302)
+                        .header("location", "/echo")
+                        .body(Body::empty()),
+                    "/redirect_away" => Response::builder()
+                        .status(302)
+                        .header("location", "http://localhost/echo")
+                        .body(Body::empty()),
+                    _ => {
+                        let method = req.method().to_string();
+                        let header = req
+                            .headers()
+                            .get("x-test")
+                            .and_then(|value| value.to_str().ok())
+                            .unwrap_or_default()
+                            .to_string();
+                        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                        let body = String::from_utf8_lossy(&body);
+                        Response::builder()
+                            .header("x-stub", "true")
+                            .body(Body::from(format!(
+                                "{} {} {} {}",
+                                method, path, header, body
+                            )))
+                    }
+                };
+                Ok::<_, Infallible>(resp.unwrap())
+            }))
+        });
+
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        addr
+    }
+}
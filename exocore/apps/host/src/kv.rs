@@ -0,0 +1,287 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::anyhow;
+use exocore_core::simple_store::{json_disk_store::JsonDiskStore, SimpleStore};
+use exocore_protos::apps::{kv_request::Request, KvRequest, KvResult, KvValue};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Name of the file in the application's directory in which its key-value
+/// storage is persisted.
+pub const STATE_FILE: &str = "kv.json";
+
+/// Maximum size of a key, in bytes.
+const MAX_KEY_SIZE: usize = 512;
+
+/// Maximum size of a value, in bytes.
+const MAX_VALUE_SIZE: usize = 1024 * 1024;
+
+/// Maximum total size of the keys and values stored by an application, in
+/// bytes.
+const MAX_TOTAL_SIZE: usize = 10 * 1024 * 1024;
+
+/// Key-value storage private to an application, used to keep state that
+/// shouldn't end up in the entities store (ex: sync cursors, settings, caches).
+///
+/// The whole storage is kept in memory and written to disk on every change,
+/// which is why its size is limited by quotas.
+pub struct KvStore {
+    state: KvState,
+    size: usize,
+    state_store: Option<JsonDiskStore<KvState>>,
+}
+
+impl KvStore {
+    /// Creates a key-value storage persisted in the given file.
+    pub fn new_persisted<P: AsRef<Path>>(state_file: P) -> Result<KvStore, Error> {
+        let state_store = JsonDiskStore::new(state_file.as_ref())
+            .map_err(|err| anyhow!("Couldn't open key-value state file: {}", err))?;
+        let state: KvState = state_store
+            .read_or_default()
+            .map_err(|err| anyhow!("Couldn't read key-value state: {}", err))?;
+
+        Ok(KvStore {
+            size: state.size(),
+            state,
+            state_store: Some(state_store),
+        })
+    }
+
+    /// Creates a key-value storage that isn't persisted.
+    pub fn new_in_memory() -> KvStore {
+        KvStore {
+            state: KvState::default(),
+            size: 0,
+            state_store: None,
+        }
+    }
+
+    /// Executes a request coming from the application.
+    pub fn handle_request(&mut self, request: KvRequest) -> Result<KvResult, Error> {
+        match request.request {
+            Some(Request::Get(get)) => Ok(KvResult {
+                value: self.get(&get.key).map(|value| KvValue {
+                    value: value.to_vec(),
+                }),
+                ..Default::default()
+            }),
+            Some(Request::Put(put)) => {
+                self.put(put.key, put.value)?;
+                Ok(KvResult::default())
+            }
+            Some(Request::Delete(delete)) => {
+                self.delete(&delete.key)?;
+                Ok(KvResult::default())
+            }
+            Some(Request::List(list)) => Ok(KvResult {
+                keys: self.list(&list.prefix),
+                ..Default::default()
+            }),
+            None => Err(anyhow!("Key-value request without request").into()),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.state
+            .entries
+            .get(key)
+            .map(|entry| entry.value.as_ref())
+    }
+
+    /// Sets the value of a key, failing if the key or value is too big or if
+    /// the storage's quota would be exceeded.
+    pub fn put(&mut self, key: String, value: Vec<u8>) -> Result<(), Error> {
+        if key.is_empty() {
+            return Err(anyhow!("Key cannot be empty").into());
+        }
+        if key.len() > MAX_KEY_SIZE {
+            return Err(anyhow!("Key is bigger than {} bytes", MAX_KEY_SIZE).into());
+        }
+        if value.len() > MAX_VALUE_SIZE {
+            return Err(anyhow!("Value is bigger than {} bytes", MAX_VALUE_SIZE).into());
+        }
+
+        let previous_size = self
+            .state
+            .entries
+            .get(&key)
+            .map(|entry| entry.size(&key))
+            .unwrap_or_default();
+        let new_size = self.size - previous_size + key.len() + value.len();
+        if new_size > MAX_TOTAL_SIZE {
+            return Err(anyhow!(
+                "Key-value storage quota of {} bytes would be exceeded",
+                MAX_TOTAL_SIZE
+            )
+            .into());
+        }
+
+        self.state.entries.insert(key, KvEntry { value });
+        self.size = new_size;
+        self.persist()
+    }
+
+    pub fn delete(&mut self, key: &str) -> Result<(), Error> {
+        if let Some(entry) = self.state.entries.remove(key) {
+            self.size -= entry.size(key);
+            self.persist()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the keys starting with the given prefix, in lexicographic
+    /// order.
+    pub fn list(&self, prefix: &str) -> Vec<String> {
+        self.state
+            .entries
+            .range(prefix.to_string()..)
+            .take_while(|(key, _entry)| key.starts_with(prefix))
+            .map(|(key, _entry)| key.clone())
+            .collect()
+    }
+
+    fn persist(&self) -> Result<(), Error> {
+        if let Some(state_store) = &self.state_store {
+            state_store
+                .write(&self.state)
+                .map_err(|err| anyhow!("Couldn't persist key-value state: {}", err))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct KvState {
+    entries: BTreeMap<String, KvEntry>,
+}
+
+impl KvState {
+    fn size(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|(key, entry)| entry.size(key))
+            .sum()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct KvEntry {
+    #[serde(
+        serialize_with = "exocore_protos::base64::as_base64",
+        deserialize_with = "exocore_protos::base64::from_base64"
+    )]
+    value: Vec<u8>,
+}
+
+impl KvEntry {
+    fn size(&self, key: &str) -> usize {
+        key.len() + self.value.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exocore_protos::apps::{KvGet, KvList, KvPut};
+
+    use super::*;
+
+    #[test]
+    fn put_get_delete() {
+        let mut kv = KvStore::new_in_memory();
+        assert!(kv.get("key").is_none());
+
+        kv.put("key".to_string(), b"value".to_vec()).unwrap();
+        assert_eq!(kv.get("key"), Some(b"value".as_ref()));
+
+        kv.put("key".to_string(), b"other".to_vec()).unwrap();
+        assert_eq!(kv.get("key"), Some(b"other".as_ref()));
+        assert_eq!(kv.size, 8);
+
+        kv.delete("key").unwrap();
+        assert!(kv.get("key").is_none());
+        assert_eq!(kv.size, 0);
+
+        // deleting a missing key is a no-op
+        kv.delete("key").unwrap();
+    }
+
+    #[test]
+    fn list_prefix() {
+        let mut kv = KvStore::new_in_memory();
+        for key in ["b", "a/2", "a/1", "ab"] {
+            kv.put(key.to_string(), Vec::new()).unwrap();
+        }
+
+        assert_eq!(kv.list("a/"), vec!["a/1", "a/2"]);
+        assert_eq!(kv.list("a"), vec!["a/1", "a/2", "ab"]);
+        assert_eq!(kv.list(""), vec!["a/1", "a/2", "ab", "b"]);
+        assert!(kv.list("c").is_empty());
+    }
+
+    #[test]
+    fn quotas() {
+        let mut kv = KvStore::new_in_memory();
+        assert!(kv.put(String::new(), Vec::new()).is_err());
+        assert!(kv.put("k".repeat(MAX_KEY_SIZE + 1), Vec::new()).is_err());
+        assert!(kv
+            .put("key".to_string(), vec![0; MAX_VALUE_SIZE + 1])
+            .is_err());
+
+        let mut i = 0;
+        let res = loop {
+            let res = kv.put(format!("key{}", i), vec![0; MAX_VALUE_SIZE]);
+            if res.is_err() {
+                break res;
+            }
+            i += 1;
+        };
+        assert!(res.is_err());
+        assert_eq!(i, MAX_TOTAL_SIZE / MAX_VALUE_SIZE - 1);
+
+        // overwriting a value with a smaller one is still allowed
+        kv.put("key0".to_string(), Vec::new()).unwrap();
+        kv.put(format!("key{}", i), vec![0; MAX_VALUE_SIZE])
+            .unwrap();
+    }
+
+    #[test]
+    fn persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join(STATE_FILE);
+
+        {
+            let mut kv = KvStore::new_persisted(&state_file).unwrap();
+            kv.handle_request(KvRequest {
+                request: Some(Request::Put(KvPut {
+                    key: "cursor".to_string(),
+                    value: b"42".to_vec(),
+                })),
+            })
+            .unwrap();
+        }
+
+        let mut kv = KvStore::new_persisted(&state_file).unwrap();
+        assert_eq!(kv.size, 8);
+
+        let res = kv
+            .handle_request(KvRequest {
+                request: Some(Request::Get(KvGet {
+                    key: "cursor".to_string(),
+                })),
+            })
+            .unwrap();
+        assert_eq!(res.value.unwrap().value, b"42");
+
+        let res = kv
+            .handle_request(KvRequest {
+                request: Some(Request::List(KvList {
+                    prefix: String::new(),
+                })),
+            })
+            .unwrap();
+        assert_eq!(res.keys, vec!["cursor"]);
+    }
+}
@@ -3,7 +3,14 @@
 extern crate log;
 
 mod config;
+pub mod election;
 mod error;
+pub mod http;
+pub mod kv;
+pub mod messaging;
+pub mod scheduler;
+pub mod state;
+pub mod triggers;
 
 #[cfg(any(
     all(
@@ -14,5 +21,17 @@ mod error;
 ))]
 pub mod runtime;
 
+#[cfg(all(
+    feature = "tests-utils",
+    any(
+        all(
+            target_arch = "x86_64",
+            any(target_os = "linux", target_os = "macos", target_os = "windows")
+        ),
+        all(target_arch = "aarch64", any(target_os = "linux", target_os = "macos"))
+    )
+))]
+pub mod testing;
+
 pub use config::Config;
 pub use error::Error;
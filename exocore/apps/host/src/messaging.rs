@@ -0,0 +1,645 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use exocore_apps_client::message_types;
+use exocore_core::{
+    cell::{Cell, Node, NodeId},
+    futures::interval,
+    time::Instant,
+};
+use exocore_protos::{
+    apps::{
+        in_message::InMessageType, ClientMessage, ClientRequest, ClientResponse, ClientSubscribe,
+        InMessage,
+    },
+    prost::Message,
+};
+use exocore_transport::{
+    messages::RendezVousId, transport::ConnectionId, InEvent, OutEvent, OutMessage, ServiceType,
+    TransportServiceHandle,
+};
+use futures::{channel::mpsc, FutureExt, SinkExt, StreamExt};
+
+use crate::Error;
+
+/// Number of messages from clients buffered per application. Once the buffer
+/// is full, messages from clients to the application are rejected until it
+/// handles the buffered ones, without holding back the messages to the other
+/// applications.
+const APP_CHANNEL_SIZE: usize = 1000;
+
+/// Configuration of the messaging between applications and clients.
+#[derive(Clone, Copy)]
+pub struct MessagingConfig {
+    /// Duration after which a client's subscriptions expire if they aren't
+    /// renewed. Needs to be higher than the renew interval of the clients.
+    pub subscription_lease: Duration,
+
+    /// Duration after which a request from a client is forgotten if the
+    /// application didn't respond to it.
+    pub request_timeout: Duration,
+
+    /// Interval at which expired subscriptions and requests are removed.
+    pub management_interval: Duration,
+}
+
+impl Default for MessagingConfig {
+    fn default() -> Self {
+        MessagingConfig {
+            subscription_lease: Duration::from_secs(30),
+            request_timeout: Duration::from_secs(30),
+            management_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Routes messages between the applications running on this node and the
+/// clients of the cell, over the transport's `Client` service.
+///
+/// Clients subscribe to topics of an application, on which the application
+/// publishes messages (ex: notifications), and send messages and requests to
+/// it. Until clients know which application host node runs an application,
+/// they send to all of them and messages for applications that aren't running
+/// on this node are ignored. Subscriptions are always sent to all nodes and
+/// are tracked even for applications not running on this node, so that they
+/// are known when this node takes over an application.
+pub struct ClientsMessaging<T: TransportServiceHandle> {
+    config: MessagingConfig,
+    transport: T,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl<T: TransportServiceHandle> ClientsMessaging<T> {
+    pub fn new(config: MessagingConfig, cell: Cell, transport: T) -> ClientsMessaging<T> {
+        let inner = Inner {
+            config,
+            cell,
+            transport_out: None,
+            apps: HashMap::new(),
+            subscriptions: HashMap::new(),
+            pending_requests: HashMap::new(),
+            next_rdv: 0,
+        };
+
+        ClientsMessaging {
+            config,
+            transport,
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    /// Handle used by the applications runtime to exchange messages between
+    /// applications and clients.
+    pub fn handle(&self) -> MessagingHandle {
+        MessagingHandle {
+            inner: Some(self.inner.clone()),
+        }
+    }
+
+    pub async fn run(self) -> Result<(), Error> {
+        let mut transport = self.transport;
+
+        let (out_sender, mut out_receiver) = mpsc::unbounded();
+        let cell = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.transport_out = Some(out_sender);
+            inner.cell.clone()
+        };
+
+        let mut transport_sink = transport.get_sink();
+        let transport_sender = async move {
+            while let Some(event) = out_receiver.next().await {
+                transport_sink.send(event).await?;
+            }
+            Ok::<(), Error>(())
+        };
+
+        let mut transport_stream = transport.get_stream();
+        let receiver_inner = self.inner.clone();
+        let receiver_cell = cell.clone();
+        let transport_receiver = async move {
+            while let Some(event) = transport_stream.next().await {
+                if let InEvent::Message(msg) = event {
+                    let mut inner = receiver_inner.lock().unwrap();
+                    if let Err(err) = inner.handle_client_message(msg) {
+                        error!(
+                            "{}: Couldn't handle message from client: {}",
+                            receiver_cell, err
+                        );
+                    }
+                }
+            }
+            Ok::<(), Error>(())
+        };
+
+        let management_interval = self.config.management_interval;
+        let management_inner = self.inner.clone();
+        let management_timer = async move {
+            let mut interval = interval(management_interval);
+            loop {
+                interval.tick().await;
+
+                let mut inner = management_inner.lock().unwrap();
+                inner.expire();
+            }
+        };
+
+        info!("{}: Applications clients messaging started", cell);
+
+        futures::select! {
+            res = transport_sender.fuse() => res?,
+            res = transport_receiver.fuse() => res?,
+            _ = management_timer.fuse() => {},
+            _ = transport.fuse() => {},
+        };
+
+        Ok(())
+    }
+}
+
+/// Handle to the clients messaging, used by the applications runtime.
+#[derive(Clone)]
+pub struct MessagingHandle {
+    inner: Option<Arc<Mutex<Inner>>>,
+}
+
+impl MessagingHandle {
+    /// Handle for a node that doesn't route messages between applications and
+    /// clients. Messages published by applications are dropped.
+    pub fn disabled() -> MessagingHandle {
+        MessagingHandle { inner: None }
+    }
+
+    /// Registers an application running on this node, returning the stream of
+    /// messages and requests sent to it by clients. A previous registration of
+    /// the application is replaced.
+    pub fn register_app(&self, app_id: &str) -> Option<mpsc::Receiver<InMessage>> {
+        let inner = self.inner.as_ref()?;
+
+        let (sender, receiver) = mpsc::channel(APP_CHANNEL_SIZE);
+        let mut inner = inner.lock().unwrap();
+        inner.apps.insert(app_id.to_string(), sender);
+
+        Some(receiver)
+    }
+
+    /// Unregisters an application that stopped running on this node.
+    pub fn unregister_app(&self, app_id: &str) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+
+        let mut inner = inner.lock().unwrap();
+        inner.apps.remove(app_id);
+        inner
+            .pending_requests
+            .retain(|_rdv, request| request.app_id != app_id);
+    }
+
+    /// Publishes a message of an application to the clients subscribed to its
+    /// topic.
+    pub fn publish(&self, app_id: &str, mut message: ClientMessage) -> Result<(), Error> {
+        let Some(inner) = &self.inner else {
+            return Ok(());
+        };
+
+        message.app_id = app_id.to_string();
+
+        let inner = inner.lock().unwrap();
+        inner.publish(message)
+    }
+
+    /// Sends the response of an application to the client that sent the
+    /// request having the given rendez-vous id.
+    pub fn respond(
+        &self,
+        app_id: &str,
+        rendez_vous_id: u32,
+        response: ClientResponse,
+    ) -> Result<(), Error> {
+        let Some(inner) = &self.inner else {
+            return Ok(());
+        };
+
+        let mut inner = inner.lock().unwrap();
+        inner.respond(app_id, rendez_vous_id, response)
+    }
+}
+
+struct Inner {
+    config: MessagingConfig,
+    cell: Cell,
+    transport_out: Option<mpsc::UnboundedSender<OutEvent>>,
+    apps: HashMap<String, mpsc::Sender<InMessage>>,
+    subscriptions: HashMap<String, HashMap<NodeId, Subscriber>>,
+    pending_requests: HashMap<u32, PendingRequest>,
+    next_rdv: u32,
+}
+
+struct Subscriber {
+    node: Node,
+    connection: Option<ConnectionId>,
+    topics: HashSet<String>,
+    expiration: Instant,
+}
+
+struct PendingRequest {
+    app_id: String,
+    node: Node,
+    connection: Option<ConnectionId>,
+    rendez_vous_id: RendezVousId,
+    expiration: Instant,
+}
+
+impl Inner {
+    /// Handles a message from a client, delivering it to the application it's
+    /// for if it's running on this node.
+    fn handle_client_message(&mut self, msg: exocore_transport::InMessage) -> Result<(), Error> {
+        match msg.typ {
+            message_types::SUBSCRIBE => {
+                let subscribe = ClientSubscribe::decode(msg.get_data()?)?;
+                let app_subscriptions = self.subscriptions.entry(subscribe.app_id).or_default();
+                if subscribe.topics.is_empty() {
+                    app_subscriptions.remove(msg.source.id());
+                } else {
+                    app_subscriptions.insert(
+                        msg.source.id().clone(),
+                        Subscriber {
+                            node: msg.source.clone(),
+                            connection: msg.connection.clone(),
+                            topics: subscribe.topics.into_iter().collect(),
+                            expiration: Instant::now() + self.config.subscription_lease,
+                        },
+                    );
+                }
+            }
+            message_types::MESSAGE => {
+                let data = msg.get_data()?;
+                let message = ClientMessage::decode(data)?;
+                let Some(app_sender) = self.apps.get_mut(&message.app_id) else {
+                    return Ok(());
+                };
+
+                let in_message = InMessage {
+                    r#type: InMessageType::ClientMessage.into(),
+                    data: data.to_vec(),
+                    ..Default::default()
+                };
+                if app_sender.try_send(in_message).is_err() {
+                    return Err(anyhow!(
+                        "app {} is too busy to handle client messages",
+                        message.app_id
+                    )
+                    .into());
+                }
+            }
+            message_types::REQUEST => {
+                let data = msg.get_data()?;
+                let request = ClientRequest::decode(data)?;
+                let rendez_vous_id = msg
+                    .rendez_vous_id
+                    .ok_or_else(|| anyhow!("got a client request without rendez-vous id"))?;
+                if !self.apps.contains_key(&request.app_id) {
+                    return Ok(());
+                }
+
+                let rdv = self.next_rdv;
+                self.next_rdv = self.next_rdv.wrapping_add(1);
+
+                let in_message = InMessage {
+                    r#type: InMessageType::ClientRequest.into(),
+                    rendez_vous_id: rdv,
+                    data: data.to_vec(),
+                    ..Default::default()
+                };
+                self.pending_requests.insert(
+                    rdv,
+                    PendingRequest {
+                        app_id: request.app_id.clone(),
+                        node: msg.source.clone(),
+                        connection: msg.connection.clone(),
+                        rendez_vous_id,
+                        expiration: Instant::now() + self.config.request_timeout,
+                    },
+                );
+
+                // the request is rejected right away if the application's queue is full, so
+                // that a busy application doesn't hold back the messages to the other ones. The
+                // registered sender is used since a cloned one would get its own slot in the queue.
+                let delivered = self
+                    .apps
+                    .get_mut(&request.app_id)
+                    .is_some_and(|app_sender| app_sender.try_send(in_message).is_ok());
+                if !delivered {
+                    let response = ClientResponse {
+                        error: "Application is too busy to handle the request".to_string(),
+                        ..Default::default()
+                    };
+                    self.respond(&request.app_id, rdv, response)?;
+                }
+            }
+            other => {
+                return Err(anyhow!("unknown message type {}", other).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn publish(&self, message: ClientMessage) -> Result<(), Error> {
+        let Some(app_subscriptions) = self.subscriptions.get(&message.app_id) else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+        let data = message.encode_to_vec();
+        for subscriber in app_subscriptions.values() {
+            if subscriber.expiration < now || !subscriber.topics.contains(&message.topic) {
+                continue;
+            }
+
+            let msg = OutMessage::from_data(
+                &self.cell,
+                ServiceType::Client,
+                message_types::MESSAGE,
+                &data,
+            )
+            .with_destination(subscriber.node.clone())
+            .with_opt_connection(subscriber.connection.clone())
+            .with_expiration(Some(now + self.config.request_timeout));
+            self.send_message(msg)?;
+        }
+
+        Ok(())
+    }
+
+    fn respond(
+        &mut self,
+        app_id: &str,
+        rendez_vous_id: u32,
+        response: ClientResponse,
+    ) -> Result<(), Error> {
+        // an application can only respond to the requests sent to it
+        let request = match self.pending_requests.remove(&rendez_vous_id) {
+            Some(request) if request.app_id == app_id => request,
+            Some(request) => {
+                self.pending_requests.insert(rendez_vous_id, request);
+                return Err(anyhow!(
+                    "client request with rendez-vous id {} isn't for this app",
+                    rendez_vous_id
+                )
+                .into());
+            }
+            None => {
+                return Err(anyhow!(
+                    "no pending client request with rendez-vous id {}",
+                    rendez_vous_id
+                )
+                .into());
+            }
+        };
+
+        let msg = OutMessage::from_data(
+            &self.cell,
+            ServiceType::Client,
+            message_types::RESPONSE,
+            &response.encode_to_vec(),
+        )
+        .with_rdv(request.rendez_vous_id)
+        .with_destination(request.node)
+        .with_opt_connection(request.connection)
+        .with_expiration(Some(Instant::now() + self.config.request_timeout));
+        self.send_message(msg)
+    }
+
+    fn send_message(&self, msg: OutMessage) -> Result<(), Error> {
+        let transport_out = self
+            .transport_out
+            .as_ref()
+            .ok_or_else(|| anyhow!("tried to send message, but transport_out was none"))?;
+
+        transport_out
+            .unbounded_send(OutEvent::Message(msg))
+            .map_err(|_err| {
+                anyhow!("tried to send message, but transport_out channel is closed")
+            })?;
+
+        Ok(())
+    }
+
+    /// Removes subscriptions that weren't renewed and requests that the
+    /// applications never responded to.
+    fn expire(&mut self) {
+        let now = Instant::now();
+
+        for app_subscriptions in self.subscriptions.values_mut() {
+            app_subscriptions.retain(|_node_id, subscriber| subscriber.expiration >= now);
+        }
+        self.subscriptions
+            .retain(|_app_id, app_subscriptions| !app_subscriptions.is_empty());
+
+        self.pending_requests
+            .retain(|_rdv, request| request.expiration >= now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exocore_apps_client::{Client, ClientConfig};
+    use exocore_core::{
+        cell::{CellNode, CellNodeRole, FullCell, LocalNode},
+        futures::{owned_spawn, sleep},
+        time::Clock,
+    };
+    use exocore_transport::testing::MockTransport;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn client_messages_and_requests() -> anyhow::Result<()> {
+        let hub = MockTransport::default();
+
+        let host_node = LocalNode::generate();
+        let client_node = LocalNode::generate();
+        let client_cell = FullCell::generate(client_node.clone())?;
+        {
+            let mut nodes = client_cell.cell().nodes_mut();
+            let mut cell_node = CellNode::new(host_node.node().clone());
+            cell_node.add_role(CellNodeRole::AppHost);
+            nodes.add_cell_node(cell_node);
+        }
+        let host_cell = client_cell.clone().with_local_node(host_node.clone());
+
+        let messaging = ClientsMessaging::new(
+            MessagingConfig::default(),
+            host_cell.cell().clone(),
+            hub.get_transport(host_node.clone(), ServiceType::Client),
+        );
+        let handle = messaging.handle();
+        let _messaging_spawn = owned_spawn(messaging.run());
+
+        let client = Client::new(
+            ClientConfig::default(),
+            client_cell.cell().clone(),
+            Clock::new(),
+            hub.get_transport(client_node.clone(), ServiceType::Client),
+        );
+        let client_handle = client.get_handle();
+        let _client_spawn = owned_spawn(client.run());
+        client_handle.on_start().await;
+
+        let mut app_receiver = handle.register_app("app").unwrap();
+
+        // messages published by the app are delivered to subscribed clients
+        let mut subscription = client_handle.subscribe("app", "notifications")?;
+        sleep(Duration::from_millis(100)).await;
+        handle.publish(
+            "app",
+            ClientMessage {
+                topic: "notifications".to_string(),
+                data: b"email is back".to_vec(),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(subscription.next().await.unwrap(), b"email is back");
+
+        // messages sent by clients are delivered to the app
+        client_handle.publish("app", "refresh", b"now".to_vec())?;
+        let in_message = app_receiver.next().await.unwrap();
+        assert_eq!(in_message.r#type, InMessageType::ClientMessage as i32);
+        let message = ClientMessage::decode(in_message.data.as_ref())?;
+        assert_eq!(message.topic, "refresh");
+
+        // requests are delivered to the app, which responds to them
+        let request_handle = client_handle.clone();
+        let response =
+            owned_spawn(async move { request_handle.request("app", "ping", Vec::new()).await });
+        let in_message = app_receiver.next().await.unwrap();
+        assert_eq!(in_message.r#type, InMessageType::ClientRequest as i32);
+
+        // other apps can't respond to the request
+        let pong = ClientResponse {
+            data: b"pong".to_vec(),
+            ..Default::default()
+        };
+        assert!(handle
+            .respond("other", in_message.rendez_vous_id, pong.clone())
+            .is_err());
+
+        handle.respond("app", in_message.rendez_vous_id, pong)?;
+        assert_eq!(response.await??, b"pong");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn busy_app_rejects_client_messages() -> anyhow::Result<()> {
+        let hub = MockTransport::default();
+
+        let host_node = LocalNode::generate();
+        let client_node = LocalNode::generate();
+        let client_cell = FullCell::generate(client_node.clone())?;
+        {
+            let mut nodes = client_cell.cell().nodes_mut();
+            let mut cell_node = CellNode::new(host_node.node().clone());
+            cell_node.add_role(CellNodeRole::AppHost);
+            nodes.add_cell_node(cell_node);
+        }
+        let host_cell = client_cell.clone().with_local_node(host_node.clone());
+
+        let messaging = ClientsMessaging::new(
+            MessagingConfig::default(),
+            host_cell.cell().clone(),
+            hub.get_transport(host_node.clone(), ServiceType::Client),
+        );
+        let handle = messaging.handle();
+        let _messaging_spawn = owned_spawn(messaging.run());
+
+        let client = Client::new(
+            ClientConfig::default(),
+            client_cell.cell().clone(),
+            Clock::new(),
+            hub.get_transport(client_node.clone(), ServiceType::Client),
+        );
+        let client_handle = client.get_handle();
+        let _client_spawn = owned_spawn(client.run());
+        client_handle.on_start().await;
+
+        let _busy_receiver = handle.register_app("busy").unwrap();
+        let mut other_receiver = handle.register_app("other").unwrap();
+
+        // fill the queue of the app that doesn't handle its messages, in batches since the
+        // mock transport drops messages once its own queue is full
+        for _ in 0..=(APP_CHANNEL_SIZE / 100) {
+            for _ in 0..100 {
+                client_handle.publish("busy", "refresh", Vec::new())?;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        // requests to the busy app are rejected right away
+        let res = client_handle.request("busy", "ping", Vec::new()).await;
+        assert!(matches!(res, Err(exocore_apps_client::Error::Remote(_))));
+
+        // other apps still get their messages
+        client_handle.publish("other", "refresh", Vec::new())?;
+        let in_message = other_receiver.next().await.unwrap();
+        assert_eq!(in_message.r#type, InMessageType::ClientMessage as i32);
+
+        Ok(())
+    }
+
+    #[test]
+    fn expire_subscriptions_and_requests() {
+        let node = LocalNode::generate();
+        let cell = FullCell::generate(node.clone()).unwrap();
+        let config = MessagingConfig {
+            subscription_lease: Duration::from_millis(10),
+            request_timeout: Duration::from_millis(10),
+            ..Default::default()
+        };
+        let messaging = ClientsMessaging::new(
+            config,
+            cell.cell().clone(),
+            MockTransport::default().get_transport(node.clone(), ServiceType::Client),
+        );
+
+        let mut inner = messaging.inner.lock().unwrap();
+        inner
+            .subscriptions
+            .entry("app".to_string())
+            .or_default()
+            .insert(
+                node.id().clone(),
+                Subscriber {
+                    node: node.node().clone(),
+                    connection: None,
+                    topics: HashSet::new(),
+                    expiration: Instant::now() + config.subscription_lease,
+                },
+            );
+        inner.pending_requests.insert(
+            0,
+            PendingRequest {
+                app_id: "app".to_string(),
+                node: node.node().clone(),
+                connection: None,
+                rendez_vous_id: RendezVousId::from(0),
+                expiration: Instant::now() + config.request_timeout,
+            },
+        );
+
+        inner.expire();
+        assert_eq!(inner.subscriptions.len(), 1);
+        assert_eq!(inner.pending_requests.len(), 1);
+
+        std::thread::sleep(Duration::from_millis(20));
+        inner.expire();
+        assert!(inner.subscriptions.is_empty());
+        assert!(inner.pending_requests.is_empty());
+    }
+}
@@ -1,4 +1,9 @@
-use std::{path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::anyhow;
 use exocore_core::{
@@ -8,7 +13,10 @@ use exocore_core::{
     utils::backoff::BackoffCalculator,
 };
 use exocore_protos::{
-    apps::{in_message::InMessageType, out_message::OutMessageType, InMessage, OutMessage},
+    apps::{
+        in_message::InMessageType, out_message::OutMessageType, ClientMessage, ClientResponse,
+        HttpRequest, InMessage, KvRequest, OutMessage, ScheduledJob,
+    },
     prost::Message,
     store::{EntityQuery, MutationRequest},
 };
@@ -19,13 +27,32 @@ use futures::{
     lock::Mutex,
     Future, SinkExt, StreamExt,
 };
+use serde::{de::DeserializeOwned, Serialize};
 
 use super::wasmtime::WasmTimeRuntime;
-use crate::{Config, Error};
+use crate::{
+    election::ElectionHandle,
+    http::HttpClient,
+    kv::{KvStore, STATE_FILE as KV_STATE_FILE},
+    messaging::MessagingHandle,
+    scheduler::{
+        Scheduler, STATE_FILE as SCHEDULER_STATE_FILE, STATE_TRAIT as SCHEDULER_STATE_TRAIT,
+    },
+    state::{decode_json, AppStateStore},
+    triggers::{Triggers, STATE_FILE as TRIGGERS_STATE_FILE, STATE_TRAIT as TRIGGERS_STATE_TRAIT},
+    Config, Error,
+};
 
 const MSG_BUFFER_SIZE: usize = 5000;
 const RUNTIME_MSG_BATCH_SIZE: usize = 1000;
 const APP_MIN_TICK_TIME: Duration = Duration::from_millis(100);
+const SCHEDULER_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const TRIGGERS_RETRY_DELAY: Duration = Duration::from_secs(5);
+const LEADERSHIP_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Handler receiving the logs of applications, with the name of the
+/// application that logged them.
+pub type LogHandler = Arc<dyn Fn(&str, log::Level, &str) + Send + Sync>;
 
 /// Exocore applications host.
 ///
@@ -38,6 +65,9 @@ pub struct Applications<S: Store> {
     clock: Clock,
     store: S,
     apps: Vec<Application>,
+    election: ElectionHandle,
+    messaging: MessagingHandle,
+    log_handler: Option<LogHandler>,
 }
 
 impl<S: Store> Applications<S> {
@@ -69,14 +99,48 @@ impl<S: Store> Applications<S> {
                 .map_err(|err| anyhow!("module file is not accessible via os fs: {}", err))?
                 .join(&module.file);
 
+            // the state directory, in which the app's key-value storage is persisted and previous
+            // versions persisted the rest of its state, is the one of the app in the cell, which is
+            // different from the app's directory when it's loaded from elsewhere (ex: development)
+            let state_dir = cell
+                .app_directory(app_manifest)
+                .map_err(|err| anyhow!("couldn't get app directory: {}", err))?
+                .as_os_path()
+                .map_err(|err| anyhow!("app directory is not accessible via os fs: {}", err))?;
+
             let app = Application {
                 cell: cell.clone(),
                 cell_app: app.clone(),
                 module_path,
+                state_dir,
             };
-            app.cell_app
-                .validate()
-                .map_err(|err| anyhow!("Couldn't validate module: {}", err))?;
+            // an invalid application isn't started, but shouldn't prevent the others from running
+            let unsigned = app_manifest.signature.is_empty();
+            let res =
+                if !config.validate_signatures || (unsigned && cell.config().allow_unsigned_apps) {
+                    app.cell_app.validate_module()
+                } else {
+                    app.cell_app.validate()
+                };
+            if let Err(err) = res {
+                error!(
+                    "Application '{}' (id={}) is invalid and won't be started: {}",
+                    cell_app.name(),
+                    cell_app.id(),
+                    err
+                );
+                continue;
+            }
+
+            // the manifest is signed by its own key, which needs to be the one configured in the cell
+            if app.cell_app.public_key() != cell_app.public_key() {
+                error!(
+                    "Application '{}' (id={}) public key doesn't match the cell's and won't be started",
+                    cell_app.name(),
+                    cell_app.id()
+                );
+                continue;
+            }
 
             apps.push(app);
         }
@@ -87,9 +151,35 @@ impl<S: Store> Applications<S> {
             clock,
             store,
             apps,
+            election: ElectionHandle::single(),
+            messaging: MessagingHandle::disabled(),
+            log_handler: None,
         })
     }
 
+    /// Only runs the applications for which this node is elected, when the
+    /// cell has multiple application host nodes. Otherwise, all applications
+    /// are run.
+    pub fn with_election(mut self, election: ElectionHandle) -> Self {
+        self.election = election;
+        self
+    }
+
+    /// Routes messages between the applications running on this node and the
+    /// clients of the cell. Otherwise, messages published by applications are
+    /// dropped.
+    pub fn with_messaging(mut self, messaging: MessagingHandle) -> Self {
+        self.messaging = messaging;
+        self
+    }
+
+    /// Streams the logs of applications to the given handler instead of the
+    /// host's logger.
+    pub fn with_log_handler(mut self, log_handler: LogHandler) -> Self {
+        self.log_handler = Some(log_handler);
+        self
+    }
+
     /// Starts and runs applications.
     pub async fn run(self) -> Result<(), Error> {
         if self.apps.is_empty() {
@@ -105,6 +195,9 @@ impl<S: Store> Applications<S> {
                 self.config,
                 app,
                 self.store.clone(),
+                self.election.clone(),
+                self.messaging.clone(),
+                self.log_handler.clone(),
             )));
         }
 
@@ -114,17 +207,55 @@ impl<S: Store> Applications<S> {
         Ok(())
     }
 
-    async fn start_app_loop(clock: Clock, config: Config, app: Application, store: S) {
-        let mut backoff = BackoffCalculator::new(clock, config.restart_backoff);
+    async fn start_app_loop(
+        clock: Clock,
+        config: Config,
+        app: Application,
+        store: S,
+        election: ElectionHandle,
+        messaging: MessagingHandle,
+        log_handler: Option<LogHandler>,
+    ) {
+        let app_id = app.cell_app.id().to_string();
+        let mut backoff = BackoffCalculator::new(clock.clone(), config.restart_backoff);
         loop {
+            if !election.is_leader(&app_id) {
+                sleep(LEADERSHIP_CHECK_INTERVAL).await;
+                continue;
+            }
+
             info!(
                 "{}: Starting application (version {})",
                 app,
                 app.cell_app.version()
             );
 
-            let store = store.clone();
-            Self::start_app(&app, store).await;
+            election.set_running(&app_id, true);
+            let app_run = Self::start_app(
+                &app,
+                store.clone(),
+                clock.clone(),
+                &messaging,
+                log_handler.clone(),
+            );
+            let leadership_lost = async {
+                while election.is_leader(&app_id) {
+                    sleep(LEADERSHIP_CHECK_INTERVAL).await;
+                }
+            };
+            let lost = futures::select! {
+                _ = app_run.fuse() => false,
+                _ = leadership_lost.fuse() => true,
+            };
+            election.set_running(&app_id, false);
+
+            if lost {
+                info!(
+                    "{}: Another application host node got elected. Stopped application",
+                    app
+                );
+                continue;
+            }
 
             backoff.increment_failure();
 
@@ -137,7 +268,54 @@ impl<S: Store> Applications<S> {
         }
     }
 
-    async fn start_app(app: &Application, store: S) {
+    async fn start_app(
+        app: &Application,
+        store: S,
+        clock: Clock,
+        messaging: &MessagingHandle,
+        log_handler: Option<LogHandler>,
+    ) {
+        let state_store = AppStateStore::new(store.clone(), app.cell_app.id());
+        let states = match state_store.read_all().await {
+            Ok(states) => states,
+            Err(err) => {
+                error!("{}: Couldn't read application state: {}", app, err);
+                return;
+            }
+        };
+
+        let scheduler = match create_scheduler(app, state_store.clone(), &states).await {
+            Ok(scheduler) => Arc::new(std::sync::Mutex::new(scheduler)),
+            Err(err) => {
+                error!("{}: Couldn't create scheduler: {}", app, err);
+                return;
+            }
+        };
+
+        let triggers = match create_triggers(app, state_store.clone(), &states).await {
+            Ok(triggers) => Arc::new(std::sync::Mutex::new(triggers)),
+            Err(err) => {
+                error!("{}: Couldn't create triggers: {}", app, err);
+                return;
+            }
+        };
+
+        let http_client = match HttpClient::new(&app.cell_app.manifest().http_domains) {
+            Ok(http_client) => http_client,
+            Err(err) => {
+                error!("{}: Couldn't create HTTP client: {}", app, err);
+                return;
+            }
+        };
+
+        let mut kv_store = match create_kv_store(app) {
+            Ok(kv_store) => kv_store,
+            Err(err) => {
+                error!("{}: Couldn't create key-value storage: {}", app, err);
+                return;
+            }
+        };
+
         let (in_sender, in_receiver) = mpsc::channel(MSG_BUFFER_SIZE);
         let (out_sender, mut out_receiver) = mpsc::channel(MSG_BUFFER_SIZE);
 
@@ -145,13 +323,16 @@ impl<S: Store> Applications<S> {
         let runtime_spawn = {
             let env = Arc::new(WiredEnvironment {
                 log_prefix: app.to_string(),
+                app_name: app.cell_app.name().to_string(),
+                log_handler,
                 sender: std::sync::Mutex::new(out_sender),
             });
 
             let app_module_path = app.module_path.clone();
             let app_prefix = app.to_string();
+            let clock = clock.clone();
             spawn_blocking(move || -> Result<(), Error> {
-                let mut app_runtime = WasmTimeRuntime::from_file(app_module_path, env)?;
+                let mut app_runtime = WasmTimeRuntime::from_file(app_module_path, env, clock)?;
                 let mut batch_receiver = BatchingStream::new(in_receiver, RUNTIME_MSG_BATCH_SIZE);
 
                 let mut started = false;
@@ -193,17 +374,33 @@ impl<S: Store> Applications<S> {
             })
         };
 
-        // Spawn a task to handle store requests coming from the application
-        let store_worker = {
+        let in_sender = Arc::new(Mutex::new(in_sender));
+
+        // Wakes up the triggers worker when a trigger has handled all its pending changes
+        let (triggers_wakeup_sender, mut triggers_wakeup_receiver) = mpsc::channel(1);
+
+        let app_id = app.cell_app.id().to_string();
+        let clients_receiver = messaging.register_app(&app_id);
+
+        // Spawn a task to handle store, scheduler, triggers, HTTP, key-value and clients
+        // messages coming from the application
+        let messages_worker = {
+            let app_id = app_id.clone();
+            let messaging = messaging.clone();
             let store = store.clone();
+            let scheduler = scheduler.clone();
+            let triggers = triggers.clone();
+            let http_client = http_client.clone();
+            let clock = clock.clone();
+            let in_sender = in_sender.clone();
             let app_prefix = app.to_string();
+            let mut triggers_wakeup_sender = triggers_wakeup_sender;
             async move {
-                let in_sender = Arc::new(Mutex::new(in_sender));
                 while let Some(message) = out_receiver.next().await {
                     match OutMessageType::try_from(message.r#type) {
                         Ok(OutMessageType::StoreEntityQuery) => {
                             let store = store.clone();
-                            handle_store_message(
+                            handle_async_message(
                                 message.rendez_vous_id,
                                 InMessageType::StoreEntityResults,
                                 in_sender.clone(),
@@ -212,13 +409,113 @@ impl<S: Store> Applications<S> {
                         }
                         Ok(OutMessageType::StoreMutationRequest) => {
                             let store = store.clone();
-                            handle_store_message(
+                            handle_async_message(
                                 message.rendez_vous_id,
                                 InMessageType::StoreMutationResult,
                                 in_sender.clone(),
                                 move || handle_entity_mutation(message, store),
                             )
                         }
+                        Ok(OutMessageType::SchedulerRegisterJob) => {
+                            let mut scheduler = scheduler.lock().unwrap();
+                            let res = ScheduledJob::decode(message.data.as_ref())
+                                .map_err(Error::from)
+                                .and_then(|job| scheduler.register(job, clock.now_chrono()));
+                            if let Err(err) = res {
+                                error!("{}: Couldn't register scheduled job: {}", app_prefix, err);
+                            }
+                        }
+                        Ok(OutMessageType::ScheduledJobDone) => {
+                            let state = {
+                                let mut scheduler = scheduler.lock().unwrap();
+                                scheduler
+                                    .complete(message.rendez_vous_id)
+                                    .then(|| scheduler.state().clone())
+                            };
+
+                            if let Some(state) = state {
+                                let res =
+                                    state_store.write_json(SCHEDULER_STATE_TRAIT, &state).await;
+                                if let Err(err) = res {
+                                    error!(
+                                        "{}: Couldn't persist scheduler state: {}",
+                                        app_prefix, err
+                                    );
+                                }
+                            }
+                        }
+                        Ok(OutMessageType::HttpRequest) => {
+                            let http_client = http_client.clone();
+                            handle_async_message(
+                                message.rendez_vous_id,
+                                InMessageType::HttpResponse,
+                                in_sender.clone(),
+                                move || handle_http_request(message, http_client),
+                            )
+                        }
+                        Ok(OutMessageType::KvRequest) => {
+                            // handled in order, since a request may depend on a previous one
+                            let mut reply = InMessage {
+                                r#type: InMessageType::KvResult.into(),
+                                rendez_vous_id: message.rendez_vous_id,
+                                ..Default::default()
+                            };
+                            match handle_kv_request(message, &mut kv_store) {
+                                Ok(data) => reply.data = data,
+                                Err(err) => reply.error = err.to_string(),
+                            }
+
+                            let mut in_sender = in_sender.lock().await;
+                            if in_sender.send(reply).await.is_err() {
+                                break;
+                            }
+                        }
+                        Ok(OutMessageType::ClientPublish) => {
+                            let res = ClientMessage::decode(message.data.as_ref())
+                                .map_err(Error::from)
+                                .and_then(|msg| messaging.publish(&app_id, msg));
+                            if let Err(err) = res {
+                                error!(
+                                    "{}: Couldn't publish message to clients: {}",
+                                    app_prefix, err
+                                );
+                            }
+                        }
+                        Ok(OutMessageType::ClientResponse) => {
+                            let res = ClientResponse::decode(message.data.as_ref())
+                                .map_err(Error::from)
+                                .and_then(|response| {
+                                    messaging.respond(&app_id, message.rendez_vous_id, response)
+                                });
+                            if let Err(err) = res {
+                                error!(
+                                    "{}: Couldn't respond to client request: {}",
+                                    app_prefix, err
+                                );
+                            }
+                        }
+                        Ok(OutMessageType::EntityTriggerDone) => {
+                            let (next, state) = {
+                                let mut triggers = triggers.lock().unwrap();
+                                let next = triggers.complete(message.rendez_vous_id);
+                                (next, triggers.state().clone())
+                            };
+
+                            let res = state_store.write_json(TRIGGERS_STATE_TRAIT, &state).await;
+                            if let Err(err) = res {
+                                error!("{}: Couldn't persist triggers state: {}", app_prefix, err);
+                            }
+
+                            if let Some(next) = next {
+                                let mut in_sender = in_sender.lock().await;
+                                if in_sender.send(next).await.is_err() {
+                                    break;
+                                }
+                            } else {
+                                // channel being full means that a wake-up is already pending
+                                let _ = triggers_wakeup_sender.try_send(());
+                            }
+                        }
                         other => {
                             error!(
                                 "{}: Got an unknown message type {:?} with id {}",
@@ -232,18 +529,145 @@ impl<S: Store> Applications<S> {
             }
         };
 
+        // Spawn a task forwarding messages and requests sent by clients to the application
+        let clients_worker = {
+            let in_sender = in_sender.clone();
+            async move {
+                if let Some(mut clients_receiver) = clients_receiver {
+                    while let Some(msg) = clients_receiver.next().await {
+                        let mut in_sender = in_sender.lock().await;
+                        if in_sender.send(msg).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+
+                // messaging is disabled, or the application got registered again
+                pending::<()>().await;
+            }
+        };
+
+        // Spawn a task sending runs of scheduled jobs to the application once they are due
+        let scheduler_worker = {
+            let in_sender = in_sender.clone();
+            async move {
+                loop {
+                    let runs = {
+                        let mut scheduler = scheduler.lock().unwrap();
+                        scheduler.poll_due(clock.now_chrono())
+                    };
+
+                    for run in runs {
+                        let mut in_sender = in_sender.lock().await;
+                        if in_sender.send(run).await.is_err() {
+                            return;
+                        }
+                    }
+
+                    sleep(SCHEDULER_POLL_INTERVAL).await;
+                }
+            }
+        };
+
+        // Spawn a task querying the store for changes matching the application's triggers
+        // whenever the store notifies that their trait types changed
+        let triggers_worker = {
+            let app_prefix = app.to_string();
+            let watch_queries = {
+                let triggers = triggers.lock().unwrap();
+                triggers.watch_queries()
+            };
+            async move {
+                let mut changes = Vec::new();
+                for (trigger_name, query) in watch_queries {
+                    match store.watched_query(query) {
+                        Ok(stream) => {
+                            changes.push(stream.map(move |_res| trigger_name.clone()).boxed())
+                        }
+                        Err(err) => {
+                            error!(
+                                "{}: Couldn't watch changes for trigger '{}': {}",
+                                app_prefix, trigger_name, err
+                            );
+                            return;
+                        }
+                    }
+                }
+                let mut changes = futures::stream::select_all(changes);
+
+                loop {
+                    let queries = {
+                        let mut triggers = triggers.lock().unwrap();
+                        triggers.queries()
+                    };
+                    if queries.is_empty() {
+                        futures::select! {
+                            trigger_name = changes.next() => {
+                                let Some(trigger_name) = trigger_name else {
+                                    // no triggers declared
+                                    pending::<()>().await;
+                                    return;
+                                };
+                                let mut triggers = triggers.lock().unwrap();
+                                triggers.notify_changed(&trigger_name);
+                            }
+                            _ = triggers_wakeup_receiver.next() => {}
+                        }
+                        continue;
+                    }
+
+                    for (trigger_name, query) in queries {
+                        let results = match store.query(query).await {
+                            Ok(results) => results,
+                            Err(err) => {
+                                error!(
+                                    "{}: Couldn't query changes for trigger '{}': {}",
+                                    app_prefix, trigger_name, err
+                                );
+                                sleep(TRIGGERS_RETRY_DELAY).await;
+                                continue;
+                            }
+                        };
+
+                        let msg = {
+                            let mut triggers = triggers.lock().unwrap();
+                            triggers.handle_results(&trigger_name, results)
+                        };
+
+                        if let Some(msg) = msg {
+                            let mut in_sender = in_sender.lock().await;
+                            if in_sender.send(msg).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+        };
+
         futures::select! {
             res = runtime_spawn.fuse() => {
                 info!("{}: App runtime spawn has stopped: {:?}", app, res);
             }
-            _ = store_worker.fuse() => {
-                info!("{}: Store worker task has stopped", app);
+            _ = messages_worker.fuse() => {
+                info!("{}: Messages worker task has stopped", app);
+            }
+            _ = scheduler_worker.fuse() => {
+                info!("{}: Scheduler worker task has stopped", app);
+            }
+            _ = triggers_worker.fuse() => {
+                info!("{}: Triggers worker task has stopped", app);
+            }
+            _ = clients_worker.fuse() => {
+                info!("{}: Clients worker task has stopped", app);
             }
         };
+
+        messaging.unregister_app(&app_id);
     }
 }
 
-fn handle_store_message<F, O>(
+fn handle_async_message<F, O>(
     rendez_vous_id: u32,
     reply_type: InMessageType,
     in_sender: Arc<Mutex<mpsc::Sender<InMessage>>>,
@@ -292,8 +716,27 @@ async fn handle_entity_mutation<S: Store>(
     Ok(res.encode_to_vec())
 }
 
+async fn handle_http_request(
+    out_message: OutMessage,
+    http_client: HttpClient,
+) -> Result<Vec<u8>, Error> {
+    let request = HttpRequest::decode(out_message.data.as_ref())?;
+    let res = http_client.execute(request).await?;
+
+    Ok(res.encode_to_vec())
+}
+
+fn handle_kv_request(out_message: OutMessage, kv_store: &mut KvStore) -> Result<Vec<u8>, Error> {
+    let request = KvRequest::decode(out_message.data.as_ref())?;
+    let res = kv_store.handle_request(request)?;
+
+    Ok(res.encode_to_vec())
+}
+
 struct WiredEnvironment {
     log_prefix: String,
+    app_name: String,
+    log_handler: Option<LogHandler>,
     sender: std::sync::Mutex<mpsc::Sender<exocore_protos::apps::OutMessage>>,
 }
 
@@ -306,14 +749,114 @@ impl super::wasmtime::HostEnvironment for WiredEnvironment {
     }
 
     fn handle_log(&self, level: log::Level, msg: &str) {
-        log!(level, "{}: WASM: {}", self.log_prefix, msg);
+        if let Some(log_handler) = &self.log_handler {
+            log_handler(&self.app_name, level, msg);
+        } else {
+            log!(level, "{}: WASM: {}", self.log_prefix, msg);
+        }
     }
 }
 
+/// Creates the scheduler of an application from its state in the cell's store.
+///
+/// Since an application only runs on the application host node elected for it,
+/// jobs are only run by this node. As the state is replicated, the node taking
+/// over the application doesn't run again the jobs that already ran.
+async fn create_scheduler<S: Store>(
+    app: &Application,
+    state_store: AppStateStore<S>,
+    states: &HashMap<String, Vec<u8>>,
+) -> Result<Scheduler, Error> {
+    let state = read_json_state(
+        app,
+        state_store,
+        states,
+        SCHEDULER_STATE_TRAIT,
+        SCHEDULER_STATE_FILE,
+    )
+    .await?;
+
+    Ok(Scheduler::new(state))
+}
+
+/// Creates the triggers declared in the manifest of an application from the
+/// changes they handled, stored in the cell's store.
+async fn create_triggers<S: Store>(
+    app: &Application,
+    state_store: AppStateStore<S>,
+    states: &HashMap<String, Vec<u8>>,
+) -> Result<Triggers, Error> {
+    let state = read_json_state(
+        app,
+        state_store,
+        states,
+        TRIGGERS_STATE_TRAIT,
+        TRIGGERS_STATE_FILE,
+    )
+    .await?;
+
+    let manifest = app.cell_app.manifest();
+    Triggers::new(&manifest.triggers, state)
+}
+
+/// Creates the key-value storage of an application, persisted in its state
+/// directory.
+///
+/// Unlike the scheduler's and triggers' state, the storage is private to the
+/// application and isn't kept in the cell's entities store. It stays on the
+/// node, and is therefore empty on a node that takes over the application.
+fn create_kv_store(app: &Application) -> Result<KvStore, Error> {
+    std::fs::create_dir_all(&app.state_dir)
+        .map_err(|err| anyhow!("couldn't create app state directory: {}", err))?;
+
+    KvStore::new_persisted(app.state_dir.join(KV_STATE_FILE))
+}
+
+/// Reads a JSON state of an application from its state stored in the cell's
+/// store.
+///
+/// The state persisted in the application's state directory by previous
+/// versions is migrated to the store.
+async fn read_json_state<S, T>(
+    app: &Application,
+    state_store: AppStateStore<S>,
+    states: &HashMap<String, Vec<u8>>,
+    trait_id: &str,
+    legacy_file: &str,
+) -> Result<T, Error>
+where
+    S: Store,
+    T: Serialize + DeserializeOwned + Default,
+{
+    if let Some(data) = states.get(trait_id) {
+        return decode_json(trait_id, data);
+    }
+
+    let legacy_file = app.state_dir.join(legacy_file);
+    if !legacy_file.exists() {
+        return Ok(T::default());
+    }
+
+    let data = std::fs::read(&legacy_file)
+        .map_err(|err| anyhow!("couldn't read state file {:?}: {}", legacy_file, err))?;
+    let state: T = decode_json(trait_id, &data)?;
+    state_store.write_json(trait_id, &state).await?;
+    remove_legacy_file(&legacy_file)?;
+
+    Ok(state)
+}
+
+fn remove_legacy_file(legacy_file: &Path) -> Result<(), Error> {
+    std::fs::remove_file(legacy_file)
+        .map_err(|err| anyhow!("couldn't remove state file {:?}: {}", legacy_file, err))?;
+    Ok(())
+}
+
 struct Application {
     cell: Cell,
     cell_app: exocore_core::cell::Application,
     module_path: PathBuf,
+    state_dir: PathBuf,
 }
 
 impl std::fmt::Display for Application {
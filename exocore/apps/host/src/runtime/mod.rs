@@ -1,4 +1,4 @@
 pub mod apps;
 pub use apps::Applications;
 
-mod wasmtime;
+pub(crate) mod wasmtime;
@@ -1,5 +1,6 @@
 use std::{path::Path, sync::Arc, time::Duration};
 
+use exocore_core::time::Clock;
 use exocore_protos::{
     apps::{InMessage, MessageStatus, OutMessage},
     prost::{self, Message},
@@ -19,11 +20,12 @@ pub struct WasmTimeRuntime<E: HostEnvironment> {
     send_message_func: FuncSendMessage,
     tick_func: FuncTick,
     store: Store<WasiCtx>,
+    clock: Clock,
     _phantom: std::marker::PhantomData<E>,
 }
 
 impl<E: HostEnvironment> WasmTimeRuntime<E> {
-    pub fn from_file<P>(file: P, env: Arc<E>) -> Result<WasmTimeRuntime<E>, Error>
+    pub fn from_file<P>(file: P, env: Arc<E>, clock: Clock) -> Result<WasmTimeRuntime<E>, Error>
     where
         P: AsRef<Path>,
     {
@@ -31,7 +33,7 @@ impl<E: HostEnvironment> WasmTimeRuntime<E> {
 
         let mut linker = Linker::new(&engine);
         wasmtime_wasi::add_to_linker(&mut linker, |s| s)?;
-        Self::setup_host_module(&mut linker, &env)?;
+        Self::setup_host_module(&mut linker, &env, &clock)?;
 
         let module = Module::from_file(&engine, file)?;
 
@@ -50,13 +52,14 @@ impl<E: HostEnvironment> WasmTimeRuntime<E> {
             send_message_func,
             tick_func,
             store,
+            clock,
             _phantom: std::marker::PhantomData,
         })
     }
 
     // Runs an iteration on the WASM module.
     pub fn tick(&mut self) -> Result<Option<Duration>, Error> {
-        let now = unix_timestamp();
+        let now = unix_timestamp(&self.clock);
         let next_tick_time = self.tick_func.call(&mut self.store, ())?;
 
         if next_tick_time > now {
@@ -85,7 +88,11 @@ impl<E: HostEnvironment> WasmTimeRuntime<E> {
         Ok(())
     }
 
-    fn setup_host_module(linker: &mut Linker<WasiCtx>, env: &Arc<E>) -> Result<(), Error> {
+    fn setup_host_module(
+        linker: &mut Linker<WasiCtx>,
+        env: &Arc<E>,
+        clock: &Clock,
+    ) -> Result<(), Error> {
         let env_clone = env.clone();
 
         linker.func_wrap(
@@ -101,10 +108,11 @@ impl<E: HostEnvironment> WasmTimeRuntime<E> {
             },
         )?;
 
+        let clock = clock.clone();
         linker.func_wrap(
             "exocore",
             "__exocore_host_now",
-            |_caller: Caller<'_, WasiCtx>| -> u64 { unix_timestamp() },
+            move |_caller: Caller<'_, WasiCtx>| -> u64 { unix_timestamp(&clock) },
         )?;
 
         let env = env.clone();
@@ -271,12 +279,9 @@ fn wasm_free(
     Ok(())
 }
 
-fn unix_timestamp() -> u64 {
+fn unix_timestamp(clock: &Clock) -> u64 {
     // TODO: Should be consistent timestamp
-    let now = std::time::SystemTime::now();
-    now.duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_nanos() as u64
+    clock.unix_elapsed().as_nanos() as u64
 }
 
 #[cfg(test)]
@@ -298,7 +303,7 @@ mod tests {
         let example_path = find_test_fixture("fixtures/example.wasm");
         let env = Arc::new(TestEnv::new());
 
-        let mut app = WasmTimeRuntime::from_file(example_path, env.clone()).unwrap();
+        let mut app = WasmTimeRuntime::from_file(example_path, env.clone(), Clock::new()).unwrap();
 
         // first tick should execute up to sleep
         app.tick().unwrap();
@@ -0,0 +1,265 @@
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc};
+
+/// Maximum number of years after which we stop looking for the next
+/// occurrence of an expression that never matches (ex: February 31st).
+const MAX_YEARS_LOOKUP: i32 = 5;
+
+/// Cron expression with 5 fields (minute, hour, day of month, month, day of
+/// week), evaluated in UTC.
+///
+/// Each field supports `*`, single values, ranges (`1-5`), steps (`*/15`,
+/// `0-30/10`) and lists of those (`1,15,30`). Days of week go from 0 (Sunday)
+/// to 6, with 7 also accepted as Sunday. As in standard cron, if both the day
+/// of month and the day of week are restricted, a day matches if any of them
+/// does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: Field,
+    hours: Field,
+    days_of_month: Field,
+    months: Field,
+    days_of_week: Field,
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> Result<CronSchedule, CronError> {
+        let fields = expr.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(CronError::FieldCount(fields.len()));
+        }
+
+        let mut days_of_week = Field::parse(fields[4], 0, 7)?;
+        if days_of_week.matches(7) {
+            days_of_week.values |= 1;
+        }
+
+        Ok(CronSchedule {
+            minutes: Field::parse(fields[0], 0, 59)?,
+            hours: Field::parse(fields[1], 0, 23)?,
+            days_of_month: Field::parse(fields[2], 1, 31)?,
+            months: Field::parse(fields[3], 1, 12)?,
+            days_of_week,
+        })
+    }
+
+    /// Returns the first time matching the expression strictly after the given
+    /// time, or `None` if the expression never matches.
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut next = truncate_minute(time) + Duration::minutes(1);
+        let max_year = time.year() + MAX_YEARS_LOOKUP;
+
+        while next.year() <= max_year {
+            if !self.months.matches(next.month()) {
+                next = start_of_next_month(next);
+                continue;
+            }
+
+            if !self.matches_day(next) {
+                next = start_of_day(next) + Duration::days(1);
+                continue;
+            }
+
+            if !self.hours.matches(next.hour()) {
+                next = truncate_hour(next) + Duration::hours(1);
+                continue;
+            }
+
+            if !self.minutes.matches(next.minute()) {
+                next += Duration::minutes(1);
+                continue;
+            }
+
+            return Some(next);
+        }
+
+        None
+    }
+
+    fn matches_day(&self, time: DateTime<Utc>) -> bool {
+        let dom = self.days_of_month.matches(time.day());
+        let dow = self
+            .days_of_week
+            .matches(time.weekday().num_days_from_sunday());
+
+        match (self.days_of_month.wildcard, self.days_of_week.wildcard) {
+            (true, true) => true,
+            (false, true) => dom,
+            (true, false) => dow,
+            (false, false) => dom || dow,
+        }
+    }
+}
+
+/// Field of a cron expression, with matching values as a bit set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Field {
+    values: u64,
+    wildcard: bool,
+}
+
+impl Field {
+    fn parse(field: &str, min: u32, max: u32) -> Result<Field, CronError> {
+        let mut values = 0u64;
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => {
+                    let step = parse_value(step, 1, max)?;
+                    (range, step)
+                }
+                None => (part, 1),
+            };
+
+            let (from, to) = if range == "*" {
+                (min, max)
+            } else if let Some((from, to)) = range.split_once('-') {
+                (parse_value(from, min, max)?, parse_value(to, min, max)?)
+            } else {
+                let value = parse_value(range, min, max)?;
+                if step > 1 {
+                    // a step on a single value goes up to the maximum (ex: `5/10`)
+                    (value, max)
+                } else {
+                    (value, value)
+                }
+            };
+
+            if from > to {
+                return Err(CronError::InvalidRange(range.to_string()));
+            }
+
+            for value in (from..=to).step_by(step as usize) {
+                values |= 1 << value;
+            }
+        }
+
+        Ok(Field {
+            values,
+            wildcard: field == "*",
+        })
+    }
+
+    fn matches(&self, value: u32) -> bool {
+        self.values & (1 << value) != 0
+    }
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32, CronError> {
+    let parsed = value
+        .parse::<u32>()
+        .map_err(|_| CronError::InvalidValue(value.to_string()))?;
+
+    if parsed < min || parsed > max {
+        return Err(CronError::InvalidValue(value.to_string()));
+    }
+
+    Ok(parsed)
+}
+
+fn truncate_minute(time: DateTime<Utc>) -> DateTime<Utc> {
+    truncate_hour(time) + Duration::minutes(time.minute() as i64)
+}
+
+fn truncate_hour(time: DateTime<Utc>) -> DateTime<Utc> {
+    start_of_day(time) + Duration::hours(time.hour() as i64)
+}
+
+fn start_of_day(time: DateTime<Utc>) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(time.year(), time.month(), time.day(), 0, 0, 0)
+        .unwrap()
+}
+
+fn start_of_next_month(time: DateTime<Utc>) -> DateTime<Utc> {
+    let (year, month) = if time.month() == 12 {
+        (time.year() + 1, 1)
+    } else {
+        (time.year(), time.month() + 1)
+    };
+
+    Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CronError {
+    #[error("Expected 5 fields in cron expression, got {0}")]
+    FieldCount(usize),
+
+    #[error("Invalid value '{0}' in cron expression")]
+    InvalidValue(String),
+
+    #[error("Invalid range '{0}' in cron expression")]
+    InvalidRange(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_invalid() {
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("* 24 * * *").is_err());
+        assert!(CronSchedule::parse("* * 0 * *").is_err());
+        assert!(CronSchedule::parse("* * * 13 *").is_err());
+        assert!(CronSchedule::parse("* * * * 8").is_err());
+        assert!(CronSchedule::parse("10-5 * * * *").is_err());
+        assert!(CronSchedule::parse("*/a * * * *").is_err());
+    }
+
+    #[test]
+    fn next_after() {
+        let time = date(2024, 3, 15, 10, 30, 20);
+
+        let every_minute = CronSchedule::parse("* * * * *").unwrap();
+        assert_eq!(
+            every_minute.next_after(time),
+            Some(date(2024, 3, 15, 10, 31, 0))
+        );
+
+        let every_15_minutes = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            every_15_minutes.next_after(time),
+            Some(date(2024, 3, 15, 10, 45, 0))
+        );
+
+        let daily = CronSchedule::parse("0 8 * * *").unwrap();
+        assert_eq!(daily.next_after(time), Some(date(2024, 3, 16, 8, 0, 0)));
+
+        let list = CronSchedule::parse("0 9,17 * * *").unwrap();
+        assert_eq!(list.next_after(time), Some(date(2024, 3, 15, 17, 0, 0)));
+
+        let first_of_month = CronSchedule::parse("0 0 1 * *").unwrap();
+        assert_eq!(
+            first_of_month.next_after(time),
+            Some(date(2024, 4, 1, 0, 0, 0))
+        );
+
+        let new_year = CronSchedule::parse("0 0 1 1 *").unwrap();
+        assert_eq!(new_year.next_after(time), Some(date(2025, 1, 1, 0, 0, 0)));
+
+        // 2024-03-15 is a friday
+        let weekdays = CronSchedule::parse("0 8 * * 1-5").unwrap();
+        assert_eq!(weekdays.next_after(time), Some(date(2024, 3, 18, 8, 0, 0)));
+
+        let sunday = CronSchedule::parse("0 8 * * 7").unwrap();
+        assert_eq!(sunday.next_after(time), Some(date(2024, 3, 17, 8, 0, 0)));
+
+        // day of month or day of week
+        let dom_or_dow = CronSchedule::parse("0 0 20 * 0").unwrap();
+        assert_eq!(
+            dom_or_dow.next_after(time),
+            Some(date(2024, 3, 17, 0, 0, 0))
+        );
+
+        let leap_day = CronSchedule::parse("0 0 29 2 *").unwrap();
+        assert_eq!(leap_day.next_after(time), Some(date(2028, 2, 29, 0, 0, 0)));
+
+        let never = CronSchedule::parse("0 0 31 2 *").unwrap();
+        assert_eq!(never.next_after(time), None);
+    }
+
+    fn date(year: i32, month: u32, day: u32, hour: u32, min: u32, sec: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, min, sec)
+            .unwrap()
+    }
+}
@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use exocore_protos::{
+    apps::{
+        in_message::InMessageType, scheduled_job::Schedule as ScheduleProto, InMessage,
+        ScheduledJob, ScheduledJobRun,
+    },
+    prost::{Message, ProstDateTimeExt},
+};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::Error;
+
+mod cron;
+pub use cron::{CronError, CronSchedule};
+
+/// Id of the trait of the application's state in which the time of the last
+/// run of each job is stored. See `AppStateStore`.
+pub const STATE_TRAIT: &str = "scheduler";
+
+/// Name of the file in the application's state directory in which the state
+/// was persisted before being stored in the cell's store. Only read to migrate
+/// it.
+pub const STATE_FILE: &str = "scheduler.json";
+
+/// Maximum interval between runs of a job on an interval.
+pub const MAX_INTERVAL_SECS: u64 = 365 * 24 * 60 * 60;
+
+/// Schedules the jobs registered by an application and keeps track of their
+/// last runs so that runs missed while the application wasn't running are
+/// caught up once it starts again.
+///
+/// The scheduler doesn't have any timer. It is polled by its owner with the
+/// current time to get the runs that are due, which are sent to the
+/// application as `ScheduledJobRun` messages. A job isn't run again until the
+/// application reports its run as done, and its last run is only recorded in
+/// the state at that point, so that runs interrupted by a restart are run
+/// again.
+///
+/// The scheduler doesn't persist its state itself. Its owner persists it once
+/// a run is completed, in the cell's store so that jobs that already ran
+/// aren't run again by the application host node taking over the application.
+///
+/// Multiple missed runs of a job are collapsed into a single run.
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: HashMap<String, Job>,
+    running: HashMap<u32, Run>,
+    next_rendez_vous_id: u32,
+    state: SchedulerState,
+}
+
+impl Scheduler {
+    /// Creates a scheduler from its previously persisted state.
+    pub fn new(state: SchedulerState) -> Scheduler {
+        Scheduler {
+            state,
+            ..Default::default()
+        }
+    }
+
+    pub fn state(&self) -> &SchedulerState {
+        &self.state
+    }
+
+    /// Registers a job, or replaces the schedule of an already registered
+    /// one.
+    ///
+    /// If the job has run before, its next run is computed from its last run,
+    /// and is therefore due right away if it was missed. Otherwise, jobs on an
+    /// interval are run right away and jobs on a cron expression at their next
+    /// occurrence.
+    pub fn register(&mut self, job: ScheduledJob, now: DateTime<Utc>) -> Result<(), Error> {
+        let schedule = match job.schedule {
+            Some(ScheduleProto::IntervalSecs(0)) | None => {
+                return Err(anyhow!("Job '{}' doesn't have a valid schedule", job.name).into());
+            }
+            Some(ScheduleProto::IntervalSecs(secs)) => {
+                let interval = (secs <= MAX_INTERVAL_SECS)
+                    .then(|| chrono::Duration::try_seconds(secs as i64))
+                    .flatten()
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Job '{}' has an interval of {} secs, which is over the maximum of {} secs",
+                            job.name,
+                            secs,
+                            MAX_INTERVAL_SECS
+                        )
+                    })?;
+                Schedule::Interval(interval)
+            }
+            Some(ScheduleProto::Cron(expr)) => Schedule::Cron(
+                CronSchedule::parse(&expr)
+                    .map_err(|err| anyhow!("Job '{}' has an invalid cron: {}", job.name, err))?,
+            ),
+        };
+
+        let next_run = match (self.state.jobs.get(&job.name), &schedule) {
+            (Some(job_state), schedule) => schedule.next_after(job_state.last_run),
+            (None, Schedule::Interval(_)) => Some(now),
+            (None, Schedule::Cron(cron)) => cron.next_after(now),
+        };
+
+        let running = self.running.values().any(|run| run.job_name == job.name);
+        self.jobs.insert(
+            job.name,
+            Job {
+                schedule,
+                next_run,
+                running,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Returns the runs of jobs that are due, to be sent to the application.
+    pub fn poll_due(&mut self, now: DateTime<Utc>) -> Vec<InMessage> {
+        let mut messages = Vec::new();
+        for (name, job) in &mut self.jobs {
+            let due = job.next_run.is_some_and(|next_run| next_run <= now);
+            if job.running || !due {
+                continue;
+            }
+
+            let rendez_vous_id = self.next_rendez_vous_id;
+            self.next_rendez_vous_id = self.next_rendez_vous_id.wrapping_add(1);
+
+            job.running = true;
+            self.running.insert(
+                rendez_vous_id,
+                Run {
+                    job_name: name.clone(),
+                    time: now,
+                },
+            );
+
+            let run = ScheduledJobRun {
+                name: name.clone(),
+                time: Some(now.to_proto_timestamp()),
+            };
+            messages.push(InMessage {
+                r#type: InMessageType::ScheduledJobRun.into(),
+                rendez_vous_id,
+                data: run.encode_to_vec(),
+                error: String::new(),
+            });
+        }
+
+        messages
+    }
+
+    /// Marks the run with the given rendez-vous id as done, recording it as
+    /// the last run of its job. Returns `true` if the state changed and needs
+    /// to be persisted.
+    pub fn complete(&mut self, rendez_vous_id: u32) -> bool {
+        let Some(run) = self.running.remove(&rendez_vous_id) else {
+            warn!("Got completion of an unknown job run {}", rendez_vous_id);
+            return false;
+        };
+
+        if let Some(job) = self.jobs.get_mut(&run.job_name) {
+            job.running = false;
+            job.next_run = job.schedule.next_after(run.time);
+        }
+
+        self.state
+            .jobs
+            .insert(run.job_name, JobState { last_run: run.time });
+        true
+    }
+
+    /// Returns the time at which the next job is due, if any.
+    pub fn next_run_time(&self) -> Option<DateTime<Utc>> {
+        self.jobs
+            .values()
+            .filter(|job| !job.running)
+            .filter_map(|job| job.next_run)
+            .min()
+    }
+}
+
+struct Job {
+    schedule: Schedule,
+    next_run: Option<DateTime<Utc>>,
+    running: bool,
+}
+
+struct Run {
+    job_name: String,
+    time: DateTime<Utc>,
+}
+
+enum Schedule {
+    Interval(chrono::Duration),
+    Cron(CronSchedule),
+}
+
+impl Schedule {
+    fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Interval(interval) => time.checked_add_signed(*interval),
+            Schedule::Cron(cron) => cron.next_after(time),
+        }
+    }
+}
+
+/// Last runs of the jobs of a scheduler.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SchedulerState {
+    jobs: HashMap<String, JobState>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct JobState {
+    last_run: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    #[test]
+    fn run_interval_job() {
+        let mut scheduler = Scheduler::default();
+        let now = Utc.with_ymd_and_hms(2024, 3, 15, 10, 0, 0).unwrap();
+
+        scheduler.register(interval_job("job", 60), now).unwrap();
+
+        // never ran, so should run right away
+        assert_eq!(scheduler.next_run_time(), Some(now));
+        let runs = scheduler.poll_due(now);
+        assert_eq!(runs.len(), 1);
+        let run = ScheduledJobRun::decode(runs[0].data.as_ref()).unwrap();
+        assert_eq!(run.name, "job");
+
+        // shouldn't run again until it's done
+        assert!(scheduler.poll_due(now + Duration::seconds(120)).is_empty());
+        assert_eq!(scheduler.next_run_time(), None);
+
+        scheduler.complete(runs[0].rendez_vous_id);
+        assert_eq!(scheduler.next_run_time(), Some(now + Duration::seconds(60)));
+        assert!(scheduler.poll_due(now + Duration::seconds(30)).is_empty());
+        assert_eq!(scheduler.poll_due(now + Duration::seconds(60)).len(), 1);
+    }
+
+    #[test]
+    fn run_cron_job() {
+        let mut scheduler = Scheduler::default();
+        let now = Utc.with_ymd_and_hms(2024, 3, 15, 10, 30, 0).unwrap();
+
+        scheduler
+            .register(cron_job("job", "0 * * * *"), now)
+            .unwrap();
+
+        // should wait for the next occurrence
+        let next_hour = Utc.with_ymd_and_hms(2024, 3, 15, 11, 0, 0).unwrap();
+        assert_eq!(scheduler.next_run_time(), Some(next_hour));
+        assert!(scheduler.poll_due(now).is_empty());
+        assert_eq!(scheduler.poll_due(next_hour).len(), 1);
+    }
+
+    #[test]
+    fn invalid_job() {
+        let mut scheduler = Scheduler::default();
+        let now = Utc::now();
+
+        assert!(scheduler.register(interval_job("job", 0), now).is_err());
+        assert!(scheduler
+            .register(interval_job("job", MAX_INTERVAL_SECS + 1), now)
+            .is_err());
+        assert!(scheduler
+            .register(interval_job("job", u64::MAX), now)
+            .is_err());
+        assert!(scheduler.register(cron_job("job", "* *"), now).is_err());
+
+        let no_schedule = ScheduledJob {
+            name: "job".to_string(),
+            schedule: None,
+        };
+        assert!(scheduler.register(no_schedule, now).is_err());
+    }
+
+    #[test]
+    fn max_interval_job() {
+        let mut scheduler = Scheduler::default();
+        let now = Utc.with_ymd_and_hms(2024, 3, 15, 10, 0, 0).unwrap();
+
+        scheduler
+            .register(interval_job("job", MAX_INTERVAL_SECS), now)
+            .unwrap();
+        let runs = scheduler.poll_due(now);
+        assert!(scheduler.complete(runs[0].rendez_vous_id));
+        assert_eq!(scheduler.next_run_time(), Some(now + Duration::days(365)));
+
+        // next run past the maximum date shouldn't overflow
+        let mut scheduler = Scheduler::default();
+        scheduler
+            .register(
+                interval_job("job", MAX_INTERVAL_SECS),
+                DateTime::<Utc>::MAX_UTC,
+            )
+            .unwrap();
+        let runs = scheduler.poll_due(DateTime::<Utc>::MAX_UTC);
+        assert!(scheduler.complete(runs[0].rendez_vous_id));
+        assert_eq!(scheduler.next_run_time(), None);
+    }
+
+    #[test]
+    fn catch_up_missed_runs() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 15, 10, 0, 0).unwrap();
+
+        let state = {
+            let mut scheduler = Scheduler::default();
+            scheduler.register(interval_job("job", 3600), now).unwrap();
+            let runs = scheduler.poll_due(now);
+            assert!(scheduler.complete(runs[0].rendez_vous_id));
+            persisted(scheduler.state())
+        };
+
+        // after downtime of multiple intervals, job should only run once right away
+        let later = now + Duration::hours(5);
+        let mut scheduler = Scheduler::new(state);
+        scheduler
+            .register(interval_job("job", 3600), later)
+            .unwrap();
+        assert_eq!(scheduler.next_run_time(), Some(now + Duration::hours(1)));
+
+        let runs = scheduler.poll_due(later);
+        assert_eq!(runs.len(), 1);
+        scheduler.complete(runs[0].rendez_vous_id);
+        assert_eq!(scheduler.next_run_time(), Some(later + Duration::hours(1)));
+    }
+
+    #[test]
+    fn interrupted_run_is_run_again() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 15, 10, 0, 0).unwrap();
+
+        let state = {
+            let mut scheduler = Scheduler::default();
+            scheduler.register(interval_job("job", 3600), now).unwrap();
+            assert_eq!(scheduler.poll_due(now).len(), 1);
+            persisted(scheduler.state())
+        };
+
+        // run was never completed, so it should be run again
+        let mut scheduler = Scheduler::new(state);
+        scheduler.register(interval_job("job", 3600), now).unwrap();
+        assert_eq!(scheduler.poll_due(now).len(), 1);
+    }
+
+    fn persisted(state: &SchedulerState) -> SchedulerState {
+        let data = serde_json::to_vec(state).unwrap();
+        serde_json::from_slice(&data).unwrap()
+    }
+
+    fn interval_job(name: &str, secs: u64) -> ScheduledJob {
+        ScheduledJob {
+            name: name.to_string(),
+            schedule: Some(ScheduleProto::IntervalSecs(secs)),
+        }
+    }
+
+    fn cron_job(name: &str, expr: &str) -> ScheduledJob {
+        ScheduledJob {
+            name: name.to_string(),
+            schedule: Some(ScheduleProto::Cron(expr.to_string())),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::{collections::HashMap, future::Future};
+
+use anyhow::anyhow;
+use exocore_core::cell::ApplicationId;
+use exocore_protos::{
+    apps::AppState,
+    prost::{Message, ProstAnyPackMessageExt},
+    store::Trait,
+    NamedMessage,
+};
+use exocore_store::{mutation::MutationBuilder, query::QueryBuilder, store::Store};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Error;
+
+/// Persists the state kept by the applications host for an application (ex:
+/// last runs of scheduled jobs, changes handled by triggers) in the cell's
+/// store, as traits of an entity reserved to the application. The key-value
+/// storage of the application isn't part of it, see `KvStore`.
+///
+/// Since the store is replicated, the state is available to the application
+/// host node taking over the application when the node running it goes down.
+/// Writes wait for the state to be indexed, so that it can be read back right
+/// away. There is no fencing: if two nodes run the application at the same
+/// time (ex: during a network partition), the last write wins.
+#[derive(Clone)]
+pub struct AppStateStore<S: Store> {
+    store: S,
+    entity_id: String,
+}
+
+impl<S: Store> AppStateStore<S> {
+    pub fn new(store: S, app_id: &ApplicationId) -> AppStateStore<S> {
+        AppStateStore {
+            store,
+            entity_id: format!("exocore_app_state_{}", app_id),
+        }
+    }
+
+    /// Reads all the state of the application, by trait id.
+    // The returned futures don't borrow `self`, since stores aren't `Sync`.
+    pub fn read_all(&self) -> impl Future<Output = Result<HashMap<String, Vec<u8>>, Error>> {
+        let query = QueryBuilder::with_id(self.entity_id.clone())
+            .programmatic()
+            .build();
+        let store = self.store.clone();
+        async move {
+            let results = store.query(query).await?;
+
+            let mut states = HashMap::new();
+            let traits = results
+                .entities
+                .into_iter()
+                .filter_map(|result| result.entity)
+                .flat_map(|entity| entity.traits);
+            for trt in traits {
+                let Some(message) = trt.message else {
+                    continue;
+                };
+                if message.type_url != AppState::protobuf_any_url() {
+                    continue;
+                }
+
+                let state = AppState::decode(message.value.as_slice())
+                    .map_err(|err| anyhow!("Couldn't decode state '{}': {}", trt.id, err))?;
+                states.insert(trt.id, state.data);
+            }
+
+            Ok(states)
+        }
+    }
+
+    pub fn write(&self, trait_id: &str, data: Vec<u8>) -> impl Future<Output = Result<(), Error>> {
+        let trt = AppState { data }
+            .pack_to_any()
+            .map_err(|err| anyhow!("Couldn't pack state '{}': {}", trait_id, err))
+            .map(|message| Trait {
+                id: trait_id.to_string(),
+                message: Some(message),
+                ..Default::default()
+            });
+        let entity_id = self.entity_id.clone();
+        let store = self.store.clone();
+        async move {
+            let mut mutation = MutationBuilder::new().put_trait(entity_id, trt?).build();
+            mutation.wait_indexed = true;
+            store.mutate(mutation).await?;
+            Ok(())
+        }
+    }
+
+    pub fn write_json<T: Serialize>(
+        &self,
+        trait_id: &str,
+        state: &T,
+    ) -> impl Future<Output = Result<(), Error>> {
+        let data = serde_json::to_vec(state)
+            .map_err(|err| anyhow!("Couldn't serialize state '{}': {}", trait_id, err));
+        let write = data.map(|data| self.write(trait_id, data));
+        async move { write?.await }
+    }
+
+    pub fn delete(&self, trait_id: &str) -> impl Future<Output = Result<(), Error>> {
+        let mut mutation = MutationBuilder::new()
+            .delete_trait(self.entity_id.clone(), trait_id)
+            .build();
+        mutation.wait_indexed = true;
+        let store = self.store.clone();
+        async move {
+            store.mutate(mutation).await?;
+            Ok(())
+        }
+    }
+}
+
+/// Decodes a JSON state read by `AppStateStore::read_all`.
+pub fn decode_json<T: DeserializeOwned>(trait_id: &str, data: &[u8]) -> Result<T, Error> {
+    let state = serde_json::from_slice(data)
+        .map_err(|err| anyhow!("Couldn't deserialize state '{}': {}", trait_id, err))?;
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use exocore_core::cell::ApplicationId;
+    use exocore_store::local::TestStore;
+
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn write_read_delete() -> anyhow::Result<()> {
+        let mut test_store = TestStore::new().await?;
+        test_store.start_store().await?;
+
+        let app_id = ApplicationId::from_string("app".to_string());
+        let state_store = AppStateStore::new(test_store.store_handle.clone(), &app_id);
+        assert!(state_store.read_all().await?.is_empty());
+
+        state_store.write("a", b"hello".to_vec()).await?;
+        state_store.write_json("b", &vec![1, 2]).await?;
+        let states = state_store.read_all().await?;
+        assert_eq!(states.get("a").unwrap(), b"hello");
+        let b: Vec<i32> = decode_json("b", states.get("b").unwrap())?;
+        assert_eq!(b, vec![1, 2]);
+
+        state_store.delete("a").await?;
+        let states = state_store.read_all().await?;
+        assert!(!states.contains_key("a"));
+        assert!(states.contains_key("b"));
+
+        Ok(())
+    }
+}
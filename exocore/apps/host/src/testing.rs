@@ -0,0 +1,533 @@
+//! Utilities to test applications' logic by running their WASM module against
+//! an in-memory store, with a mocked clock driven by the test.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use exocore_core::{cell::Application, dir::os::OsDirectory, time::Clock};
+use exocore_protos::{
+    apps::{
+        in_message::InMessageType, out_message::OutMessageType, ClientMessage, ClientRequest,
+        ClientResponse, HttpRequest, InMessage, KvRequest, Manifest, OutMessage, ScheduledJob,
+    },
+    prost::Message,
+    reflect::FileDescriptorSet,
+    store::{EntityQuery, EntityResults, MutationRequest, MutationResult},
+};
+use exocore_store::{
+    local::{StoreConfig, TestStore},
+    mutation::MutationRequestLike,
+    store::Store,
+};
+
+use crate::{
+    http::HttpClient,
+    kv::KvStore,
+    runtime::wasmtime::{HostEnvironment, WasmTimeRuntime},
+    scheduler::Scheduler,
+    triggers::{Triggers, TriggersState},
+    Error,
+};
+
+/// Maximum number of times the application is ticked in a single harness tick
+/// to reply to the requests it sent, to prevent looping forever on an
+/// application that keeps sending requests.
+const MAX_TICK_ROUNDS: usize = 100;
+
+/// Runs an application's WASM module against a `TestStore`, for applications
+/// to unit test their logic.
+///
+/// The application only gets executed when the test ticks it. Store requests
+/// sent by the application are executed against the store, HTTP requests are
+/// executed for real (to a local stub server, preferably), key-value requests
+/// are executed against an in-memory storage, and all are replied to during
+/// the tick. Messages published to clients are recorded, and the test can send
+/// messages and requests as a client. Scheduled jobs that are due are run and
+/// entities matching the application's triggers are delivered. The
+/// application's clock is mocked and only moves forward when the test
+/// advances it.
+pub struct AppTestHarness {
+    runtime: WasmTimeRuntime<TestEnvironment>,
+    env: Arc<TestEnvironment>,
+    store: TestStore,
+    scheduler: Scheduler,
+    triggers: Triggers,
+    http_client: HttpClient,
+    kv_store: KvStore,
+    clock: Clock,
+    queries: Vec<EntityQuery>,
+    mutations: Vec<MutationRequest>,
+    http_requests: Vec<HttpRequest>,
+    client_publications: Vec<ClientMessage>,
+    client_responses: HashMap<u32, ClientResponse>,
+    next_client_request: u32,
+}
+
+impl AppTestHarness {
+    /// Loads the application from its directory, registering its schemas in
+    /// the store and running the module defined in its manifest, with its
+    /// triggers and allowed HTTP domains.
+    pub async fn from_directory<P: AsRef<Path>>(dir: P) -> Result<AppTestHarness, Error> {
+        let dir = dir.as_ref();
+        let app = Application::from_directory(OsDirectory::new(dir.to_path_buf()))
+            .map_err(|err| anyhow!("Couldn't load application: {}", err))?;
+
+        let module = app
+            .manifest()
+            .module
+            .as_ref()
+            .ok_or_else(|| anyhow!("Application doesn't have a module"))?;
+
+        Self::new(dir.join(&module.file), app.schemas(), app.manifest()).await
+    }
+
+    /// Runs the given WASM module, without registering any application
+    /// schemas, triggers nor allowed HTTP domains.
+    pub async fn from_module<P: AsRef<Path>>(module_path: P) -> Result<AppTestHarness, Error> {
+        Self::new(
+            module_path.as_ref().to_path_buf(),
+            &[],
+            &Manifest::default(),
+        )
+        .await
+    }
+
+    async fn new(
+        module_path: PathBuf,
+        schemas: &[FileDescriptorSet],
+        manifest: &Manifest,
+    ) -> Result<AppTestHarness, Error> {
+        if !module_path.exists() {
+            return Err(anyhow!(
+                "Module file {:?} doesn't exist. Is the application built?",
+                module_path
+            )
+            .into());
+        }
+
+        let mut store = TestStore::new_with_schemas(
+            StoreConfig::default(),
+            TestStore::test_index_config(),
+            schemas,
+        )
+        .await?;
+        store.start_store().await?;
+
+        let triggers = Triggers::new(&manifest.triggers, TriggersState::default())?;
+        let http_client = HttpClient::new(&manifest.http_domains)?;
+        let clock = Clock::new_fixed_mocked(Instant::now());
+        let env = Arc::new(TestEnvironment::default());
+        let runtime = WasmTimeRuntime::from_file(module_path, env.clone(), clock.clone())?;
+
+        Ok(AppTestHarness {
+            runtime,
+            env,
+            store,
+            scheduler: Scheduler::default(),
+            triggers,
+            http_client,
+            kv_store: KvStore::new_in_memory(),
+            clock,
+            queries: Vec::new(),
+            mutations: Vec::new(),
+            http_requests: Vec::new(),
+            client_publications: Vec::new(),
+            client_responses: HashMap::new(),
+            next_client_request: 0,
+        })
+    }
+
+    /// Mocked clock of the application.
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// Key-value storage of the application.
+    pub fn kv_store(&self) -> &KvStore {
+        &self.kv_store
+    }
+
+    /// Key-value storage of the application, to seed it before ticking the
+    /// application.
+    pub fn kv_store_mut(&mut self) -> &mut KvStore {
+        &mut self.kv_store
+    }
+
+    /// Executes a mutation on the store, bypassing the application, and waits
+    /// for it to be committed so that the application can query it.
+    pub async fn seed<M: Into<MutationRequestLike> + Send>(
+        &mut self,
+        mutation: M,
+    ) -> Result<MutationResult, Error> {
+        let result = self.store.mutate(mutation).await?;
+        self.wait_committed(&result);
+
+        Ok(result)
+    }
+
+    /// Executes a query on the store, bypassing the application.
+    pub async fn query(&mut self, query: EntityQuery) -> Result<EntityResults, Error> {
+        Ok(self.store.query(query).await?)
+    }
+
+    /// Ticks the application, replying to the store, HTTP and key-value
+    /// requests it sent, running its due scheduled jobs and delivering
+    /// entities matching its triggers until it doesn't send any new requests.
+    ///
+    /// Returns the duration after which the application expects to be ticked
+    /// again, if any.
+    pub async fn tick(&mut self) -> Result<Option<Duration>, Error> {
+        let mut next_tick = self.runtime.tick()?;
+
+        for _ in 0..MAX_TICK_ROUNDS {
+            let messages = self.env.take_messages();
+            let mut replies = Vec::new();
+            for message in messages {
+                if let Some(reply) = self.handle_message(message).await {
+                    replies.push(reply);
+                }
+            }
+            replies.extend(self.scheduler.poll_due(self.clock.now_chrono()));
+            replies.extend(self.poll_triggers().await?);
+
+            if replies.is_empty() {
+                return Ok(self.next_tick(next_tick));
+            }
+
+            for reply in replies {
+                self.runtime.send_message(reply)?;
+            }
+
+            next_tick = self.runtime.tick()?;
+        }
+
+        Err(anyhow!(
+            "Application still sending requests after {} ticks",
+            MAX_TICK_ROUNDS
+        )
+        .into())
+    }
+
+    /// Advances the mocked clock of the application and ticks it.
+    pub async fn advance(&mut self, duration: Duration) -> Result<Option<Duration>, Error> {
+        self.clock.add_fixed_instant_duration(duration);
+        self.tick().await
+    }
+
+    /// Queries sent by the application to the store, in order.
+    pub fn queries(&self) -> &[EntityQuery] {
+        &self.queries
+    }
+
+    /// Mutations sent by the application to the store, in order.
+    pub fn mutations(&self) -> &[MutationRequest] {
+        &self.mutations
+    }
+
+    /// HTTP requests sent by the application, in order.
+    pub fn http_requests(&self) -> &[HttpRequest] {
+        &self.http_requests
+    }
+
+    /// Messages published to clients by the application, in order.
+    pub fn client_publications(&self) -> &[ClientMessage] {
+        &self.client_publications
+    }
+
+    /// Clears the queries, mutations, HTTP requests and client publications
+    /// sent by the application so far.
+    pub fn clear_requests(&mut self) {
+        self.queries.clear();
+        self.mutations.clear();
+        self.http_requests.clear();
+        self.client_publications.clear();
+    }
+
+    /// Sends a message on a topic to the application, as a client would, and
+    /// ticks it.
+    pub async fn send_client_message(
+        &mut self,
+        topic: &str,
+        data: Vec<u8>,
+    ) -> Result<Option<Duration>, Error> {
+        let message = ClientMessage {
+            topic: topic.to_string(),
+            data,
+            ..Default::default()
+        };
+        self.runtime.send_message(InMessage {
+            r#type: InMessageType::ClientMessage.into(),
+            data: message.encode_to_vec(),
+            ..Default::default()
+        })?;
+
+        self.tick().await
+    }
+
+    /// Sends a request to a method of the application, as a client would, and
+    /// ticks it until it responds.
+    pub async fn client_request(
+        &mut self,
+        method: &str,
+        data: Vec<u8>,
+    ) -> Result<ClientResponse, Error> {
+        let rendez_vous_id = self.next_client_request;
+        self.next_client_request += 1;
+
+        let request = ClientRequest {
+            method: method.to_string(),
+            data,
+            ..Default::default()
+        };
+        self.runtime.send_message(InMessage {
+            r#type: InMessageType::ClientRequest.into(),
+            rendez_vous_id,
+            data: request.encode_to_vec(),
+            ..Default::default()
+        })?;
+
+        self.tick().await?;
+
+        self.client_responses
+            .remove(&rendez_vous_id)
+            .ok_or_else(|| anyhow!("Application didn't respond to request to '{}'", method).into())
+    }
+
+    /// Messages logged by the application, in order.
+    pub fn logs(&self) -> Vec<(log::Level, String)> {
+        self.env.logs.lock().unwrap().clone()
+    }
+
+    /// Finds the first message logged by the application containing the
+    /// needle.
+    pub fn find_log(&self, needle: &str) -> Option<String> {
+        let logs = self.env.logs.lock().unwrap();
+        logs.iter()
+            .find(|(_level, msg)| msg.contains(needle))
+            .map(|(_level, msg)| msg.clone())
+    }
+
+    /// Last message logged by the application.
+    pub fn last_log(&self) -> Option<String> {
+        let logs = self.env.logs.lock().unwrap();
+        logs.last().map(|(_level, msg)| msg.clone())
+    }
+
+    /// Duration after which either the application or the scheduler expects
+    /// to be ticked again.
+    fn next_tick(&self, runtime_next_tick: Option<Duration>) -> Option<Duration> {
+        let now = self.clock.now_chrono();
+        let scheduler_next_tick = self
+            .scheduler
+            .next_run_time()
+            .map(|time| (time - now).to_std().unwrap_or_default());
+
+        match (runtime_next_tick, scheduler_next_tick) {
+            (Some(runtime), Some(scheduler)) => Some(runtime.min(scheduler)),
+            (runtime, scheduler) => runtime.or(scheduler),
+        }
+    }
+
+    async fn handle_message(&mut self, message: OutMessage) -> Option<InMessage> {
+        let mut reply = InMessage {
+            rendez_vous_id: message.rendez_vous_id,
+            ..Default::default()
+        };
+
+        let res = match OutMessageType::try_from(message.r#type) {
+            Ok(OutMessageType::StoreEntityQuery) => {
+                reply.r#type = InMessageType::StoreEntityResults.into();
+                self.handle_entity_query(message).await
+            }
+            Ok(OutMessageType::StoreMutationRequest) => {
+                reply.r#type = InMessageType::StoreMutationResult.into();
+                self.handle_entity_mutation(message).await
+            }
+            Ok(OutMessageType::HttpRequest) => {
+                reply.r#type = InMessageType::HttpResponse.into();
+                self.handle_http_request(message).await
+            }
+            Ok(OutMessageType::KvRequest) => {
+                reply.r#type = InMessageType::KvResult.into();
+                self.handle_kv_request(message)
+            }
+            Ok(OutMessageType::SchedulerRegisterJob) => {
+                let res = ScheduledJob::decode(message.data.as_ref())
+                    .map_err(Error::from)
+                    .and_then(|job| self.scheduler.register(job, self.clock.now_chrono()));
+                if let Err(err) = res {
+                    error!("Couldn't register scheduled job: {}", err);
+                }
+                return None;
+            }
+            Ok(OutMessageType::ScheduledJobDone) => {
+                self.scheduler.complete(message.rendez_vous_id);
+                return None;
+            }
+            Ok(OutMessageType::EntityTriggerDone) => {
+                return self.triggers.complete(message.rendez_vous_id);
+            }
+            Ok(OutMessageType::ClientPublish) => {
+                match ClientMessage::decode(message.data.as_ref()) {
+                    Ok(msg) => self.client_publications.push(msg),
+                    Err(err) => error!("Couldn't decode client publication: {}", err),
+                }
+                return None;
+            }
+            Ok(OutMessageType::ClientResponse) => {
+                match ClientResponse::decode(message.data.as_ref()) {
+                    Ok(response) => {
+                        self.client_responses
+                            .insert(message.rendez_vous_id, response);
+                    }
+                    Err(err) => error!("Couldn't decode client response: {}", err),
+                }
+                return None;
+            }
+            other => {
+                error!(
+                    "Got an unknown message type {:?} with id {}",
+                    other, message.r#type
+                );
+                return None;
+            }
+        };
+
+        match res {
+            Ok(data) => reply.data = data,
+            Err(err) => reply.error = err.to_string(),
+        }
+
+        Some(reply)
+    }
+
+    /// Queries the store for entities matching the application's triggers,
+    /// and returns the ones to deliver.
+    async fn poll_triggers(&mut self) -> Result<Vec<InMessage>, Error> {
+        // the store isn't watched, so it may have changed since last tick
+        self.triggers.notify_all_changed();
+
+        // triggers are scanned in multiple queries, until they have changes to deliver
+        // or they are done
+        let mut messages = Vec::new();
+        loop {
+            let queries = self.triggers.queries();
+            if queries.is_empty() {
+                return Ok(messages);
+            }
+
+            for (trigger_name, query) in queries {
+                let results = self.store.store_handle.query(query).await?;
+                messages.extend(self.triggers.handle_results(&trigger_name, results));
+            }
+        }
+    }
+
+    async fn handle_entity_query(&mut self, message: OutMessage) -> Result<Vec<u8>, Error> {
+        let query = EntityQuery::decode(message.data.as_ref())?;
+        self.queries.push(query.clone());
+
+        let results = self.store.store_handle.query(query).await?;
+
+        Ok(results.encode_to_vec())
+    }
+
+    async fn handle_entity_mutation(&mut self, message: OutMessage) -> Result<Vec<u8>, Error> {
+        let mutation = MutationRequest::decode(message.data.as_ref())?;
+        self.mutations.push(mutation.clone());
+
+        let result = self.store.store_handle.mutate(mutation).await?;
+        self.wait_committed(&result);
+
+        Ok(result.encode_to_vec())
+    }
+
+    async fn handle_http_request(&mut self, message: OutMessage) -> Result<Vec<u8>, Error> {
+        let request = HttpRequest::decode(message.data.as_ref())?;
+        self.http_requests.push(request.clone());
+
+        let response = self.http_client.execute(request).await?;
+
+        Ok(response.encode_to_vec())
+    }
+
+    fn handle_kv_request(&mut self, message: OutMessage) -> Result<Vec<u8>, Error> {
+        let request = KvRequest::decode(message.data.as_ref())?;
+        let result = self.kv_store.handle_request(request)?;
+
+        Ok(result.encode_to_vec())
+    }
+
+    fn wait_committed(&self, result: &MutationResult) {
+        for operation_id in &result.operation_ids {
+            self.store
+                .cluster
+                .wait_operation_committed(0, *operation_id);
+        }
+    }
+}
+
+/// Environment capturing the messages and logs of the application.
+#[derive(Default)]
+struct TestEnvironment {
+    logs: Mutex<Vec<(log::Level, String)>>,
+    messages: Mutex<Vec<OutMessage>>,
+}
+
+impl TestEnvironment {
+    fn take_messages(&self) -> Vec<OutMessage> {
+        let mut messages = self.messages.lock().unwrap();
+        std::mem::take(&mut *messages)
+    }
+}
+
+impl HostEnvironment for TestEnvironment {
+    fn handle_message(&self, msg: OutMessage) {
+        let mut messages = self.messages.lock().unwrap();
+        messages.push(msg);
+    }
+
+    fn handle_log(&self, level: log::Level, msg: &str) {
+        log!(level, "WASM APP: {}", msg);
+        let mut logs = self.logs.lock().unwrap();
+        logs.push((level, msg.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exocore_core::tests_utils::find_test_fixture;
+
+    use super::*;
+
+    /// Runs the application defined in `exocore-apps-example` against the
+    /// store. See its `lib.rs` to follow the sequence.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn example_against_store() -> anyhow::Result<()> {
+        let example_path = find_test_fixture("fixtures/example.wasm");
+        let mut harness = AppTestHarness::from_module(example_path).await?;
+
+        // first tick should execute up to sleep, which doesn't end until the clock moves
+        let next_tick = harness.tick().await?;
+        assert!(next_tick.is_some());
+        assert!(harness.last_log().unwrap().contains("before sleep"));
+
+        harness.tick().await?;
+        assert!(harness.find_log("after sleep").is_none());
+
+        // advancing clock should wake up the app, which then mutates and queries the store
+        harness.advance(Duration::from_millis(100)).await?;
+        assert!(harness.find_log("after sleep").is_some());
+        assert_eq!(harness.mutations().len(), 1);
+        assert_eq!(harness.queries().len(), 1);
+        assert!(harness.find_log("query success").is_some());
+        assert_eq!(harness.last_log(), Some("task done".to_string()));
+
+        Ok(())
+    }
+}
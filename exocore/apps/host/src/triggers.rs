@@ -0,0 +1,672 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use anyhow::anyhow;
+use exocore_protos::{
+    apps::{in_message::InMessageType, EntityTriggered, InMessage, ManifestTrigger},
+    prost::Message,
+    store::{ordering_value, EntityQuery, EntityResult, EntityResults, Paging},
+};
+use exocore_store::{ordering::value_from_u64, query::QueryBuilder};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Id of the trait of the application's state in which the changes handled
+/// by each trigger are stored. See `AppStateStore`.
+pub const STATE_TRAIT: &str = "triggers";
+
+/// Name of the file in the application's state directory in which the state
+/// was persisted before being stored in the cell's store. Only read to migrate
+/// it.
+pub const STATE_FILE: &str = "triggers.json";
+
+/// Maximum number of entities fetched at once for a trigger.
+const QUERY_BATCH_SIZE: u32 = 50;
+
+/// Tracks the entity changes matching the triggers declared in an
+/// application's manifest, to be delivered to the application.
+///
+/// Changes are found by querying the store for entities having a trait of the
+/// trigger's type, ordered by the offset of the chain block in which the
+/// trait's last mutation got committed. Since blocks are indexed in order by
+/// the local node, a change replicated from another node always ends up after
+/// the changes that were already indexed, whatever its operation id. Each
+/// trigger therefore keeps the position of the last change it handled in the
+/// chain, and scans the changes after it.
+///
+/// Changes that aren't indexed in the chain yet are scanned separately, so
+/// that they get delivered without waiting for their block to be committed and
+/// indexed. The operation ids of the ones handled by the application are kept
+/// until they get indexed in the chain, so that they aren't delivered again.
+///
+/// Changes are only recorded as handled in the state once the application
+/// reports them as done, after which the owner of the triggers persists the
+/// state. Changes are delivered one at a time per trigger, in order, and are
+/// delivered again if the application restarts before handling them, which
+/// gives an at-least-once delivery.
+///
+/// Triggers are only scanned once they got notified that their trait type
+/// changed (see `watch_queries` and `notify_changed`), and when they are
+/// created.
+pub struct Triggers {
+    triggers: HashMap<String, Trigger>,
+    running: HashMap<u32, String>,
+    next_rendez_vous_id: u32,
+    state: TriggersState,
+}
+
+impl Triggers {
+    /// Creates the triggers of an application from their previously
+    /// persisted state.
+    pub fn new(triggers: &[ManifestTrigger], state: TriggersState) -> Result<Triggers, Error> {
+        let mut names = HashSet::new();
+        for trigger in triggers {
+            if trigger.name.is_empty() || trigger.trait_type.is_empty() {
+                return Err(
+                    anyhow!("Trigger '{}' needs a name and a trait type", trigger.name).into(),
+                );
+            }
+
+            if !names.insert(trigger.name.clone()) {
+                return Err(
+                    anyhow!("Trigger '{}' is declared more than once", trigger.name).into(),
+                );
+            }
+        }
+
+        let triggers = triggers
+            .iter()
+            .map(|trigger| {
+                let state = Trigger {
+                    trait_type: trigger.trait_type.clone(),
+                    queue: VecDeque::new(),
+                    running: None,
+                    scan: Scan::Chain(None),
+                    scanned_pending: HashSet::new(),
+                    changed: false,
+                };
+                (trigger.name.clone(), state)
+            })
+            .collect();
+
+        Ok(Triggers {
+            triggers,
+            running: HashMap::new(),
+            next_rendez_vous_id: 0,
+            state,
+        })
+    }
+
+    pub fn state(&self) -> &TriggersState {
+        &self.state
+    }
+
+    /// Returns the queries to watch on the store to get notified of changes
+    /// to the trait types of the triggers.
+    ///
+    /// For each trigger, the most recent changes are watched, as well as the
+    /// last changes indexed in the chain, which includes changes replicated
+    /// from other nodes.
+    pub fn watch_queries(&self) -> Vec<(String, EntityQuery)> {
+        self.triggers
+            .iter()
+            .flat_map(|(name, trigger)| {
+                let recent = QueryBuilder::with_trait_name(&trigger.trait_type)
+                    .order_by_operations(false)
+                    .count(QUERY_BATCH_SIZE)
+                    .programmatic()
+                    .build();
+                let indexed = QueryBuilder::with_trait_name(&trigger.trait_type)
+                    .order_by_block_offset(false)
+                    .count(QUERY_BATCH_SIZE)
+                    .programmatic()
+                    .build();
+                [(name.clone(), recent), (name.clone(), indexed)]
+            })
+            .collect()
+    }
+
+    /// Notifies that the trait type of the given trigger changed, which
+    /// scans it for changes once the current scan is done.
+    pub fn notify_changed(&mut self, trigger_name: &str) {
+        if let Some(trigger) = self.triggers.get_mut(trigger_name) {
+            trigger.changed = true;
+        }
+    }
+
+    /// Notifies that the trait types of all triggers may have changed.
+    pub fn notify_all_changed(&mut self) {
+        for trigger in self.triggers.values_mut() {
+            trigger.changed = true;
+        }
+    }
+
+    /// Returns the queries to execute on the store to find changes for the
+    /// triggers that need to be scanned and that don't have any pending
+    /// changes to deliver.
+    pub fn queries(&mut self) -> Vec<(String, EntityQuery)> {
+        let mut queries = Vec::new();
+        for (name, trigger) in &mut self.triggers {
+            if !trigger.is_idle() {
+                continue;
+            }
+
+            if trigger.scan == Scan::Done && trigger.changed {
+                trigger.scan = Scan::Chain(None);
+                trigger.changed = false;
+            }
+
+            let paging = match trigger.scan {
+                Scan::Done => continue,
+                Scan::Chain(after) => {
+                    let state = self.state.triggers.get(name);
+                    let after = after
+                        .or_else(|| state.and_then(|state| state.chain_position))
+                        .unwrap_or(ChainPosition::BEFORE_INDEXED);
+                    Paging {
+                        after_ordering_value: Some(value_from_u64(
+                            after.block_offset,
+                            after.operation_id,
+                        )),
+                        count: QUERY_BATCH_SIZE,
+                        ..Default::default()
+                    }
+                }
+                Scan::Pending(after_operation_id) => Paging {
+                    after_ordering_value: Some(value_from_u64(0, after_operation_id)),
+                    before_ordering_value: Some(value_from_u64(1, 0)),
+                    count: QUERY_BATCH_SIZE,
+                    ..Default::default()
+                },
+            };
+
+            let query = QueryBuilder::with_trait_name(&trigger.trait_type)
+                .order_by_block_offset(true)
+                .with_paging(paging)
+                .programmatic()
+                .build();
+            queries.push((name.clone(), query));
+        }
+
+        queries
+    }
+
+    /// Queues the changes found by a trigger's query that weren't handled
+    /// yet, and returns the first change to deliver to the application, if
+    /// any.
+    pub fn handle_results(
+        &mut self,
+        trigger_name: &str,
+        results: EntityResults,
+    ) -> Option<InMessage> {
+        let state = self
+            .state
+            .triggers
+            .entry(trigger_name.to_string())
+            .or_default();
+        let trigger = self.triggers.get_mut(trigger_name)?;
+
+        if !trigger.is_idle() {
+            // changes got queued since the query was made, they will be queried again once
+            // those are handled
+            return None;
+        }
+
+        let full_batch = results.entities.len() >= QUERY_BATCH_SIZE as usize;
+        match trigger.scan {
+            Scan::Chain(_) => {
+                let mut last_position = None;
+                for result in &results.entities {
+                    let position = ChainPosition::from_result(result);
+                    last_position = Some(position);
+
+                    // changes handled before being indexed in the chain only need to be
+                    // recorded as handled
+                    let deliver = !state.pending.contains(&position.operation_id);
+                    trigger.queue.push_back(Change {
+                        triggered: EntityTriggered {
+                            trigger: trigger_name.to_string(),
+                            entity: result.entity.clone(),
+                            operation_id: position.operation_id,
+                        },
+                        position: Position::Chain(position),
+                        deliver,
+                    });
+                }
+
+                // a full batch means that there may be more changes after the last one
+                trigger.scan = match last_position {
+                    Some(position) if full_batch => Scan::Chain(Some(position)),
+                    _ => {
+                        trigger.scanned_pending.clear();
+                        Scan::Pending(0)
+                    }
+                };
+            }
+            Scan::Pending(_) => {
+                let mut last_operation_id = None;
+                for result in &results.entities {
+                    let operation_id = ChainPosition::from_result(result).operation_id;
+                    last_operation_id = Some(operation_id);
+                    trigger.scanned_pending.insert(operation_id);
+
+                    if state.pending.contains(&operation_id) {
+                        continue;
+                    }
+
+                    trigger.queue.push_back(Change {
+                        triggered: EntityTriggered {
+                            trigger: trigger_name.to_string(),
+                            entity: result.entity.clone(),
+                            operation_id,
+                        },
+                        position: Position::Pending(operation_id),
+                        deliver: true,
+                    });
+                }
+
+                trigger.scan = match last_operation_id {
+                    Some(operation_id) if full_batch => Scan::Pending(operation_id),
+                    _ => {
+                        // changes that left the pending index without being seen in the chain
+                        // won't be indexed in it anymore
+                        state
+                            .pending
+                            .retain(|operation_id| trigger.scanned_pending.contains(operation_id));
+                        Scan::Done
+                    }
+                };
+            }
+            Scan::Done => {}
+        }
+
+        self.next_message(trigger_name)
+    }
+
+    /// Marks the change delivered with the given rendez-vous id as handled in
+    /// the state of its trigger, and returns the next change to deliver for
+    /// this trigger, if any.
+    pub fn complete(&mut self, rendez_vous_id: u32) -> Option<InMessage> {
+        let Some(trigger_name) = self.running.remove(&rendez_vous_id) else {
+            warn!("Got completion of an unknown trigger {}", rendez_vous_id);
+            return None;
+        };
+
+        let position = self.triggers.get_mut(&trigger_name)?.running.take()?;
+
+        self.state
+            .triggers
+            .entry(trigger_name.clone())
+            .or_default()
+            .handled(position);
+
+        self.next_message(&trigger_name)
+    }
+
+    fn next_message(&mut self, trigger_name: &str) -> Option<InMessage> {
+        let trigger = self.triggers.get_mut(trigger_name)?;
+        if trigger.running.is_some() {
+            return None;
+        }
+
+        let change = loop {
+            let change = trigger.queue.pop_front()?;
+            if change.deliver {
+                break change;
+            }
+
+            self.state
+                .triggers
+                .entry(trigger_name.to_string())
+                .or_default()
+                .handled(change.position);
+        };
+        trigger.running = Some(change.position);
+
+        let rendez_vous_id = self.next_rendez_vous_id;
+        self.next_rendez_vous_id = self.next_rendez_vous_id.wrapping_add(1);
+        self.running
+            .insert(rendez_vous_id, trigger_name.to_string());
+
+        Some(InMessage {
+            r#type: InMessageType::EntityTriggered.into(),
+            rendez_vous_id,
+            data: change.triggered.encode_to_vec(),
+            error: String::new(),
+        })
+    }
+}
+
+struct Trigger {
+    trait_type: String,
+    queue: VecDeque<Change>,
+    running: Option<Position>,
+    scan: Scan,
+
+    /// Operation ids of the changes found by the current scan of the changes
+    /// that aren't indexed in the chain yet.
+    scanned_pending: HashSet<u64>,
+
+    changed: bool,
+}
+
+impl Trigger {
+    fn is_idle(&self) -> bool {
+        self.running.is_none() && self.queue.is_empty()
+    }
+}
+
+struct Change {
+    triggered: EntityTriggered,
+    position: Position,
+
+    /// If `false`, the change was already handled and is only queued to be
+    /// recorded as such in order.
+    deliver: bool,
+}
+
+/// Progress of the scan of a trigger's changes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Scan {
+    /// Changes indexed in the chain need to be scanned after the given
+    /// position, or after the last handled one if none.
+    Chain(Option<ChainPosition>),
+
+    /// Changes that aren't indexed in the chain yet need to be scanned after
+    /// the given operation id.
+    Pending(u64),
+
+    /// All changes were found.
+    Done,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Position {
+    Chain(ChainPosition),
+    Pending(u64),
+}
+
+/// Position of a change in the order in which changes got indexed in the
+/// chain.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+struct ChainPosition {
+    block_offset: u64,
+    operation_id: u64,
+}
+
+impl ChainPosition {
+    /// Position before any change indexed in the chain, but after the
+    /// changes that aren't indexed in it yet, which have an offset of 0.
+    const BEFORE_INDEXED: ChainPosition = ChainPosition {
+        block_offset: 0,
+        operation_id: u64::MAX,
+    };
+
+    fn from_result(result: &EntityResult) -> ChainPosition {
+        let ordering_value = result.ordering_value.as_ref();
+        let block_offset = match ordering_value.and_then(|value| value.value.as_ref()) {
+            Some(ordering_value::Value::Uint64(block_offset)) => *block_offset,
+            _ => 0,
+        };
+        ChainPosition {
+            block_offset,
+            operation_id: ordering_value
+                .map(|value| value.operation_id)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Changes handled by the triggers of an application.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct TriggersState {
+    triggers: HashMap<String, TriggerState>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct TriggerState {
+    /// Position of the last change indexed in the chain that got handled by
+    /// the application.
+    #[serde(default)]
+    chain_position: Option<ChainPosition>,
+
+    /// Operation ids of the changes handled by the application before they
+    /// got indexed in the chain.
+    #[serde(default)]
+    pending: BTreeSet<u64>,
+}
+
+impl TriggerState {
+    fn handled(&mut self, position: Position) {
+        match position {
+            Position::Chain(position) => {
+                self.chain_position = Some(position);
+                self.pending.remove(&position.operation_id);
+            }
+            Position::Pending(operation_id) => {
+                self.pending.insert(operation_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exocore_protos::store::{Entity, EntityResult};
+
+    use super::*;
+
+    #[test]
+    fn deliver_in_order() {
+        let mut triggers = Triggers::new(&[trigger("emails")], TriggersState::default()).unwrap();
+
+        let queries = triggers.queries();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].0, "emails");
+
+        // changes are delivered one at a time
+        let msg = triggers
+            .handle_results("emails", results(&[("e1", 1, 10), ("e2", 2, 20)]))
+            .unwrap();
+        assert_eq!(triggered(&msg), ("e1".to_string(), 10));
+        assert!(triggers.queries().is_empty());
+
+        let msg = triggers.complete(msg.rendez_vous_id).unwrap();
+        assert_eq!(triggered(&msg), ("e2".to_string(), 20));
+
+        // once changes indexed in the chain are handled, the ones that aren't are scanned
+        assert!(triggers.complete(msg.rendez_vous_id).is_none());
+        let queries = triggers.queries();
+        let paging = queries[0].1.paging.as_ref().unwrap();
+        assert_eq!(paging.before_ordering_value, Some(value_from_u64(1, 0)));
+        assert!(triggers.handle_results("emails", results(&[])).is_none());
+
+        // once all changes are handled, trigger is only queried again once notified of a change
+        assert!(triggers.queries().is_empty());
+
+        triggers.notify_changed("emails");
+        let queries = triggers.queries();
+        assert_eq!(queries.len(), 1);
+
+        // scan continues after the last handled change
+        let paging = queries[0].1.paging.as_ref().unwrap();
+        assert_eq!(paging.after_ordering_value, Some(value_from_u64(2, 20)));
+        let msg = triggers
+            .handle_results("emails", results(&[("e3", 3, 30)]))
+            .unwrap();
+        assert_eq!(triggered(&msg), ("e3".to_string(), 30));
+    }
+
+    #[test]
+    fn deliver_replicated_older_change() {
+        let mut triggers = Triggers::new(&[trigger("emails")], TriggersState::default()).unwrap();
+
+        let now = secs_op_id(1_000_000);
+        triggers.queries();
+        let msg = triggers
+            .handle_results("emails", results(&[("e1", 1, now)]))
+            .unwrap();
+        assert!(triggers.complete(msg.rendez_vous_id).is_none());
+        triggers.queries();
+        assert!(triggers.handle_results("emails", results(&[])).is_none());
+
+        // a change much older than the handled one got replicated from another node, and
+        // is indexed in a later block
+        triggers.notify_changed("emails");
+        assert_eq!(triggers.queries().len(), 1);
+        let older = now - secs_op_id(7 * 24 * 3600);
+        let msg = triggers
+            .handle_results("emails", results(&[("e2", 2, older)]))
+            .unwrap();
+        assert_eq!(triggered(&msg), ("e2".to_string(), older));
+    }
+
+    #[test]
+    fn deliver_pending_change_once() {
+        let mut triggers = Triggers::new(&[trigger("emails")], TriggersState::default()).unwrap();
+
+        // change that isn't indexed in the chain yet is delivered
+        triggers.queries();
+        assert!(triggers.handle_results("emails", results(&[])).is_none());
+        let msg = triggers
+            .handle_results("emails", results(&[("e1", 0, 10)]))
+            .unwrap();
+        assert_eq!(triggered(&msg), ("e1".to_string(), 10));
+        assert!(triggers.complete(msg.rendez_vous_id).is_none());
+
+        // it's not delivered again while it's not indexed in the chain
+        triggers.notify_changed("emails");
+        triggers.queries();
+        assert!(triggers.handle_results("emails", results(&[])).is_none());
+        triggers.queries();
+        let res = triggers.handle_results("emails", results(&[("e1", 0, 10)]));
+        assert!(res.is_none());
+
+        // nor once it got indexed in the chain, but other changes are
+        triggers.notify_changed("emails");
+        triggers.queries();
+        let msg = triggers
+            .handle_results("emails", results(&[("e1", 1, 10), ("e2", 1, 20)]))
+            .unwrap();
+        assert_eq!(triggered(&msg), ("e2".to_string(), 20));
+        assert!(triggers.complete(msg.rendez_vous_id).is_none());
+
+        let state = triggers.state().triggers.get("emails").unwrap();
+        assert!(state.pending.is_empty());
+        assert_eq!(
+            state.chain_position,
+            Some(ChainPosition {
+                block_offset: 1,
+                operation_id: 20,
+            })
+        );
+    }
+
+    #[test]
+    fn scan_in_batches() {
+        let mut triggers = Triggers::new(&[trigger("emails")], TriggersState::default()).unwrap();
+
+        let ids = (1..=QUERY_BATCH_SIZE as u64)
+            .map(|i| (format!("e{}", i), i))
+            .collect::<Vec<_>>();
+        let batch = ids
+            .iter()
+            .map(|(id, op)| (id.as_str(), 1, *op))
+            .collect::<Vec<_>>();
+
+        triggers.queries();
+        let mut msg = triggers.handle_results("emails", results(&batch));
+        while let Some(current) = msg {
+            msg = triggers.complete(current.rendez_vous_id);
+        }
+
+        // batch was full, so the scan continues after its last change without notification
+        let queries = triggers.queries();
+        assert_eq!(queries.len(), 1);
+        let paging = queries[0].1.paging.as_ref().unwrap();
+        let after = paging.after_ordering_value.as_ref().unwrap();
+        assert_eq!(after.operation_id, QUERY_BATCH_SIZE as u64);
+
+        assert!(triggers.handle_results("emails", results(&[])).is_none());
+        assert_eq!(triggers.queries().len(), 1);
+        assert!(triggers.handle_results("emails", results(&[])).is_none());
+        assert!(triggers.queries().is_empty());
+    }
+
+    #[test]
+    fn invalid_triggers() {
+        let state = TriggersState::default();
+        assert!(Triggers::new(&[trigger("a"), trigger("a")], state.clone()).is_err());
+
+        let no_type = ManifestTrigger {
+            name: "a".to_string(),
+            trait_type: String::new(),
+        };
+        assert!(Triggers::new(&[no_type], state).is_err());
+    }
+
+    #[test]
+    fn unhandled_change_delivered_again() {
+        let state = {
+            let mut triggers =
+                Triggers::new(&[trigger("emails")], TriggersState::default()).unwrap();
+            let msg = triggers
+                .handle_results("emails", results(&[("e1", 1, 10), ("e2", 2, 20)]))
+                .unwrap();
+            triggers.complete(msg.rendez_vous_id);
+
+            // e2 is delivered, but never reported as done
+            persisted(triggers.state())
+        };
+
+        let mut triggers = Triggers::new(&[trigger("emails")], state).unwrap();
+        let queries = triggers.queries();
+        assert_eq!(queries.len(), 1);
+        let paging = queries[0].1.paging.as_ref().unwrap();
+        assert_eq!(paging.after_ordering_value, Some(value_from_u64(1, 10)));
+        let msg = triggers
+            .handle_results("emails", results(&[("e2", 2, 20)]))
+            .unwrap();
+        assert_eq!(triggered(&msg), ("e2".to_string(), 20));
+    }
+
+    fn persisted(state: &TriggersState) -> TriggersState {
+        let data = serde_json::to_vec(state).unwrap();
+        serde_json::from_slice(&data).unwrap()
+    }
+
+    fn secs_op_id(secs: u64) -> u64 {
+        std::time::Duration::from_secs(secs).as_nanos() as u64
+    }
+
+    fn trigger(name: &str) -> ManifestTrigger {
+        ManifestTrigger {
+            name: name.to_string(),
+            trait_type: "exocore.test.TestMessage".to_string(),
+        }
+    }
+
+    /// Results of entities with the block offset and operation id of their
+    /// change. A block offset of 0 is for changes not indexed in the chain.
+    fn results(entities: &[(&str, u64, u64)]) -> EntityResults {
+        EntityResults {
+            entities: entities
+                .iter()
+                .map(|(id, block_offset, operation_id)| EntityResult {
+                    entity: Some(Entity {
+                        id: id.to_string(),
+                        ..Default::default()
+                    }),
+                    ordering_value: Some(value_from_u64(*block_offset, *operation_id)),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn triggered(msg: &InMessage) -> (String, u64) {
+        let triggered = EntityTriggered::decode(msg.data.as_ref()).unwrap();
+        (triggered.entity.unwrap().id, triggered.operation_id)
+    }
+}
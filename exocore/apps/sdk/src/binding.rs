@@ -1,5 +1,5 @@
 use exocore_protos::{
-    apps::{in_message::InMessageType, InMessage, MessageStatus},
+    apps::{in_message::InMessageType, InMessage, MessageStatus, OutMessage},
     prost::Message,
 };
 
@@ -29,6 +29,58 @@ pub(crate) unsafe fn __exocore_host_out_message(_bytes: *const u8, _len: usize)
     panic!("Not implemented in outside of wasm environment");
 }
 
+/// Sends messages to the host.
+///
+/// In tests, messages are sent to the function given to `from_fn` instead
+/// since there is no host outside of the wasm environment.
+#[derive(Default)]
+pub(crate) struct HostMessageSender {
+    #[cfg(test)]
+    sender: Option<Box<dyn Fn(OutMessage) -> MessageStatus + Send + Sync>>,
+}
+
+impl HostMessageSender {
+    #[cfg(test)]
+    pub(crate) fn from_fn<F>(sender: F) -> HostMessageSender
+    where
+        F: Fn(OutMessage) -> MessageStatus + Send + Sync + 'static,
+    {
+        HostMessageSender {
+            sender: Some(Box::new(sender)),
+        }
+    }
+
+    pub(crate) fn send(&self, msg: OutMessage) -> Result<(), HostMessageError> {
+        let code = self.send_to_host(msg);
+        match MessageStatus::try_from(code as i32) {
+            Ok(MessageStatus::Ok) => Ok(()),
+            Ok(status) => Err(HostMessageError::Status(status)),
+            Err(_) => Err(HostMessageError::UnknownStatus(code)),
+        }
+    }
+
+    #[cfg(not(test))]
+    fn send_to_host(&self, msg: OutMessage) -> u32 {
+        let encoded = msg.encode_to_vec();
+        unsafe { __exocore_host_out_message(encoded.as_ptr(), encoded.len()) }
+    }
+
+    #[cfg(test)]
+    fn send_to_host(&self, msg: OutMessage) -> u32 {
+        let sender = self.sender.as_ref().expect("No host message sender set");
+        sender(msg) as u32
+    }
+}
+
+/// Error returned when a message couldn't be handled by the host.
+#[derive(Debug, thiserror::Error)]
+pub enum HostMessageError {
+    #[error("Host message error: {0:?}")]
+    Status(MessageStatus),
+    #[error("Unknown host message status code: {0}")]
+    UnknownStatus(u32),
+}
+
 /* Added by the macro
 #[no_mangle]
 pub extern "C" fn __exocore_app_init() {}
@@ -40,6 +92,8 @@ pub extern "C" fn __exocore_init() {
 
     let exocore = crate::client::Exocore::get();
     exocore.store.start();
+    exocore.http.start();
+    exocore.kv.start();
 }
 
 /// Ticks timer, executor and returns the next timestamp at which we should
@@ -76,6 +130,12 @@ pub extern "C" fn __exocore_in_message(ptr: *const u8, size: usize) -> u32 {
     let res = match InMessageType::try_from(msg.r#type) {
         Ok(InMessageType::StoreEntityResults) => exomind.store.handle_query_results(msg),
         Ok(InMessageType::StoreMutationResult) => exomind.store.handle_mutation_result(msg),
+        Ok(InMessageType::ScheduledJobRun) => exomind.scheduler.handle_job_run(msg),
+        Ok(InMessageType::EntityTriggered) => exomind.triggers.handle_entity_triggered(msg),
+        Ok(InMessageType::HttpResponse) => exomind.http.handle_response(msg),
+        Ok(InMessageType::KvResult) => exomind.kv.handle_result(msg),
+        Ok(InMessageType::ClientMessage) => exomind.clients.handle_message(msg),
+        Ok(InMessageType::ClientRequest) => exomind.clients.handle_request(msg),
         Ok(InMessageType::Invalid) => {
             error!("Received an invalid message type: {}", msg.r#type);
             return MessageStatus::Unhandled as u32;
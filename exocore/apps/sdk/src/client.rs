@@ -1,10 +1,18 @@
 use std::sync::{Arc, Mutex};
 
-use crate::{app::App, store::Store};
+use crate::{
+    app::App, clients::Clients, http::Http, kv::Kv, scheduler::Scheduler, store::Store,
+    triggers::Triggers,
+};
 
 lazy_static! {
     static ref EXOCORE: Exocore = Exocore {
         store: Arc::new(Store::new()),
+        scheduler: Arc::new(Scheduler::new()),
+        triggers: Arc::new(Triggers::new()),
+        http: Arc::new(Http::new()),
+        kv: Arc::new(Kv::new()),
+        clients: Arc::new(Clients::new()),
         app: Arc::new(Mutex::new(None)),
     };
 }
@@ -12,6 +20,11 @@ lazy_static! {
 /// Exocore client.
 pub struct Exocore {
     pub store: Arc<Store>,
+    pub scheduler: Arc<Scheduler>,
+    pub triggers: Arc<Triggers>,
+    pub http: Arc<Http>,
+    pub kv: Arc<Kv>,
+    pub clients: Arc<Clients>,
     app: Arc<Mutex<Option<Box<dyn App>>>>,
 }
 
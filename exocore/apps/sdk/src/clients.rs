@@ -0,0 +1,302 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use exocore_protos::{
+    apps::{
+        out_message::OutMessageType, ClientMessage, ClientRequest, ClientResponse, InMessage,
+        MessageStatus, OutMessage,
+    },
+    prost::Message,
+};
+use futures::{future::BoxFuture, Future, FutureExt};
+
+use crate::{
+    binding::{HostMessageError, HostMessageSender},
+    executor::spawn,
+};
+
+type MessageHandlerFn =
+    dyn Fn(Vec<u8>) -> BoxFuture<'static, Result<(), anyhow::Error>> + Send + Sync;
+
+type RequestHandlerFn =
+    dyn Fn(Vec<u8>) -> BoxFuture<'static, Result<Vec<u8>, anyhow::Error>> + Send + Sync;
+
+/// Messaging with the clients of the cell (web, mobile), routed by the host.
+///
+/// The application publishes messages on topics to which clients subscribe
+/// (ex: notifications), and handles messages and requests sent by clients.
+/// Messages are only delivered to clients that are connected at the time they
+/// are published.
+///
+/// Handlers need to be registered when the application starts.
+pub struct Clients {
+    message_handlers: Mutex<HashMap<String, Arc<MessageHandlerFn>>>,
+    request_handlers: Mutex<HashMap<String, Arc<RequestHandlerFn>>>,
+
+    host_message_sender: HostMessageSender,
+}
+
+impl Clients {
+    pub(crate) fn new() -> Clients {
+        Clients {
+            message_handlers: Mutex::new(HashMap::new()),
+            request_handlers: Mutex::new(HashMap::new()),
+
+            host_message_sender: HostMessageSender::default(),
+        }
+    }
+
+    /// Publishes a message on a topic to the clients subscribed to it.
+    pub fn publish(
+        &self,
+        topic: impl Into<String>,
+        data: impl Into<Vec<u8>>,
+    ) -> Result<(), ClientsError> {
+        let message = ClientMessage {
+            topic: topic.into(),
+            data: data.into(),
+            ..Default::default()
+        };
+
+        self.host_message_sender.send(OutMessage {
+            r#type: OutMessageType::ClientPublish.into(),
+            rendez_vous_id: 0,
+            data: message.encode_to_vec(),
+        })?;
+
+        Ok(())
+    }
+
+    /// Registers the handler of the messages sent by clients on the given
+    /// topic.
+    pub fn on_message<F, O>(&self, topic: impl Into<String>, handler: F)
+    where
+        F: (Fn(Vec<u8>) -> O) + Send + Sync + 'static,
+        O: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    {
+        let mut handlers = self.message_handlers.lock().unwrap();
+        handlers.insert(topic.into(), Arc::new(move |data| handler(data).boxed()));
+    }
+
+    /// Registers the handler of the requests sent by clients to the given
+    /// method. The data returned by the handler, or its error, is sent back to
+    /// the client.
+    pub fn on_request<F, O>(&self, method: impl Into<String>, handler: F)
+    where
+        F: (Fn(Vec<u8>) -> O) + Send + Sync + 'static,
+        O: Future<Output = Result<Vec<u8>, anyhow::Error>> + Send + 'static,
+    {
+        let mut handlers = self.request_handlers.lock().unwrap();
+        handlers.insert(method.into(), Arc::new(move |data| handler(data).boxed()));
+    }
+
+    pub(crate) fn handle_message(&self, msg: InMessage) -> Result<(), MessageStatus> {
+        let message = ClientMessage::decode(msg.data.as_ref()).map_err(|err| {
+            error!("Error decoding incoming client message: {}", err);
+            MessageStatus::DecodeError
+        })?;
+
+        let handler = {
+            let handlers = self.message_handlers.lock().unwrap();
+            handlers.get(&message.topic).cloned()
+        };
+
+        let Some(handler) = handler else {
+            warn!(
+                "Got a client message on topic '{}' without handler",
+                message.topic
+            );
+            return Ok(());
+        };
+
+        spawn(async move {
+            if let Err(err) = handler(message.data).await {
+                error!(
+                    "Handler of client messages on topic '{}' failed: {}",
+                    message.topic, err
+                );
+            }
+        });
+
+        Ok(())
+    }
+
+    pub(crate) fn handle_request(self: &Arc<Clients>, msg: InMessage) -> Result<(), MessageStatus> {
+        let request = ClientRequest::decode(msg.data.as_ref()).map_err(|err| {
+            error!("Error decoding incoming client request: {}", err);
+            MessageStatus::DecodeError
+        })?;
+
+        let handler = {
+            let handlers = self.request_handlers.lock().unwrap();
+            handlers.get(&request.method).cloned()
+        };
+
+        let clients = self.clone();
+        spawn(async move {
+            let result = match handler {
+                Some(handler) => handler(request.data).await,
+                None => Err(anyhow::anyhow!(
+                    "No handler for client requests to method '{}'",
+                    request.method
+                )),
+            };
+
+            // the client is always responded to so that it doesn't wait until it times out
+            let response = match result {
+                Ok(data) => ClientResponse {
+                    data,
+                    ..Default::default()
+                },
+                Err(err) => ClientResponse {
+                    error: err.to_string(),
+                    ..Default::default()
+                },
+            };
+
+            let res = clients.host_message_sender.send(OutMessage {
+                r#type: OutMessageType::ClientResponse.into(),
+                rendez_vous_id: msg.rendez_vous_id,
+                data: response.encode_to_vec(),
+            });
+            if let Err(err) = res {
+                error!(
+                    "Couldn't respond to client request to method '{}': {}",
+                    request.method, err
+                );
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientsError {
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+    #[error(transparent)]
+    HostMessage(#[from] HostMessageError),
+}
+
+#[cfg(test)]
+mod tests {
+    use exocore_protos::apps::in_message::InMessageType;
+    use futures::channel::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn publish() {
+        let (mut out_msg_rcv, clients) = create_test_clients();
+
+        clients.publish("notifications", b"hello".to_vec()).unwrap();
+
+        let out_msg = out_msg_rcv.try_next().unwrap().unwrap();
+        assert_eq!(out_msg.r#type, OutMessageType::ClientPublish as i32);
+        let message = ClientMessage::decode(out_msg.data.as_ref()).unwrap();
+        assert_eq!(message.topic, "notifications");
+        assert_eq!(message.data, b"hello");
+    }
+
+    #[test]
+    fn handle_message() {
+        let (_out_msg_rcv, clients) = create_test_clients();
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        {
+            let received = received.clone();
+            clients.on_message("refresh", move |data| {
+                let received = received.clone();
+                async move {
+                    received.lock().unwrap().push(data);
+                    Ok(())
+                }
+            });
+        }
+
+        let message = ClientMessage {
+            topic: "refresh".to_string(),
+            data: b"now".to_vec(),
+            ..Default::default()
+        };
+        clients
+            .handle_message(InMessage {
+                r#type: InMessageType::ClientMessage.into(),
+                data: message.encode_to_vec(),
+                ..Default::default()
+            })
+            .unwrap();
+        crate::executor::poll_executor();
+
+        assert_eq!(*received.lock().unwrap(), vec![b"now".to_vec()]);
+    }
+
+    #[test]
+    fn handle_request() {
+        let (mut out_msg_rcv, clients) = create_test_clients();
+
+        clients.on_request("ping", |data| async move {
+            assert_eq!(data, b"ping");
+            Ok(b"pong".to_vec())
+        });
+
+        clients.handle_request(request_message("ping", 3)).unwrap();
+        crate::executor::poll_executor();
+
+        let out_msg = out_msg_rcv.try_next().unwrap().unwrap();
+        assert_eq!(out_msg.r#type, OutMessageType::ClientResponse as i32);
+        assert_eq!(out_msg.rendez_vous_id, 3);
+        let response = ClientResponse::decode(out_msg.data.as_ref()).unwrap();
+        assert_eq!(response.data, b"pong");
+        assert!(response.error.is_empty());
+    }
+
+    #[test]
+    fn request_without_handler() {
+        let (mut out_msg_rcv, clients) = create_test_clients();
+
+        // client should be responded an error so that it doesn't wait for a response
+        clients
+            .handle_request(request_message("unknown", 4))
+            .unwrap();
+        crate::executor::poll_executor();
+
+        let out_msg = out_msg_rcv.try_next().unwrap().unwrap();
+        assert_eq!(out_msg.rendez_vous_id, 4);
+        let response = ClientResponse::decode(out_msg.data.as_ref()).unwrap();
+        assert!(!response.error.is_empty());
+    }
+
+    fn request_message(method: &str, rendez_vous_id: u32) -> InMessage {
+        InMessage {
+            r#type: InMessageType::ClientRequest.into(),
+            rendez_vous_id,
+            data: ClientRequest {
+                method: method.to_string(),
+                data: b"ping".to_vec(),
+                ..Default::default()
+            }
+            .encode_to_vec(),
+            error: String::new(),
+        }
+    }
+
+    fn create_test_clients() -> (mpsc::Receiver<OutMessage>, Arc<Clients>) {
+        let (out_msg_sender, out_msg_rcv) = mpsc::channel(10);
+        let clients = {
+            let mut clients = Clients::new();
+            let out_msg_sender = Arc::new(Mutex::new(out_msg_sender));
+            clients.host_message_sender = HostMessageSender::from_fn(move |msg| {
+                let mut out_msg_sender = out_msg_sender.lock().unwrap();
+                out_msg_sender.try_send(msg).unwrap();
+                MessageStatus::Ok
+            });
+            Arc::new(clients)
+        };
+
+        (out_msg_rcv, clients)
+    }
+}
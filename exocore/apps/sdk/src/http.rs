@@ -0,0 +1,232 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use exocore_protos::{
+    apps::{
+        out_message::OutMessageType, HttpRequest, HttpResponse, InMessage, MessageStatus,
+        OutMessage,
+    },
+    prost::Message,
+};
+use futures::channel::oneshot;
+
+use crate::{
+    binding::{HostMessageError, HostMessageSender},
+    prelude::{sleep, spawn},
+    time::{now, Timestamp},
+};
+
+// Host caps requests' timeout to 60 seconds
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(65);
+const TIMEOUT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// HTTP client executing requests through the host.
+///
+/// Requests can only be sent to the domains declared in the `http_domains` of
+/// the application's manifest.
+pub struct Http {
+    next_rdv: AtomicUsize,
+    pending_requests: Mutex<HashMap<usize, PendingRequest>>,
+
+    host_message_sender: HostMessageSender,
+}
+
+struct PendingRequest {
+    sender: oneshot::Sender<Result<HttpResponse, HttpError>>,
+    timeout: Timestamp,
+}
+
+impl Http {
+    pub(crate) fn new() -> Http {
+        Http {
+            next_rdv: AtomicUsize::new(0),
+            pending_requests: Mutex::new(HashMap::new()),
+
+            host_message_sender: HostMessageSender::default(),
+        }
+    }
+
+    /// Sends a `GET` request to the given URL.
+    pub async fn get(self: &Arc<Http>, url: impl Into<String>) -> Result<HttpResponse, HttpError> {
+        self.request(HttpRequest {
+            url: url.into(),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Sends a request. Responses with an error status are returned as
+    /// responses, while errors are returned if the request couldn't be
+    /// executed.
+    pub async fn request(
+        self: &Arc<Http>,
+        request: HttpRequest,
+    ) -> Result<HttpResponse, HttpError> {
+        let rdv = self.next_rdv.fetch_add(1, Ordering::SeqCst);
+        let msg = OutMessage {
+            r#type: OutMessageType::HttpRequest.into(),
+            rendez_vous_id: rdv as u32,
+            data: request.encode_to_vec(),
+        };
+
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut pending_requests = self.pending_requests.lock().unwrap();
+            let pending = PendingRequest {
+                sender,
+                timeout: now() + REQUEST_TIMEOUT,
+            };
+            pending_requests.insert(rdv, pending);
+        }
+
+        self.host_message_sender.send(msg)?;
+
+        receiver.await.map_err(HttpError::from)?
+    }
+
+    pub(crate) fn handle_response(&self, msg: InMessage) -> Result<(), MessageStatus> {
+        let mut pending_requests = self.pending_requests.lock().unwrap();
+        let rdv = msg.rendez_vous_id as usize;
+
+        if let Some(req) = pending_requests.remove(&rdv) {
+            let response = if msg.error.is_empty() {
+                Ok(HttpResponse::decode(msg.data.as_ref()).map_err(|err| {
+                    error!("Error decoding incoming HTTP response: {}", err);
+                    MessageStatus::DecodeError
+                })?)
+            } else {
+                Err(HttpError::Remote(msg.error))
+            };
+            let _ = req.sender.send(response);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn start(self: &Arc<Http>) {
+        let http = self.clone();
+        spawn(async move {
+            loop {
+                let now = now();
+
+                {
+                    let mut pending_requests = http.pending_requests.lock().unwrap();
+                    pending_requests.retain(|_rdv, req| req.timeout >= now);
+                }
+
+                sleep(TIMEOUT_CHECK_INTERVAL).await;
+            }
+        });
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HttpError {
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+    #[error(transparent)]
+    HostMessage(#[from] HostMessageError),
+    #[error("HTTP request error: {0}")]
+    Remote(String),
+    #[error("Request got cancelled or timed out")]
+    Cancelled(#[from] oneshot::Canceled),
+}
+
+#[cfg(test)]
+mod tests {
+    use exocore_protos::apps::in_message::InMessageType;
+    use futures::{channel::mpsc, StreamExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request() {
+        let (mut out_msg_rcv, http) = create_test_http();
+
+        // spawn a request
+        let (res_sender, mut res_receiver) = oneshot::channel();
+        {
+            let http = http.clone();
+            tokio::spawn(async move {
+                let res = http.get("https://example.com").await;
+                res_sender.send(res).unwrap();
+            });
+        }
+
+        // the request should have been sent to host
+        let out_msg = out_msg_rcv.next().await.expect("no message sent to host");
+        assert_eq!(out_msg.r#type, OutMessageType::HttpRequest as i32);
+        let request = HttpRequest::decode(out_msg.data.as_ref()).unwrap();
+        assert_eq!(request.url, "https://example.com");
+
+        // request shouldn't have resolved yet since we didn't send response back
+        assert!(res_receiver.try_recv().unwrap().is_none());
+
+        // host sends back response
+        http.handle_response(InMessage {
+            r#type: InMessageType::HttpResponse.into(),
+            data: HttpResponse {
+                status: 200,
+                body: b"hello".to_vec(),
+                ..Default::default()
+            }
+            .encode_to_vec(),
+            rendez_vous_id: out_msg.rendez_vous_id,
+            error: String::new(),
+        })
+        .unwrap();
+
+        // request should now have been resolved
+        let res = res_receiver.await.unwrap().unwrap();
+        assert_eq!(res.status, 200);
+        assert_eq!(res.body, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_request_error() {
+        let (mut out_msg_rcv, http) = create_test_http();
+
+        let (res_sender, res_receiver) = oneshot::channel();
+        {
+            let http = http.clone();
+            tokio::spawn(async move {
+                let res = http.get("https://not.allowed.com").await;
+                res_sender.send(res).unwrap();
+            });
+        }
+
+        let out_msg = out_msg_rcv.next().await.expect("no message sent to host");
+        http.handle_response(InMessage {
+            r#type: InMessageType::HttpResponse.into(),
+            rendez_vous_id: out_msg.rendez_vous_id,
+            error: "domain not allowed".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let res = res_receiver.await.unwrap();
+        assert!(matches!(res, Err(HttpError::Remote(_))));
+    }
+
+    fn create_test_http() -> (mpsc::Receiver<OutMessage>, Arc<Http>) {
+        let (out_msg_sender, out_msg_rcv) = mpsc::channel(1);
+        let http = {
+            let mut http = Http::new();
+            let out_msg_sender = Arc::new(Mutex::new(out_msg_sender));
+            http.host_message_sender = HostMessageSender::from_fn(move |msg| {
+                let mut out_msg_sender = out_msg_sender.lock().unwrap();
+                out_msg_sender.try_send(msg).unwrap();
+                MessageStatus::Ok
+            });
+            Arc::new(http)
+        };
+
+        (out_msg_rcv, http)
+    }
+}
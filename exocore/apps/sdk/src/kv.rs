@@ -0,0 +1,266 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use exocore_protos::{
+    apps::{
+        kv_request::Request, out_message::OutMessageType, InMessage, KvDelete, KvGet, KvList,
+        KvPut, KvRequest, KvResult, MessageStatus, OutMessage,
+    },
+    prost::Message,
+};
+use futures::channel::oneshot;
+
+use crate::{
+    binding::{HostMessageError, HostMessageSender},
+    prelude::{sleep, spawn},
+    time::{now, Timestamp},
+};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const TIMEOUT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Key-value storage private to the application, persisted by the host.
+///
+/// Used to keep state that shouldn't be stored as entities in the store (ex:
+/// sync cursors, settings, caches). The host limits the size of keys, values
+/// and of the whole storage.
+pub struct Kv {
+    next_rdv: AtomicUsize,
+    pending_requests: Mutex<HashMap<usize, PendingRequest>>,
+
+    host_message_sender: HostMessageSender,
+}
+
+struct PendingRequest {
+    sender: oneshot::Sender<Result<KvResult, KvError>>,
+    timeout: Timestamp,
+}
+
+impl Kv {
+    pub(crate) fn new() -> Kv {
+        Kv {
+            next_rdv: AtomicUsize::new(0),
+            pending_requests: Mutex::new(HashMap::new()),
+
+            host_message_sender: HostMessageSender::default(),
+        }
+    }
+
+    /// Returns the value of a key, if it exists.
+    pub async fn get(self: &Arc<Kv>, key: impl Into<String>) -> Result<Option<Vec<u8>>, KvError> {
+        let result = self
+            .request(Request::Get(KvGet { key: key.into() }))
+            .await?;
+
+        Ok(result.value.map(|value| value.value))
+    }
+
+    /// Sets the value of a key, replacing its previous value if any.
+    pub async fn put(
+        self: &Arc<Kv>,
+        key: impl Into<String>,
+        value: impl Into<Vec<u8>>,
+    ) -> Result<(), KvError> {
+        self.request(Request::Put(KvPut {
+            key: key.into(),
+            value: value.into(),
+        }))
+        .await?;
+
+        Ok(())
+    }
+
+    /// Deletes a key, if it exists.
+    pub async fn delete(self: &Arc<Kv>, key: impl Into<String>) -> Result<(), KvError> {
+        self.request(Request::Delete(KvDelete { key: key.into() }))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the keys starting with the given prefix, in lexicographic
+    /// order.
+    pub async fn list(self: &Arc<Kv>, prefix: impl Into<String>) -> Result<Vec<String>, KvError> {
+        let result = self
+            .request(Request::List(KvList {
+                prefix: prefix.into(),
+            }))
+            .await?;
+
+        Ok(result.keys)
+    }
+
+    async fn request(self: &Arc<Kv>, request: Request) -> Result<KvResult, KvError> {
+        let rdv = self.next_rdv.fetch_add(1, Ordering::SeqCst);
+        let msg = OutMessage {
+            r#type: OutMessageType::KvRequest.into(),
+            rendez_vous_id: rdv as u32,
+            data: KvRequest {
+                request: Some(request),
+            }
+            .encode_to_vec(),
+        };
+
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut pending_requests = self.pending_requests.lock().unwrap();
+            let pending = PendingRequest {
+                sender,
+                timeout: now() + REQUEST_TIMEOUT,
+            };
+            pending_requests.insert(rdv, pending);
+        }
+
+        self.host_message_sender.send(msg)?;
+
+        receiver.await.map_err(KvError::from)?
+    }
+
+    pub(crate) fn handle_result(&self, msg: InMessage) -> Result<(), MessageStatus> {
+        let mut pending_requests = self.pending_requests.lock().unwrap();
+        let rdv = msg.rendez_vous_id as usize;
+
+        if let Some(req) = pending_requests.remove(&rdv) {
+            let result = if msg.error.is_empty() {
+                Ok(KvResult::decode(msg.data.as_ref()).map_err(|err| {
+                    error!("Error decoding incoming key-value result: {}", err);
+                    MessageStatus::DecodeError
+                })?)
+            } else {
+                Err(KvError::Remote(msg.error))
+            };
+            let _ = req.sender.send(result);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn start(self: &Arc<Kv>) {
+        let kv = self.clone();
+        spawn(async move {
+            loop {
+                let now = now();
+
+                {
+                    let mut pending_requests = kv.pending_requests.lock().unwrap();
+                    pending_requests.retain(|_rdv, req| req.timeout >= now);
+                }
+
+                sleep(TIMEOUT_CHECK_INTERVAL).await;
+            }
+        });
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum KvError {
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+    #[error(transparent)]
+    HostMessage(#[from] HostMessageError),
+    #[error("Key-value storage error: {0}")]
+    Remote(String),
+    #[error("Request got cancelled or timed out")]
+    Cancelled(#[from] oneshot::Canceled),
+}
+
+#[cfg(test)]
+mod tests {
+    use exocore_protos::apps::{in_message::InMessageType, KvValue};
+    use futures::{channel::mpsc, StreamExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get() {
+        let (mut out_msg_rcv, kv) = create_test_kv();
+
+        let (res_sender, mut res_receiver) = oneshot::channel();
+        {
+            let kv = kv.clone();
+            tokio::spawn(async move {
+                let res = kv.get("cursor").await;
+                res_sender.send(res).unwrap();
+            });
+        }
+
+        // the request should have been sent to host
+        let out_msg = out_msg_rcv.next().await.expect("no message sent to host");
+        assert_eq!(out_msg.r#type, OutMessageType::KvRequest as i32);
+        let request = KvRequest::decode(out_msg.data.as_ref()).unwrap();
+        assert_eq!(
+            request.request,
+            Some(Request::Get(KvGet {
+                key: "cursor".to_string()
+            }))
+        );
+
+        // request shouldn't have resolved yet since we didn't send result back
+        assert!(res_receiver.try_recv().unwrap().is_none());
+
+        kv.handle_result(InMessage {
+            r#type: InMessageType::KvResult.into(),
+            data: KvResult {
+                value: Some(KvValue {
+                    value: b"42".to_vec(),
+                }),
+                ..Default::default()
+            }
+            .encode_to_vec(),
+            rendez_vous_id: out_msg.rendez_vous_id,
+            error: String::new(),
+        })
+        .unwrap();
+
+        let res = res_receiver.await.unwrap().unwrap();
+        assert_eq!(res, Some(b"42".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_put_error() {
+        let (mut out_msg_rcv, kv) = create_test_kv();
+
+        let (res_sender, res_receiver) = oneshot::channel();
+        {
+            let kv = kv.clone();
+            tokio::spawn(async move {
+                let res = kv.put("cursor", b"42".to_vec()).await;
+                res_sender.send(res).unwrap();
+            });
+        }
+
+        let out_msg = out_msg_rcv.next().await.expect("no message sent to host");
+        kv.handle_result(InMessage {
+            r#type: InMessageType::KvResult.into(),
+            rendez_vous_id: out_msg.rendez_vous_id,
+            error: "quota exceeded".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let res = res_receiver.await.unwrap();
+        assert!(matches!(res, Err(KvError::Remote(_))));
+    }
+
+    fn create_test_kv() -> (mpsc::Receiver<OutMessage>, Arc<Kv>) {
+        let (out_msg_sender, out_msg_rcv) = mpsc::channel(1);
+        let kv = {
+            let mut kv = Kv::new();
+            let out_msg_sender = Arc::new(Mutex::new(out_msg_sender));
+            kv.host_message_sender = HostMessageSender::from_fn(move |msg| {
+                let mut out_msg_sender = out_msg_sender.lock().unwrap();
+                out_msg_sender.try_send(msg).unwrap();
+                MessageStatus::Ok
+            });
+            Arc::new(kv)
+        };
+
+        (out_msg_rcv, kv)
+    }
+}
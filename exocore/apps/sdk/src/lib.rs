@@ -8,19 +8,30 @@ pub(crate) mod logging;
 
 pub mod app;
 pub mod client;
+pub mod clients;
 pub mod executor;
+pub mod http;
+pub mod kv;
+pub mod scheduler;
 pub mod store;
 pub mod time;
+pub mod triggers;
 
+pub use binding::HostMessageError;
 pub use exocore_apps_macros::exocore_app;
 
 pub mod prelude {
     pub use super::{
         app::{App, AppError},
         client::Exocore,
+        clients::{Clients, ClientsError},
         executor::spawn,
         exocore_app,
+        http::{Http, HttpError},
+        kv::{Kv, KvError},
+        scheduler::{Schedule, Scheduler},
         store::{Store, StoreError},
         time::{now, sleep, Timestamp},
+        triggers::Triggers,
     };
 }
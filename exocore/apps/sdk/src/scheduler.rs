@@ -0,0 +1,241 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use exocore_protos::{
+    apps::{
+        out_message::OutMessageType, scheduled_job::Schedule as ScheduleProto, InMessage,
+        MessageStatus, OutMessage, ScheduledJob, ScheduledJobRun,
+    },
+    prost::Message,
+};
+use futures::{future::BoxFuture, Future, FutureExt};
+
+use crate::{
+    binding::{HostMessageError, HostMessageSender},
+    executor::spawn,
+};
+
+type JobFn = dyn Fn() -> BoxFuture<'static, Result<(), anyhow::Error>> + Send + Sync;
+
+/// Schedule of a job.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// Runs the job at a fixed interval, with a precision of a second, of up
+    /// to a year.
+    Interval(Duration),
+
+    /// Runs the job according to a cron expression (minute, hour, day of
+    /// month, month, day of week), in UTC. Ex: `0 8 * * 1-5` runs the job at
+    /// 8:00 on week days.
+    Cron(String),
+}
+
+/// Scheduler of jobs run periodically by the application host.
+///
+/// Unlike a loop with a `sleep`, the host persists the last run of each job,
+/// so that a run missed while the application wasn't running is caught up as
+/// soon as the job gets registered again. Multiple missed runs are collapsed
+/// into a single run, and a job never runs concurrently with itself.
+///
+/// Jobs need to be registered every time the application starts.
+pub struct Scheduler {
+    jobs: Mutex<HashMap<String, Arc<JobFn>>>,
+
+    host_message_sender: HostMessageSender,
+}
+
+impl Scheduler {
+    pub(crate) fn new() -> Scheduler {
+        Scheduler {
+            jobs: Mutex::new(HashMap::new()),
+
+            host_message_sender: HostMessageSender::default(),
+        }
+    }
+
+    /// Registers a job with a name unique within the application. The job is
+    /// considered done once its future completes, whether it succeeded or
+    /// not.
+    pub fn schedule<F, O>(
+        &self,
+        name: impl Into<String>,
+        schedule: Schedule,
+        job: F,
+    ) -> Result<(), SchedulerError>
+    where
+        F: (Fn() -> O) + Send + Sync + 'static,
+        O: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    {
+        let name = name.into();
+        let schedule = match schedule {
+            Schedule::Interval(interval) => ScheduleProto::IntervalSecs(interval.as_secs()),
+            Schedule::Cron(expr) => ScheduleProto::Cron(expr),
+        };
+
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.insert(name.clone(), Arc::new(move || job().boxed()));
+        }
+
+        let job = ScheduledJob {
+            name,
+            schedule: Some(schedule),
+        };
+        self.host_message_sender.send(OutMessage {
+            r#type: OutMessageType::SchedulerRegisterJob.into(),
+            rendez_vous_id: 0,
+            data: job.encode_to_vec(),
+        })?;
+
+        Ok(())
+    }
+
+    pub(crate) fn handle_job_run(
+        self: &Arc<Scheduler>,
+        msg: InMessage,
+    ) -> Result<(), MessageStatus> {
+        let run = ScheduledJobRun::decode(msg.data.as_ref()).map_err(|err| {
+            error!("Error decoding incoming job run: {}", err);
+            MessageStatus::DecodeError
+        })?;
+
+        let job = {
+            let jobs = self.jobs.lock().unwrap();
+            jobs.get(&run.name).cloned()
+        };
+
+        let scheduler = self.clone();
+        spawn(async move {
+            match job {
+                Some(job) => {
+                    debug!("Running scheduled job '{}'", run.name);
+                    if let Err(err) = job().await {
+                        error!("Scheduled job '{}' failed: {}", run.name, err);
+                    }
+                }
+                None => {
+                    error!("Got a run for unknown scheduled job '{}'", run.name);
+                }
+            }
+
+            // the run is reported as done even if it failed so that the job isn't blocked
+            let res = scheduler.host_message_sender.send(OutMessage {
+                r#type: OutMessageType::ScheduledJobDone.into(),
+                rendez_vous_id: msg.rendez_vous_id,
+                data: Vec::new(),
+            });
+            if let Err(err) = res {
+                error!(
+                    "Couldn't report scheduled job '{}' as done: {}",
+                    run.name, err
+                );
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SchedulerError {
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+    #[error(transparent)]
+    HostMessage(#[from] HostMessageError),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use exocore_protos::apps::in_message::InMessageType;
+    use futures::channel::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn schedule_and_run_job() {
+        let (mut out_msg_rcv, scheduler) = create_test_scheduler();
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        {
+            let runs = runs.clone();
+            scheduler
+                .schedule(
+                    "job",
+                    Schedule::Interval(Duration::from_secs(60)),
+                    move || {
+                        let runs = runs.clone();
+                        async move {
+                            runs.fetch_add(1, Ordering::SeqCst);
+                            Ok(())
+                        }
+                    },
+                )
+                .unwrap();
+        }
+
+        // job should have been registered to host
+        let out_msg = out_msg_rcv.try_next().unwrap().unwrap();
+        assert_eq!(out_msg.r#type, OutMessageType::SchedulerRegisterJob as i32);
+        let job = ScheduledJob::decode(out_msg.data.as_ref()).unwrap();
+        assert_eq!(job.name, "job");
+        assert_eq!(job.schedule, Some(ScheduleProto::IntervalSecs(60)));
+
+        // host requests a run, which should run the job and report it as done
+        scheduler.handle_job_run(job_run_message("job", 3)).unwrap();
+        crate::executor::poll_executor();
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        let out_msg = out_msg_rcv.try_next().unwrap().unwrap();
+        assert_eq!(out_msg.r#type, OutMessageType::ScheduledJobDone as i32);
+        assert_eq!(out_msg.rendez_vous_id, 3);
+    }
+
+    #[test]
+    fn run_unknown_job() {
+        let (mut out_msg_rcv, scheduler) = create_test_scheduler();
+
+        // run should be reported as done so that the host doesn't wait for it
+        scheduler
+            .handle_job_run(job_run_message("unknown", 4))
+            .unwrap();
+        crate::executor::poll_executor();
+
+        let out_msg = out_msg_rcv.try_next().unwrap().unwrap();
+        assert_eq!(out_msg.r#type, OutMessageType::ScheduledJobDone as i32);
+        assert_eq!(out_msg.rendez_vous_id, 4);
+    }
+
+    fn job_run_message(name: &str, rendez_vous_id: u32) -> InMessage {
+        InMessage {
+            r#type: InMessageType::ScheduledJobRun.into(),
+            rendez_vous_id,
+            data: ScheduledJobRun {
+                name: name.to_string(),
+                time: None,
+            }
+            .encode_to_vec(),
+            error: String::new(),
+        }
+    }
+
+    fn create_test_scheduler() -> (mpsc::Receiver<OutMessage>, Arc<Scheduler>) {
+        let (out_msg_sender, out_msg_rcv) = mpsc::channel(10);
+        let scheduler = {
+            let mut scheduler = Scheduler::new();
+            let out_msg_sender = Arc::new(Mutex::new(out_msg_sender));
+            scheduler.host_message_sender = HostMessageSender::from_fn(move |msg| {
+                let mut out_msg_sender = out_msg_sender.lock().unwrap();
+                out_msg_sender.try_send(msg).unwrap();
+                MessageStatus::Ok
+            });
+            Arc::new(scheduler)
+        };
+
+        (out_msg_rcv, scheduler)
+    }
+}
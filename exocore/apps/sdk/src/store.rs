@@ -17,6 +17,7 @@ use exocore_store::mutation::MutationRequestLike;
 use futures::channel::oneshot;
 
 use crate::{
+    binding::{HostMessageError, HostMessageSender},
     prelude::{sleep, spawn},
     time::{now, Timestamp},
 };
@@ -31,8 +32,7 @@ pub struct Store {
     next_rdv: AtomicUsize,
     inner: Mutex<Inner>,
 
-    #[cfg(test)]
-    host_message_sender: Option<Box<dyn Fn(OutMessage) -> MessageStatus + Send + Sync>>,
+    host_message_sender: HostMessageSender,
 }
 
 #[derive(Default)]
@@ -52,8 +52,7 @@ impl Store {
             next_rdv: AtomicUsize::new(0),
             inner: Mutex::new(Inner::default()),
 
-            #[cfg(test)]
-            host_message_sender: None,
+            host_message_sender: HostMessageSender::default(),
         }
     }
 
@@ -81,7 +80,7 @@ impl Store {
             inner.pending_mutations.insert(rdv, pending);
         }
 
-        self.send_host_message(msg)?;
+        self.host_message_sender.send(msg)?;
 
         receiver.await.map_err(StoreError::from)?
     }
@@ -105,7 +104,7 @@ impl Store {
             inner.pending_queries.insert(rdv, pending);
         }
 
-        self.send_host_message(msg)?;
+        self.host_message_sender.send(msg)?;
 
         receiver.await.map_err(StoreError::from)?
     }
@@ -164,26 +163,6 @@ impl Store {
             }
         });
     }
-
-    #[cfg(not(test))]
-    fn send_host_message(&self, msg: OutMessage) -> Result<(), StoreError> {
-        let encoded = msg.encode_to_vec();
-        unsafe {
-            let code = crate::binding::__exocore_host_out_message(encoded.as_ptr(), encoded.len());
-            StoreError::from_message_status(code as i32)?;
-        }
-
-        Ok(())
-    }
-
-    #[cfg(test)]
-    fn send_host_message(&self, msg: OutMessage) -> Result<(), StoreError> {
-        let sender = self.host_message_sender.as_ref().unwrap();
-        let code = sender(msg);
-        StoreError::from_message_status(code as i32)?;
-
-        Ok(())
-    }
 }
 
 fn check_timed_out_queries(inner: &mut std::sync::MutexGuard<Inner>, now: Timestamp) {
@@ -216,27 +195,14 @@ fn check_timed_out_mutations(inner: &mut std::sync::MutexGuard<Inner>, now: Time
 pub enum StoreError {
     #[error(transparent)]
     Unknown(#[from] anyhow::Error),
-    #[error("Host message error: {0:?}")]
-    HostMessage(MessageStatus),
+    #[error(transparent)]
+    HostMessage(#[from] HostMessageError),
     #[error("Remote store error: {0:?}")]
     Remote(String),
     #[error("Query or mutation got cancelled or timed out")]
     Cancelled(#[from] oneshot::Canceled),
 }
 
-impl StoreError {
-    fn from_message_status(code: i32) -> Result<(), StoreError> {
-        match MessageStatus::try_from(code) {
-            Ok(MessageStatus::Ok) => Ok(()),
-            Ok(status) => Err(StoreError::HostMessage(status)),
-            Err(err) => Err(StoreError::Unknown(anyhow::anyhow!(
-                "Unknown message status code: {}. err: {err}",
-                code
-            ))),
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use exocore_protos::{apps::in_message::InMessageType, store::MutationRequest};
@@ -327,11 +293,11 @@ mod tests {
         let store = {
             let mut store = Store::new();
             let out_msg_sender = Arc::new(Mutex::new(out_msg_sender));
-            store.host_message_sender = Some(Box::new(move |msg| {
+            store.host_message_sender = HostMessageSender::from_fn(move |msg| {
                 let mut out_msg_sender = out_msg_sender.lock().unwrap();
                 out_msg_sender.try_send(msg).unwrap();
                 MessageStatus::Ok
-            }));
+            });
             Arc::new(store)
         };
 
@@ -0,0 +1,189 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use exocore_protos::{
+    apps::{out_message::OutMessageType, EntityTriggered, InMessage, MessageStatus, OutMessage},
+    prost::Message,
+    store::Entity,
+};
+use futures::{future::BoxFuture, Future, FutureExt};
+
+use crate::{binding::HostMessageSender, executor::spawn};
+
+type HandlerFn = dyn Fn(Entity) -> BoxFuture<'static, Result<(), anyhow::Error>> + Send + Sync;
+
+/// Handlers of the triggers declared in the application's manifest.
+///
+/// Every time a trait of a trigger's type is put on an entity, the host
+/// delivers the entity to the trigger's handler, including when the trait got
+/// replicated from another node. Entities are delivered one at a time per
+/// trigger, in the order in which their mutations got indexed by the node, and
+/// are delivered again if the application stops before their handler
+/// completes. Handlers should therefore be idempotent.
+///
+/// Handlers need to be registered when the application starts.
+pub struct Triggers {
+    handlers: Mutex<HashMap<String, Arc<HandlerFn>>>,
+
+    host_message_sender: HostMessageSender,
+}
+
+impl Triggers {
+    pub(crate) fn new() -> Triggers {
+        Triggers {
+            handlers: Mutex::new(HashMap::new()),
+
+            host_message_sender: HostMessageSender::default(),
+        }
+    }
+
+    /// Registers the handler of the trigger with the given name in the
+    /// manifest. The entity is considered handled once the handler's future
+    /// completes, whether it succeeded or not.
+    pub fn on<F, O>(&self, trigger: impl Into<String>, handler: F)
+    where
+        F: (Fn(Entity) -> O) + Send + Sync + 'static,
+        O: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    {
+        let mut handlers = self.handlers.lock().unwrap();
+        handlers.insert(
+            trigger.into(),
+            Arc::new(move |entity| handler(entity).boxed()),
+        );
+    }
+
+    pub(crate) fn handle_entity_triggered(
+        self: &Arc<Triggers>,
+        msg: InMessage,
+    ) -> Result<(), MessageStatus> {
+        let triggered = EntityTriggered::decode(msg.data.as_ref()).map_err(|err| {
+            error!("Error decoding incoming triggered entity: {}", err);
+            MessageStatus::DecodeError
+        })?;
+
+        let handler = {
+            let handlers = self.handlers.lock().unwrap();
+            handlers.get(&triggered.trigger).cloned()
+        };
+
+        // the entity isn't reported as handled so that it gets delivered again once a
+        // handler is registered, which blocks the trigger until then
+        let Some(handler) = handler else {
+            error!(
+                "Got an entity for trigger '{}' without handler. Leaving it unhandled.",
+                triggered.trigger
+            );
+            return Ok(());
+        };
+
+        let triggers = self.clone();
+        spawn(async move {
+            if let Some(entity) = triggered.entity {
+                let entity_id = entity.id.clone();
+                if let Err(err) = handler(entity).await {
+                    error!(
+                        "Trigger '{}' failed on entity {}: {}",
+                        triggered.trigger, entity_id, err
+                    );
+                }
+            } else {
+                error!("Got trigger '{}' without entity", triggered.trigger);
+            }
+
+            // the entity is reported as handled even if it failed so that the trigger isn't blocked
+            let res = triggers.host_message_sender.send(OutMessage {
+                r#type: OutMessageType::EntityTriggerDone.into(),
+                rendez_vous_id: msg.rendez_vous_id,
+                data: Vec::new(),
+            });
+            if let Err(err) = res {
+                error!("Couldn't report triggered entity as handled: {}", err);
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exocore_protos::apps::in_message::InMessageType;
+    use futures::channel::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn handle_triggered_entity() {
+        let (mut out_msg_rcv, triggers) = create_test_triggers();
+
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        {
+            let handled = handled.clone();
+            triggers.on("emails", move |entity| {
+                let handled = handled.clone();
+                async move {
+                    handled.lock().unwrap().push(entity.id);
+                    Ok(())
+                }
+            });
+        }
+
+        triggers
+            .handle_entity_triggered(triggered_message("emails", "entity1", 3))
+            .unwrap();
+        crate::executor::poll_executor();
+        assert_eq!(*handled.lock().unwrap(), vec!["entity1".to_string()]);
+
+        let out_msg = out_msg_rcv.try_next().unwrap().unwrap();
+        assert_eq!(out_msg.r#type, OutMessageType::EntityTriggerDone as i32);
+        assert_eq!(out_msg.rendez_vous_id, 3);
+    }
+
+    #[test]
+    fn trigger_without_handler() {
+        let (mut out_msg_rcv, triggers) = create_test_triggers();
+
+        // entity shouldn't be reported as handled so that it gets delivered again
+        triggers
+            .handle_entity_triggered(triggered_message("unknown", "entity1", 4))
+            .unwrap();
+        crate::executor::poll_executor();
+
+        assert!(out_msg_rcv.try_next().is_err());
+    }
+
+    fn triggered_message(trigger: &str, entity_id: &str, rendez_vous_id: u32) -> InMessage {
+        InMessage {
+            r#type: InMessageType::EntityTriggered.into(),
+            rendez_vous_id,
+            data: EntityTriggered {
+                trigger: trigger.to_string(),
+                entity: Some(Entity {
+                    id: entity_id.to_string(),
+                    ..Default::default()
+                }),
+                operation_id: 1,
+            }
+            .encode_to_vec(),
+            error: String::new(),
+        }
+    }
+
+    fn create_test_triggers() -> (mpsc::Receiver<OutMessage>, Arc<Triggers>) {
+        let (out_msg_sender, out_msg_rcv) = mpsc::channel(10);
+        let triggers = {
+            let mut triggers = Triggers::new();
+            let out_msg_sender = Arc::new(Mutex::new(out_msg_sender));
+            triggers.host_message_sender = HostMessageSender::from_fn(move |msg| {
+                let mut out_msg_sender = out_msg_sender.lock().unwrap();
+                out_msg_sender.try_send(msg).unwrap();
+                MessageStatus::Ok
+            });
+            Arc::new(triggers)
+        };
+
+        (out_msg_rcv, triggers)
+    }
+}
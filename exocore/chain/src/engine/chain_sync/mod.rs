@@ -137,6 +137,28 @@ impl<CS: ChainStore> ChainSynchronizer<CS> {
             }
         }
 
+        // make sure that no other node has a more advanced chain than ours if we're the
+        // leader, which can happen if we missed signatures of a block that got committed
+        // by another node
+        if self.status == Status::Synchronized && self.im_leader() {
+            if let Some(last_block) = store.get_last_block()? {
+                let local_height = last_block.get_height()?;
+                let node_ahead = self.nodes_info.values().any(|info| {
+                    info.status() == NodeStatus::Synchronized
+                        && info
+                            .last_known_block
+                            .as_ref()
+                            .is_some_and(|block| block.height > local_height)
+                });
+
+                if node_ahead {
+                    info!("Lost leadership status because another node has a more advanced chain");
+                    self.leader = None;
+                    self.status = Status::Unknown;
+                }
+            }
+        }
+
         // if we lost synchronization, we reset request trackers to allow quicker
         // resynchronization by not waiting for request interval
         if status_start == Status::Synchronized && self.status != Status::Synchronized {
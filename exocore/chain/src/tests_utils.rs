@@ -19,7 +19,7 @@ use tempfile::TempDir;
 use crate::{
     block::{BlockBuilder, BlockOffset},
     chain::ChainStore,
-    engine::{EngineHandle, EngineOperation, Event, RequestTrackerConfig},
+    engine::{ChainSyncConfig, EngineHandle, EngineOperation, Event, RequestTrackerConfig},
     operation::OperationId,
     *,
 };
@@ -49,9 +49,16 @@ pub struct ClusterSpec {
 
 impl TestChainCluster {
     pub fn new(count: usize) -> Result<TestChainCluster, anyhow::Error> {
-        let tempdir = tempfile::tempdir()?;
+        Self::new_with_transport(count, MockTransport::default())
+    }
 
-        let transport_hub = MockTransport::default();
+    /// Creates a cluster communicating through the given transport, which can
+    /// simulate an unreliable network.
+    pub fn new_with_transport(
+        count: usize,
+        transport_hub: MockTransport,
+    ) -> Result<TestChainCluster, anyhow::Error> {
+        let tempdir = tempfile::tempdir()?;
 
         let mut clocks = Vec::new();
         let mut nodes = Vec::new();
@@ -77,6 +84,13 @@ impl TestChainCluster {
 
             let engine_config = EngineConfig {
                 manager_timer_interval: Duration::from_millis(20),
+                chain_sync_config: ChainSyncConfig {
+                    request_tracker: RequestTrackerConfig {
+                        min_interval: Duration::from_millis(200),
+                        max_interval: Duration::from_millis(1000),
+                    },
+                    ..ChainSyncConfig::default()
+                },
                 pending_sync_config: PendingSyncConfig {
                     request_tracker_config: RequestTrackerConfig {
                         min_interval: Duration::from_millis(200),
@@ -1,5 +1,13 @@
 use exocore_chain::{operation::Operation, tests_utils::*, *};
-use exocore_core::{cell::CellNodeRole, tests_utils::expect_result_eventually};
+use std::time::Duration;
+
+use exocore_core::{
+    cell::CellNodeRole,
+    futures::sleep,
+    tests_utils::{expect_eventually, expect_result_eventually},
+    time::Clock,
+};
+use exocore_transport::testing::{MockTransport, NetworkConditions};
 use itertools::Itertools;
 
 #[macro_use]
@@ -167,6 +175,218 @@ async fn two_nodes_full_replication() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn two_nodes_replication_unreliable_network() -> anyhow::Result<()> {
+    let transport = MockTransport::new_simulated(Clock::new_mocked(), 3838);
+    let mut cluster = TestChainCluster::new_with_transport(2, transport)?;
+    cluster.create_node(0)?;
+    cluster.create_node(1)?;
+
+    cluster.create_chain_genesis_block(0);
+
+    cluster.start_engine(0).await;
+    cluster.start_engine(1).await;
+    cluster.wait_started(0);
+    cluster.wait_started(1);
+
+    // every message gets duplicated and held back, which shouldn't prevent
+    // operations from being committed
+    cluster
+        .transport_hub
+        .set_network_conditions(NetworkConditions {
+            duplication: 1.0,
+            reordering: 1.0,
+            ..Default::default()
+        });
+    let op1 = cluster
+        .get_handle_mut(0)
+        .write_entry_operation(b"i love rust 0")?;
+    cluster.wait_operations_committed(0, &[op1]);
+    cluster.wait_operations_committed(1, &[op1]);
+
+    let stats = cluster.transport_hub.network_stats();
+    assert!(stats.duplicated > 0);
+    assert!(stats.reordered > 0);
+
+    // every message gets lost, until the network gets somewhat reliable again
+    cluster
+        .transport_hub
+        .set_network_conditions(NetworkConditions {
+            loss: 1.0,
+            ..Default::default()
+        });
+    let mut operations_id = vec![op1];
+    operations_id.push(
+        cluster
+            .get_handle_mut(1)
+            .write_entry_operation(b"i love rust 1")?,
+    );
+    expect_eventually(|| cluster.transport_hub.network_stats().lost > 0);
+
+    cluster
+        .transport_hub
+        .set_network_conditions(NetworkConditions {
+            latency: Duration::from_millis(5),
+            jitter: Duration::from_millis(10),
+            loss: 0.1,
+            duplication: 0.1,
+            reordering: 0.1,
+        });
+    for i in 2..5 {
+        operations_id.push(
+            cluster
+                .get_handle_mut(i % 2)
+                .write_entry_operation(b"i love rust")?,
+        );
+    }
+
+    cluster.wait_operations_committed(0, &operations_id);
+    cluster.wait_operations_committed(1, &operations_id);
+
+    let segments_0 = cluster.get_handle(0).get_chain_segments()?;
+    let segments_1 = cluster.get_handle(1).get_chain_segments()?;
+    assert_eq!(segments_0, segments_1);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn two_nodes_partition_heal() -> anyhow::Result<()> {
+    let mut cluster = TestChainCluster::new(2)?;
+    cluster.create_node(0)?;
+    cluster.create_node(1)?;
+
+    cluster.create_chain_genesis_block(0);
+
+    cluster.start_engine(0).await;
+    cluster.start_engine(1).await;
+    cluster.wait_started(0);
+    cluster.wait_started(1);
+
+    let node0 = cluster.nodes[0].id().clone();
+    let node1 = cluster.nodes[1].id().clone();
+    cluster.transport_hub.partition(&[node0], &[node1]);
+
+    // without the other node, there is no majority to commit the operation
+    let op1 = cluster
+        .get_handle_mut(0)
+        .write_entry_operation(b"i love rust 0")?;
+    sleep(Duration::from_secs(1)).await;
+    assert!(cluster.get_handle(1).get_operation(op1)?.is_none());
+    let node0_op = cluster.get_handle(0).get_operation(op1)?.unwrap();
+    assert!(!node0_op.status.is_committed());
+    assert!(cluster.transport_hub.network_stats().partitioned > 0);
+
+    cluster.transport_hub.heal_partitions();
+
+    let op2 = cluster
+        .get_handle_mut(1)
+        .write_entry_operation(b"i love rust 1")?;
+    cluster.wait_operations_committed(0, &[op1, op2]);
+    cluster.wait_operations_committed(1, &[op1, op2]);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn two_nodes_divergent_pending_stores_heal() -> anyhow::Result<()> {
+    let mut cluster = TestChainCluster::new(2)?;
+    cluster.create_node(0)?;
+    cluster.create_node(1)?;
+
+    cluster.create_chain_genesis_block(0);
+
+    cluster.start_engine(0).await;
+    cluster.start_engine(1).await;
+    cluster.wait_started(0);
+    cluster.wait_started(1);
+
+    let node0 = cluster.nodes[0].id().clone();
+    let node1 = cluster.nodes[1].id().clone();
+    cluster.transport_hub.partition(&[node0], &[node1]);
+
+    // each side gets operations that the other side doesn't know about
+    let mut node0_ops = Vec::new();
+    let mut node1_ops = Vec::new();
+    for _i in 0..3 {
+        node0_ops.push(cluster.get_handle(0).write_entry_operation(b"node 0")?);
+        node1_ops.push(cluster.get_handle(1).write_entry_operation(b"node 1")?);
+    }
+    sleep(Duration::from_secs(1)).await;
+    for op in &node0_ops {
+        assert!(cluster.get_handle(1).get_operation(*op)?.is_none());
+    }
+    for op in &node1_ops {
+        assert!(cluster.get_handle(0).get_operation(*op)?.is_none());
+    }
+
+    cluster.transport_hub.heal_partitions();
+
+    // once healed, pending stores get merged and all operations get committed
+    let all_ops = node0_ops.iter().chain(&node1_ops).cloned().collect_vec();
+    cluster.wait_operations_committed(0, &all_ops);
+    cluster.wait_operations_committed(1, &all_ops);
+
+    let segments_0 = cluster.get_handle(0).get_chain_segments()?;
+    let segments_1 = cluster.get_handle(1).get_chain_segments()?;
+    assert_eq!(segments_0, segments_1);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn three_nodes_competing_commits_after_heal() -> anyhow::Result<()> {
+    let mut cluster = TestChainCluster::new(3)?;
+    cluster.create_node(0)?;
+    cluster.create_node(1)?;
+    cluster.create_node(2)?;
+
+    cluster.create_chain_genesis_block(0);
+
+    for i in 0..3 {
+        cluster.start_engine(i).await;
+    }
+    for i in 0..3 {
+        cluster.wait_started(i);
+    }
+
+    let node0 = cluster.nodes[0].id().clone();
+    let node1 = cluster.nodes[1].id().clone();
+    let node2 = cluster.nodes[2].id().clone();
+    cluster.transport_hub.partition(&[node0, node1], &[node2]);
+
+    // the majority side keeps committing blocks, while the minority side can only
+    // propose blocks that can't get enough signatures
+    let majority_op = cluster.get_handle(0).write_entry_operation(b"majority")?;
+    let minority_op = cluster.get_handle(2).write_entry_operation(b"minority")?;
+    cluster.wait_operation_committed(0, majority_op);
+    cluster.wait_operation_committed(1, majority_op);
+
+    sleep(Duration::from_secs(1)).await;
+    let node2_op = cluster.get_handle(2).get_operation(minority_op)?.unwrap();
+    assert!(!node2_op.status.is_committed());
+
+    cluster.transport_hub.heal_partitions();
+
+    // once healed, the minority node catches up with the majority's chain, and its
+    // operation gets committed in a later block on top of it
+    let other_op = cluster.get_handle(1).write_entry_operation(b"healed")?;
+    let all_ops = [majority_op, minority_op, other_op];
+    for i in 0..3 {
+        cluster.wait_operations_committed(i, &all_ops);
+    }
+
+    let segments_0 = cluster.get_handle(0).get_chain_segments()?;
+    let segments_2 = cluster.get_handle(2).get_chain_segments()?;
+    assert_eq!(segments_0, segments_2);
+
+    let (offset_0, height_0) = cluster.get_handle(0).get_chain_last_block_info()?.unwrap();
+    let (offset_2, height_2) = cluster.get_handle(2).get_chain_last_block_info()?.unwrap();
+    assert_eq!((offset_0, height_0), (offset_2, height_2));
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn two_nodes_pending_store_cleanup() -> anyhow::Result<()> {
     let mut cluster = TestChainCluster::new(2)?;
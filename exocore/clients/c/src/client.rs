@@ -1,10 +1,11 @@
 use std::{
-    ffi::CString,
+    ffi::{CStr, CString},
     os::raw::c_void,
     sync::{Arc, Mutex, Weak},
     time::Duration,
 };
 
+use exocore_apps_client::{Client as AppsClient, ClientHandle as AppsClientHandle};
 use exocore_core::{cell::Cell, futures::Runtime, time::Clock};
 use exocore_protos::{
     generated::exocore_store::EntityQuery, prost::Message, store::MutationRequest,
@@ -254,6 +255,162 @@ pub unsafe extern "C" fn exocore_store_watched_query_cancel(
     client.cancel_operation(handle.query_id);
 }
 
+/// Publishes a message on a topic to an application, without waiting for it to
+/// be handled.
+///
+/// `data_bytes` and `data_size` describes the message's data, which is still
+/// owned by caller after call.
+///
+/// # Safety
+/// * `client` needs to be a valid `Client`.
+/// * `app_id` and `topic` need to be valid C strings, owned by the caller.
+/// * `data_bytes` needs to be a byte array of size `data_size`.
+#[no_mangle]
+pub unsafe extern "C" fn exocore_app_publish(
+    client: *mut Client,
+    app_id: *const libc::c_char,
+    topic: *const libc::c_char,
+    data_bytes: *const libc::c_uchar,
+    data_size: usize,
+) -> AppPublishStatus {
+    let client = client.as_mut().unwrap();
+
+    match client.app_publish(app_id, topic, data_bytes, data_size) {
+        Ok(()) => AppPublishStatus::Success,
+        Err(status) => status,
+    }
+}
+
+#[repr(u8)]
+pub enum AppPublishStatus {
+    Success = 0,
+    Error,
+}
+
+/// Sends a request to a method of an application, for which the response or
+/// failure will be reported via the given `callback`.
+///
+/// `data_bytes` and `data_size` describes the request's data, which is still
+/// owned by caller after call. Callback's response is owned by the library.
+///
+/// `callback` is called exactly once (with `callback_ctx` as first argument)
+/// when the response is received or the request failed.
+///
+/// # Safety
+/// * `client` needs to be a valid `Client`.
+/// * `app_id` and `method` need to be valid C strings, owned by the caller.
+/// * `data_bytes` needs to be a byte array of size `data_size`.
+/// * `callback_ctx` needs to be safe to send and use across threads.
+/// * `callback_ctx` is owned by the caller and should be freed when after
+///   callback got called.
+#[no_mangle]
+pub unsafe extern "C" fn exocore_app_request(
+    client: *mut Client,
+    app_id: *const libc::c_char,
+    method: *const libc::c_char,
+    data_bytes: *const libc::c_uchar,
+    data_size: usize,
+    callback: extern "C" fn(status: AppRequestStatus, *const libc::c_uchar, usize, *const c_void),
+    callback_ctx: *const c_void,
+) -> AppRequestHandle {
+    let client = client.as_mut().unwrap();
+
+    match client.app_request(
+        app_id,
+        method,
+        data_bytes,
+        data_size,
+        callback,
+        callback_ctx,
+    ) {
+        Ok(res) => res,
+        Err(status) => AppRequestHandle { status },
+    }
+}
+
+#[repr(u8)]
+pub enum AppRequestStatus {
+    Success = 0,
+    Error,
+}
+
+#[repr(C)]
+pub struct AppRequestHandle {
+    status: AppRequestStatus,
+}
+
+/// Subscribes to the messages published by an application on a topic.
+///
+/// `callback` is called (with `callback_ctx` as first argument) when a message
+/// is received, or when the subscription has completed. When a call with a
+/// `Done` or `Error` status is made, no data is given and no further calls will
+/// be done. Callback's data is owned by the library.
+///
+/// Unless it has already failed, a subscription needs to be cancelled with
+/// `exocore_app_subscribe_cancel`.
+///
+/// # Safety
+/// * `client` needs to be a valid `Client`.
+/// * `app_id` and `topic` need to be valid C strings, owned by the caller.
+/// * `callback_ctx` needs to be safe to send and use across threads.
+/// * `callback_ctx` is owned by client and should be freed when receiving a
+///   `Done` or `Error` status.
+#[no_mangle]
+pub unsafe extern "C" fn exocore_app_subscribe(
+    client: *mut Client,
+    app_id: *const libc::c_char,
+    topic: *const libc::c_char,
+    callback: extern "C" fn(
+        status: AppSubscriptionStatus,
+        *const libc::c_uchar,
+        usize,
+        *const c_void,
+    ),
+    callback_ctx: *const c_void,
+) -> AppSubscriptionHandle {
+    let client = client.as_mut().unwrap();
+
+    match client.app_subscribe(app_id, topic, callback, callback_ctx) {
+        Ok(res) => res,
+        Err(status) => AppSubscriptionHandle {
+            status,
+            subscription_id: 0,
+        },
+    }
+}
+
+#[repr(u8)]
+pub enum AppSubscriptionStatus {
+    Success = 0,
+    Done,
+    Error,
+}
+
+#[repr(C)]
+pub struct AppSubscriptionHandle {
+    status: AppSubscriptionStatus,
+    subscription_id: u64,
+}
+
+/// Cancels a subscription to an application's topic so that no further
+/// messages can be received.
+///
+/// It is OK to cancel a subscription even if it may have already been
+/// cancelled or failed. If the subscription is successfully cancelled, the
+/// callback will be called with a `Done` status, and the callback context will
+/// need to be freed by caller.
+///
+/// # Safety
+/// * `client` needs to be a valid `Client`.
+#[no_mangle]
+pub unsafe extern "C" fn exocore_app_subscribe_cancel(
+    client: *mut Client,
+    handle: AppSubscriptionHandle,
+) {
+    let client = client.as_mut().unwrap();
+    client.cancel_operation(handle.subscription_id);
+}
+
 /// Returns a list of HTTP endpoints available on nodes of the cell, returned as
 /// a `;` delimited string.
 ///
@@ -338,6 +495,7 @@ pub struct Client {
     clock: Clock,
     cell: Cell,
     store_handle: Arc<ClientHandle>,
+    apps_handle: AppsClientHandle,
     inner: Mutex<SyncInner>,
 }
 
@@ -371,9 +529,9 @@ impl Client {
         })?;
 
         let transport_config = Libp2pTransportConfig::default();
-        let mut transport = Libp2pTransport::new(local_node.node.clone(), transport_config);
-
         let clock = Clock::new();
+        let mut transport =
+            Libp2pTransport::new(local_node.node.clone(), transport_config, clock.clone());
 
         let store_transport = transport
             .get_handle(cell.clone(), ServiceType::Store)
@@ -381,7 +539,12 @@ impl Client {
                 error!("Couldn't get transport handle for remote store: {}", err);
                 ClientStatus::Error
             })?;
-        let remote_store_config = ClientConfiguration::default();
+        // mobile clients are often offline, so mutations are queued and queries are
+        // answered from cache until a store node is reachable
+        let remote_store_config = ClientConfiguration {
+            offline_mode: true,
+            ..ClientConfiguration::default()
+        };
         let remote_store_client = StoreClient::new(
             remote_store_config,
             cell.clone(),
@@ -394,6 +557,21 @@ impl Client {
         })?;
 
         let store_handle = Arc::new(remote_store_client.get_handle());
+
+        let apps_transport = transport
+            .get_handle(cell.clone(), ServiceType::Client)
+            .map_err(|err| {
+                error!("Couldn't get transport handle for apps client: {}", err);
+                ClientStatus::Error
+            })?;
+        let apps_client = AppsClient::new(
+            Default::default(),
+            cell.clone(),
+            clock.clone(),
+            apps_transport,
+        );
+        let apps_handle = apps_client.get_handle();
+
         let management_transport_handle = transport
             .get_handle(cell.clone(), ServiceType::None)
             .map_err(|err| {
@@ -413,11 +591,17 @@ impl Client {
             info!("Remote store is done");
         });
 
+        runtime.spawn(async move {
+            let _ = apps_client.run().await;
+            info!("Apps client is done");
+        });
+
         Ok(Client {
             _runtime: runtime,
             clock,
             cell,
             store_handle,
+            apps_handle,
             inner: Mutex::new(SyncInner {
                 transport_handle: management_transport_handle,
                 operations_canceller: WeakKeyHashMap::new(),
@@ -636,6 +820,159 @@ impl Client {
         })
     }
 
+    unsafe fn app_publish(
+        &self,
+        app_id: *const libc::c_char,
+        topic: *const libc::c_char,
+        data_bytes: *const libc::c_uchar,
+        data_size: usize,
+    ) -> Result<(), AppPublishStatus> {
+        let app_id = CStr::from_ptr(app_id).to_string_lossy();
+        let topic = CStr::from_ptr(topic).to_string_lossy();
+        let data = std::slice::from_raw_parts(data_bytes, data_size).to_vec();
+
+        self.apps_handle
+            .publish(&app_id, &topic, data)
+            .map_err(|err| {
+                warn!("Couldn't publish to app {}: {}", app_id, err);
+                AppPublishStatus::Error
+            })
+    }
+
+    #[allow(clippy::redundant_locals)] // because of redefinition of callback_ctx
+    unsafe fn app_request(
+        &self,
+        app_id: *const libc::c_char,
+        method: *const libc::c_char,
+        data_bytes: *const libc::c_uchar,
+        data_size: usize,
+        callback: extern "C" fn(
+            status: AppRequestStatus,
+            *const libc::c_uchar,
+            usize,
+            *const c_void,
+        ),
+        callback_ctx: *const c_void,
+    ) -> Result<AppRequestHandle, AppRequestStatus> {
+        let app_id = CStr::from_ptr(app_id).to_string_lossy().to_string();
+        let method = CStr::from_ptr(method).to_string_lossy().to_string();
+        let data = std::slice::from_raw_parts(data_bytes, data_size).to_vec();
+
+        debug!("Sending a request to app {}", app_id);
+
+        let apps = self.apps_handle.clone();
+        let callback_ctx = CallbackContext { ctx: callback_ctx };
+        self._runtime.spawn(async move {
+            let callback_ctx = callback_ctx; // required since the struct is send + sync, not the field
+
+            match apps.request(&app_id, &method, data).await {
+                Ok(response) => {
+                    debug!("App request response received");
+                    callback(
+                        AppRequestStatus::Success,
+                        response.as_ptr(),
+                        response.len(),
+                        callback_ctx.ctx,
+                    );
+                }
+                Err(err) => {
+                    warn!("App request to method '{}' has failed: {}", method, err);
+                    callback(
+                        AppRequestStatus::Error,
+                        std::ptr::null(),
+                        0,
+                        callback_ctx.ctx,
+                    );
+                }
+            }
+        });
+
+        Ok(AppRequestHandle {
+            status: AppRequestStatus::Success,
+        })
+    }
+
+    #[allow(clippy::redundant_locals)] // because of redefinition of callback_ctx
+    unsafe fn app_subscribe(
+        &self,
+        app_id: *const libc::c_char,
+        topic: *const libc::c_char,
+        callback: extern "C" fn(
+            status: AppSubscriptionStatus,
+            *const libc::c_uchar,
+            usize,
+            *const c_void,
+        ),
+        callback_ctx: *const c_void,
+    ) -> Result<AppSubscriptionHandle, AppSubscriptionStatus> {
+        let app_id = CStr::from_ptr(app_id).to_string_lossy();
+        let topic = CStr::from_ptr(topic).to_string_lossy();
+        let mut subscription = self.apps_handle.subscribe(&app_id, &topic).map_err(|err| {
+            warn!(
+                "Couldn't subscribe to topic '{}' of app {}: {}",
+                topic, app_id, err
+            );
+            AppSubscriptionStatus::Error
+        })?;
+
+        let (cancel_sender, cancel_receiver) = oneshot::channel();
+        let operation_id = {
+            let mut inner = self.inner.lock().unwrap();
+            let operation_id = Arc::new(inner.next_operation_id);
+            inner.next_operation_id += 1;
+
+            inner
+                .operations_canceller
+                .insert(operation_id.clone(), cancel_sender);
+
+            operation_id
+        };
+        debug!("Subscribed to an app topic (id={})", operation_id);
+
+        let callback_ctx = CallbackContext { ctx: callback_ctx };
+        let operation_id_clone = operation_id.clone(); // subscription keeps strong ref to it since it's used for cancellation
+        self._runtime.spawn(async move {
+            let callback_ctx = callback_ctx; // required since the struct is send + sync, not the field
+
+            let mut cancel_receiver = cancel_receiver.fuse();
+            loop {
+                let data = select! {
+                    _ = cancel_receiver => {
+                        debug!("App subscription cancelled (id={})", operation_id_clone);
+                        break;
+                    }
+                    data = subscription.next().fuse() => {
+                        data
+                    }
+                };
+
+                let Some(data) = data else {
+                    debug!("App subscription done (id={})", operation_id_clone);
+                    break;
+                };
+
+                callback(
+                    AppSubscriptionStatus::Success,
+                    data.as_ptr(),
+                    data.len(),
+                    callback_ctx.ctx,
+                );
+            }
+
+            callback(
+                AppSubscriptionStatus::Done,
+                std::ptr::null(),
+                0,
+                callback_ctx.ctx,
+            );
+        });
+
+        Ok(AppSubscriptionHandle {
+            status: AppSubscriptionStatus::Success,
+            subscription_id: *operation_id,
+        })
+    }
+
     fn cancel_operation(&self, operation_id: SpawnedOperationId) {
         debug!("Cancelling operation {}", operation_id);
         let mut inner = self.inner.lock().unwrap();
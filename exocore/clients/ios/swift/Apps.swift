@@ -0,0 +1,109 @@
+import Foundation
+
+public class Apps {
+    weak var client: ClientInstance?
+
+    init(client: ClientInstance) {
+        self.client = client
+    }
+
+    public func publish(appId: String, topic: String, data: Data) -> Bool {
+        guard let client = self.client?.client else { return false }
+
+        let status = data.withUnsafeBytes { (dataPtr) -> ExocoreAppPublishStatus in
+            let dataAddr = dataPtr.bindMemory(to: UInt8.self).baseAddress
+
+            return exocore_app_publish(client, appId, topic, dataAddr, UInt(data.count))
+        }
+
+        return status == UInt8(ExocoreAppPublishStatus_Success.rawValue)
+    }
+
+    public func request(appId: String, method: String, data: Data, onCompletion: @escaping (AppRequestStatus, Data?) -> Void) {
+        let cbCtx = AppCallbackContext<AppRequestStatus>(cb: onCompletion)
+        let cbCtxPtr = UnsafeRawPointer(Unmanaged.passRetained(cbCtx).toOpaque())
+
+        let handle = data.withUnsafeBytes { (dataPtr) -> ExocoreAppRequestHandle in
+            let dataAddr = dataPtr.bindMemory(to: UInt8.self).baseAddress
+
+            return exocore_app_request(self.client!.client, appId, method, dataAddr, UInt(data.count), { (status, responsePtr, responseSize, cbCtxPtr) in
+                let cbCtx = Unmanaged<AppCallbackContext<AppRequestStatus>>.fromOpaque(cbCtxPtr!).takeRetainedValue() // consume ptr
+
+                if status == UInt8(ExocoreAppRequestStatus_Error.rawValue) {
+                    cbCtx.cb(.error, nil)
+                } else {
+                    cbCtx.cb(.success, Data(bytes: responsePtr!, count: Int(responseSize)))
+                }
+            }, cbCtxPtr)
+        }
+
+        if handle.status == UInt8(ExocoreAppRequestStatus_Error.rawValue) {
+            // callback won't be called
+            let cbCtx = Unmanaged<AppCallbackContext<AppRequestStatus>>.fromOpaque(cbCtxPtr).takeRetainedValue() // consume ptr
+            cbCtx.cb(.error, nil)
+        }
+    }
+
+    public func subscribe(appId: String, topic: String, onMessage: @escaping (AppSubscriptionStatus, Data?) -> Void) -> AppSubscriptionHandle {
+        let cbCtx = AppCallbackContext<AppSubscriptionStatus>(cb: onMessage)
+        let cbCtxPtr = UnsafeRawPointer(Unmanaged.passRetained(cbCtx).toOpaque())
+
+        let handle = exocore_app_subscribe(self.client!.client, appId, topic, { (status, dataPtr, dataSize, cbCtxPtr) in
+            if status == UInt8(ExocoreAppSubscriptionStatus_Done.rawValue) {
+                let cbCtx = Unmanaged<AppCallbackContext<AppSubscriptionStatus>>.fromOpaque(cbCtxPtr!).takeRetainedValue() // consume ptr
+                cbCtx.cb(.done, nil)
+                return
+            } else if status == UInt8(ExocoreAppSubscriptionStatus_Error.rawValue) {
+                let cbCtx = Unmanaged<AppCallbackContext<AppSubscriptionStatus>>.fromOpaque(cbCtxPtr!).takeRetainedValue() // consume ptr
+                cbCtx.cb(.error, nil)
+                return
+            }
+
+            let cbCtx = Unmanaged<AppCallbackContext<AppSubscriptionStatus>>.fromOpaque(cbCtxPtr!).takeUnretainedValue() // don't consume the ptr
+            cbCtx.cb(.message, Data(bytes: dataPtr!, count: Int(dataSize)))
+        }, cbCtxPtr)
+
+        if handle.status == UInt8(ExocoreAppSubscriptionStatus_Error.rawValue) {
+            // callback won't be called
+            let cbCtx = Unmanaged<AppCallbackContext<AppSubscriptionStatus>>.fromOpaque(cbCtxPtr).takeRetainedValue() // consume ptr
+            cbCtx.cb(.error, nil)
+        }
+
+        return AppSubscriptionHandle(subscriptionHandle: handle, client: self.client!)
+    }
+}
+
+public enum AppRequestStatus {
+    case success
+    case error
+}
+
+public enum AppSubscriptionStatus {
+    case message
+    case done
+    case error
+}
+
+public class AppSubscriptionHandle {
+    var handle: ExocoreAppSubscriptionHandle
+    weak var client: ClientInstance?
+
+    init(subscriptionHandle: ExocoreAppSubscriptionHandle, client: ClientInstance) {
+        self.handle = subscriptionHandle
+        self.client = client
+    }
+
+    deinit {
+        if let client = self.client, self.handle.status == UInt8(ExocoreAppSubscriptionStatus_Success.rawValue) {
+            exocore_app_subscribe_cancel(client.client, self.handle)
+        }
+    }
+}
+
+class AppCallbackContext<Status> {
+    var cb: (Status, Data?) -> Void
+
+    init(cb: @escaping (Status, Data?) -> Void) {
+        self.cb = cb
+    }
+}
@@ -45,6 +45,12 @@ public class ExocoreClient {
             ExocoreClient.defaultInstance!.store
         }
     }
+
+    public static var apps: Apps {
+        get {
+            ExocoreClient.defaultInstance!.apps
+        }
+    }
 }
 
 public class ClientInstance {
@@ -68,6 +74,10 @@ public class ClientInstance {
         Store(client: self)
     }()
 
+    public lazy var apps: Apps = {
+        Apps(client: self)
+    }()
+
     public func resetTransport() {
         exocore_reset_transport(self.client)
     }
@@ -0,0 +1,81 @@
+use std::{cell::RefCell, rc::Rc};
+
+use exocore_apps_client::ClientHandle;
+use exocore_core::futures::spawn_future_non_send;
+use futures::{channel::oneshot, prelude::*};
+use wasm_bindgen::prelude::*;
+
+type CallbackCell = Rc<RefCell<Option<js_sys::Function>>>;
+
+/// Subscription to the messages published by an application on a topic. The
+/// subscription is removed once freed.
+#[wasm_bindgen]
+pub struct AppSubscription {
+    callback_cell: CallbackCell,
+    _drop_sender: oneshot::Sender<()>,
+}
+
+#[wasm_bindgen]
+impl AppSubscription {
+    pub(crate) fn new(apps_handle: ClientHandle, app_id: String, topic: String) -> AppSubscription {
+        let callback_cell = Rc::new(RefCell::<Option<js_sys::Function>>::new(None));
+
+        let callback_cell1 = callback_cell.clone();
+        let report_message = move |data: Vec<u8>| {
+            let callback = callback_cell1.borrow();
+            if let Some(func) = &*callback {
+                let data = js_sys::Uint8Array::from(data.as_ref());
+                if let Err(err) = func.call1(&JsValue::null(), &data) {
+                    error!("Error calling app subscription callback: {:?}", err);
+                }
+            }
+        };
+
+        let (drop_sender, drop_receiver) = oneshot::channel();
+        spawn_future_non_send(async move {
+            let mut messages = match apps_handle.subscribe(&app_id, &topic) {
+                Ok(ok) => ok,
+                Err(err) => {
+                    error!(
+                        "Error subscribing to topic '{}' of app {}: {}",
+                        topic, app_id, err
+                    );
+                    return Ok(());
+                }
+            };
+            let mut drop_receiver = drop_receiver.fuse();
+
+            loop {
+                futures::select! {
+                    data = messages.next().fuse() => {
+                        let Some(data) = data else {
+                            return Ok(());
+                        };
+
+                        report_message(data);
+                    }
+                    _ = drop_receiver => {
+                        return Ok(());
+                    }
+                };
+            }
+        });
+
+        Self {
+            callback_cell,
+            _drop_sender: drop_sender,
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn on_message(&self, callback: js_sys::Function) {
+        let mut cb = self.callback_cell.borrow_mut();
+        *cb = Some(callback);
+    }
+}
+
+impl Drop for AppSubscription {
+    fn drop(&mut self) {
+        debug!("AppSubscription got dropped");
+    }
+}
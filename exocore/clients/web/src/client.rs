@@ -1,5 +1,6 @@
 use std::{rc::Rc, sync::Mutex, time::Duration};
 
+use exocore_apps_client::{Client as AppsClient, ClientHandle as AppsClientHandle};
 use exocore_core::{cell::Cell, futures::spawn_future_non_send, time::Clock};
 use exocore_protos::{
     generated::exocore_store::EntityQuery, prost::Message, store::MutationRequest,
@@ -15,13 +16,17 @@ use exocore_transport::{
 use futures::StreamExt;
 use wasm_bindgen::prelude::*;
 
-use crate::{js::into_js_error, node::LocalNode, watched_query::WatchedQuery};
+use crate::{
+    app_subscription::AppSubscription, js::into_js_error, node::LocalNode,
+    watched_query::WatchedQuery,
+};
 
 #[wasm_bindgen]
 pub struct ExocoreClient {
     clock: Clock,
     cell: Cell,
     store_handle: Rc<ClientHandle>,
+    apps_handle: AppsClientHandle,
     _inner: Rc<Mutex<Inner>>,
 }
 
@@ -47,9 +52,8 @@ impl ExocoreClient {
         let cell = either_cell.cell().clone();
 
         let transport_config = Libp2pTransportConfig::default();
-        let mut transport = Libp2pTransport::new(local_node, transport_config);
-
         let clock = Clock::new();
+        let mut transport = Libp2pTransport::new(local_node, transport_config, clock.clone());
 
         let store_handle = transport
             .get_handle(cell.clone(), ServiceType::Store)
@@ -77,16 +81,35 @@ impl ExocoreClient {
             Ok(())
         });
 
+        let apps_transport_handle = transport
+            .get_handle(cell.clone(), ServiceType::Client)
+            .unwrap();
+        let apps_client = AppsClient::new(
+            Default::default(),
+            cell.clone(),
+            clock.clone(),
+            apps_transport_handle,
+        );
+        let apps_handle = apps_client.get_handle();
+
+        spawn_future_non_send(async move {
+            if let Err(err) = apps_client.run().await {
+                error!("Error running apps client: {}", err);
+            }
+            Ok(())
+        });
+
         let inner = Rc::new(Mutex::new(Inner {
             status_change_callback,
         }));
 
-        let mut client_transport_handle = transport
-            .get_handle(cell.clone(), ServiceType::Client)
+        // nodes' status are dispatched to all services' handles
+        let mut status_transport_handle = transport
+            .get_handle(cell.clone(), ServiceType::None)
             .unwrap();
         let inner_clone = inner.clone();
         spawn_future_non_send(async move {
-            let mut stream = client_transport_handle.get_stream();
+            let mut stream = status_transport_handle.get_stream();
 
             while let Some(event) = stream.next().await {
                 if let InEvent::NodeStatus(_, status) = event {
@@ -124,6 +147,7 @@ impl ExocoreClient {
             clock,
             cell,
             store_handle,
+            apps_handle,
             _inner: inner,
         })
     }
@@ -204,6 +228,45 @@ impl ExocoreClient {
 
         store_node_urls.collect()
     }
+
+    #[wasm_bindgen]
+    pub fn app_publish(
+        &self,
+        app_id: String,
+        topic: String,
+        data: js_sys::Uint8Array,
+    ) -> Result<(), JsValue> {
+        self.apps_handle
+            .publish(&app_id, &topic, js_bytes_to_vec(data))
+            .map_err(|err| into_js_error("publishing to app", err))
+    }
+
+    #[wasm_bindgen]
+    pub fn app_request(
+        &self,
+        app_id: String,
+        method: String,
+        data: js_sys::Uint8Array,
+    ) -> js_sys::Promise {
+        let data = js_bytes_to_vec(data);
+
+        let apps_handle = self.apps_handle.clone();
+        let fut_response = async move {
+            let response = apps_handle
+                .request(&app_id, &method, data)
+                .await
+                .map_err(|err| into_js_error("requesting app", err))?;
+
+            Ok(js_sys::Uint8Array::from(response.as_ref()).into())
+        };
+
+        wasm_bindgen_futures::future_to_promise(fut_response)
+    }
+
+    #[wasm_bindgen]
+    pub fn app_subscribe(&self, app_id: String, topic: String) -> AppSubscription {
+        AppSubscription::new(self.apps_handle.clone(), app_id, topic)
+    }
 }
 
 impl Drop for ExocoreClient {
@@ -3,6 +3,7 @@
 #[macro_use]
 extern crate log;
 
+pub mod app_subscription;
 pub mod client;
 pub mod discovery;
 pub mod node;
@@ -0,0 +1,44 @@
+import { AppSubscription, ExocoreClient } from "./wasm";
+
+export class Apps {
+    wasmClient: ExocoreClient;
+
+    constructor(wasmClient: ExocoreClient) {
+        this.wasmClient = wasmClient;
+    }
+
+    publish(appId: string, topic: string, data: Uint8Array): void {
+        this.wasmClient.app_publish(appId, topic, data);
+    }
+
+    async request(appId: string, method: string, data: Uint8Array): Promise<Uint8Array> {
+        return await this.wasmClient.app_request(appId, method, data);
+    }
+
+    subscribe(appId: string, topic: string): AppSubscriptionWrapper {
+        return new AppSubscriptionWrapper(this.wasmClient.app_subscribe(appId, topic));
+    }
+}
+
+export class AppSubscriptionWrapper {
+    inner: AppSubscription;
+
+    constructor(inner: AppSubscription) {
+        this.inner = inner;
+    }
+
+    onMessage(cb: (data: Uint8Array) => void): AppSubscriptionWrapper {
+        this.inner.on_message((data: Uint8Array) => {
+            try {
+                cb(data);
+            } catch (e) {
+                console.log(`Failed to handle app message: ${e}`);
+            }
+        })
+        return this;
+    }
+
+    free(): void {
+        this.inner.free();
+    }
+}
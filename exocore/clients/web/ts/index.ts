@@ -16,6 +16,9 @@ export { Registry, matchTrait }
 import { Store, WatchedQueryWrapper, MutationBuilder, QueryBuilder, TraitQueryBuilder } from './store';
 export { WatchedQueryWrapper, MutationBuilder, QueryBuilder, TraitQueryBuilder };
 
+import { Apps, AppSubscriptionWrapper } from './apps';
+export { Apps, AppSubscriptionWrapper };
+
 import * as wasm from './wasm';
 import { WasmModule, ExocoreClient, LocalNode, Discovery } from './wasm';
 export { WasmModule, ExocoreClient, LocalNode, Discovery };
@@ -57,6 +60,10 @@ export class Exocore {
         return Exocore.default.store;
     }
 
+    static get apps(): Apps {
+        return Exocore.default.apps;
+    }
+
     static get registry(): Registry {
         return Exocore.default.registry;
     }
@@ -83,6 +90,7 @@ export class ExocoreInstance {
     wasmClient: ExocoreClient;
     cell: CellWrapper;
     store: Store;
+    apps: Apps;
     status: string;
     registry: Registry;
     node: LocalNode;
@@ -92,6 +100,7 @@ export class ExocoreInstance {
         this.wasmClient = client;
         this.cell = new CellWrapper(client);
         this.store = new Store(client);
+        this.apps = new Apps(client);
         this.registry = new Registry();
         this.node = node;
     }
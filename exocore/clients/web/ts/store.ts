@@ -229,6 +229,33 @@ export class QueryBuilder {
         return this;
     }
 
+    thenOrderByField(field: string, ascending: boolean): QueryBuilder {
+        return this.thenOrderBy(new exocore.store.Ordering({
+            ascending: ascending === true,
+            field: field,
+        }));
+    }
+
+    thenOrderByUpdatedAt(ascending: boolean): QueryBuilder {
+        return this.thenOrderBy(new exocore.store.Ordering({
+            ascending: ascending === true,
+            updatedAt: true,
+        }));
+    }
+
+    thenOrderBy(ordering: exocore.store.IOrdering): QueryBuilder {
+        if (!this.query.ordering) {
+            this.query.ordering = new exocore.store.Ordering();
+        }
+        this.query.ordering.then = (this.query.ordering.then ?? []).concat([ordering]);
+        return this;
+    }
+
+    countTotal(): QueryBuilder {
+        this.query.countTotal = true;
+        return this;
+    }
+
     includeDeleted(): QueryBuilder {
         this.query.includeDeleted = true;
         return this;
@@ -4,8 +4,9 @@ type ExocoreClient = import("../wasm/exocore_client_web").ExocoreClient;
 type Discovery = import("../wasm/exocore_client_web").Discovery;
 type LocalNode = import("../wasm/exocore_client_web").LocalNode;
 type WatchedQuery = import("../wasm/exocore_client_web").WatchedQuery;
+type AppSubscription = import("../wasm/exocore_client_web").AppSubscription;
 
-export { WasmModule, ExocoreClient, Discovery, LocalNode, WatchedQuery };
+export { WasmModule, ExocoreClient, Discovery, LocalNode, WatchedQuery, AppSubscription };
 
 var module: WasmModule = null;
 
@@ -0,0 +1,350 @@
+use std::{collections::HashMap, path::Path};
+
+use exocore_protos::{
+    core::{
+        NodeAddressBook, NodeAddressBookEntry, NodeAddressesAnnounce as NodeAddressesAnnounceProto,
+        NodeAddressesAnnounceData,
+    },
+    prost::Message,
+};
+use libp2p::Multiaddr;
+
+use super::{Cell, CellId, CellNodes, Error, NodeId};
+use crate::{
+    dir::DynDirectory,
+    time::{Clock, ConsistentTimestamp},
+};
+
+const ADDRESS_BOOK_FILE: &str = "address_book.pb";
+
+/// Maximum number of addresses kept per node in the address book. Oldest ones
+/// get discarded first.
+const MAX_NODE_ADDRESSES: usize = 10;
+
+/// Addresses on which a node is listening, announced by the node itself and
+/// signed by its keypair so that it can be gossiped by other nodes of the cell.
+#[derive(Clone)]
+pub struct NodeAddressesAnnounce {
+    cell_id: CellId,
+    node_id: NodeId,
+    addresses: Vec<Multiaddr>,
+    announce_date: ConsistentTimestamp,
+    signed: NodeAddressesAnnounceProto,
+}
+
+impl NodeAddressesAnnounce {
+    /// Creates an announce of the given addresses for the cell's local node.
+    pub fn new(
+        cell: &Cell,
+        clock: &Clock,
+        addresses: Vec<Multiaddr>,
+    ) -> Result<NodeAddressesAnnounce, Error> {
+        let local_node = cell.local_node();
+        let announce_date = clock.consistent_time(local_node.node());
+
+        let data = NodeAddressesAnnounceData {
+            cell_id: cell.id().as_bytes().to_vec(),
+            node_id: local_node.id().to_bytes(),
+            p2p: addresses.iter().map(|addr| addr.to_string()).collect(),
+            announce_date: Some(announce_date.into()),
+        };
+
+        let data = data.encode_to_vec();
+        let signature = local_node.keypair().sign(&data)?;
+
+        Ok(NodeAddressesAnnounce {
+            cell_id: cell.id().clone(),
+            node_id: local_node.id().clone(),
+            addresses,
+            announce_date,
+            signed: NodeAddressesAnnounceProto { data, signature },
+        })
+    }
+
+    /// Unmarshal an announce from its encoded protobuf message, validating
+    /// that it was signed by a node of the given cell.
+    pub fn decode_and_validate(cell: &Cell, bytes: &[u8]) -> Result<NodeAddressesAnnounce, Error> {
+        let signed = NodeAddressesAnnounceProto::decode(bytes)
+            .map_err(|err| Error::Node(anyhow!("Couldn't decode addresses announce: {}", err)))?;
+        let data = NodeAddressesAnnounceData::decode(signed.data.as_slice()).map_err(|err| {
+            Error::Node(anyhow!("Couldn't decode addresses announce data: {}", err))
+        })?;
+
+        let cell_id = CellId::from_bytes(&data.cell_id);
+        if &cell_id != cell.id() {
+            return Err(Error::Node(anyhow!(
+                "Addresses announce is for another cell: {}",
+                cell_id
+            )));
+        }
+
+        let node_id = NodeId::from_bytes(data.node_id)?;
+        let signature_valid = {
+            let cell_nodes = cell.nodes();
+            let cell_node = cell_nodes.get(&node_id).ok_or_else(|| {
+                Error::Node(anyhow!("Addresses announced by unknown node {}", node_id))
+            })?;
+            cell_node
+                .node()
+                .public_key()
+                .verify(&signed.data, &signed.signature)
+        };
+        if !signature_valid {
+            return Err(Error::Node(anyhow!(
+                "Invalid signature on addresses announced by node {}",
+                node_id
+            )));
+        }
+
+        let addresses = data
+            .p2p
+            .iter()
+            .map(|addr| {
+                addr.parse()
+                    .map_err(|err| Error::Node(anyhow!("Invalid address '{}': {}", addr, err)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let announce_date = data
+            .announce_date
+            .ok_or_else(|| Error::Node(anyhow!("Addresses announce doesn't have a date")))?
+            .into();
+
+        Ok(NodeAddressesAnnounce {
+            cell_id,
+            node_id,
+            addresses,
+            announce_date,
+            signed,
+        })
+    }
+
+    pub fn cell_id(&self) -> &CellId {
+        &self.cell_id
+    }
+
+    pub fn node_id(&self) -> &NodeId {
+        &self.node_id
+    }
+
+    pub fn addresses(&self) -> &[Multiaddr] {
+        &self.addresses
+    }
+
+    pub fn announce_date(&self) -> ConsistentTimestamp {
+        self.announce_date
+    }
+
+    pub fn encode_to_vec(&self) -> Vec<u8> {
+        self.signed.encode_to_vec()
+    }
+}
+
+impl std::fmt::Debug for NodeAddressesAnnounce {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NodeAddressesAnnounce")
+            .field("cell_id", &self.cell_id)
+            .field("node_id", &self.node_id)
+            .field("addresses", &self.addresses)
+            .field("announce_date", &self.announce_date)
+            .finish()
+    }
+}
+
+/// Book of the addresses announced by the nodes of a cell, persisted in the
+/// cell's directory next to its config.
+///
+/// The latest announce of each node is also kept in memory so that it can be
+/// gossiped to other nodes.
+pub struct AddressBook {
+    dir: DynDirectory,
+    nodes: HashMap<NodeId, Vec<(Multiaddr, ConsistentTimestamp)>>,
+    announces: HashMap<NodeId, NodeAddressesAnnounce>,
+}
+
+impl AddressBook {
+    /// Loads the address book of the cell from its directory, or creates an
+    /// empty one if none was persisted yet.
+    pub fn load(cell: &Cell) -> Result<AddressBook, Error> {
+        let dir = cell.directory().clone();
+
+        let mut nodes: HashMap<NodeId, Vec<(Multiaddr, ConsistentTimestamp)>> = HashMap::new();
+        if dir.exists(Path::new(ADDRESS_BOOK_FILE)) {
+            let mut file = dir.open_read(Path::new(ADDRESS_BOOK_FILE))?;
+            let mut bytes = Vec::new();
+            std::io::Read::read_to_end(&mut file, &mut bytes).map_err(crate::dir::Error::from)?;
+
+            let book = NodeAddressBook::decode(bytes.as_slice())
+                .map_err(|err| Error::Cell(anyhow!("Couldn't decode address book: {}", err)))?;
+            for entry in book.entries {
+                let (Ok(node_id), Ok(address)) = (entry.node_id.parse(), entry.address.parse())
+                else {
+                    warn!("Ignoring invalid address book entry: {:?}", entry);
+                    continue;
+                };
+                let last_seen = entry
+                    .last_seen
+                    .map(Into::into)
+                    .unwrap_or(ConsistentTimestamp(0));
+                nodes.entry(node_id).or_default().push((address, last_seen));
+            }
+
+            for addresses in nodes.values_mut() {
+                sort_by_recency(addresses);
+            }
+        }
+
+        Ok(AddressBook {
+            dir,
+            nodes,
+            announces: HashMap::new(),
+        })
+    }
+
+    /// Adds the addresses of an announce to the book. Returns `true` if the
+    /// announce is newer than the latest one we had for the node, in which case
+    /// it should be gossiped further and the book saved.
+    pub fn add_announce(&mut self, announce: NodeAddressesAnnounce) -> bool {
+        if let Some(current) = self.announces.get(announce.node_id()) {
+            if current.announce_date() >= announce.announce_date() {
+                return false;
+            }
+        }
+
+        let addresses = self.nodes.entry(announce.node_id().clone()).or_default();
+        for announced in announce.addresses() {
+            if let Some(existing) = addresses.iter_mut().find(|(addr, _)| addr == announced) {
+                existing.1 = existing.1.max(announce.announce_date());
+            } else {
+                addresses.push((announced.clone(), announce.announce_date()));
+            }
+        }
+        sort_by_recency(addresses);
+        addresses.truncate(MAX_NODE_ADDRESSES);
+
+        self.announces.insert(announce.node_id().clone(), announce);
+
+        true
+    }
+
+    /// Returns the known addresses of the node, most recently announced first.
+    pub fn node_addresses(&self, node_id: &NodeId) -> Vec<Multiaddr> {
+        self.nodes
+            .get(node_id)
+            .map(|addresses| addresses.iter().map(|(addr, _)| addr.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the latest announce received for each node.
+    pub fn announces(&self) -> impl Iterator<Item = &NodeAddressesAnnounce> {
+        self.announces.values()
+    }
+
+    /// Persists the address book in the cell's directory.
+    pub fn save(&self) -> Result<(), Error> {
+        let entries = self
+            .nodes
+            .iter()
+            .flat_map(|(node_id, addresses)| {
+                addresses
+                    .iter()
+                    .map(move |(address, last_seen)| NodeAddressBookEntry {
+                        node_id: node_id.to_string(),
+                        address: address.to_string(),
+                        last_seen: Some((*last_seen).into()),
+                    })
+            })
+            .collect();
+
+        let book = NodeAddressBook { entries };
+        let mut file = self.dir.open_create(Path::new(ADDRESS_BOOK_FILE))?;
+        std::io::Write::write_all(&mut file, &book.encode_to_vec())
+            .map_err(crate::dir::Error::from)?;
+
+        Ok(())
+    }
+}
+
+fn sort_by_recency(addresses: &mut [(Multiaddr, ConsistentTimestamp)]) {
+    addresses.sort_by(|(_, a), (_, b)| b.cmp(a));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::{FullCell, LocalNode};
+
+    #[test]
+    fn announce_validation() -> anyhow::Result<()> {
+        let node1 = LocalNode::generate();
+        let cell1 = FullCell::generate(node1)?;
+
+        let node2 = LocalNode::generate();
+        let cell2 = cell1.clone().with_local_node(node2.clone());
+        cell1.cell().nodes_mut().add(node2.node().clone());
+
+        let clock = Clock::new();
+        let addr: Multiaddr = "/ip4/10.0.0.1/tcp/3330".parse()?;
+        let announce = NodeAddressesAnnounce::new(cell2.cell(), &clock, vec![addr.clone()])?;
+
+        let decoded =
+            NodeAddressesAnnounce::decode_and_validate(cell1.cell(), &announce.encode_to_vec())?;
+        assert_eq!(decoded.node_id(), node2.id());
+        assert_eq!(decoded.addresses(), &[addr]);
+
+        // tampered announce
+        let mut tampered = announce.signed.clone();
+        tampered.signature[0] = tampered.signature[0].wrapping_add(1);
+        assert!(NodeAddressesAnnounce::decode_and_validate(
+            cell1.cell(),
+            &tampered.encode_to_vec()
+        )
+        .is_err());
+
+        // announce from a node that isn't in the cell
+        let other_cell = FullCell::generate(LocalNode::generate())?;
+        let other_announce = NodeAddressesAnnounce::new(other_cell.cell(), &clock, vec![])?;
+        assert!(NodeAddressesAnnounce::decode_and_validate(
+            cell1.cell(),
+            &other_announce.encode_to_vec()
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn address_book_recency_and_persistence() -> anyhow::Result<()> {
+        let node1 = LocalNode::generate();
+        let cell1 = FullCell::generate(node1)?;
+
+        let node2 = LocalNode::generate();
+        let cell2 = cell1.clone().with_local_node(node2.clone());
+        cell1.cell().nodes_mut().add(node2.node().clone());
+
+        let clock = Clock::new_mocked();
+        let home: Multiaddr = "/ip4/192.168.1.2/tcp/3330".parse()?;
+        let office: Multiaddr = "/ip4/10.0.0.2/tcp/3330".parse()?;
+
+        let mut book = AddressBook::load(cell1.cell())?;
+        let announce1 = NodeAddressesAnnounce::new(cell2.cell(), &clock, vec![home.clone()])?;
+        assert!(book.add_announce(announce1.clone()));
+
+        clock.add_fixed_instant_duration(std::time::Duration::from_secs(60));
+        let announce2 = NodeAddressesAnnounce::new(cell2.cell(), &clock, vec![office.clone()])?;
+        assert!(book.add_announce(announce2));
+
+        // older announce is ignored
+        assert!(!book.add_announce(announce1));
+
+        assert_eq!(
+            book.node_addresses(node2.id()),
+            vec![office.clone(), home.clone()]
+        );
+
+        book.save()?;
+        let book = AddressBook::load(cell1.cell())?;
+        assert_eq!(book.node_addresses(node2.id()), vec![office, home]);
+
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ use std::{path::Path, sync::Arc};
 
 use exocore_protos::{
     generated::exocore_apps::{manifest_schema::Source, Manifest},
+    prost::Message as ProstMessage,
     reflect::{FileDescriptorSet, Message},
 };
 use libp2p::PeerId;
@@ -40,12 +41,13 @@ impl Application {
         let keypair = Keypair::generate_ed25519();
         let dir = dir.into();
 
-        let manifest = Manifest {
+        let mut manifest = Manifest {
             name,
             public_key: keypair.public().encode_base58_string(),
             version: "0.0.1".to_string(),
             ..Default::default()
         };
+        sign_manifest(&mut manifest, &keypair)?;
 
         Ok((keypair, Application::from_manifest(dir, manifest)?))
     }
@@ -131,8 +133,16 @@ impl Application {
         &self.dir
     }
 
+    /// Validates that the manifest was signed by the application's keypair
+    /// and that the module matches the multihash of the manifest.
     pub fn validate(&self) -> Result<(), Error> {
-        // validate module
+        verify_manifest(self.manifest())?;
+        self.validate_module()
+    }
+
+    /// Validates that the module matches the multihash of the manifest,
+    /// without validating the manifest's signature.
+    pub fn validate_module(&self) -> Result<(), Error> {
         if let Some(module) = &self.manifest().module {
             let module_file = self.directory().open_read(Path::new(&module.file))?;
 
@@ -181,6 +191,61 @@ impl Application {
     }
 }
 
+/// Signs the manifest with the application's keypair, which needs to match the
+/// manifest's public key.
+///
+/// Since the signature covers the module through its multihash, the module's
+/// multihash needs to be up to date in the manifest before signing it.
+pub fn sign_manifest(manifest: &mut Manifest, keypair: &Keypair) -> Result<(), Error> {
+    if keypair.public().encode_base58_string() != manifest.public_key {
+        return Err(Error::Application(
+            manifest.name.clone(),
+            anyhow!("Keypair doesn't match the public key of the manifest"),
+        ));
+    }
+
+    let signature = keypair.sign(&manifest_signature_payload(manifest))?;
+    manifest.signature = bs58::encode(signature).into_string();
+
+    Ok(())
+}
+
+/// Verifies that the manifest was signed by the keypair of its public key.
+pub fn verify_manifest(manifest: &Manifest) -> Result<(), Error> {
+    if manifest.signature.is_empty() {
+        return Err(Error::Application(
+            manifest.name.clone(),
+            anyhow!("Manifest isn't signed"),
+        ));
+    }
+
+    let public_key = PublicKey::decode_base58_string(&manifest.public_key)?;
+    let signature = bs58::decode(&manifest.signature)
+        .into_vec()
+        .map_err(|err| {
+            Error::Application(
+                manifest.name.clone(),
+                anyhow!("Couldn't decode manifest signature: {}", err),
+            )
+        })?;
+
+    if !public_key.verify(&manifest_signature_payload(manifest), &signature) {
+        return Err(Error::Application(
+            manifest.name.clone(),
+            anyhow!("Manifest signature doesn't match its public key"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The signature covers the encoded manifest, without the signature itself.
+fn manifest_signature_payload(manifest: &Manifest) -> Vec<u8> {
+    let mut manifest = manifest.clone();
+    manifest.signature.clear();
+    manifest.encode_to_vec()
+}
+
 /// Unique identifier of an application, which is built by hashing the public
 /// key.
 ///
@@ -260,6 +325,37 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn validate_manifest_signature() -> anyhow::Result<()> {
+        let dir: DynDirectory = RamDirectory::new().into();
+        let (kp, app) = Application::generate(dir.clone(), "some_app".to_string())?;
+
+        // tampered manifest
+        let mut manifest = app.manifest().clone();
+        manifest.version = "0.0.2".to_string();
+        let tampered_app = Application::from_manifest(dir.clone(), manifest.clone())?;
+        assert!(tampered_app.validate().is_err());
+
+        // resigned manifest
+        sign_manifest(&mut manifest, &kp)?;
+        Application::from_manifest(dir.clone(), manifest.clone())?.validate()?;
+
+        // unsigned manifest
+        manifest.signature.clear();
+        let unsigned_app = Application::from_manifest(dir.clone(), manifest.clone())?;
+        assert!(unsigned_app.validate().is_err());
+        unsigned_app.validate_module()?;
+
+        // signed with another keypair
+        let other_kp = Keypair::generate_ed25519();
+        assert!(sign_manifest(&mut manifest, &other_kp).is_err());
+        manifest.signature = bs58::encode(other_kp.sign(b"other")?).into_string();
+        let other_app = Application::from_manifest(dir, manifest)?;
+        assert!(other_app.validate().is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn app_id_conversion() {
         let kp = crate::sec::keys::Keypair::generate_ed25519();
@@ -6,7 +6,7 @@ use std::{
 
 use exocore_protos::{generated::exocore_core::CellApplicationConfig, registry::Registry};
 
-use super::{Application, ApplicationId, CellId, Error};
+use super::{Application, ApplicationId, CellApplicationConfigExt, CellId, Error};
 use crate::{dir::DynDirectory, sec::keys::PublicKey};
 
 /// Applications installed in a cell.
@@ -62,6 +62,14 @@ impl CellApplications {
         Ok(())
     }
 
+    /// Adds an application loaded from a directory outside of the cell, such
+    /// as an application being developed locally. If the application was
+    /// already added, it gets replaced and its schemas get registered again.
+    pub fn add_application(&self, application: Application) -> Result<(), Error> {
+        let cell_app_config = CellApplicationConfig::from_manifest(application.manifest().clone());
+        self.add_loaded_application(cell_app_config, application)
+    }
+
     fn add_loaded_application(
         &self,
         cell_app_config: CellApplicationConfig,
@@ -237,6 +237,10 @@ pub enum CellNodeRole {
 
     /// Indicates that the node is an applications host.
     AppHost,
+
+    /// Indicates that the node is always reachable and relays messages between
+    /// nodes of the cell that can't reach each other directly.
+    Relay,
 }
 
 impl CellNodeRole {
@@ -245,6 +249,7 @@ impl CellNodeRole {
             cell_node_config::Role::ChainRole => Ok(CellNodeRole::Chain),
             cell_node_config::Role::StoreRole => Ok(CellNodeRole::Store),
             cell_node_config::Role::AppHostRole => Ok(CellNodeRole::AppHost),
+            cell_node_config::Role::RelayRole => Ok(CellNodeRole::Relay),
             cell_node_config::Role::InvalidRole => {
                 Err(Error::Cell(anyhow!("Invalid cell node role")))
             }
@@ -256,6 +261,7 @@ impl CellNodeRole {
             CellNodeRole::Chain => cell_node_config::Role::ChainRole,
             CellNodeRole::Store => cell_node_config::Role::StoreRole,
             CellNodeRole::AppHost => cell_node_config::Role::AppHostRole,
+            CellNodeRole::Relay => cell_node_config::Role::RelayRole,
         }
     }
 }
@@ -276,7 +276,7 @@ mod tests {
 
     #[test]
     fn parse_node_config_yaml_ser_deser() -> anyhow::Result<()> {
-        use exocore_protos::generated::exocore_core::NodeStoreConfig;
+        use exocore_protos::generated::exocore_core::{NodeP2pConfig, NodeStoreConfig};
 
         let conf_ser = LocalNodeConfig {
             keypair: "keypair".to_string(),
@@ -324,6 +324,7 @@ mod tests {
                                 location: None,
                             },
                         ],
+                        allow_unsigned_apps: true,
                     })),
                 },
                 NodeCellConfig {
@@ -347,11 +348,15 @@ mod tests {
                         indexer_num_threads: Some(2),
                         indexer_heap_size_bytes: Some(30_000_000),
                         entity_mutations_cache_size: Some(2000),
+                        text_analyzer: None,
+                        entity_query_max_pages: None,
                     }),
                     chain_index: Some(MutationIndexConfig {
                         indexer_num_threads: Some(2),
                         indexer_heap_size_bytes: Some(30_000_000),
                         entity_mutations_cache_size: Some(2000),
+                        text_analyzer: None,
+                        entity_query_max_pages: None,
                     }),
                     ..Default::default()
                 }),
@@ -361,6 +366,9 @@ mod tests {
                 segment_max_size: Some(1_000),
                 segment_max_open_mmap: Some(2),
             }),
+            p2p: Some(NodeP2pConfig {
+                mdns_discovery: Some(false),
+            }),
         };
 
         let conf_yaml = conf_ser.to_yaml_string()?;
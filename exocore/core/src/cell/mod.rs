@@ -1,5 +1,6 @@
 #![allow(clippy::module_inception)]
 
+mod address_book;
 mod app;
 mod cell;
 mod cell_apps;
@@ -8,7 +9,8 @@ pub(crate) mod config;
 mod error;
 mod node;
 
-pub use app::{Application, ApplicationId};
+pub use address_book::{AddressBook, NodeAddressesAnnounce};
+pub use app::{sign_manifest, verify_manifest, Application, ApplicationId};
 pub use cell::{Cell, CellId, EitherCell, FullCell};
 pub use cell_apps::{CellApplication, CellApplications};
 pub use cell_nodes::{
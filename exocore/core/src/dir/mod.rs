@@ -27,6 +27,17 @@ pub trait Directory: Send + Sync {
         ScopedDirectory::new(dir, path).into()
     }
 
+    /// Moves a file to another path, replacing the file at that path if any.
+    /// The replacement is atomic on directories that support it.
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        {
+            let mut src_file = self.open_read(from)?;
+            let mut dst_file = self.open_create(to)?;
+            std::io::copy(&mut src_file, &mut dst_file)?;
+        }
+        self.delete(from)
+    }
+
     fn copy_to(&self, to: DynDirectory) -> Result<(), Error> {
         let file_stats = self.list(None)?;
         for file_stat in file_stats {
@@ -216,6 +227,27 @@ mod tests {
         assert!(!dir.exists(Path::new("test")));
     }
 
+    pub fn test_rename(dir: impl Into<DynDirectory>) {
+        let dir = dir.into();
+        {
+            let mut file = dir.open_create(Path::new("file1")).unwrap();
+            file.write_all(b"Hello").unwrap();
+
+            let mut file = dir.open_create(Path::new("file2")).unwrap();
+            file.write_all(b"Hello world").unwrap();
+        }
+
+        dir.rename(Path::new("file1"), Path::new("file2")).unwrap();
+        assert!(!dir.exists(Path::new("file1")));
+
+        let mut file = dir.open_read(Path::new("file2")).unwrap();
+        let mut buf = String::new();
+        file.read_to_string(&mut buf).unwrap();
+        assert_eq!("Hello", buf);
+
+        assert!(dir.rename(Path::new("file1"), Path::new("file2")).is_err());
+    }
+
     #[test]
     pub fn test_copy_directory() {
         let src = super::ram::RamDirectory::new();
@@ -141,6 +141,15 @@ impl Directory for OsDirectory {
         Ok(())
     }
 
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        let from = self.resolve_path(from, true)?;
+        let to = self.resolve_path(to, true)?;
+        create_parent_path(&to)?;
+
+        std::fs::rename(from, to)?;
+        Ok(())
+    }
+
     fn clone(&self) -> DynDirectory {
         OsDirectory {
             base_path: self.base_path.clone(),
@@ -237,6 +246,13 @@ mod tests {
         super::super::tests::test_delete(dir);
     }
 
+    #[test]
+    fn test_rename() {
+        let tmp = tempdir().unwrap();
+        let dir = OsDirectory::new(tmp.into_path());
+        super::super::tests::test_rename(dir);
+    }
+
     #[test]
     fn test_as_os_path() {
         let tmp = tempdir().unwrap();
@@ -106,6 +106,20 @@ impl Directory for RamDirectory {
         Ok(())
     }
 
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        if to.parent().is_none() {
+            return Err(Error::Path(anyhow!("expected a non-root path to a file")));
+        }
+
+        let mut files = self.files.write().unwrap();
+        let file = files
+            .remove(from)
+            .ok_or_else(|| Error::NotFound(from.to_path_buf()))?;
+        files.insert(to.to_path_buf(), file);
+
+        Ok(())
+    }
+
     fn clone(&self) -> DynDirectory {
         RamDirectory {
             files: self.files.clone(),
@@ -230,4 +244,9 @@ mod tests {
     fn test_delete() {
         super::super::tests::test_delete(RamDirectory::new());
     }
+
+    #[test]
+    fn test_rename() {
+        super::super::tests::test_rename(RamDirectory::new());
+    }
 }
@@ -78,6 +78,12 @@ impl Directory for ScopedDirectory {
         self.inner.delete(&path)
     }
 
+    fn rename(&self, from: &std::path::Path, to: &std::path::Path) -> Result<(), super::Error> {
+        let from = self.join_path(from, true)?;
+        let to = self.join_path(to, true)?;
+        self.inner.rename(&from, &to)
+    }
+
     fn clone(&self) -> DynDirectory {
         ScopedDirectory {
             inner: self.inner.clone(),
@@ -159,6 +165,13 @@ mod tests {
         super::super::tests::test_delete(scoped);
     }
 
+    #[test]
+    fn test_rename() {
+        let ram = RamDirectory::new();
+        let scoped = ScopedDirectory::new(ram, PathBuf::from("sub"));
+        super::super::tests::test_rename(scoped);
+    }
+
     #[test]
     fn test_as_os_path() {
         let dir = tempdir().unwrap();
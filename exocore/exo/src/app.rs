@@ -5,7 +5,9 @@ use std::{
 };
 
 use exocore_core::{
-    cell::{Application, Cell, CellApplicationConfigExt, CellConfigExt, ManifestExt},
+    cell::{
+        sign_manifest, Application, Cell, CellApplicationConfigExt, CellConfigExt, ManifestExt,
+    },
     dir::os::OsDirectory,
     sec::{
         hash::{multihash_sha3_256_file, MultihashExt},
@@ -35,6 +37,10 @@ pub enum AppCommand {
 
     /// Packages an application.
     Package(PackageOptions),
+
+    /// Runs an application in a local development cell, reloading it when its
+    /// files change.
+    Dev(crate::app_dev::DevOptions),
 }
 
 #[derive(clap::Parser)]
@@ -46,12 +52,24 @@ pub struct GenerateOptions {
 #[derive(clap::Parser)]
 pub struct PackageOptions {
     directory: Option<PathBuf>,
+
+    /// Keypair of the application, as generated by the `generate` command,
+    /// used to sign the package.
+    #[clap(long, env = "EXO_APP_KEYPAIR", hide_env_values = true)]
+    keypair: String,
 }
 
-pub async fn handle_cmd(ctx: &Context, app_opts: &AppOptions) {
+pub async fn handle_cmd(ctx: &Context, app_opts: &AppOptions) -> anyhow::Result<()> {
     match &app_opts.command {
-        AppCommand::Generate(gen_opts) => cmd_generate(ctx, app_opts, gen_opts),
-        AppCommand::Package(pkg_opts) => cmd_package(ctx, app_opts, pkg_opts),
+        AppCommand::Generate(gen_opts) => {
+            cmd_generate(ctx, app_opts, gen_opts);
+            Ok(())
+        }
+        AppCommand::Package(pkg_opts) => {
+            cmd_package(ctx, app_opts, pkg_opts);
+            Ok(())
+        }
+        AppCommand::Dev(dev_opts) => crate::app_dev::cmd_dev(ctx, dev_opts).await,
     }
 }
 
@@ -66,6 +84,9 @@ fn cmd_generate(_ctx: &Context, _app_opts: &AppOptions, gen_opts: &GenerateOptio
         public_key: kp.public().encode_base58_string(),
         schemas: Vec::new(),
         module: None,
+        signature: String::new(),
+        triggers: Vec::new(),
+        http_domains: Vec::new(),
     };
 
     let manifest_path = cur_dir.join("app.yaml");
@@ -103,6 +124,10 @@ fn cmd_package(_ctx: &Context, _app_opts: &AppOptions, pkg_opts: &PackageOptions
             .encode_bs58();
     }
 
+    let keypair =
+        Keypair::decode_base58_string(&pkg_opts.keypair).expect("Couldn't decode keypair");
+    sign_manifest(&mut manifest, &keypair).expect("Couldn't sign manifest");
+
     let zip_file_path = cur_dir.join(format!("{}.zip", manifest.name));
     let zip_file = File::create(&zip_file_path).expect("Couldn't create zip file");
     let zip_file_buf = BufWriter::new(zip_file);
@@ -230,7 +255,7 @@ impl AppPackage {
         let application = Application::from_directory(temp_dir)?;
         application
             .validate()
-            .expect("Failed to validate the application");
+            .map_err(|err| anyhow!("Invalid application package: {}", err))?;
 
         if app_dir_path.exists() {
             if overwrite {
@@ -0,0 +1,505 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use console::style;
+use exocore_chain::{
+    block::BlockBuilder, chain::ChainStore, DirectoryChainStore, DirectoryChainStoreConfig, Engine,
+    EngineConfig, MemoryPendingStore,
+};
+use exocore_core::{
+    cell::{Application, CellNodeRole, FullCell, LocalNode},
+    dir::os::OsDirectory,
+    futures::{owned_spawn, sleep, OwnedSpawn},
+    sec::hash::{multihash_sha3_256_file, MultihashExt},
+    time::Clock,
+};
+use exocore_store::local::{EntityIndex, EntityIndexConfig, Store as LocalStore, StoreHandle};
+use exocore_transport::{p2p::Libp2pTransportConfig, Libp2pTransport, ServiceType};
+
+use crate::{term::*, utils::expand_tild, Context};
+
+/// Directories of the application that are never watched for changes, since
+/// they contain build artifacts or dependencies. The application's module is
+/// always watched, even if it's built in one of them.
+const IGNORED_DIRECTORIES: &[&str] = &["target", "node_modules"];
+
+#[derive(clap::Parser)]
+pub struct DevOptions {
+    /// Directory of the application, containing its manifest. Defaults to the
+    /// current directory.
+    directory: Option<PathBuf>,
+
+    /// Command building the application's module, run in the application's
+    /// directory at startup and every time one of its files changes.
+    #[clap(long)]
+    build: Option<String>,
+
+    /// Interval at which the application's files are checked for changes, in
+    /// milliseconds.
+    #[clap(long, default_value = "500")]
+    watch_interval: u64,
+
+    /// Minimum level of the application's logs that are streamed to the
+    /// terminal (error, warn, info, debug, trace).
+    #[clap(long, default_value = "info")]
+    app_log: String,
+}
+
+/// Runs the application in a cell created in a temporary directory, with a
+/// single node hosting the chain, the store and the application.
+///
+/// The application is loaded directly from its directory, without being
+/// packaged, and gets reloaded every time one of its files changes.
+#[cfg(any(
+    all(
+        target_arch = "x86_64",
+        any(target_os = "linux", target_os = "macos", target_os = "windows")
+    ),
+    all(target_arch = "aarch64", any(target_os = "linux", target_os = "macos"))
+))]
+pub async fn cmd_dev(_ctx: &Context, dev_opts: &DevOptions) -> anyhow::Result<()> {
+    use exocore_apps_host::{runtime::Applications, Config as ApplicationsConfig};
+    use futures::FutureExt;
+
+    let cur_dir = std::env::current_dir()?;
+    let app_dir = expand_tild(dev_opts.directory.clone().unwrap_or(cur_dir))?;
+    let watch_interval = Duration::from_millis(dev_opts.watch_interval);
+    let app_log_level = log::Level::from_str(&dev_opts.app_log)?;
+
+    let temp_dir = tempfile::tempdir()?;
+    print_step(format!(
+        "Creating development cell in {}",
+        style_value(temp_dir.path())
+    ));
+    let clock = Clock::new();
+    let cell = create_dev_cell(temp_dir.path())?;
+    let (store, _services) = start_dev_store(&cell, &clock).await?;
+
+    if let Some(build_cmd) = &dev_opts.build {
+        run_build(&app_dir, build_cmd);
+    }
+    let mut snapshot = FilesSnapshot::take_app(&app_dir);
+
+    loop {
+        let apps = match load_application(&cell, &app_dir) {
+            Ok(app) => {
+                let apps_config = ApplicationsConfig {
+                    validate_signatures: false,
+                    ..Default::default()
+                };
+                let apps = Applications::new(
+                    apps_config,
+                    clock.clone(),
+                    cell.cell().clone(),
+                    store.clone(),
+                )
+                .await?
+                .with_log_handler(Arc::new(move |app_name, level, msg| {
+                    if level <= app_log_level {
+                        print_app_log(app_name, level, msg);
+                    }
+                }));
+
+                print_success(format!(
+                    "Application {} started. Watching for changes...",
+                    style_value(app.name())
+                ));
+                Some(apps)
+            }
+            Err(err) => {
+                print_error(format!("Couldn't load application: {}", err));
+                None
+            }
+        };
+
+        // the application runs until one of its files changes, in which case it gets
+        // dropped and stopped before being loaded again
+        let changed_files = {
+            let apps_run = async move {
+                match apps {
+                    Some(apps) => apps.run().await,
+                    None => futures::future::pending().await,
+                }
+            };
+            let changes = wait_changes(&app_dir, &mut snapshot, watch_interval);
+            futures::pin_mut!(apps_run, changes);
+
+            futures::select! {
+                res = apps_run.fuse() => {
+                    print_error(format!("Applications host has stopped: {:?}", res));
+                    return Ok(());
+                }
+                changed_files = changes.fuse() => changed_files,
+            }
+        };
+
+        for file in &changed_files {
+            print_action(format!("Changed: {}", style_value(file)));
+        }
+
+        if let Some(build_cmd) = &dev_opts.build {
+            run_build(&app_dir, build_cmd);
+
+            // files written by the build aren't considered as changes
+            snapshot = FilesSnapshot::take_app(&app_dir);
+        }
+
+        print_info("Reloading application...");
+    }
+}
+
+#[cfg(not(any(
+    all(
+        target_arch = "x86_64",
+        any(target_os = "linux", target_os = "macos", target_os = "windows")
+    ),
+    all(target_arch = "aarch64", any(target_os = "linux", target_os = "macos"))
+)))]
+pub async fn cmd_dev(_ctx: &Context, _dev_opts: &DevOptions) -> anyhow::Result<()> {
+    Err(anyhow!("Cannot host app on this target."))
+}
+
+/// Creates a cell with a single node hosting the chain, the store and the
+/// applications.
+fn create_dev_cell(dir: &Path) -> anyhow::Result<FullCell> {
+    let local_node = LocalNode::generate_in_directory(OsDirectory::new(dir.to_path_buf()))?;
+    let cell = FullCell::generate(local_node)?;
+
+    {
+        let mut cell_nodes = cell.cell().nodes_mut();
+        let local_cell_node = cell_nodes.local_cell_node_mut();
+        local_cell_node.add_role(CellNodeRole::Chain);
+        local_cell_node.add_role(CellNodeRole::Store);
+        local_cell_node.add_role(CellNodeRole::AppHost);
+    }
+
+    Ok(cell)
+}
+
+/// Starts the chain engine and the local store of the development cell.
+///
+/// The services are stopped when the returned spawn handles get dropped.
+async fn start_dev_store(
+    cell: &FullCell,
+    clock: &Clock,
+) -> anyhow::Result<(
+    StoreHandle<DirectoryChainStore, MemoryPendingStore>,
+    Vec<OwnedSpawn<()>>,
+)> {
+    let mut services = Vec::new();
+    let local_node = cell.cell().local_node().clone();
+
+    let chain_dir = cell.cell().chain_directory().as_os_path()?;
+    std::fs::create_dir_all(&chain_dir)?;
+    let mut chain_store =
+        DirectoryChainStore::create(DirectoryChainStoreConfig::default(), &chain_dir)?;
+    chain_store.write_block(&BlockBuilder::build_genesis(cell)?)?;
+
+    // the node is alone in its cell, the transport is only needed by the engine
+    let mut transport =
+        Libp2pTransport::new(local_node, Libp2pTransportConfig::default(), clock.clone());
+    let chain_transport = transport.get_handle(cell.cell().clone(), ServiceType::Chain)?;
+    services.push(owned_spawn(async move {
+        let res = transport.run().await;
+        info!("Transport is done: {:?}", res);
+    }));
+
+    let mut engine = Engine::new(
+        EngineConfig::default(),
+        clock.clone(),
+        chain_transport,
+        chain_store,
+        MemoryPendingStore::new(),
+        cell.cell().clone(),
+    );
+    let chain_handle = engine.get_handle();
+    services.push(owned_spawn(async move {
+        let res = engine.run().await;
+        info!("Engine is done: {:?}", res);
+    }));
+
+    let index = EntityIndex::open_or_create(
+        cell.clone(),
+        EntityIndexConfig::default(),
+        chain_handle.clone(),
+        clock.clone(),
+    )?;
+    let local_store = LocalStore::new(
+        Default::default(),
+        cell.cell().clone(),
+        clock.clone(),
+        chain_handle.clone(),
+        index,
+    )?;
+    let store_handle = local_store.get_handle();
+    services.push(owned_spawn(async move {
+        let res = local_store.run().await;
+        info!("Local store is done: {:?}", res);
+    }));
+
+    store_handle.on_start().await;
+    chain_handle.on_started().await;
+
+    Ok((store_handle, services))
+}
+
+/// Loads the application from its directory into the cell, registering its
+/// schemas in the cell's registry.
+///
+/// Since the module gets rebuilt without being packaged, the multihash of the
+/// manifest is replaced by the one of the module at the time it's loaded. The
+/// manifest's signature, which is then invalid, isn't validated by the host.
+fn load_application(cell: &FullCell, app_dir: &Path) -> anyhow::Result<Application> {
+    let dir = OsDirectory::new(app_dir.to_path_buf());
+    let mut manifest = Application::from_directory(dir.clone())?.manifest().clone();
+
+    match &mut manifest.module {
+        Some(module) => {
+            let module_path = app_dir.join(&module.file);
+            if !module_path.exists() {
+                return Err(anyhow!("Module file {} doesn't exist", module.file));
+            }
+
+            module.multihash = multihash_sha3_256_file(module_path)?.encode_bs58();
+        }
+        None => {
+            print_warning("Application doesn't have a module. Only its schemas are loaded.");
+        }
+    }
+
+    let app = Application::from_manifest(dir, manifest)?;
+    cell.cell().applications().add_application(app.clone())?;
+
+    Ok(app)
+}
+
+fn print_app_log(app_name: &str, level: log::Level, msg: &str) {
+    let level = match level {
+        log::Level::Error => style(level).red(),
+        log::Level::Warn => style(level).yellow(),
+        _ => style(level).dim(),
+    };
+    println!("{} {:5} {}", style(app_name).bold(), level, msg);
+}
+
+fn run_build(app_dir: &Path, build_cmd: &str) {
+    print_step(format!("Building application: {}", style_value(build_cmd)));
+
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(build_cmd)
+        .current_dir(app_dir)
+        .status();
+
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => print_error(format!("Build has failed: {}", status)),
+        Err(err) => print_error(format!("Couldn't run build command: {}", err)),
+    }
+}
+
+/// Waits until at least one file of the application changes, returning the
+/// changed files.
+async fn wait_changes(
+    app_dir: &Path,
+    snapshot: &mut FilesSnapshot,
+    interval: Duration,
+) -> Vec<PathBuf> {
+    loop {
+        sleep(interval).await;
+
+        let new_snapshot = FilesSnapshot::take_app(app_dir);
+        let changed_files = snapshot.changed_files(&new_snapshot);
+        *snapshot = new_snapshot;
+
+        if !changed_files.is_empty() {
+            return changed_files;
+        }
+    }
+}
+
+/// Modification times of the files of a directory, used to detect changes by
+/// comparing snapshots.
+#[derive(Default)]
+struct FilesSnapshot {
+    files: HashMap<PathBuf, SystemTime>,
+}
+
+impl FilesSnapshot {
+    fn take(dir: &Path) -> FilesSnapshot {
+        let mut snapshot = FilesSnapshot::default();
+        snapshot.add_directory(dir);
+        snapshot
+    }
+
+    /// Takes a snapshot of the files of the application's directory, and of its
+    /// module file, which may be in an ignored directory (ex: `target`).
+    fn take_app(app_dir: &Path) -> FilesSnapshot {
+        let mut snapshot = FilesSnapshot::take(app_dir);
+
+        let dir = OsDirectory::new(app_dir.to_path_buf());
+        let module = Application::from_directory(dir)
+            .ok()
+            .and_then(|app| app.manifest().module.clone());
+        if let Some(module) = module {
+            snapshot.add_file(&app_dir.join(module.file));
+        }
+
+        snapshot
+    }
+
+    fn add_file(&mut self, path: &Path) {
+        if let Ok(modified) = std::fs::metadata(path).and_then(|metadata| metadata.modified()) {
+            self.files.insert(path.to_path_buf(), modified);
+        }
+    }
+
+    fn add_directory(&mut self, dir: &Path) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            if file_name.starts_with('.') || IGNORED_DIRECTORIES.contains(&file_name.as_ref()) {
+                continue;
+            }
+
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                self.add_directory(&path);
+            } else if let Ok(modified) = metadata.modified() {
+                self.files.insert(path, modified);
+            }
+        }
+    }
+
+    fn changed_files(&self, other: &FilesSnapshot) -> Vec<PathBuf> {
+        let mut changed = other
+            .files
+            .iter()
+            .filter(|(path, modified)| self.files.get(*path) != Some(modified))
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+
+        changed.extend(
+            self.files
+                .keys()
+                .filter(|path| !other.files.contains_key(*path))
+                .cloned(),
+        );
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    #[test]
+    fn files_snapshot_changes() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let time = SystemTime::now();
+        write_file(&dir.path().join("app.yaml"), time)?;
+        write_file(&dir.path().join("src/lib.rs"), time)?;
+
+        let snapshot = FilesSnapshot::take(dir.path());
+        assert!(snapshot
+            .changed_files(&FilesSnapshot::take(dir.path()))
+            .is_empty());
+
+        // modified file
+        write_file(
+            &dir.path().join("src/lib.rs"),
+            time + Duration::from_secs(1),
+        )?;
+        let new_snapshot = FilesSnapshot::take(dir.path());
+        assert_eq!(
+            snapshot.changed_files(&new_snapshot),
+            vec![dir.path().join("src/lib.rs")]
+        );
+        let snapshot = new_snapshot;
+
+        // added file
+        write_file(&dir.path().join("src/other.rs"), time)?;
+        let new_snapshot = FilesSnapshot::take(dir.path());
+        assert_eq!(
+            snapshot.changed_files(&new_snapshot),
+            vec![dir.path().join("src/other.rs")]
+        );
+        let snapshot = new_snapshot;
+
+        // removed file
+        std::fs::remove_file(dir.path().join("app.yaml"))?;
+        let new_snapshot = FilesSnapshot::take(dir.path());
+        assert_eq!(
+            snapshot.changed_files(&new_snapshot),
+            vec![dir.path().join("app.yaml")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn files_snapshot_ignored_files() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let time = SystemTime::now();
+        write_file(&dir.path().join("app.yaml"), time)?;
+
+        let snapshot = FilesSnapshot::take(dir.path());
+
+        write_file(&dir.path().join("target/app.wasm"), time)?;
+        write_file(&dir.path().join("node_modules/dep/index.js"), time)?;
+        write_file(&dir.path().join(".git/HEAD"), time)?;
+        write_file(&dir.path().join(".hidden"), time)?;
+        assert!(snapshot
+            .changed_files(&FilesSnapshot::take(dir.path()))
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn files_snapshot_added_file() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let time = SystemTime::now();
+        let module_file = dir.path().join("target/app.wasm");
+        write_file(&module_file, time)?;
+
+        let take_snapshot = || {
+            let mut snapshot = FilesSnapshot::take(dir.path());
+            snapshot.add_file(&module_file);
+            snapshot
+        };
+        let snapshot = take_snapshot();
+
+        // files added explicitly are watched even if they are in an ignored directory
+        write_file(&module_file, time + Duration::from_secs(1))?;
+        assert_eq!(
+            snapshot.changed_files(&take_snapshot()),
+            vec![module_file.clone()]
+        );
+
+        Ok(())
+    }
+
+    fn write_file(path: &Path, modified: SystemTime) -> anyhow::Result<()> {
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let file = File::create(path)?;
+        file.set_modified(modified)?;
+        Ok(())
+    }
+}
@@ -142,6 +142,11 @@ struct JoinOptions {
     #[clap(long)]
     app_host: bool,
 
+    /// The node will relay messages between nodes of the cell that can't
+    /// reach each other directly. It should be always on and reachable.
+    #[clap(long)]
+    relay: bool,
+
     /// Manually join a cell using its cell configuration yaml.
     #[clap(long)]
     manual: bool,
@@ -484,6 +489,10 @@ async fn cmd_node_add(
         if has_role(cell_node_config::Role::AppHostRole) {
             print_action(style_emphasis("application host"));
         }
+
+        if has_role(cell_node_config::Role::RelayRole) {
+            print_action(style_emphasis("relay"));
+        }
     } else {
         print_info("The node will have no roles");
     }
@@ -547,6 +556,7 @@ fn cmd_node_list(ctx: &Context, cell_opts: &CellOptions) {
             print_role(cell_node, CellNodeRole::Chain),
             print_role(cell_node, CellNodeRole::Store),
             print_role(cell_node, CellNodeRole::AppHost),
+            print_role(cell_node, CellNodeRole::Relay),
         ]);
     }
 
@@ -557,6 +567,7 @@ fn cmd_node_list(ctx: &Context, cell_opts: &CellOptions) {
             "Chain".to_string(),
             "Store".to_string(),
             "AppHost".to_string(),
+            "Relay".to_string(),
         ],
         rows,
     );
@@ -595,6 +606,14 @@ async fn cmd_join(
         roles.push(cell_node_config::Role::AppHostRole);
     }
 
+    if join_opts.relay {
+        print_action(format!(
+            "The node will have {} role",
+            style_emphasis("relay")
+        ));
+        roles.push(cell_node_config::Role::RelayRole);
+    }
+
     let cell_node = node_config.create_cell_node_config(roles);
     let cell_node_yaml = cell_node
         .to_yaml_string()
@@ -1377,6 +1396,16 @@ async fn unpack_cell_apps(cell: &Cell, unpack_opts: &AppUnpackOptions) {
             .await
             .expect("Couldn't fetch package");
 
+        // the package is signed by its own manifest key, which needs to be the one of the
+        // application configured in the cell
+        if pkg.app.public_key() != cell_app.public_key() {
+            print_error(format!(
+                "Package of app {} isn't for the public key configured in the cell. Skipping it.",
+                style_value(cell_app.name()),
+            ));
+            continue;
+        }
+
         pkg.install(cell, !unpack_opts.no_overwrite)
             .await
             .expect("Couldn't install app");
@@ -1392,20 +1421,11 @@ async fn unpack_cell_apps(cell: &Cell, unpack_opts: &AppUnpackOptions) {
 
 fn get_cell(ctx: &Context, cell_opts: &CellOptions) -> (LocalNode, EitherCell) {
     let (local_node, either_cells) = ctx.options.get_node_and_cells();
-
-    let cell = if let Some(pk) = &cell_opts.public_key {
-        extract_cell_by_pk(either_cells, pk.as_str())
-            .expect("Couldn't find cell with given public key")
-    } else if let Some(name) = &cell_opts.name {
-        extract_cell_by_name(either_cells, name.as_str())
-            .expect("Couldn't find cell with given name")
-    } else {
-        if either_cells.len() != 1 {
-            panic!("Node config needs to contain only 1 cell if no public key is specified. Use -p option.");
-        }
-
-        either_cells.into_iter().next().expect("Couldn't find cell")
-    };
+    let cell = find_cell(
+        either_cells,
+        cell_opts.public_key.as_deref(),
+        cell_opts.name.as_deref(),
+    );
 
     print_info(format!(
         "Using cell {} with public key {}",
@@ -1416,6 +1436,26 @@ fn get_cell(ctx: &Context, cell_opts: &CellOptions) -> (LocalNode, EitherCell) {
     (local_node, cell)
 }
 
+/// Finds the cell with the given public key or name among the node's cells. If
+/// none is specified, the node needs to be part of a single cell.
+pub fn find_cell(
+    either_cells: Vec<EitherCell>,
+    public_key: Option<&str>,
+    name: Option<&str>,
+) -> EitherCell {
+    if let Some(pk) = public_key {
+        extract_cell_by_pk(either_cells, pk).expect("Couldn't find cell with given public key")
+    } else if let Some(name) = name {
+        extract_cell_by_name(either_cells, name).expect("Couldn't find cell with given name")
+    } else {
+        if either_cells.len() != 1 {
+            panic!("Node config needs to contain only 1 cell if no public key is specified. Use -p option.");
+        }
+
+        either_cells.into_iter().next().expect("Couldn't find cell")
+    }
+}
+
 fn extract_cell_by_pk(either_cells: Vec<EitherCell>, key: &str) -> Option<EitherCell> {
     either_cells
         .into_iter()
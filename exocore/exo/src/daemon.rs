@@ -28,8 +28,8 @@ pub async fn cmd_daemon(ctx: &Context) -> anyhow::Result<()> {
     let clock = Clock::new();
 
     let mut p2p_transport = {
-        let p2p_config = Libp2pTransportConfig::default();
-        Libp2pTransport::new(local_node.clone(), p2p_config)
+        let p2p_config = Libp2pTransportConfig::from(node_config.p2p.unwrap_or_default());
+        Libp2pTransport::new(local_node.clone(), p2p_config, clock.clone())
     };
 
     let mut http_transport = {
@@ -97,6 +97,7 @@ pub async fn cmd_daemon(ctx: &Context) -> anyhow::Result<()> {
 
                 let entities_index_config: EntityIndexConfig = node_config
                     .store
+                    .clone()
                     .and_then(|s| s.index)
                     .map(|e| e.into())
                     .unwrap_or_default();
@@ -133,6 +134,7 @@ pub async fn cmd_daemon(ctx: &Context) -> anyhow::Result<()> {
                         clock.clone(),
                         cell.clone(),
                         store_handle.clone(),
+                        &mut p2p_transport,
                         &mut services_completion,
                     )
                     .await?;
@@ -187,24 +189,17 @@ async fn create_app_host(
     clock: Clock,
     cell: Cell,
     store_handle: impl exocore_store::store::Store,
+    p2p_transport: &mut Libp2pTransport,
     services_completion: &mut Vec<Pin<Box<dyn Future<Output = ()>>>>,
 ) -> anyhow::Result<()> {
-    use exocore_apps_host::{runtime::Applications, Config as ApplicationsConfig};
+    use exocore_apps_host::{
+        election::{AppsElection, ElectionConfig},
+        messaging::{ClientsMessaging, MessagingConfig},
+        runtime::Applications,
+        Config as ApplicationsConfig,
+    };
     use exocore_core::cell::CellNodes;
 
-    // make sure that we are the only node with app host role.
-    // TODO: Support for multiple app host nodes https://github.com/appaquet/exocore/issues/619
-    {
-        let nodes = cell.nodes();
-        let app_host_count = nodes.count_with_role(CellNodeRole::AppHost);
-        if app_host_count != 1 {
-            return Err(anyhow!(
-                "{}: Only one node can be an application host",
-                cell
-            ));
-        }
-    }
-
     let apps_config = ApplicationsConfig::default();
     let apps = match Applications::new(apps_config, clock.clone(), cell.clone(), store_handle).await
     {
@@ -217,6 +212,58 @@ async fn create_app_host(
         }
     };
 
+    // when multiple nodes have the app host role, each application is only run by the node
+    // elected for it
+    let app_host_count = cell.nodes().count_with_role(CellNodeRole::AppHost);
+    let apps = if app_host_count > 1 {
+        let election_transport = p2p_transport.get_handle(cell.clone(), ServiceType::AppHost)?;
+        let election = AppsElection::new(
+            ElectionConfig::default(),
+            clock.clone(),
+            cell.clone(),
+            election_transport,
+        );
+        let apps = apps.with_election(election.handle());
+
+        let election_cell = cell.clone();
+        services_completion.push(
+            async move {
+                let res = election.run().await;
+                info!(
+                    "{}: Application hosts election completed with result {:?}",
+                    election_cell, res
+                );
+            }
+            .boxed(),
+        );
+
+        apps
+    } else {
+        apps
+    };
+
+    // messages between applications and clients are routed through the transport's client
+    // service
+    let messaging_transport = p2p_transport.get_handle(cell.clone(), ServiceType::Client)?;
+    let messaging = ClientsMessaging::new(
+        MessagingConfig::default(),
+        cell.clone(),
+        messaging_transport,
+    );
+    let apps = apps.with_messaging(messaging.handle());
+
+    let messaging_cell = cell.clone();
+    services_completion.push(
+        async move {
+            let res = messaging.run().await;
+            info!(
+                "{}: Applications clients messaging completed with result {:?}",
+                messaging_cell, res
+            );
+        }
+        .boxed(),
+    );
+
     services_completion.push(
         async move {
             let res = apps.run().await;
@@ -242,6 +289,7 @@ async fn create_app_host(
     _clock: Clock,
     _cell: Cell,
     _store_handle: impl exocore_store::store::Store,
+    _p2p_transport: &mut Libp2pTransport,
     _services_completion: &mut Vec<Pin<Box<dyn Future<Output = ()>>>>,
 ) -> anyhow::Result<()> {
     Err(anyhow!("Cannot host app on this target."))
@@ -255,7 +303,7 @@ async fn create_local_store<T: TransportServiceHandle>(
     clock: Clock,
     entities_index: EntityIndex<DirectoryChainStore, MemoryPendingStore>,
 ) -> anyhow::Result<(impl exocore_store::store::Store, impl Future<Output = ()>)> {
-    let store_config = config.store.map(|c| c.into()).unwrap_or_default();
+    let store_config = config.store.clone().map(|c| c.into()).unwrap_or_default();
     let local_store = Store::new(
         store_config,
         full_cell.cell().clone(),
@@ -1,10 +1,12 @@
 mod app;
+mod app_dev;
 mod cell;
 mod config;
 mod daemon;
 mod disco;
 mod node;
 mod sec;
+mod store;
 mod term;
 mod utils;
 
@@ -101,6 +103,9 @@ pub enum Command {
     /// Node configuration related commands.
     Config(config::ConfigOptions),
 
+    /// Store related commands, to query and mutate entities of a cell.
+    Store(store::StoreOptions),
+
     /// Starts the node daemon, with all its cells and roles.
     Daemon,
 
@@ -129,15 +134,13 @@ async fn main() -> anyhow::Result<()> {
     let result = match &ctx.options.subcommand {
         Command::Node(node_opts) => node::handle_cmd(&ctx, node_opts),
         Command::Cell(cell_opts) => cell::handle_cmd(&ctx, cell_opts).await,
-        Command::App(app_opts) => {
-            app::handle_cmd(&ctx, app_opts).await;
-            Ok(())
-        }
+        Command::App(app_opts) => app::handle_cmd(&ctx, app_opts).await,
         Command::Sec(keys_opts) => {
             sec::handle_cmd(&ctx, keys_opts);
             Ok(())
         }
         Command::Config(config_opts) => config::handle_cmd(&ctx, config_opts),
+        Command::Store(store_opts) => store::handle_cmd(&ctx, store_opts).await,
         Command::Daemon => daemon::cmd_daemon(&ctx).await,
         Command::Discovery(disco_opts) => disco::cmd_daemon(&ctx, disco_opts).await,
         Command::Version => {
@@ -0,0 +1,362 @@
+use std::{future::Future, time::Duration};
+
+use exocore::client::Client;
+use exocore_protos::{
+    prost::ProstTimestampExt,
+    reflect::{self, ReflectMessage},
+    registry::Registry,
+    store::{Entity, EntityResults, MutationResult, Trait},
+};
+use exocore_store::{mutation::MutationBuilder, query::QueryBuilder, store::Store};
+use exocore_transport::http::json::{JsonEntity, JsonMutationResult, JsonResults};
+use futures::StreamExt;
+
+use crate::{cell::find_cell, term::*, Context};
+
+/// Store commands connect to the cell's store nodes through the network, like
+/// any other client. The node therefore needs to be part of the cell, and can't
+/// be the node of a daemon running on the same directory.
+#[derive(clap::Parser)]
+pub struct StoreOptions {
+    /// Public key of the cell whose store we want to use. If not specified
+    /// and the node config only contains 1 cell, this cell will be taken.
+    #[clap(long, short)]
+    public_key: Option<String>,
+
+    /// Name of the cell whose store we want to use. If not specified and the
+    /// node config only contains 1 cell, this cell will be taken.
+    #[clap(long, short)]
+    name: Option<String>,
+
+    /// Print results in JSON instead of tables.
+    #[clap(long)]
+    json: bool,
+
+    /// Time to wait for a response from the store, in seconds.
+    #[clap(long, default_value = "10")]
+    timeout: u64,
+
+    #[clap(subcommand)]
+    command: StoreCommand,
+}
+
+#[derive(clap::Parser)]
+enum StoreCommand {
+    /// Queries entities using a query string.
+    Query(QueryOptions),
+
+    /// Gets an entity by its id.
+    Get(GetOptions),
+
+    /// Puts a trait in an entity.
+    Put(PutOptions),
+
+    /// Deletes an entity or one of its traits.
+    Delete(DeleteOptions),
+
+    /// Watches the results of a query string, printing them every time they
+    /// change.
+    Watch(QueryOptions),
+}
+
+#[derive(clap::Parser)]
+struct QueryOptions {
+    /// Query string.
+    query: String,
+
+    /// Number of entities to return.
+    #[clap(long, default_value = "20")]
+    count: u32,
+}
+
+#[derive(clap::Parser)]
+struct GetOptions {
+    /// Id of the entity.
+    entity_id: String,
+}
+
+#[derive(clap::Parser)]
+struct PutOptions {
+    /// Message of the trait, in JSON, as `{"type": "<message full name>",
+    /// "value": {<fields>}}`.
+    message: String,
+
+    /// Id of the entity in which the trait is put. A new entity is created if
+    /// not specified.
+    #[clap(long)]
+    entity_id: Option<String>,
+
+    /// Id of the trait to put. A new trait is created if not specified.
+    #[clap(long)]
+    trait_id: Option<String>,
+}
+
+#[derive(clap::Parser)]
+struct DeleteOptions {
+    /// Id of the entity.
+    entity_id: String,
+
+    /// Id of the trait to delete. The whole entity is deleted if not
+    /// specified.
+    #[clap(long)]
+    trait_id: Option<String>,
+}
+
+pub async fn handle_cmd(ctx: &Context, store_opts: &StoreOptions) -> anyhow::Result<()> {
+    let (local_node, either_cells) = ctx.options.get_node_and_cells();
+    let either_cell = find_cell(
+        either_cells,
+        store_opts.public_key.as_deref(),
+        store_opts.name.as_deref(),
+    );
+    let cell = either_cell.cell();
+
+    let client = with_timeout(store_opts, Client::with_cell(local_node, cell)).await??;
+    let schemas = cell.schemas().as_ref();
+
+    match &store_opts.command {
+        StoreCommand::Query(query_opts) => {
+            cmd_query(store_opts, query_opts, &client, schemas).await
+        }
+        StoreCommand::Get(get_opts) => cmd_get(store_opts, get_opts, &client, schemas).await,
+        StoreCommand::Put(put_opts) => cmd_put(store_opts, put_opts, &client, schemas).await,
+        StoreCommand::Delete(delete_opts) => {
+            cmd_delete(store_opts, delete_opts, &client, schemas).await
+        }
+        StoreCommand::Watch(query_opts) => {
+            cmd_watch(store_opts, query_opts, &client, schemas).await
+        }
+    }
+}
+
+async fn cmd_query(
+    store_opts: &StoreOptions,
+    query_opts: &QueryOptions,
+    client: &Client,
+    schemas: &Registry,
+) -> anyhow::Result<()> {
+    let query = QueryBuilder::from_query_string(&query_opts.query)
+        .count(query_opts.count)
+        .build();
+    let results = with_timeout(store_opts, client.store.query(query)).await??;
+
+    print_results(store_opts, schemas, results)
+}
+
+async fn cmd_get(
+    store_opts: &StoreOptions,
+    get_opts: &GetOptions,
+    client: &Client,
+    schemas: &Registry,
+) -> anyhow::Result<()> {
+    let query = QueryBuilder::with_id(&get_opts.entity_id).build();
+    let results = with_timeout(store_opts, client.store.query(query)).await??;
+
+    let entity = results
+        .entities
+        .into_iter()
+        .find_map(|result| result.entity)
+        .ok_or_else(|| anyhow!("Couldn't find entity {}", get_opts.entity_id))?;
+
+    if store_opts.json {
+        let json_entity = JsonEntity::from_entity(schemas, entity);
+        println!("{}", serde_json::to_string_pretty(&json_entity)?);
+        return Ok(());
+    }
+
+    print_info(format!("Entity {}", style_value(&entity.id)));
+    print_spacer();
+    print_traits_table(schemas, &entity.traits);
+
+    Ok(())
+}
+
+async fn cmd_put(
+    store_opts: &StoreOptions,
+    put_opts: &PutOptions,
+    client: &Client,
+    schemas: &Registry,
+) -> anyhow::Result<()> {
+    let json_message: serde_json::Value = serde_json::from_str(&put_opts.message)
+        .map_err(|err| anyhow!("Couldn't parse trait message JSON: {}", err))?;
+    let message = reflect::from_json(schemas, &json_message)?.encode_to_prost_any()?;
+
+    let trt = Trait {
+        id: put_opts.trait_id.clone().unwrap_or_default(),
+        message: Some(message),
+        ..Default::default()
+    };
+    let mutation = MutationBuilder::new()
+        .put_trait(put_opts.entity_id.clone().unwrap_or_default(), trt)
+        .return_entities()
+        .build();
+    let result = with_timeout(store_opts, client.store.mutate(mutation)).await??;
+
+    print_mutation_result(store_opts, schemas, result)
+}
+
+async fn cmd_delete(
+    store_opts: &StoreOptions,
+    delete_opts: &DeleteOptions,
+    client: &Client,
+    schemas: &Registry,
+) -> anyhow::Result<()> {
+    let mutation = if let Some(trait_id) = &delete_opts.trait_id {
+        MutationBuilder::new().delete_trait(&delete_opts.entity_id, trait_id)
+    } else {
+        MutationBuilder::new().delete_entity(&delete_opts.entity_id)
+    };
+    let result = with_timeout(store_opts, client.store.mutate(mutation.build())).await??;
+
+    print_mutation_result(store_opts, schemas, result)
+}
+
+async fn cmd_watch(
+    store_opts: &StoreOptions,
+    query_opts: &QueryOptions,
+    client: &Client,
+    schemas: &Registry,
+) -> anyhow::Result<()> {
+    let query = QueryBuilder::from_query_string(&query_opts.query)
+        .count(query_opts.count)
+        .build();
+    let mut stream = client.store.watched_query(query)?;
+
+    while let Some(results) = stream.next().await {
+        let results = results?;
+        if store_opts.json {
+            // each update is printed on its own line so that it can be consumed as JSON lines
+            let json_results = JsonResults::from_results(schemas, results, false);
+            println!("{}", serde_json::to_string(&json_results)?);
+        } else {
+            print_step("Results changed");
+            print_results(store_opts, schemas, results)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_results(
+    store_opts: &StoreOptions,
+    schemas: &Registry,
+    results: EntityResults,
+) -> anyhow::Result<()> {
+    if store_opts.json {
+        let json_results = JsonResults::from_results(schemas, results, false);
+        println!("{}", serde_json::to_string_pretty(&json_results)?);
+        return Ok(());
+    }
+
+    let entities = results
+        .entities
+        .into_iter()
+        .flat_map(|result| result.entity)
+        .collect::<Vec<_>>();
+
+    print_spacer();
+    print_entities_table(&entities);
+    print_info(format!(
+        "{} entities out of about {}",
+        style_value(entities.len()),
+        style_value(results.estimated_count)
+    ));
+
+    Ok(())
+}
+
+fn print_mutation_result(
+    store_opts: &StoreOptions,
+    schemas: &Registry,
+    result: MutationResult,
+) -> anyhow::Result<()> {
+    if store_opts.json {
+        let json_result = JsonMutationResult::from_result(schemas, result);
+        println!("{}", serde_json::to_string_pretty(&json_result)?);
+        return Ok(());
+    }
+
+    print_success(format!(
+        "Mutation executed with operation ids {}",
+        style_value(&result.operation_ids)
+    ));
+
+    if !result.entities.is_empty() {
+        print_spacer();
+        print_entities_table(&result.entities);
+    }
+
+    Ok(())
+}
+
+fn print_entities_table(entities: &[Entity]) {
+    let columns = vec![
+        "ID".to_string(),
+        "Traits".to_string(),
+        "Modification date".to_string(),
+    ];
+
+    let rows = entities
+        .iter()
+        .map(|entity| {
+            let traits = entity
+                .traits
+                .iter()
+                .map(trait_type)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let date = entity.modification_date.or(entity.creation_date);
+
+            vec![entity.id.clone(), traits, format_date(date)]
+        })
+        .collect();
+
+    print_table(columns, rows);
+}
+
+fn print_traits_table(schemas: &Registry, traits: &[Trait]) {
+    let columns = vec![
+        "Trait ID".to_string(),
+        "Type".to_string(),
+        "Modification date".to_string(),
+        "Value".to_string(),
+    ];
+
+    let rows = traits
+        .iter()
+        .map(|trt| {
+            let value = trt
+                .message
+                .as_ref()
+                .and_then(|message| {
+                    let dyn_message = reflect::from_prost_any(schemas, message).ok()?;
+                    let json = dyn_message.encode_json(schemas).ok()?;
+                    json.get("value").map(|value| value.to_string())
+                })
+                .unwrap_or_default();
+            let date = trt.modification_date.or(trt.creation_date);
+
+            vec![trt.id.clone(), trait_type(trt), format_date(date), value]
+        })
+        .collect();
+
+    print_table(columns, rows);
+}
+
+fn trait_type(trt: &Trait) -> String {
+    trt.message
+        .as_ref()
+        .map(|message| reflect::any_url_to_full_name(&message.type_url))
+        .unwrap_or_default()
+}
+
+fn format_date(date: Option<exocore_protos::prost::Timestamp>) -> String {
+    date.map(|date| date.to_chrono_datetime().to_rfc3339())
+        .unwrap_or_default()
+}
+
+async fn with_timeout<F: Future>(store_opts: &StoreOptions, fut: F) -> anyhow::Result<F::Output> {
+    tokio::time::timeout(Duration::from_secs(store_opts.timeout), fut)
+        .await
+        .map_err(|_| anyhow!("Timed out waiting for a response from the store"))
+}
@@ -22,11 +22,15 @@ fn main() {
                 "./protobuf/exocore/store/query.proto",
                 "./protobuf/exocore/store/mutation.proto",
                 "./protobuf/exocore/test/test.proto",
+                "./protobuf/exocore/core/addresses.proto",
                 "./protobuf/exocore/core/auth.proto",
                 "./protobuf/exocore/core/config.proto",
+                "./protobuf/exocore/core/relay.proto",
                 "./protobuf/exocore/core/build.proto",
                 "./protobuf/exocore/apps/manifest.proto",
                 "./protobuf/exocore/apps/runtime.proto",
+                "./protobuf/exocore/apps/host.proto",
+                "./protobuf/exocore/apps/messaging.proto",
             ];
 
             let mut config = prost_build::Config::new();
@@ -48,6 +52,7 @@ fn main() {
                 .field_attribute("NodeCellConfig.id", "#[serde(default)]") // TODO: Remove once migrated to new cell config
                 .type_attribute("NodeStoreConfig", "#[derive(Serialize, Deserialize)]")
                 .type_attribute("ChainConfig", "#[derive(Serialize, Deserialize)]")
+                .type_attribute("NodeP2pConfig", "#[derive(Serialize, Deserialize)]")
                 .type_attribute("EntityIndexConfig", "#[derive(Serialize, Deserialize)]")
                 .type_attribute("MutationIndexConfig", "#[derive(Serialize, Deserialize)]")
                 .type_attribute("EntityGarbageCollectorConfig", "#[derive(Serialize, Deserialize)]")
@@ -67,6 +72,7 @@ fn main() {
                 .field_attribute("CellApplicationConfig.location", "#[serde(flatten)]")
                 .type_attribute("Manifest", "#[derive(Serialize, Deserialize)]")
                 .type_attribute("ManifestModule", "#[derive(Serialize, Deserialize)]")
+                .type_attribute("ManifestTrigger", "#[derive(Serialize, Deserialize)]")
                 .type_attribute("ManifestSchema", "#[derive(Serialize, Deserialize)]")
                 .type_attribute("ManifestSchema.source", "#[derive(Serialize, Deserialize)]")
                 .type_attribute(
@@ -79,6 +85,8 @@ fn main() {
                 .field_attribute("LocalNodeConfig.id", "#[serde(default)]")
                 .field_attribute("LocalNodeConfig.listen_addresses", "#[serde(default)]")
                 .field_attribute("LocalNodeConfig.store", "#[serde(default)]")
+                .field_attribute("LocalNodeConfig.p2p", "#[serde(default)]")
+                .field_attribute("NodeP2pConfig.mdns_discovery", "#[serde(default)]")
                 .field_attribute("NodeStoreConfig.index", "#[serde(default)]")
                 .field_attribute("NodeStoreConfig.query_parallelism", "#[serde(default)]")
                 .field_attribute("EntityIndexConfig.chain_index_min_depth", "#[serde(default)]")
@@ -92,6 +100,8 @@ fn main() {
                 .field_attribute("MutationIndexConfig.indexer_num_threads", "#[serde(default)]")
                 .field_attribute("MutationIndexConfig.indexer_heap_size_bytes", "#[serde(default)]")
                 .field_attribute("MutationIndexConfig.entity_mutations_cache_size", "#[serde(default)]")
+                .field_attribute("MutationIndexConfig.text_analyzer", "#[serde(default)]")
+                .field_attribute("MutationIndexConfig.entity_query_max_pages", "#[serde(default)]")
                 .field_attribute("EntityGarbageCollectorConfig.run_interval_secs", "#[serde(default)]")
                 .field_attribute("EntityGarbageCollectorConfig.queue_size", "#[serde(default)]")
                 .field_attribute("NodeConfig.name", "#[serde(default)]")
@@ -102,8 +112,12 @@ fn main() {
                 .field_attribute("CellConfig.keypair", "#[serde(default)]")
                 .field_attribute("CellConfig.id", "#[serde(default)]")
                 .field_attribute("CellConfig.apps", "#[serde(default)]")
+                .field_attribute("CellConfig.allow_unsigned_apps", "#[serde(default)]")
                 .field_attribute("CellNodeConfig.roles", "#[serde(default)]")
                 .field_attribute("Manifest.schemas", "#[serde(default)]")
+                .field_attribute("Manifest.signature", "#[serde(default)]")
+                .field_attribute("Manifest.triggers", "#[serde(default)]")
+                .field_attribute("Manifest.http_domains", "#[serde(default)]")
                 .field_attribute("ManifestModule.multihash", "#[serde(default)]");
 
             config
@@ -12,6 +12,21 @@ pub struct Manifest {
     pub schemas: ::prost::alloc::vec::Vec<ManifestSchema>,
     #[prost(message, optional, tag = "5")]
     pub module: ::core::option::Option<ManifestModule>,
+    /// Base58 encoded signature of the manifest, without this field, by the
+    /// application's keypair. Since the manifest contains the multihash of the
+    /// module, the signature also covers the module.
+    #[prost(string, tag = "7")]
+    #[serde(default)]
+    pub signature: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "8")]
+    #[serde(default)]
+    pub triggers: ::prost::alloc::vec::Vec<ManifestTrigger>,
+    /// Domains to which the application is allowed to send HTTP requests. A
+    /// domain also allows its subdomains (ex: `example.com` allows
+    /// `www.example.com`).
+    #[prost(string, repeated, tag = "9")]
+    #[serde(default)]
+    pub http_domains: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 #[derive(Serialize, Deserialize, Clone, PartialEq, ::prost::Message)]
 pub struct ManifestSchema {
@@ -37,6 +52,18 @@ pub struct ManifestModule {
     #[serde(default)]
     pub multihash: ::prost::alloc::string::String,
 }
+/// Trigger on which the application gets notified of changes to entities.
+#[derive(Serialize, Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct ManifestTrigger {
+    /// Name of the trigger, unique within the application. Used to persist the
+    /// changes handled by the trigger.
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    /// Full name of a trait type (ex: `exomind.base.v1.Email`). The trigger
+    /// matches every time a trait of this type is put on an entity.
+    #[prost(string, tag = "2")]
+    pub trait_type: ::prost::alloc::string::String,
+}
 /// Message sent to application running in WASM from runtime.
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct InMessage {
@@ -59,6 +86,12 @@ pub mod in_message {
         Invalid = 0,
         StoreEntityResults = 1,
         StoreMutationResult = 2,
+        ScheduledJobRun = 3,
+        EntityTriggered = 4,
+        HttpResponse = 5,
+        KvResult = 6,
+        ClientMessage = 7,
+        ClientRequest = 8,
     }
     impl InMessageType {
         /// String value of the enum field names used in the ProtoBuf definition.
@@ -70,6 +103,12 @@ pub mod in_message {
                 InMessageType::Invalid => "INVALID",
                 InMessageType::StoreEntityResults => "STORE_ENTITY_RESULTS",
                 InMessageType::StoreMutationResult => "STORE_MUTATION_RESULT",
+                InMessageType::ScheduledJobRun => "SCHEDULED_JOB_RUN",
+                InMessageType::EntityTriggered => "ENTITY_TRIGGERED",
+                InMessageType::HttpResponse => "HTTP_RESPONSE",
+                InMessageType::KvResult => "KV_RESULT",
+                InMessageType::ClientMessage => "CLIENT_MESSAGE",
+                InMessageType::ClientRequest => "CLIENT_REQUEST",
             }
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
@@ -78,6 +117,12 @@ pub mod in_message {
                 "INVALID" => Some(Self::Invalid),
                 "STORE_ENTITY_RESULTS" => Some(Self::StoreEntityResults),
                 "STORE_MUTATION_RESULT" => Some(Self::StoreMutationResult),
+                "SCHEDULED_JOB_RUN" => Some(Self::ScheduledJobRun),
+                "ENTITY_TRIGGERED" => Some(Self::EntityTriggered),
+                "HTTP_RESPONSE" => Some(Self::HttpResponse),
+                "KV_RESULT" => Some(Self::KvResult),
+                "CLIENT_MESSAGE" => Some(Self::ClientMessage),
+                "CLIENT_REQUEST" => Some(Self::ClientRequest),
                 _ => None,
             }
         }
@@ -103,6 +148,13 @@ pub mod out_message {
         Invalid = 0,
         StoreEntityQuery = 1,
         StoreMutationRequest = 2,
+        SchedulerRegisterJob = 3,
+        ScheduledJobDone = 4,
+        EntityTriggerDone = 5,
+        HttpRequest = 6,
+        KvRequest = 7,
+        ClientPublish = 8,
+        ClientResponse = 9,
     }
     impl OutMessageType {
         /// String value of the enum field names used in the ProtoBuf definition.
@@ -114,6 +166,13 @@ pub mod out_message {
                 OutMessageType::Invalid => "INVALID",
                 OutMessageType::StoreEntityQuery => "STORE_ENTITY_QUERY",
                 OutMessageType::StoreMutationRequest => "STORE_MUTATION_REQUEST",
+                OutMessageType::SchedulerRegisterJob => "SCHEDULER_REGISTER_JOB",
+                OutMessageType::ScheduledJobDone => "SCHEDULED_JOB_DONE",
+                OutMessageType::EntityTriggerDone => "ENTITY_TRIGGER_DONE",
+                OutMessageType::HttpRequest => "HTTP_REQUEST",
+                OutMessageType::KvRequest => "KV_REQUEST",
+                OutMessageType::ClientPublish => "CLIENT_PUBLISH",
+                OutMessageType::ClientResponse => "CLIENT_RESPONSE",
             }
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
@@ -122,11 +181,158 @@ pub mod out_message {
                 "INVALID" => Some(Self::Invalid),
                 "STORE_ENTITY_QUERY" => Some(Self::StoreEntityQuery),
                 "STORE_MUTATION_REQUEST" => Some(Self::StoreMutationRequest),
+                "SCHEDULER_REGISTER_JOB" => Some(Self::SchedulerRegisterJob),
+                "SCHEDULED_JOB_DONE" => Some(Self::ScheduledJobDone),
+                "ENTITY_TRIGGER_DONE" => Some(Self::EntityTriggerDone),
+                "HTTP_REQUEST" => Some(Self::HttpRequest),
+                "KV_REQUEST" => Some(Self::KvRequest),
+                "CLIENT_PUBLISH" => Some(Self::ClientPublish),
+                "CLIENT_RESPONSE" => Some(Self::ClientResponse),
                 _ => None,
             }
         }
     }
 }
+/// Job registered by an application to be run periodically by its host.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ScheduledJob {
+    /// Name of the job, unique within the application. Used to persist the time
+    /// of its last run.
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(oneof = "scheduled_job::Schedule", tags = "2, 3")]
+    pub schedule: ::core::option::Option<scheduled_job::Schedule>,
+}
+/// Nested message and enum types in `ScheduledJob`.
+pub mod scheduled_job {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Schedule {
+        /// Interval in seconds between runs, up to a year.
+        #[prost(uint64, tag = "2")]
+        IntervalSecs(u64),
+        /// Cron expression (minute, hour, day of month, month, day of week), in UTC.
+        #[prost(string, tag = "3")]
+        Cron(::prost::alloc::string::String),
+    }
+}
+/// Run of a scheduled job requested by the host. Once the job is done, the
+/// application replies with a `SCHEDULED_JOB_DONE` message having the same
+/// rendez-vous id.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ScheduledJobRun {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    /// Time at which the run was triggered.
+    #[prost(message, optional, tag = "2")]
+    pub time: ::core::option::Option<::prost_types::Timestamp>,
+}
+/// Entity matching a trigger declared in the application's manifest. Once the
+/// entity is handled, the application replies with an `ENTITY_TRIGGER_DONE`
+/// message having the same rendez-vous id.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EntityTriggered {
+    /// Name of the trigger in the manifest.
+    #[prost(string, tag = "1")]
+    pub trigger: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub entity: ::core::option::Option<super::store::Entity>,
+    /// Id of the operation that made the entity match the trigger.
+    #[prost(uint64, tag = "3")]
+    pub operation_id: u64,
+}
+/// HTTP request executed by the host on behalf of the application. The host
+/// only allows requests to the domains declared in the application's manifest.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HttpRequest {
+    /// Method of the request (ex: `GET`, `POST`). Defaults to `GET` if empty.
+    #[prost(string, tag = "1")]
+    pub method: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub url: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub headers: ::prost::alloc::vec::Vec<HttpHeader>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub body: ::prost::alloc::vec::Vec<u8>,
+    /// Timeout of the request in seconds. Defaults to 30 seconds if 0, and is
+    /// capped at 60 seconds.
+    #[prost(uint32, tag = "5")]
+    pub timeout_secs: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HttpResponse {
+    #[prost(uint32, tag = "1")]
+    pub status: u32,
+    #[prost(message, repeated, tag = "2")]
+    pub headers: ::prost::alloc::vec::Vec<HttpHeader>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub body: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HttpHeader {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub value: ::prost::alloc::string::String,
+}
+/// Request on the key-value storage of the application, private to the
+/// application and persisted by the host. The host replies with a `KV_RESULT`
+/// message having the same rendez-vous id.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KvRequest {
+    #[prost(oneof = "kv_request::Request", tags = "1, 2, 3, 4")]
+    pub request: ::core::option::Option<kv_request::Request>,
+}
+/// Nested message and enum types in `KvRequest`.
+pub mod kv_request {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Request {
+        #[prost(message, tag = "1")]
+        Get(super::KvGet),
+        #[prost(message, tag = "2")]
+        Put(super::KvPut),
+        #[prost(message, tag = "3")]
+        Delete(super::KvDelete),
+        #[prost(message, tag = "4")]
+        List(super::KvList),
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KvGet {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KvPut {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(bytes = "vec", tag = "2")]
+    pub value: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KvDelete {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+}
+/// Lists the keys starting with the given prefix, in lexicographic order.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KvList {
+    #[prost(string, tag = "1")]
+    pub prefix: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KvResult {
+    /// Value of the key for a `get` request, if it exists.
+    #[prost(message, optional, tag = "1")]
+    pub value: ::core::option::Option<KvValue>,
+    /// Keys matching the prefix of a `list` request.
+    #[prost(string, repeated, tag = "2")]
+    pub keys: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KvValue {
+    #[prost(bytes = "vec", tag = "1")]
+    pub value: ::prost::alloc::vec::Vec<u8>,
+}
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum MessageStatus {
@@ -156,3 +362,65 @@ impl MessageStatus {
         }
     }
 }
+/// Heartbeat sent periodically by an application host node to the other
+/// application host nodes of the cell, used to elect the node running each
+/// application.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AppHostHeartbeat {
+    /// Ids of the applications currently running on the node.
+    #[prost(string, repeated, tag = "1")]
+    pub running_apps: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// State of an application kept by the application host node running it (ex:
+/// last runs of scheduled jobs). It is stored as traits of an entity reserved
+/// to the application so that it gets replicated to the other application host
+/// nodes, which can then take over the application without losing it.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AppState {
+    /// Serialized state, whose format depends on the trait storing it.
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+}
+/// Subscription of a client to topics published by an application, sent to
+/// application host nodes. Subscriptions expire if they aren't renewed
+/// periodically by the client. An empty list of topics removes the client's
+/// subscriptions to the application.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClientSubscribe {
+    #[prost(string, tag = "1")]
+    pub app_id: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "2")]
+    pub topics: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// Message on a topic, published by an application to its subscribed clients or
+/// by a client to an application.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClientMessage {
+    #[prost(string, tag = "1")]
+    pub app_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub topic: ::prost::alloc::string::String,
+    #[prost(bytes = "vec", tag = "3")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+}
+/// Request sent by a client to an application, which replies with a
+/// `ClientResponse`.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClientRequest {
+    #[prost(string, tag = "1")]
+    pub app_id: ::prost::alloc::string::String,
+    /// Method of the application handling the request.
+    #[prost(string, tag = "2")]
+    pub method: ::prost::alloc::string::String,
+    #[prost(bytes = "vec", tag = "3")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClientResponse {
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+    /// Error returned by the application, or by the host if the application
+    /// couldn't handle the request.
+    #[prost(string, tag = "2")]
+    pub error: ::prost::alloc::string::String,
+}
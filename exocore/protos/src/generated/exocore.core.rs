@@ -1,4 +1,42 @@
 // This file is @generated by prost-build.
+/// Addresses on which a node of a cell is listening, announced by the node
+/// itself and gossiped to the other nodes of the cell.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct NodeAddressesAnnounce {
+    /// Encoded `NodeAddressesAnnounceData`.
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+    /// Signature of `data` by the announcing node's keypair.
+    #[prost(bytes = "vec", tag = "2")]
+    pub signature: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct NodeAddressesAnnounceData {
+    #[prost(bytes = "vec", tag = "1")]
+    pub cell_id: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub node_id: ::prost::alloc::vec::Vec<u8>,
+    #[prost(string, repeated, tag = "3")]
+    pub p2p: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "4")]
+    pub announce_date: ::core::option::Option<::prost_types::Timestamp>,
+}
+/// Addresses of the nodes of a cell that were announced by them, persisted next
+/// to the cell config.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct NodeAddressBook {
+    #[prost(message, repeated, tag = "1")]
+    pub entries: ::prost::alloc::vec::Vec<NodeAddressBookEntry>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct NodeAddressBookEntry {
+    #[prost(string, tag = "1")]
+    pub node_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub address: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "3")]
+    pub last_seen: ::core::option::Option<::prost_types::Timestamp>,
+}
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct AuthToken {
     #[prost(bytes = "vec", tag = "1")]
@@ -41,6 +79,9 @@ pub struct LocalNodeConfig {
     pub store: ::core::option::Option<NodeStoreConfig>,
     #[prost(message, optional, tag = "9")]
     pub chain: ::core::option::Option<ChainConfig>,
+    #[prost(message, optional, tag = "11")]
+    #[serde(default)]
+    pub p2p: ::core::option::Option<NodeP2pConfig>,
 }
 #[derive(Serialize, Deserialize, Clone, PartialEq, ::prost::Message)]
 pub struct NodeAddresses {
@@ -71,7 +112,7 @@ pub mod node_cell_config {
     }
 }
 /// Entity store configuration for the node (i.e. not global)
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, ::prost::Message)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, ::prost::Message)]
 pub struct NodeStoreConfig {
     /// Entity index config.
     #[prost(message, optional, tag = "1")]
@@ -82,6 +123,14 @@ pub struct NodeStoreConfig {
     #[serde(default)]
     pub query_parallelism: ::core::option::Option<u32>,
 }
+/// Peer-to-peer transport configuration for the node.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, ::prost::Message)]
+pub struct NodeP2pConfig {
+    /// Discovers nodes of the cells on the local network via mDNS. Disabled by default.
+    #[prost(message, optional, tag = "1")]
+    #[serde(default)]
+    pub mdns_discovery: ::core::option::Option<bool>,
+}
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, ::prost::Message)]
 pub struct ChainConfig {
     /// Maximum size in bytes per segment. This is a soft limit since the last
@@ -99,7 +148,7 @@ pub struct ChainConfig {
     pub segment_max_open_mmap: ::core::option::Option<u32>,
 }
 /// Configuration of the entity index
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, ::prost::Message)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, ::prost::Message)]
 pub struct EntityIndexConfig {
     /// What is the minimum depth that a block needs to be the chain to be
     /// indexed. This is required to lower the odds that we are going to
@@ -152,7 +201,7 @@ pub struct EntityIndexConfig {
     pub garbage_collector: ::core::option::Option<EntityGarbageCollectorConfig>,
 }
 /// Trait index configuration
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, ::prost::Message)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, ::prost::Message)]
 pub struct MutationIndexConfig {
     /// Number of indexing threads.
     #[prost(message, optional, tag = "1")]
@@ -166,6 +215,18 @@ pub struct MutationIndexConfig {
     #[prost(message, optional, tag = "3")]
     #[serde(default)]
     pub entity_mutations_cache_size: ::core::option::Option<u32>,
+    /// Analyzer used to full-text index text fields that don't specify one via the
+    /// `text_analyzer` field option.
+    /// Ex: "default", "ascii", "ngram", "english", "french"
+    #[prost(message, optional, tag = "4")]
+    #[serde(default)]
+    pub text_analyzer: ::core::option::Option<::prost::alloc::string::String>,
+    /// Maximum number of mutation pages (see `iterator_page_size`) that an entity query can
+    /// go through to fill its page, since matching mutations may get discarded if they were
+    /// overridden. Queries with `count_total` go through all pages.
+    #[prost(message, optional, tag = "5")]
+    #[serde(default)]
+    pub entity_query_max_pages: ::core::option::Option<u32>,
 }
 /// Configuration for entity garbage collector.
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, ::prost::Message)]
@@ -202,6 +263,12 @@ pub struct CellConfig {
     #[prost(message, repeated, tag = "7")]
     #[serde(default)]
     pub apps: ::prost::alloc::vec::Vec<CellApplicationConfig>,
+    /// Allows applications with an unsigned manifest to run in the cell, for
+    /// cells that installed their applications before manifests were signed.
+    /// Their module still needs to match the multihash of their manifest.
+    #[prost(bool, tag = "8")]
+    #[serde(default)]
+    pub allow_unsigned_apps: bool,
 }
 #[derive(Serialize, Deserialize, Clone, PartialEq, ::prost::Message)]
 pub struct CellNodeConfig {
@@ -232,6 +299,7 @@ pub mod cell_node_config {
         ChainRole = 1,
         StoreRole = 2,
         AppHostRole = 3,
+        RelayRole = 4,
     }
     impl Role {
         /// String value of the enum field names used in the ProtoBuf definition.
@@ -244,6 +312,7 @@ pub mod cell_node_config {
                 Role::ChainRole => "CHAIN_ROLE",
                 Role::StoreRole => "STORE_ROLE",
                 Role::AppHostRole => "APP_HOST_ROLE",
+                Role::RelayRole => "RELAY_ROLE",
             }
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
@@ -253,6 +322,7 @@ pub mod cell_node_config {
                 "CHAIN_ROLE" => Some(Self::ChainRole),
                 "STORE_ROLE" => Some(Self::StoreRole),
                 "APP_HOST_ROLE" => Some(Self::AppHostRole),
+                "RELAY_ROLE" => Some(Self::RelayRole),
                 _ => None,
             }
         }
@@ -296,6 +366,20 @@ pub mod cell_application_config {
         Inline(super::super::apps::Manifest),
     }
 }
+/// Message forwarded through a node of the cell having the relay role, on behalf
+/// of a node that can't reach the destination node directly.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RelayedMessage {
+    #[prost(bytes = "vec", tag = "1")]
+    pub destination_node_id: ::prost::alloc::vec::Vec<u8>,
+    /// Node that originally sent the message. Set by the relay node from the
+    /// authenticated connection on which it received the message.
+    #[prost(bytes = "vec", tag = "2")]
+    pub source_node_id: ::prost::alloc::vec::Vec<u8>,
+    /// Encoded envelope of the relayed message.
+    #[prost(bytes = "vec", tag = "3")]
+    pub envelope: ::prost::alloc::vec::Vec<u8>,
+}
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct BuildInfo {
     #[prost(string, tag = "1")]
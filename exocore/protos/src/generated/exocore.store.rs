@@ -92,10 +92,21 @@ pub struct EntityQuery {
     /// got received for a while.
     #[prost(bool, tag = "13")]
     pub programmatic: bool,
+    /// If specified, snippets of the traits' full-text fields with highlighted terms
+    /// matching the query are returned in `EntityResult.snippets`.
+    #[prost(message, optional, tag = "16")]
+    pub highlighting: ::core::option::Option<Highlighting>,
+    /// If specified, the exact number of entities matching the query is returned in
+    /// `EntityResults.total_count`. This requires going through all matching mutations,
+    /// without the limit of pages that entity queries otherwise go through (see
+    /// `MutationIndexConfig.entity_query_max_pages`), and is therefore more expensive
+    /// than the estimated count.
+    #[prost(bool, tag = "18")]
+    pub count_total: bool,
     /// Main search predicate on individual traits of the entity.
     #[prost(
         oneof = "entity_query::Predicate",
-        tags = "1, 2, 3, 4, 10, 11, 14, 15, 99"
+        tags = "1, 2, 3, 4, 10, 11, 14, 15, 17, 99"
     )]
     pub predicate: ::core::option::Option<entity_query::Predicate>,
 }
@@ -120,10 +131,18 @@ pub mod entity_query {
         Boolean(super::BooleanPredicate),
         #[prost(message, tag = "15")]
         QueryString(super::QueryStringPredicate),
+        #[prost(message, tag = "17")]
+        Similarity(super::SimilarityPredicate),
         #[prost(message, tag = "99")]
         Test(super::TestPredicate),
     }
 }
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct Highlighting {
+    /// Maximum number of characters of each snippet. Defaults to 150 if 0.
+    #[prost(uint32, tag = "1")]
+    pub max_chars: u32,
+}
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Projection {
     /// If specified, a prefix match will be done against traits' Protobuf full name (`some.package.Name`).
@@ -166,6 +185,22 @@ pub struct OperationsPredicate {
 /// Query all entities.
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
 pub struct AllPredicate {}
+/// Query entities that have a trait with an embedding vector (see `options.proto`.`embedding`)
+/// similar to the given vector. Matching traits are scored by their cosine similarity.
+///
+/// Embeddings aren't indexed in a nearest neighbour structure and are compared one by one,
+/// up to a maximum number of embeddings per query (see `MutationIndexConfig`). A query that
+/// would need to compare more embeddings fails instead of returning incomplete results.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SimilarityPredicate {
+    /// Vector to compare traits' embeddings against. Embeddings with a different number of
+    /// dimensions are ignored.
+    #[prost(float, repeated, tag = "1")]
+    pub vector: ::prost::alloc::vec::Vec<f32>,
+    /// Traits with a cosine similarity (from -1.0 to 1.0) lower or equal to this value don't match.
+    #[prost(float, tag = "2")]
+    pub min_similarity: f32,
+}
 /// Used for tests.
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
 pub struct TestPredicate {
@@ -184,7 +219,7 @@ pub mod boolean_predicate {
     pub struct SubQuery {
         #[prost(enumeration = "Occur", tag = "1")]
         pub occur: i32,
-        #[prost(oneof = "sub_query::Predicate", tags = "2, 3, 4, 5, 6, 7, 8")]
+        #[prost(oneof = "sub_query::Predicate", tags = "2, 3, 4, 5, 6, 7, 8, 9")]
         pub predicate: ::core::option::Option<sub_query::Predicate>,
     }
     /// Nested message and enum types in `SubQuery`.
@@ -205,6 +240,8 @@ pub mod boolean_predicate {
             All(super::super::AllPredicate),
             #[prost(message, tag = "8")]
             Boolean(super::super::BooleanPredicate),
+            #[prost(message, tag = "9")]
+            Similarity(super::super::SimilarityPredicate),
         }
     }
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
@@ -343,7 +380,7 @@ pub struct QueryStringPredicate {
     #[prost(string, tag = "1")]
     pub query: ::prost::alloc::string::String,
 }
-#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Paging {
     /// Returns results after this given ordering value.
     #[prost(message, optional, tag = "1")]
@@ -357,6 +394,12 @@ pub struct Paging {
     /// Mutation index use only, no effect on entity query.
     #[prost(uint32, tag = "4")]
     pub offset: u32,
+    /// Opaque cursor returned in `EntityResults.next_page` pointing after the last
+    /// returned entity. Since it is based on the ordering value of that entity, it stays
+    /// valid when the index changes. Takes precedence over `after_ordering_value`
+    /// and `before_ordering_value`.
+    #[prost(string, tag = "5")]
+    pub cursor: ::prost::alloc::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Ordering {
@@ -369,8 +412,14 @@ pub struct Ordering {
     /// If match score used, don't boost results that have references.
     #[prost(bool, tag = "6")]
     pub no_reference_boost: bool,
+    /// Orderings used to order results that have equal values for this ordering, in order
+    /// of precedence (ex: by `weight` descending, then by `updated_at` descending).
+    /// Ordering by score isn't supported, and these orderings can't have `then` orderings
+    /// themselves.
+    #[prost(message, repeated, tag = "9")]
+    pub then: ::prost::alloc::vec::Vec<Ordering>,
     /// Value by which we want results to be ordered.
-    #[prost(oneof = "ordering::Value", tags = "1, 2, 3, 7, 8")]
+    #[prost(oneof = "ordering::Value", tags = "1, 2, 3, 7, 8, 10")]
     pub value: ::core::option::Option<ordering::Value>,
 }
 /// Nested message and enum types in `Ordering`.
@@ -393,14 +442,29 @@ pub mod ordering {
         /// by update date
         #[prost(bool, tag = "8")]
         UpdatedAt(bool),
+        /// By offset of the chain block in which the mutation got committed, which is the
+        /// order in which mutations got indexed in the chain by the local node. Mutations
+        /// that aren't indexed in the chain yet have an offset of 0. Mutations out of the
+        /// paging's bounds are skipped, so that all changes after a given offset can be
+        /// paged through.
+        #[prost(bool, tag = "10")]
+        BlockOffset(bool),
     }
 }
-#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct OrderingValue {
     /// Secondary comparison, in case values were equal. In this case,
     /// the last operation id that mutated the entity is used.
     #[prost(uint64, tag = "6")]
     pub operation_id: u64,
+    /// Values of the `Ordering.then` orderings, compared in order if `value` is equal and before
+    /// comparing `operation_id`.
+    #[prost(message, repeated, tag = "7")]
+    pub then: ::prost::alloc::vec::Vec<OrderingValue>,
+    /// For values of `Ordering.then` orderings, indicates that the value is to be compared
+    /// in the opposite direction of the primary ordering.
+    #[prost(bool, tag = "8")]
+    pub reverse: bool,
     /// Primary comparison
     #[prost(oneof = "ordering_value::Value", tags = "1, 2, 3, 4, 5")]
     pub value: ::core::option::Option<ordering_value::Value>,
@@ -444,6 +508,13 @@ pub struct EntityResults {
     /// changed by using the `result_hash` field on the query.
     #[prost(uint64, tag = "6")]
     pub hash: u64,
+    /// Exact number of entities matching, if `count_total` was specified on the query.
+    #[prost(uint32, tag = "7")]
+    pub total_count: u32,
+    /// Set if no store node was reachable and the results were taken from the
+    /// client's cache of recent results, which may be outdated.
+    #[prost(bool, tag = "8")]
+    pub stale: bool,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct EntityResult {
@@ -468,6 +539,38 @@ pub struct EntityResult {
     /// Hash of the entity result. Can be used to compare if the entity has changed since last results.
     #[prost(uint64, tag = "4")]
     pub hash: u64,
+    /// Snippets of the traits' full-text fields that matched the query, if highlighting
+    /// was requested via `EntityQuery.highlighting`.
+    #[prost(message, repeated, tag = "5")]
+    pub snippets: ::prost::alloc::vec::Vec<Snippet>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Snippet {
+    /// Id of the trait from which the snippet was extracted.
+    #[prost(string, tag = "1")]
+    pub trait_id: ::prost::alloc::string::String,
+    /// Name of the field from which the snippet was extracted. Fields of sub-messages are
+    /// separated by a dot (ex: `field.sub_field`).
+    #[prost(string, tag = "2")]
+    pub field: ::prost::alloc::string::String,
+    /// Fragment of the field's text.
+    #[prost(string, tag = "3")]
+    pub fragment: ::prost::alloc::string::String,
+    /// Ranges of the fragment matching the query.
+    #[prost(message, repeated, tag = "4")]
+    pub highlights: ::prost::alloc::vec::Vec<snippet::Highlight>,
+}
+/// Nested message and enum types in `Snippet`.
+pub mod snippet {
+    #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+    pub struct Highlight {
+        /// Offset in characters of the start of the range in the fragment (inclusive).
+        #[prost(uint32, tag = "1")]
+        pub start: u32,
+        /// Offset in characters of the end of the range in the fragment (exclusive).
+        #[prost(uint32, tag = "2")]
+        pub end: u32,
+    }
 }
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
@@ -521,11 +624,40 @@ pub struct MutationResult {
     /// Mutated entities if requested.
     #[prost(message, repeated, tag = "2")]
     pub entities: ::prost::alloc::vec::Vec<Entity>,
+    /// Set if no store node was reachable and the mutation was queued by the
+    /// client, to be sent once a store node is reachable. Operation ids and
+    /// entities aren't set in that case.
+    #[prost(bool, tag = "3")]
+    pub queued: bool,
+}
+/// Mutations queued by a remote store client while no store node was reachable,
+/// persisted in the client's directory until they are sent to a store node.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MutationQueue {
+    #[prost(message, repeated, tag = "1")]
+    pub mutations: ::prost::alloc::vec::Vec<MutationRequest>,
+    /// Queued mutations that got refused by a store node once sent.
+    #[prost(message, repeated, tag = "2")]
+    pub refused: ::prost::alloc::vec::Vec<RefusedMutation>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RefusedMutation {
+    #[prost(message, optional, tag = "1")]
+    pub request: ::core::option::Option<MutationRequest>,
+    /// Error returned by the store node.
+    #[prost(string, tag = "2")]
+    pub error: ::prost::alloc::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct EntityMutation {
     #[prost(string, tag = "1")]
     pub entity_id: ::prost::alloc::string::String,
+    /// If set, identifies a mutation that may get written more than once (ex: a mutation queued
+    /// by a client while offline, and replayed to another store node because the response of
+    /// the first one got lost). Only the mutation with the lowest operation id is applied among
+    /// the mutations of the entity having the same key.
+    #[prost(uint64, tag = "8")]
+    pub idempotency_key: u64,
     #[prost(oneof = "entity_mutation::Mutation", tags = "2, 3, 4, 7, 99")]
     pub mutation: ::core::option::Option<entity_mutation::Mutation>,
 }
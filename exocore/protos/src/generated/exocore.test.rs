@@ -38,6 +38,12 @@ pub struct TestMessage {
     #[prost(map = "string, string", tag = "22")]
     pub map1:
         ::std::collections::HashMap<::prost::alloc::string::String, ::prost::alloc::string::String>,
+    #[prost(string, tag = "23")]
+    pub string4: ::prost::alloc::string::String,
+    #[prost(float, repeated, packed = "false", tag = "24")]
+    pub embedding1: ::prost::alloc::vec::Vec<f32>,
+    #[prost(float, repeated, packed = "false", tag = "25")]
+    pub embedding2: ::prost::alloc::vec::Vec<f32>,
     #[prost(oneof = "test_message::Fields", tags = "4, 5")]
     pub fields: ::core::option::Option<test_message::Fields>,
 }
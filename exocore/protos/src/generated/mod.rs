@@ -26,3 +26,4 @@ pub use self::exocore_test as test;
 
 pub const STORE_FDSET: &[u8] = include_bytes!("./exocore_store.fd");
 pub const TEST_FDSET: &[u8] = include_bytes!("./exocore_test.fd");
+pub const APPS_HOST_FDSET: &[u8] = include_bytes!("./exocore_apps_host.fd");
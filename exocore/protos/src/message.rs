@@ -1,4 +1,7 @@
-use super::test::{TestMessage, TestMessage2};
+use super::{
+    apps::AppState,
+    test::{TestMessage, TestMessage2},
+};
 
 pub trait NamedMessage {
     fn full_name() -> &'static str;
@@ -19,3 +22,9 @@ impl NamedMessage for TestMessage2 {
         "exocore.test.TestMessage2"
     }
 }
+
+impl NamedMessage for AppState {
+    fn full_name() -> &'static str {
+        "exocore.apps.AppState"
+    }
+}
@@ -2,7 +2,10 @@ use std::{collections::HashMap, convert::TryFrom, fmt::Debug, sync::Arc};
 
 pub use protobuf::{descriptor::FileDescriptorSet, Message};
 use protobuf::{
-    reflect::{FieldDescriptor as FieldDescriptorProto, ReflectFieldRef, ReflectValueRef},
+    reflect::{
+        FieldDescriptor as FieldDescriptorProto, MessageDescriptor, ReflectFieldRef,
+        ReflectValueBox, ReflectValueRef, RuntimeFieldType, RuntimeType,
+    },
     well_known_types::any::Any,
     MessageDyn,
 };
@@ -81,6 +84,9 @@ fn field_value_to_json(value: FieldValue, registry: &Registry) -> Result<serde_j
         FieldValue::Uint32(v) => Value::Number(v.into()),
         FieldValue::Int64(v) => Value::Number(v.into()),
         FieldValue::Uint64(v) => Value::Number(v.into()),
+        FieldValue::Float(v) => serde_json::Number::from_f64(v.into())
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
         FieldValue::Reference(reference) => {
             let mut obj = serde_json::Map::new();
             obj.insert("entity_id".to_string(), Value::String(reference.entity_id));
@@ -186,6 +192,10 @@ fn convert_field_value(
             ReflectValueRef::U64(v) => Ok(FieldValue::Uint64(v)),
             v => Err(Error::Other(anyhow!("expected uint64 field, got: {v:?}"))),
         },
+        FieldType::Float => match value {
+            ReflectValueRef::F32(v) => Ok(FieldValue::Float(v)),
+            v => Err(Error::Other(anyhow!("expected float field, got: {v:?}"))),
+        },
         FieldType::DateTime => match value {
             ReflectValueRef::Message(msg) => {
                 let msg_desc = msg.descriptor_dyn();
@@ -305,6 +315,8 @@ pub struct FieldDescriptor {
     pub indexed_flag: bool,
     pub sorted_flag: bool,
     pub text_flag: bool,
+    pub text_analyzer: Option<String>,
+    pub embedding_flag: bool,
     pub groups: Vec<FieldGroupId>,
 }
 
@@ -315,6 +327,7 @@ pub enum FieldType {
     Uint32,
     Int64,
     Uint64,
+    Float,
     DateTime,
     Reference,
     Message(String),
@@ -328,6 +341,7 @@ pub enum FieldValue {
     Uint32(u32),
     Int64(i64),
     Uint64(u64),
+    Float(f32),
     Reference(Reference),
     DateTime(chrono::DateTime<chrono::Utc>),
     Message(String, Box<dyn MessageDyn>),
@@ -410,6 +424,205 @@ pub fn from_any_url_and_data(
     })
 }
 
+/// Decodes a message from its JSON representation, as encoded by
+/// `ReflectMessage::encode_json`. The type of the message is resolved from the
+/// `type` (or `@type`) key of the JSON object against the registry.
+pub fn from_json(registry: &Registry, json: &serde_json::Value) -> Result<DynamicMessage, Error> {
+    let (full_name, values) = json_message_parts(json)?;
+
+    let descriptor = registry.get_message_descriptor(&full_name)?;
+    let mut message = descriptor.message.new_instance();
+    json_to_message(registry, &descriptor, message.as_mut(), values)?;
+
+    Ok(DynamicMessage {
+        message,
+        descriptor,
+    })
+}
+
+type JsonObject = serde_json::Map<String, serde_json::Value>;
+
+fn json_message_parts(json: &serde_json::Value) -> Result<(String, Option<&JsonObject>), Error> {
+    let obj = json
+        .as_object()
+        .ok_or_else(|| Error::Other(anyhow!("expected message to be a JSON object")))?;
+
+    let typ = obj
+        .get("type")
+        .or_else(|| obj.get("@type"))
+        .and_then(|typ| typ.as_str())
+        .ok_or_else(|| Error::Other(anyhow!("expected message to have a `type` key")))?;
+
+    let values = match obj.get("value") {
+        Some(value) => Some(
+            value
+                .as_object()
+                .ok_or_else(|| Error::Other(anyhow!("expected message value to be an object")))?,
+        ),
+        None => None,
+    };
+
+    Ok((any_url_to_full_name(typ), values))
+}
+
+fn json_to_message(
+    registry: &Registry,
+    descriptor: &ReflectMessageDescriptor,
+    message: &mut dyn MessageDyn,
+    values: Option<&JsonObject>,
+) -> Result<(), Error> {
+    // fields are set using the message's own descriptor since the registry's
+    // descriptor may come from another file descriptor instance
+    let message_descriptor = message.descriptor_dyn();
+
+    for (name, value) in values.into_iter().flatten() {
+        let field = descriptor
+            .fields
+            .values()
+            .find(|field| field.name == *name)
+            .ok_or_else(|| Error::Other(anyhow!("no field named `{name}`")))?;
+        let field_proto = message_descriptor
+            .field_by_number(field.id)
+            .ok_or(Error::NoSuchField(field.id))?;
+
+        if field_proto.is_map() {
+            return Err(Error::NotSupported);
+        }
+
+        if let FieldType::Repeated(inner_type) = &field.field_type {
+            let json_values = value
+                .as_array()
+                .ok_or_else(|| Error::Other(anyhow!("expected array value for field `{name}`")))?;
+            let mut repeated = field_proto.mut_repeated(message);
+            for json_value in json_values {
+                repeated.push(json_to_field_value(
+                    registry,
+                    inner_type,
+                    &field_proto,
+                    json_value,
+                )?);
+            }
+        } else {
+            let field_value =
+                json_to_field_value(registry, &field.field_type, &field_proto, value)?;
+            field_proto.set_singular_field(message, field_value);
+        }
+    }
+
+    Ok(())
+}
+
+fn json_to_field_value(
+    registry: &Registry,
+    field_type: &FieldType,
+    field_proto: &FieldDescriptorProto,
+    value: &serde_json::Value,
+) -> Result<ReflectValueBox, Error> {
+    let invalid_value = || {
+        Error::Other(anyhow!(
+            "invalid value for {:?} field `{}`: {}",
+            field_type,
+            field_proto.name(),
+            value
+        ))
+    };
+
+    Ok(match field_type {
+        FieldType::String => {
+            ReflectValueBox::String(value.as_str().ok_or_else(invalid_value)?.to_string())
+        }
+        FieldType::Int32 => {
+            let value = value.as_i64().ok_or_else(invalid_value)?;
+            ReflectValueBox::I32(i32::try_from(value).map_err(|_| invalid_value())?)
+        }
+        FieldType::Uint32 => {
+            let value = value.as_u64().ok_or_else(invalid_value)?;
+            ReflectValueBox::U32(u32::try_from(value).map_err(|_| invalid_value())?)
+        }
+        FieldType::Int64 => ReflectValueBox::I64(value.as_i64().ok_or_else(invalid_value)?),
+        FieldType::Uint64 => ReflectValueBox::U64(value.as_u64().ok_or_else(invalid_value)?),
+        FieldType::Float => ReflectValueBox::F32(value.as_f64().ok_or_else(invalid_value)? as f32),
+        FieldType::DateTime => {
+            let date = value.as_str().ok_or_else(invalid_value)?;
+            let date = chrono::DateTime::parse_from_rfc3339(date).map_err(|_| invalid_value())?;
+
+            let mut message = field_message_descriptor(field_proto)?.new_instance();
+            set_message_field(message.as_mut(), 1, ReflectValueBox::I64(date.timestamp()))?;
+            set_message_field(
+                message.as_mut(),
+                2,
+                ReflectValueBox::I32(date.timestamp_subsec_nanos() as i32),
+            )?;
+            ReflectValueBox::Message(message)
+        }
+        FieldType::Reference => {
+            let obj = value.as_object().ok_or_else(invalid_value)?;
+            let get_str = |key: &str| -> Result<String, Error> {
+                match obj.get(key) {
+                    Some(value) => Ok(value.as_str().ok_or_else(invalid_value)?.to_string()),
+                    None => Ok(String::new()),
+                }
+            };
+
+            let mut message = field_message_descriptor(field_proto)?.new_instance();
+            set_message_field(
+                message.as_mut(),
+                1,
+                ReflectValueBox::String(get_str("entity_id")?),
+            )?;
+            set_message_field(
+                message.as_mut(),
+                2,
+                ReflectValueBox::String(get_str("trait_id")?),
+            )?;
+            ReflectValueBox::Message(message)
+        }
+        FieldType::Message(msg_type) => {
+            // sub-messages are encoded with their type, but we also accept their fields
+            // directly since the type is known from the field
+            let values = if value.get("value").is_some() {
+                json_message_parts(value)?.1
+            } else {
+                Some(value.as_object().ok_or_else(invalid_value)?)
+            };
+
+            let descriptor = registry.get_message_descriptor(msg_type)?;
+            let mut message = field_message_descriptor(field_proto)?.new_instance();
+            json_to_message(registry, &descriptor, message.as_mut(), values)?;
+            ReflectValueBox::Message(message)
+        }
+        FieldType::Repeated(_) => {
+            return Err(Error::Other(anyhow!(
+                "nested repeated field `{}` not supported",
+                field_proto.name()
+            )));
+        }
+    })
+}
+
+fn field_message_descriptor(
+    field_proto: &FieldDescriptorProto,
+) -> Result<MessageDescriptor, Error> {
+    match field_proto.runtime_field_type() {
+        RuntimeFieldType::Singular(RuntimeType::Message(descriptor))
+        | RuntimeFieldType::Repeated(RuntimeType::Message(descriptor)) => Ok(descriptor),
+        _ => Err(Error::InvalidFieldType),
+    }
+}
+
+fn set_message_field(
+    message: &mut dyn MessageDyn,
+    field_number: u32,
+    value: ReflectValueBox,
+) -> Result<(), Error> {
+    let field = message
+        .descriptor_dyn()
+        .field_by_number(field_number)
+        .ok_or(Error::NoSuchField(field_number))?;
+    field.set_singular_field(message, value);
+    Ok(())
+}
+
 pub fn any_url_to_full_name(url: &str) -> String {
     url.replace("type.googleapis.com/", "")
 }
@@ -449,6 +662,7 @@ mod tests {
                 string1: "str1".to_string(),
             }),
             map1,
+            embedding1: vec![0.5, 1.0],
             ..Default::default()
         };
 
@@ -487,6 +701,18 @@ mod tests {
         let dyn_struct = dyn_msg.get_field_value(3)?.into_message(&registry)?;
         assert_eq!(dyn_struct.get_field_value(1)?.as_str()?, "str1");
 
+        let field24 = dyn_msg.get_field(24).unwrap();
+        assert_eq!(
+            field24.field_type,
+            FieldType::Repeated(Box::new(FieldType::Float))
+        );
+        let FieldValue::Repeated(values) = dyn_msg.get_field_value(24)? else {
+            panic!("expected repeated field value");
+        };
+        assert!(
+            matches!(values.as_slice(), [FieldValue::Float(a), FieldValue::Float(b)] if *a == 0.5 && *b == 1.0)
+        );
+
         // TODO: Maps not supported yet
         // let field22 = dyn_msg.get_field(22).unwrap();
         // assert_eq!(
@@ -559,6 +785,7 @@ mod tests {
             struct1: Some(TestStruct {
                 string1: "str1".to_string(),
             }),
+            embedding1: vec![0.5, 1.0],
             ..Default::default()
         };
 
@@ -582,6 +809,8 @@ mod tests {
                         "string1": "str1"
                     },
                 },
+                "embedding1": [0.5, 1.0],
+                "embedding2": [],
             }
         });
 
@@ -589,4 +818,68 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn dyn_message_decode_json() -> anyhow::Result<()> {
+        let registry = Registry::new_with_exocore_types();
+
+        let date = "2022-02-25T02:11:27.793936+00:00";
+        let date = chrono::DateTime::parse_from_rfc3339(date)?;
+        let msg = TestMessage {
+            string1: "val1".to_string(),
+            int1: 1,
+            date1: Some(date.to_proto_timestamp()),
+            ref1: Some(Reference {
+                entity_id: "et1".to_string(),
+                trait_id: "trt1".to_string(),
+            }),
+            struct1: Some(TestStruct {
+                string1: "str1".to_string(),
+            }),
+            embedding1: vec![0.5, 1.0],
+            ..Default::default()
+        };
+
+        // encoded json can be decoded back
+        let msg_any = msg.pack_to_stepan_any()?;
+        let json = from_stepan_any(&registry, &msg_any)?.encode_json(&registry)?;
+        let dyn_msg = from_json(&registry, &json)?;
+        assert_eq!(
+            <TestMessage as prost::Message>::decode(dyn_msg.encode()?.as_slice())?,
+            msg
+        );
+
+        // sub-messages type can be omitted, and `@type` can be used
+        let json = serde_json::json!({
+            "@type": "exocore.test.TestMessage",
+            "value": {
+                "string1": "val1",
+                "struct1": {
+                    "string1": "str1"
+                },
+            }
+        });
+        let dyn_msg = from_json(&registry, &json)?;
+        let decoded = <TestMessage as prost::Message>::decode(dyn_msg.encode()?.as_slice())?;
+        assert_eq!(decoded.string1, "val1");
+        assert_eq!(decoded.struct1.unwrap().string1, "str1");
+
+        // invalid types or fields are rejected
+        let json = serde_json::json!({"type": "exocore.test.DoesntExist"});
+        assert!(from_json(&registry, &json).is_err());
+
+        let json = serde_json::json!({
+            "type": "exocore.test.TestMessage",
+            "value": {"doesnt_exist": "val1"}
+        });
+        assert!(from_json(&registry, &json).is_err());
+
+        let json = serde_json::json!({
+            "type": "exocore.test.TestMessage",
+            "value": {"string1": 1}
+        });
+        assert!(from_json(&registry, &json).is_err());
+
+        Ok(())
+    }
 }
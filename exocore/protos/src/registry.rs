@@ -41,6 +41,9 @@ impl Registry {
         reg.register_file_descriptor_set_bytes(super::generated::TEST_FDSET)
             .expect("Couldn't register exocore_test FileDescriptorProto");
 
+        reg.register_file_descriptor_set_bytes(super::generated::APPS_HOST_FDSET)
+            .expect("Couldn't register exocore_apps_host FileDescriptorProto");
+
         reg
     }
 
@@ -132,6 +135,7 @@ impl Registry {
                 Some(Ok(ProtoFieldType::TYPE_UINT32)) => FieldType::Uint32,
                 Some(Ok(ProtoFieldType::TYPE_INT64)) => FieldType::Int64,
                 Some(Ok(ProtoFieldType::TYPE_UINT64)) => FieldType::Uint64,
+                Some(Ok(ProtoFieldType::TYPE_FLOAT)) => FieldType::Float,
                 Some(Ok(ProtoFieldType::TYPE_MESSAGE)) => {
                     let typ = field_proto.type_name().trim_start_matches('.');
                     match typ {
@@ -164,6 +168,8 @@ impl Registry {
                         indexed_flag: Registry::field_has_option(field_proto, 1373),
                         sorted_flag: Registry::field_has_option(field_proto, 1374),
                         text_flag: Registry::field_has_option(field_proto, 1375),
+                        text_analyzer: Registry::get_field_string_option(field_proto, 1378),
+                        embedding_flag: Registry::field_has_option(field_proto, 1379),
                         groups: Registry::get_field_u32s_option(field_proto, 1376),
                     },
                 );
@@ -240,6 +246,19 @@ impl Registry {
         ret
     }
 
+    fn get_field_string_option(
+        field: &FieldDescriptorProto,
+        option_field_id: u32,
+    ) -> Option<String> {
+        if let Some(UnknownValueRef::LengthDelimited(bytes)) =
+            field.options.unknown_fields().get(option_field_id)
+        {
+            Some(String::from_utf8_lossy(bytes).to_string())
+        } else {
+            None
+        }
+    }
+
     fn get_message_strings_option(
         msg_desc: &protobuf::reflect::MessageDescriptor,
         option_field_id: u32,
@@ -297,6 +316,15 @@ mod tests {
         assert!(descriptor.fields.get(&18).unwrap().sorted_flag);
         assert!(!descriptor.fields.get(&11).unwrap().sorted_flag);
 
+        assert_eq!(descriptor.fields.get(&1).unwrap().text_analyzer, None);
+        assert_eq!(
+            descriptor.fields.get(&23).unwrap().text_analyzer.as_deref(),
+            Some("french")
+        );
+
+        assert!(descriptor.fields.get(&24).unwrap().embedding_flag);
+        assert!(!descriptor.fields.get(&1).unwrap().embedding_flag);
+
         assert!(descriptor.fields.get(&19).unwrap().groups.is_empty());
         assert_eq!(descriptor.fields.get(&20).unwrap().groups, vec![1]);
         assert_eq!(descriptor.fields.get(&21).unwrap().groups, vec![1, 2]);
@@ -5,6 +5,7 @@ use anyhow::anyhow;
 use log::info;
 
 use crate::{
+    apps::client::{Client as AppsClient, ClientHandle as AppsHandle},
     core::{cell::Cell, futures::spawn_future, time::Clock},
     store::remote::{Client as StoreClient, ClientHandle as StoreHandle},
     transport::{Libp2pTransport, ServiceType},
@@ -13,6 +14,7 @@ use crate::{
 #[derive(Clone)]
 pub struct Client {
     pub store: StoreHandle,
+    pub apps: AppsHandle,
 }
 
 impl Client {
@@ -27,20 +29,31 @@ impl Client {
         let either_cell = cells
             .first()
             .ok_or_else(|| anyhow!("Node doesn't have any cell configured"))?;
-        let cell = either_cell.cell();
 
+        Self::with_cell(local_node, either_cell.cell()).await
+    }
+
+    /// Creates a client connected to the store and applications of the given
+    /// cell, for nodes that are part of multiple cells.
+    pub async fn with_cell(local_node: LocalNode, cell: &Cell) -> anyhow::Result<Self> {
         let clock = Clock::new();
 
-        let mut transport = Libp2pTransport::new(local_node.clone(), Default::default());
+        let mut transport =
+            Libp2pTransport::new(local_node.clone(), Default::default(), clock.clone());
         let store_transport = transport.get_handle(cell.clone(), ServiceType::Store)?;
+        let apps_transport = transport.get_handle(cell.clone(), ServiceType::Client)?;
 
         spawn_future(async move {
             let res = transport.run().await;
             info!("Transport done: {:?}", res);
         });
 
-        let store_client =
-            StoreClient::new(Default::default(), cell.clone(), clock, store_transport)?;
+        let store_client = StoreClient::new(
+            Default::default(),
+            cell.clone(),
+            clock.clone(),
+            store_transport,
+        )?;
         let store_handle = store_client.get_handle();
 
         spawn_future(async move {
@@ -48,10 +61,20 @@ impl Client {
             info!("Remote client done: {:?}", res);
         });
 
+        let apps_client = AppsClient::new(Default::default(), cell.clone(), clock, apps_transport);
+        let apps_handle = apps_client.get_handle();
+
+        spawn_future(async move {
+            let res = apps_client.run().await;
+            info!("Apps client done: {:?}", res);
+        });
+
         store_handle.on_start().await;
+        apps_handle.on_start().await;
 
         Ok(Client {
             store: store_handle,
+            apps: apps_handle,
         })
     }
 }
@@ -1,3 +1,7 @@
+#[allow(unused_imports)]
+#[macro_use]
+extern crate log;
+
 #[cfg(feature = "exocore-chain")]
 pub extern crate exocore_chain as chain;
 #[cfg(feature = "exocore-core")]
@@ -9,10 +13,16 @@ pub extern crate exocore_store as store;
 #[cfg(feature = "exocore-transport")]
 pub extern crate exocore_transport as transport;
 
-#[cfg(feature = "exocore-apps-sdk")]
+#[cfg(any(feature = "exocore-apps-client", feature = "exocore-apps-sdk"))]
 pub mod apps {
+    #[cfg(feature = "exocore-apps-client")]
+    pub extern crate exocore_apps_client as client;
+    #[cfg(feature = "exocore-apps-sdk")]
     pub extern crate exocore_apps_sdk as sdk;
 }
 
 #[cfg(feature = "client")]
 pub mod client;
+
+#[cfg(feature = "tests-utils")]
+pub mod tests_utils;
@@ -0,0 +1,616 @@
+//! Harness simulating a cell of multiple full nodes (chain engine, local store,
+//! remote store server and applications host) and store clients, all
+//! communicating through the simulated network of a `MockTransport`.
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use exocore_apps_host::{
+    election::{AppsElection, ElectionConfig},
+    messaging::{ClientsMessaging, MessagingConfig},
+    runtime::Applications,
+    Config as ApplicationsConfig,
+};
+use exocore_chain::{
+    block::BlockBuilder, chain::ChainStore, CommitManagerConfig, DirectoryChainStore,
+    DirectoryChainStoreConfig, Engine, EngineConfig, EngineHandle, MemoryPendingStore,
+    PendingSyncConfig,
+};
+use exocore_core::{
+    cell::{CellNode, CellNodeRole, CellNodes, FullCell, LocalNode},
+    dir::os::OsDirectory,
+    futures::{interval, owned_spawn, sleep, OwnedSpawn},
+    time::{Clock, Instant},
+};
+use exocore_protos::{
+    prost::ProstAnyPackMessageExt,
+    store::{EntityQuery, EntityResults, MutationRequest, Trait},
+    test::TestMessage,
+};
+use exocore_store::{
+    local::{EntityIndex, EntityIndexConfig, Store as LocalStore, StoreConfig, StoreHandle},
+    mutation::MutationBuilder,
+    query::QueryBuilder,
+    remote::{Client, ClientConfiguration, ClientHandle, Server, ServerConfiguration},
+    store::Store,
+};
+use exocore_transport::{
+    testing::{MockTransport, NetworkConditions, NetworkStats},
+    transport::ConnectionStatus,
+    ServiceType,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use tempfile::TempDir;
+
+const WORKLOAD_ENTITIES: usize = 10;
+const WORKLOAD_TRAITS: usize = 3;
+const WORKLOAD_WORDS: [&str; 4] = ["rust", "cell", "node", "chain"];
+
+/// Duration by which the simulation's clock is moved forward at each tick of
+/// the runtime's time.
+const CLOCK_TICK: Duration = Duration::from_millis(10);
+
+/// Interval at which convergence is checked, and maximum number of checks
+/// before giving up.
+const CONVERGENCE_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+const CONVERGENCE_MAX_CHECKS: usize = 300;
+
+/// Configuration of a `CellSimulation`.
+#[derive(Clone, Debug)]
+pub struct CellSimulationConfig {
+    /// Number of full nodes, which all have the chain and store roles.
+    pub nodes_count: usize,
+
+    /// Number of store clients connected to the full nodes.
+    pub clients_count: usize,
+
+    /// Number of full nodes, starting from the first one, that also have the
+    /// applications host role.
+    pub app_hosts_count: usize,
+
+    /// Seed used for all random decisions of the simulated network and the
+    /// generated workloads.
+    pub seed: u64,
+
+    /// Initial conditions of the simulated network.
+    pub network: NetworkConditions,
+}
+
+impl Default for CellSimulationConfig {
+    fn default() -> Self {
+        CellSimulationConfig {
+            nodes_count: 3,
+            clients_count: 2,
+            app_hosts_count: 1,
+            seed: 0,
+            network: NetworkConditions::default(),
+        }
+    }
+}
+
+/// Simulated cell of full nodes and store clients.
+///
+/// All nodes share the same fixed mocked clock, which also drives the
+/// simulated network. The clock is only moved forward with the runtime's time,
+/// so that simulations are reproducible when run on a current-thread runtime
+/// with paused time (`#[tokio::test(start_paused = true)]`). Services are
+/// stopped when the simulation is dropped.
+pub struct CellSimulation {
+    pub config: CellSimulationConfig,
+    pub clock: Clock,
+    pub transport_hub: MockTransport,
+    pub nodes: Vec<SimulatedNode>,
+    pub clients: Vec<SimulatedClient>,
+    _clock_driver: OwnedSpawn<()>,
+    _temp_dir: TempDir,
+}
+
+/// Full node of a simulated cell.
+pub struct SimulatedNode {
+    pub cell: FullCell,
+    pub chain: EngineHandle<DirectoryChainStore, MemoryPendingStore>,
+    pub store: StoreHandle<DirectoryChainStore, MemoryPendingStore>,
+    _services: Vec<OwnedSpawn<()>>,
+}
+
+/// Store client of a simulated cell.
+pub struct SimulatedClient {
+    pub cell: FullCell,
+    pub store: ClientHandle,
+    _client: OwnedSpawn<()>,
+}
+
+impl CellSimulation {
+    /// Creates the nodes and clients of the cell, and starts all their
+    /// services.
+    pub async fn start(config: CellSimulationConfig) -> anyhow::Result<CellSimulation> {
+        let temp_dir = tempfile::tempdir()?;
+        let clock = Clock::new_fixed_mocked(Instant::now());
+        let clock_driver = {
+            let clock = clock.clone();
+            owned_spawn(async move {
+                let mut interval = interval(CLOCK_TICK);
+                loop {
+                    interval.tick().await;
+                    clock.add_fixed_instant_duration(CLOCK_TICK);
+                }
+            })
+        };
+
+        let transport_hub = MockTransport::new_simulated(clock.clone(), config.seed);
+        transport_hub.set_network_conditions(config.network.clone());
+
+        let mut local_nodes = Vec::new();
+        for node_idx in 0..config.nodes_count {
+            let node_dir = OsDirectory::new(temp_dir.path().join(format!("node{}", node_idx)));
+            local_nodes.push(LocalNode::generate_in_directory(node_dir)?);
+        }
+        let client_nodes = (0..config.clients_count)
+            .map(|_| LocalNode::generate())
+            .collect::<Vec<_>>();
+
+        let first_node = local_nodes
+            .first()
+            .ok_or_else(|| anyhow!("Simulation needs at least one node"))?;
+        let first_cell = FullCell::generate(first_node.clone())?;
+        let cells = local_nodes
+            .iter()
+            .chain(client_nodes.iter())
+            .map(|local_node| first_cell.clone().with_local_node(local_node.clone()))
+            .collect::<Vec<_>>();
+
+        // add all nodes and clients to each other's cell
+        for cell in &cells {
+            let mut cell_nodes = cell.cell().nodes_mut();
+            for (node_idx, local_node) in local_nodes.iter().chain(client_nodes.iter()).enumerate()
+            {
+                let mut roles = Vec::new();
+                if node_idx < config.nodes_count {
+                    roles.push(CellNodeRole::Chain);
+                    roles.push(CellNodeRole::Store);
+                }
+                if node_idx < config.app_hosts_count.min(config.nodes_count) {
+                    roles.push(CellNodeRole::AppHost);
+                }
+
+                if cell.cell().local_node().id() == local_node.id() {
+                    let local_cell_node = cell_nodes.local_cell_node_mut();
+                    for role in roles {
+                        local_cell_node.add_role(role);
+                    }
+                } else {
+                    let mut cell_node = CellNode::new(local_node.node().clone());
+                    for role in roles {
+                        cell_node.add_role(role);
+                    }
+                    cell_nodes.add_cell_node(cell_node);
+                }
+            }
+        }
+
+        let mut nodes = Vec::new();
+        for (node_idx, cell) in cells.iter().take(config.nodes_count).enumerate() {
+            let node =
+                SimulatedNode::start(cell.clone(), &clock, &transport_hub, node_idx == 0).await?;
+            nodes.push(node);
+        }
+
+        let mut clients = Vec::new();
+        for cell in cells.iter().skip(config.nodes_count) {
+            clients.push(SimulatedClient::start(cell.clone(), &clock, &transport_hub).await?);
+        }
+
+        // store clients only send requests to nodes they know are connected
+        for node in &nodes {
+            transport_hub.notify_node_connection_status(
+                node.cell.cell().local_node().id(),
+                ConnectionStatus::Connected,
+            );
+        }
+
+        Ok(CellSimulation {
+            config,
+            clock,
+            transport_hub,
+            nodes,
+            clients,
+            _clock_driver: clock_driver,
+            _temp_dir: temp_dir,
+        })
+    }
+
+    /// Executes the steps of the workload in order, waiting for each of them
+    /// to complete before executing the next one.
+    ///
+    /// Failed steps don't interrupt the workload since they are expected on an
+    /// unreliable network. They are counted in the returned report.
+    pub async fn run_workload(&self, workload: &Workload) -> WorkloadReport {
+        let mut report = WorkloadReport::default();
+        for step in &workload.steps {
+            match step {
+                WorkloadStep::Mutation { client, request } => {
+                    let client = &self.clients[*client % self.clients.len()];
+                    match client.store.mutate(request.clone()).await {
+                        Ok(_) => report.mutations_succeeded += 1,
+                        Err(err) => {
+                            debug!("Workload mutation failed: {}", err);
+                            report.mutations_failed += 1;
+                        }
+                    }
+                }
+                WorkloadStep::Query { client, query } => {
+                    let client = &self.clients[*client % self.clients.len()];
+                    match client.store.query(query.as_ref().clone()).await {
+                        Ok(_) => report.queries_succeeded += 1,
+                        Err(err) => {
+                            debug!("Workload query failed: {}", err);
+                            report.queries_failed += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Waits for all nodes to have converged: same last chain block, and same
+    /// results for the given queries on all store nodes. Fails if nodes didn't
+    /// converge within 30 seconds of simulated time.
+    pub async fn wait_converged(&self, queries: &[EntityQuery]) -> anyhow::Result<()> {
+        let mut checks = 0;
+        loop {
+            match self.check_converged(queries).await {
+                Ok(()) => return Ok(()),
+                Err(err) if checks >= CONVERGENCE_MAX_CHECKS => {
+                    return Err(err.context("Nodes didn't converge"));
+                }
+                Err(_) => {
+                    checks += 1;
+                    sleep(CONVERGENCE_CHECK_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    /// Checks if all nodes have converged. See `wait_converged`.
+    pub async fn check_converged(&self, queries: &[EntityQuery]) -> anyhow::Result<()> {
+        let first_node = &self.nodes[0];
+        let first_block = first_node.chain.get_chain_last_block_info()?;
+        for (node_idx, node) in self.nodes.iter().enumerate().skip(1) {
+            let block = node.chain.get_chain_last_block_info()?;
+            if block != first_block {
+                return Err(anyhow!(
+                    "Node {} last block {:?} is different than node 0 last block {:?}",
+                    node_idx,
+                    block,
+                    first_block,
+                ));
+            }
+        }
+
+        for query in queries {
+            let first_results = results_summary(&first_node.store.query(query.clone()).await?);
+            for (node_idx, node) in self.nodes.iter().enumerate().skip(1) {
+                let results = results_summary(&node.store.query(query.clone()).await?);
+                if results != first_results {
+                    return Err(anyhow!(
+                        "Node {} results {:?} are different than node 0 results {:?} for query {:?}",
+                        node_idx,
+                        results,
+                        first_results,
+                        query,
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn network_stats(&self) -> NetworkStats {
+        self.transport_hub.network_stats()
+    }
+}
+
+impl SimulatedNode {
+    async fn start(
+        cell: FullCell,
+        clock: &Clock,
+        transport_hub: &MockTransport,
+        genesis: bool,
+    ) -> anyhow::Result<SimulatedNode> {
+        let mut services = Vec::new();
+        let local_node = cell.cell().local_node().clone();
+
+        let chain_dir = cell.cell().chain_directory().as_os_path()?;
+        std::fs::create_dir_all(&chain_dir)?;
+        let mut chain_store =
+            DirectoryChainStore::create(DirectoryChainStoreConfig::default(), &chain_dir)?;
+        if genesis {
+            chain_store.write_block(&BlockBuilder::build_genesis(&cell)?)?;
+        }
+
+        let mut engine = Engine::new(
+            engine_config(),
+            clock.clone(),
+            transport_hub.get_transport(local_node.clone(), ServiceType::Chain),
+            chain_store,
+            MemoryPendingStore::new(),
+            cell.cell().clone(),
+        );
+        let chain = engine.get_handle();
+        let store_chain = engine.get_handle();
+        services.push(owned_spawn(async move {
+            let res = engine.run().await;
+            info!("Engine is done: {:?}", res);
+        }));
+
+        let index = EntityIndex::open_or_create(
+            cell.clone(),
+            index_config(),
+            chain.clone(),
+            clock.clone(),
+        )?;
+        let local_store = LocalStore::new(
+            store_config(),
+            cell.cell().clone(),
+            clock.clone(),
+            store_chain,
+            index,
+        )?;
+        let store = local_store.get_handle();
+        services.push(owned_spawn(async move {
+            let res = local_store.run().await;
+            info!("Local store is done: {:?}", res);
+        }));
+        store.on_start().await;
+
+        let server = Server::new(
+            ServerConfiguration::default(),
+            cell.cell().clone(),
+            store.clone(),
+            transport_hub.get_transport(local_node.clone(), ServiceType::Store),
+        )?;
+        services.push(owned_spawn(async move {
+            let res = server.run().await;
+            info!("Remote store server is done: {:?}", res);
+        }));
+
+        if cell.cell().local_node_has_role(CellNodeRole::AppHost) {
+            let apps = Applications::new(
+                ApplicationsConfig::default(),
+                clock.clone(),
+                cell.cell().clone(),
+                store.clone(),
+            )
+            .await?;
+
+            let app_host_count = cell.cell().nodes().count_with_role(CellNodeRole::AppHost);
+            let apps = if app_host_count > 1 {
+                let election = AppsElection::new(
+                    ElectionConfig::default(),
+                    clock.clone(),
+                    cell.cell().clone(),
+                    transport_hub.get_transport(local_node.clone(), ServiceType::AppHost),
+                );
+                let apps = apps.with_election(election.handle());
+                services.push(owned_spawn(async move {
+                    let res = election.run().await;
+                    info!("Applications hosts election is done: {:?}", res);
+                }));
+                apps
+            } else {
+                apps
+            };
+
+            let messaging = ClientsMessaging::new(
+                MessagingConfig::default(),
+                cell.cell().clone(),
+                transport_hub.get_transport(local_node, ServiceType::Client),
+            );
+            let apps = apps.with_messaging(messaging.handle());
+            services.push(owned_spawn(async move {
+                let res = messaging.run().await;
+                info!("Applications clients messaging is done: {:?}", res);
+            }));
+
+            services.push(owned_spawn(async move {
+                let res = apps.run().await;
+                info!("Applications host is done: {:?}", res);
+            }));
+        }
+
+        chain.on_started().await;
+
+        Ok(SimulatedNode {
+            cell,
+            chain,
+            store,
+            _services: services,
+        })
+    }
+}
+
+impl SimulatedClient {
+    async fn start(
+        cell: FullCell,
+        clock: &Clock,
+        transport_hub: &MockTransport,
+    ) -> anyhow::Result<SimulatedClient> {
+        let client = Client::new(
+            ClientConfiguration::default(),
+            cell.cell().clone(),
+            clock.clone(),
+            transport_hub.get_transport(cell.cell().local_node().clone(), ServiceType::Store),
+        )?;
+        let store = client.get_handle();
+        let client = owned_spawn(async move {
+            let res = client.run().await;
+            info!("Store client is done: {:?}", res);
+        });
+        store.on_start().await;
+
+        Ok(SimulatedClient {
+            cell,
+            store,
+            _client: client,
+        })
+    }
+}
+
+/// Scripted sequence of mutations and queries executed by the clients of a
+/// simulation.
+#[derive(Clone, Debug, Default)]
+pub struct Workload {
+    pub steps: Vec<WorkloadStep>,
+}
+
+#[derive(Clone, Debug)]
+pub enum WorkloadStep {
+    Mutation {
+        client: usize,
+        request: MutationRequest,
+    },
+    Query {
+        client: usize,
+        query: Box<EntityQuery>,
+    },
+}
+
+impl Workload {
+    /// Generates a workload of random trait puts, entity deletions and queries
+    /// on a small set of entities, so that mutations often conflict. The same
+    /// seed always generates the same workload.
+    pub fn generate(seed: u64, clients_count: usize, steps_count: usize) -> Workload {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let steps = (0..steps_count)
+            .map(|step_idx| {
+                let client = rng.gen_range(0..clients_count.max(1));
+                let entity_id = format!("entity{}", rng.gen_range(0..WORKLOAD_ENTITIES));
+                let word = WORKLOAD_WORDS.choose(&mut rng).unwrap();
+
+                match rng.gen_range(0..10) {
+                    0..=5 => {
+                        let trt = Trait {
+                            id: format!("trait{}", rng.gen_range(0..WORKLOAD_TRAITS)),
+                            message: Some(
+                                TestMessage {
+                                    string1: format!("{} {}", word, step_idx),
+                                    ..Default::default()
+                                }
+                                .pack_to_any()
+                                .unwrap(),
+                            ),
+                            ..Default::default()
+                        };
+                        let request = MutationBuilder::new().put_trait(entity_id, trt).build();
+                        WorkloadStep::Mutation { client, request }
+                    }
+                    6 => {
+                        let request = MutationBuilder::new().delete_entity(entity_id).build();
+                        WorkloadStep::Mutation { client, request }
+                    }
+                    _ => {
+                        let query = Box::new(QueryBuilder::matches(*word).build());
+                        WorkloadStep::Query { client, query }
+                    }
+                }
+            })
+            .collect();
+
+        Workload { steps }
+    }
+
+    /// Queries that should return the same results on all store nodes once
+    /// they converged after executing the workload.
+    pub fn convergence_queries() -> Vec<EntityQuery> {
+        let mut queries = vec![QueryBuilder::all().count(1000).build()];
+        for word in WORKLOAD_WORDS {
+            queries.push(QueryBuilder::matches(word).count(1000).build());
+        }
+        queries
+    }
+}
+
+/// Outcome of the steps of a workload.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WorkloadReport {
+    pub mutations_succeeded: usize,
+    pub mutations_failed: usize,
+    pub queries_succeeded: usize,
+    pub queries_failed: usize,
+}
+
+fn engine_config() -> EngineConfig {
+    EngineConfig {
+        manager_timer_interval: Duration::from_millis(20),
+        pending_sync_config: PendingSyncConfig {
+            request_tracker_config: exocore_chain::engine::RequestTrackerConfig {
+                min_interval: Duration::from_millis(200),
+                max_interval: Duration::from_millis(1000),
+            },
+            ..PendingSyncConfig::default()
+        },
+        commit_manager_config: CommitManagerConfig {
+            commit_maximum_interval: Duration::from_millis(100),
+            block_proposal_timeout: Duration::from_millis(333),
+            ..CommitManagerConfig::default()
+        },
+        ..EngineConfig::default()
+    }
+}
+
+/// Chain blocks are indexed as soon as they are committed, since deferred
+/// indexation would be postponed indefinitely by convergence checks queries.
+fn store_config() -> StoreConfig {
+    StoreConfig {
+        chain_index_deferred_interval: None,
+        ..StoreConfig::default()
+    }
+}
+
+fn index_config() -> EntityIndexConfig {
+    EntityIndexConfig {
+        chain_index_in_memory: true,
+        chain_index_depth_leeway: 0,
+        ..EntityIndexConfig::default()
+    }
+}
+
+/// Entities and the id and content of their traits returned by a query,
+/// independently of their order and scores since those may differ between
+/// nodes depending on which mutations are still pending.
+fn results_summary(results: &EntityResults) -> Vec<(String, Vec<TraitSummary>)> {
+    let mut summary = results
+        .entities
+        .iter()
+        .filter_map(|result| result.entity.as_ref())
+        .map(|entity| {
+            let mut traits = entity
+                .traits
+                .iter()
+                .map(|trt| {
+                    let message = trt.message.as_ref();
+                    TraitSummary {
+                        id: trt.id.clone(),
+                        type_url: message.map(|msg| msg.type_url.clone()).unwrap_or_default(),
+                        value: message.map(|msg| msg.value.clone()).unwrap_or_default(),
+                    }
+                })
+                .collect::<Vec<_>>();
+            traits.sort();
+            (entity.id.clone(), traits)
+        })
+        .collect::<Vec<_>>();
+    summary.sort();
+    summary
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct TraitSummary {
+    id: String,
+    type_url: String,
+    value: Vec<u8>,
+}
@@ -59,6 +59,9 @@ pub enum Error {
     #[error("Not connected to any store node")]
     NotConnected,
 
+    #[error("No store node is reachable and query results weren't cached")]
+    Offline,
+
     #[error("Try to lock a mutex that was poisoned")]
     Poisoned,
 
@@ -25,6 +25,10 @@ const GC_INACTIVE_OPS_THRESHOLD: usize = 10;
 /// Operations are merged in order they got committed to the chain, and then
 /// operation id within a block. If an old operation gets committed after a
 /// newer operation, the old operation gets discarded to prevent inconsistency.
+///
+/// Among mutations having the same idempotency key (ex: a mutation replayed by
+/// an offline client to more than one node), only the one with the lowest
+/// operation id is merged.
 pub struct EntityAggregator {
     pub entity_id: String,
 
@@ -92,6 +96,17 @@ impl EntityAggregator {
         let mut pending_deletion = false;
         let mut mutation_count = 0;
 
+        let sorted_mutations = sorted_mutations.collect_vec();
+        let mut idempotent_operations = HashMap::<u64, OperationId>::new();
+        for mutation in &sorted_mutations {
+            if let Some(key) = mutation.idempotency_key {
+                let operation_id = idempotent_operations
+                    .entry(key)
+                    .or_insert(mutation.operation_id);
+                *operation_id = (*operation_id).min(mutation.operation_id);
+            }
+        }
+
         for current_mutation in sorted_mutations {
             assert_ne!(
                 Some(current_mutation.operation_id),
@@ -114,6 +129,12 @@ impl EntityAggregator {
 
             entity_id.clone_from(&current_mutation.entity_id);
 
+            if let Some(key) = current_mutation.idempotency_key {
+                if idempotent_operations.get(&key) != Some(&current_operation_id) {
+                    continue;
+                }
+            }
+
             match &current_mutation.mutation_type {
                 MutationType::TraitPut(put_trait) => {
                     let agg = TraitAggregator::get_for_trait(&mut traits, &put_trait.trait_id);
@@ -534,6 +555,34 @@ pub(crate) mod tests {
         assert!(em.active_operations.contains(&1));
     }
 
+    #[test]
+    fn idempotent_mutations() {
+        let t1 = "t1".to_string();
+
+        // a mutation got written twice by different nodes, then overridden
+        let with_key = |mut mutation: MutationMetadata| {
+            mutation.idempotency_key = Some(42);
+            mutation
+        };
+        let mutations = vec![
+            with_key(mock_put_trait(&t1, TYPE1, Some(1), 2, None, None)),
+            with_key(mock_put_trait(&t1, TYPE1, Some(2), 1, None, None)),
+            mock_put_trait(&t1, TYPE1, Some(2), 3, None, None),
+        ];
+
+        // only the copy with the lowest operation id is merged
+        let em = EntityAggregator::new(mutations.clone().into_iter().take(2));
+        assert_eq!(em.traits.get(&t1).unwrap().last_operation_id, Some(1));
+        assert_eq!(em.active_operations.len(), 1);
+        assert!(em.active_operations.contains(&1));
+        assert_eq!(em.mutation_count, 2);
+
+        let em = EntityAggregator::new(mutations.into_iter());
+        assert_eq!(em.traits.get(&t1).unwrap().last_operation_id, Some(3));
+        assert_eq!(em.active_operations.len(), 1);
+        assert!(em.active_operations.contains(&3));
+    }
+
     #[test]
     fn trait_dates() {
         let t1 = "t1".to_string();
@@ -906,6 +955,7 @@ pub(crate) mod tests {
             operation_id,
             block_offset,
             entity_id: String::new(),
+            idempotency_key: None,
             mutation_type: MutationType::TraitPut(PutTraitMetadata {
                 trait_id: trait_id.into(),
                 trait_type: Some(trait_type.into()),
@@ -930,6 +980,7 @@ pub(crate) mod tests {
             operation_id,
             block_offset,
             entity_id: String::new(),
+            idempotency_key: None,
             mutation_type: MutationType::TraitTombstone(trait_id.into()),
             sort_value: OrderingValueWrapper {
                 value: OrderingValue::default(),
@@ -947,6 +998,7 @@ pub(crate) mod tests {
             operation_id,
             block_offset,
             entity_id: String::new(),
+            idempotency_key: None,
             mutation_type: MutationType::EntityTombstone,
             sort_value: OrderingValueWrapper {
                 value: OrderingValue::default(),
@@ -964,6 +1016,7 @@ pub(crate) mod tests {
             operation_id,
             block_offset,
             entity_id: String::new(),
+            idempotency_key: None,
             mutation_type: MutationType::PendingDeletion,
             sort_value: OrderingValueWrapper {
                 value: OrderingValue::default(),
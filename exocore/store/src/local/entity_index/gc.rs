@@ -35,9 +35,9 @@ const DAY_SECS: u64 = 86_400;
 /// because the last mutation was a deletion (tombstone on entity or trait), the
 /// search result is discarded (but could be returned by a later valid
 /// mutations). This eventually creates a problem where a lot of mutations get
-/// returned by the mutation index to be then discarded by the entity index, up
-/// to eventually creating the issue where we hit the maximum number of pages
-/// and don't return valid entities.
+/// returned by the mutation index to be then discarded by the entity index,
+/// which needs to go through a lot of mutation pages before filling a page of
+/// valid entities.
 ///
 /// At interval, the entity store calls the entity index to do a garbage
 /// collection run.
@@ -683,6 +683,7 @@ mod tests {
             operation_id,
             block_offset,
             entity_id: String::new(),
+            idempotency_key: None,
             mutation_type: MutationType::TraitPut(PutTraitMetadata {
                 trait_id: trait_id.into(),
                 trait_type: None,
@@ -691,10 +692,7 @@ mod tests {
                 has_reference: false,
             }),
             sort_value: OrderingValueWrapper {
-                value: OrderingValue {
-                    operation_id: 0,
-                    value: None,
-                },
+                value: OrderingValue::default(),
                 reverse: false,
                 ignore: false,
             },
@@ -12,7 +12,7 @@ use extsort::ExternalSorter;
 use super::EntityAggregator;
 use crate::{
     error::Error,
-    local::mutation_index::{MutationMetadata, MutationType, PutTraitMetadata},
+    local::mutation_index::{idempotency_key, MutationMetadata, MutationType, PutTraitMetadata},
     ordering::OrderingValueWrapper,
 };
 
@@ -234,6 +234,7 @@ fn put_trait_to_metadata(
         operation_id: committed_entity.operation_id,
         block_offset: Some(committed_entity.block_offset),
         entity_id: mutation.entity_id.clone(),
+        idempotency_key: idempotency_key(mutation),
         mutation_type: MutationType::TraitPut(PutTraitMetadata {
             trait_id: trt.id.clone(),
             trait_type: None,
@@ -257,6 +258,7 @@ fn del_trait_to_metadata(
         operation_id: committed_entity.operation_id,
         block_offset: Some(committed_entity.block_offset),
         entity_id: mutation.entity_id.clone(),
+        idempotency_key: idempotency_key(mutation),
         mutation_type: MutationType::TraitTombstone(del.trait_id.clone()),
         sort_value: OrderingValueWrapper::default(),
     }
@@ -271,6 +273,7 @@ fn del_entity_to_metadata(
         operation_id: committed_entity.operation_id,
         block_offset: Some(committed_entity.block_offset),
         entity_id: mutation.entity_id.clone(),
+        idempotency_key: idempotency_key(mutation),
         mutation_type: MutationType::EntityTombstone,
         sort_value: OrderingValueWrapper::default(),
     }
@@ -18,7 +18,7 @@ use exocore_core::{
 };
 use exocore_protos::{
     generated::exocore_store::{
-        entity_mutation::Mutation, EntityMutation, EntityQuery, EntityResults, Trait,
+        entity_mutation::Mutation, EntityMutation, EntityQuery, EntityResults, Snippet, Trait,
     },
     prost::{Message, ProstDateTimeExt},
     registry::Registry,
@@ -27,7 +27,7 @@ use exocore_protos::{
 use gc::GarbageCollector;
 use itertools::Itertools;
 
-use super::mutation_index::{IndexOperation, MutationIndex, MutationMetadata};
+use super::mutation_index::{Highlighter, IndexOperation, MutationIndex, MutationMetadata};
 use crate::error::Error;
 
 mod config;
@@ -237,6 +237,16 @@ where
     }
 
     pub fn search<Q: Borrow<EntityQuery>>(&self, query: Q) -> Result<EntityResults, Error> {
+        let highlighter = query
+            .borrow()
+            .highlighting
+            .as_ref()
+            .map(|highlighting| {
+                self.chain_index
+                    .highlighter(query.borrow(), highlighting.max_chars)
+            })
+            .transpose()?;
+
         let searcher = searcher::Searcher::new(
             &self.chain_index,
             &self.pending_index,
@@ -245,7 +255,7 @@ where
                 self.fetch_and_cache_entity_mutations_metadata(cache, entity_id, projections)
             },
             |entity_results, include_deleted| {
-                self.populate_results_traits(entity_results, include_deleted)
+                self.populate_results_traits(entity_results, include_deleted, highlighter.as_ref())
             },
             query.borrow(),
         );
@@ -538,7 +548,7 @@ where
         entity_id: &str,
     ) -> Result<exocore_protos::generated::exocore_store::Entity, Error> {
         let aggr = self.fetch_aggregated_entity_mutations(entity_id)?;
-        let traits = self.fetch_entity_traits(&aggr, false);
+        let traits = self.fetch_entity_traits(&aggr, false, None);
 
         Ok(exocore_protos::generated::exocore_store::Entity {
             id: entity_id.to_string(),
@@ -620,18 +630,26 @@ where
     }
 
     /// Populates traits in the EntityResult by fetching each entity's traits
-    /// from the chain layer.
+    /// from the chain layer. If a highlighter is given, snippets of the
+    /// traits' text fields matching the query are also populated.
     fn populate_results_traits(
         &self,
         entity_results: &mut Vec<searcher::SearchResult>,
         include_deleted: bool,
+        highlighter: Option<&Highlighter>,
     ) {
         for entity_result in entity_results {
             if entity_result.mutations.should_collect() {
                 self.gc.maybe_flag_for_collection(&entity_result.mutations);
             }
 
-            let traits = self.fetch_entity_traits(&entity_result.mutations, include_deleted);
+            let mut snippets = Vec::new();
+            let traits = self.fetch_entity_traits(
+                &entity_result.mutations,
+                include_deleted,
+                highlighter.map(|highlighter| (highlighter, &mut snippets)),
+            );
+            entity_result.proto.snippets = snippets;
             if let Some(entity) = entity_result.proto.entity.as_mut() {
                 entity.traits = traits;
             }
@@ -639,10 +657,14 @@ where
     }
 
     /// Fetches traits data from chain layer.
+    ///
+    /// Snippets are generated before projections are applied since
+    /// projections may exclude the fields that matched the query.
     fn fetch_entity_traits(
         &self,
         entity_mutations: &EntityAggregator,
         include_deleted: bool,
+        mut highlighter: Option<(&Highlighter, &mut Vec<Snippet>)>,
     ) -> Vec<Trait> {
         entity_mutations
             .traits
@@ -691,6 +713,10 @@ where
                     | Mutation::Test(_) => return None,
                 }?;
 
+                if let Some((highlighter, snippets)) = highlighter.as_mut() {
+                    snippets.extend(highlighter.trait_snippets(&trt));
+                }
+
                 if let Some(projection) = &agg.projection {
                     let res = project_trait_fields(
                         self.full_cell.cell().schemas().as_ref(),
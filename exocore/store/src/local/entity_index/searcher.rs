@@ -5,8 +5,11 @@ use std::{
 };
 
 use exocore_protos::{
-    prost::ProstDateTimeExt,
-    store::{Entity, EntityQuery, EntityResult, EntityResultSource, EntityResults, Projection},
+    prost::{Message, ProstDateTimeExt},
+    store::{
+        ordering, Entity, EntityQuery, EntityResult, EntityResultSource, EntityResults, Paging,
+        Projection,
+    },
 };
 use itertools::Itertools;
 
@@ -16,7 +19,7 @@ use crate::{
     error::Error,
     local::{
         entity_index::result_hasher,
-        mutation_index::{MutationIndex, MutationMetadata},
+        mutation_index::{MutationIndex, MutationIndexConfig, MutationMetadata},
         top_results::ReScoredTopResultsIterable,
     },
     ordering::{OrderingValueExt, OrderingValueWrapper},
@@ -67,8 +70,10 @@ where
         let mut query_page = self
             .query
             .paging
+            .clone()
             .unwrap_or_else(crate::query::default_paging);
         crate::query::fill_default_paging(&mut query_page);
+        resolve_paging_cursor(&mut query_page)?;
 
         let reference_boost = self
             .query
@@ -76,7 +81,7 @@ where
             .as_ref()
             .map_or(true, |o| !o.no_reference_boost);
 
-        let (chain_hits, pending_hits, combined_results) = self.search_hits()?;
+        let (chain_hits, pending_hits, mut combined_results) = self.search_hits(&query_page)?;
 
         let after_query_instant = Instant::now();
 
@@ -85,10 +90,10 @@ where
         let mut entity_mutations_cache = HashMap::<EntityId, Rc<EntityAggregator>>::new();
         let mut matched_entities = HashSet::new();
 
-        // iterate through results and returning the first N entities
-        let mut entity_results = combined_results
-            // iterate through results, starting with best scores
-            .flat_map(|(matched_mutation, index_source)| {
+        // converts a matched mutation to a result of the page, unless its entity was
+        // already matched, or it got discarded
+        let mut to_result =
+            |(matched_mutation, index_source): (MutationMetadata, EntityResultSource)| {
                 let entity_id = matched_mutation.entity_id.clone();
 
                 // check if we already processed this entity through another trait result that
@@ -152,6 +157,7 @@ where
                             source: index_source.into(),
                             ordering_value: Some(ordering_value.value),
                             hash: entity_mutations.hash,
+                            snippets: Vec::new(),
                         },
                         mutations: entity_mutations,
                     };
@@ -160,7 +166,13 @@ where
                 } else {
                     None
                 }
-            })
+            };
+
+        // iterate through results and returning the first N entities
+        let mut entity_results = combined_results
+            .by_ref()
+            // iterate through results, starting with best scores
+            .flat_map(&mut to_result)
             // this steps consumes the results up until we reach the best 10 results based on the
             // score of the highest matching trait, but re-scored negatively based on
             // other traits
@@ -192,6 +204,17 @@ where
             (self.entity_fetcher)(&mut entity_results, self.query.include_deleted);
         }
 
+        // exact count requires going through the remaining matching mutations, which
+        // also marks their entities as matched
+        let total_count = if self.query.count_total {
+            for hit in combined_results {
+                to_result(hit);
+            }
+            matched_entities.len() as u32
+        } else {
+            0
+        };
+
         let end_instant = Instant::now();
         debug!(
             "Query done chain_hits={} pending_hits={} aggr_fetch={} query={:?} aggr={:?} fetch={:?} total={:?} page={:?} next_page={:?}",
@@ -214,11 +237,14 @@ where
             current_page: Some(query_page),
             estimated_count: (chain_hits + pending_hits) as u32,
             hash: results_hash,
+            total_count,
+            stale: false,
         })
     }
 
     fn search_hits(
         &self,
+        query_page: &Paging,
     ) -> Result<
         (
             usize,
@@ -230,15 +256,41 @@ where
         // query pending & chain mutation index without original query paging since we
         // need to do our own paging here since we are re-ranking results and
         // that we may have more than one mutation match for each entity.
+        //
+        // When ordering by block offset, which is used to incrementally go through
+        // changes, the page's ordering value bounds are kept so that mutation indices
+        // skip mutations out of them instead of paging through them. For other
+        // orderings, mutations out of the bounds are still needed since they
+        // determine which of an entity's mutations ranks first.
+        let by_block_offset = self.query.ordering.as_ref().is_some_and(|ordering| {
+            matches!(ordering.value, Some(ordering::Value::BlockOffset(_)))
+        });
+        let paging = by_block_offset.then(|| Paging {
+            after_ordering_value: query_page.after_ordering_value.clone(),
+            before_ordering_value: query_page.before_ordering_value.clone(),
+            ..Default::default()
+        });
         let mutations_query = Rc::new(EntityQuery {
-            paging: None,
+            paging,
             ..self.query.clone()
         });
 
-        let chain_results = self.chain_index.search_iter(mutations_query.clone())?;
+        // a lot of mutations may get discarded, and we want to fill the page if more
+        // matching entities exist, which requires going through more mutation pages.
+        // An exact total count requires going through all of them.
+        let max_pages = |config: &MutationIndexConfig| {
+            if self.query.count_total {
+                usize::MAX
+            } else {
+                config.entity_query_max_pages
+            }
+        };
+        let mut chain_results = self.chain_index.search_iter(mutations_query.clone())?;
+        chain_results.max_pages = max_pages(self.chain_index.config());
         let chain_hits = chain_results.total_results;
 
-        let pending_results = self.pending_index.search_iter(mutations_query)?;
+        let mut pending_results = self.pending_index.search_iter(mutations_query)?;
+        pending_results.max_pages = max_pages(self.pending_index.config());
         let pending_hits = pending_results.total_results;
 
         let chain_results = chain_results.map(|res| (res, EntityResultSource::Chain));
@@ -254,10 +306,10 @@ where
     fn next_paging(
         &self,
         entity_results: &[SearchResult],
-        query_paging: &exocore_protos::store::Paging,
-    ) -> Option<exocore_protos::store::Paging> {
+        query_paging: &Paging,
+    ) -> Option<Paging> {
         if let Some(last_result) = entity_results.last() {
-            let mut new_paging = *query_paging;
+            let mut new_paging = query_paging.clone();
 
             let ascending = self
                 .query
@@ -266,10 +318,11 @@ where
                 .map(|s| s.ascending)
                 .unwrap_or(false);
             if !ascending {
-                new_paging.before_ordering_value = Some(last_result.ordering_value.value);
+                new_paging.before_ordering_value = Some(last_result.ordering_value.value.clone());
             } else {
-                new_paging.after_ordering_value = Some(last_result.ordering_value.value);
+                new_paging.after_ordering_value = Some(last_result.ordering_value.value.clone());
             }
+            new_paging.cursor = encode_paging_cursor(&new_paging);
 
             Some(new_paging)
         } else {
@@ -288,6 +341,34 @@ pub struct SearchResult {
     pub mutations: Rc<EntityAggregator>,
 }
 
+/// Encodes the ordering bounds of a page into an opaque cursor.
+fn encode_paging_cursor(paging: &Paging) -> String {
+    let cursor_paging = Paging {
+        after_ordering_value: paging.after_ordering_value.clone(),
+        before_ordering_value: paging.before_ordering_value.clone(),
+        ..Default::default()
+    };
+    bs58::encode(cursor_paging.encode_to_vec()).into_string()
+}
+
+/// Replaces the ordering bounds of the page by the ones of its cursor, if any.
+fn resolve_paging_cursor(paging: &mut Paging) -> Result<(), Error> {
+    if paging.cursor.is_empty() {
+        return Ok(());
+    }
+
+    let cursor_bytes = bs58::decode(&paging.cursor)
+        .into_vec()
+        .map_err(|err| Error::QueryParsing(anyhow!("Invalid paging cursor: {}", err)))?;
+    let cursor_paging = Paging::decode(cursor_bytes.as_slice())
+        .map_err(|err| Error::QueryParsing(anyhow!("Invalid paging cursor: {}", err)))?;
+
+    paging.after_ordering_value = cursor_paging.after_ordering_value;
+    paging.before_ordering_value = cursor_paging.before_ordering_value;
+
+    Ok(())
+}
+
 fn opt_date_to_proto(
     dt: Option<chrono::DateTime<chrono::Utc>>,
 ) -> Option<exocore_protos::prost::Timestamp> {
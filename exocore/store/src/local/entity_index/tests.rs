@@ -7,17 +7,19 @@ use exocore_core::tests_utils::{
 use exocore_protos::{
     generated::{exocore_store::Paging, exocore_test::TestMessage},
     prost::Message,
-    store::{EntityResult, EntityResultSource, EntityResults, Reference, Trait},
+    store::{EntityQuery, EntityResult, EntityResultSource, EntityResults, Reference, Trait},
     test::TestMessage2,
 };
 use itertools::Itertools;
 
 use crate::{
     local::{
-        entity_index::test_index::TestEntityIndex, mutation_index::MutationType, EntityIndexConfig,
+        entity_index::test_index::TestEntityIndex,
+        mutation_index::{MutationIndexConfig, MutationType},
+        EntityIndexConfig,
     },
-    mutation::MutationBuilder,
-    ordering::{value_from_u64, value_max},
+    mutation::{MutationBuilder, OperationId},
+    ordering::{value_from_u64, value_max, OrderingValueExt},
     query::{ProjectionBuilder, QueryBuilder as Q},
 };
 
@@ -577,6 +579,206 @@ async fn query_paging() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn query_paging_cursor_discarded_mutations() -> anyhow::Result<()> {
+    // with small mutation pages and a low page limit, the discarded mutations
+    // would previously have prevented pages from being filled
+    let mutation_index_config = MutationIndexConfig {
+        indexer_num_threads: Some(1),
+        iterator_page_size: 5,
+        iterator_max_pages: 1,
+        ..MutationIndexConfig::default()
+    };
+    let config = EntityIndexConfig {
+        pending_index_config: mutation_index_config,
+        chain_index_config: mutation_index_config,
+        ..TestEntityIndex::test_config()
+    };
+    let mut test_index = TestEntityIndex::new_with_config(config).await?;
+
+    // each entity's trait gets overridden a few times, which makes all but the last
+    // mutation of each entity discarded
+    let mut ops_id = Vec::new();
+    for version in 0..5 {
+        for i in 0..10 {
+            ops_id.push(test_index.put_test_trait(
+                format!("entity{i}"),
+                "trt",
+                format!("name{i} version{version}"),
+            )?);
+        }
+    }
+    test_index.wait_operations_emitted(&ops_id);
+    test_index.handle_engine_events()?;
+
+    // oldest operations first, which are the discarded ones
+    let query_builder = Q::with_trait::<TestMessage>()
+        .order_by_operations(true)
+        .count(3)
+        .count_total();
+
+    let mut entities = Vec::new();
+    let mut res = test_index.index.search(query_builder.clone().build())?;
+    loop {
+        assert_eq!(res.total_count, 10);
+        if res.entities.is_empty() {
+            break;
+        }
+
+        if entities.len() + 3 <= 10 {
+            assert_eq!(res.entities.len(), 3);
+        }
+        entities.extend(
+            extract_results_entities_id(&res)
+                .into_iter()
+                .map(|id| id.to_string()),
+        );
+
+        let cursor = res.next_page.unwrap().cursor;
+        assert!(!cursor.is_empty());
+        res = test_index
+            .index
+            .search(query_builder.clone().after_cursor(cursor).build())?;
+    }
+
+    let expected = (0..10).map(|i| format!("entity{i}")).collect_vec();
+    assert_eq!(entities, expected);
+
+    // invalid cursors are rejected
+    let res = test_index
+        .index
+        .search(query_builder.after_cursor("invalid cursor").build());
+    assert!(res.is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn query_max_pages_discarded_mutations() -> anyhow::Result<()> {
+    // entity queries can only go through a limited number of mutation pages
+    let mutation_index_config = MutationIndexConfig {
+        indexer_num_threads: Some(1),
+        iterator_page_size: 5,
+        entity_query_max_pages: 2,
+        ..MutationIndexConfig::default()
+    };
+    let config = EntityIndexConfig {
+        pending_index_config: mutation_index_config,
+        chain_index_config: mutation_index_config,
+        ..TestEntityIndex::test_config()
+    };
+    let mut test_index = TestEntityIndex::new_with_config(config).await?;
+
+    let mut ops_id = Vec::new();
+    for version in 0..5 {
+        for i in 0..10 {
+            ops_id.push(test_index.put_test_trait(
+                format!("entity{i}"),
+                "trt",
+                format!("name{i} version{version}"),
+            )?);
+        }
+    }
+    test_index.wait_operations_emitted(&ops_id);
+    test_index.handle_engine_events()?;
+
+    // oldest operations first, which are the discarded ones, and which fill the
+    // allowed mutation pages
+    let query_builder = Q::with_trait::<TestMessage>()
+        .order_by_operations(true)
+        .count(3);
+    let res = test_index.index.search(query_builder.clone().build())?;
+    assert!(res.entities.is_empty());
+
+    // counting the exact total goes through all pages
+    let res = test_index
+        .index
+        .search(query_builder.count_total().build())?;
+    assert_eq!(res.entities.len(), 3);
+    assert_eq!(res.total_count, 10);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn query_block_offset_ordering() -> anyhow::Result<()> {
+    let mutation_index_config = MutationIndexConfig {
+        indexer_num_threads: Some(1),
+        iterator_page_size: 2,
+        entity_query_max_pages: 2,
+        ..MutationIndexConfig::default()
+    };
+    let config = EntityIndexConfig {
+        chain_index_min_depth: 0, // index blocks as soon as they are committed
+        pending_index_config: mutation_index_config,
+        chain_index_config: mutation_index_config,
+        ..TestEntityIndex::test_config()
+    };
+    let mut test_index = TestEntityIndex::new_with_config(config).await?;
+    test_index.handle_engine_events()?;
+
+    // each trait is committed in its own block
+    for i in 0..8 {
+        let op = test_index.put_test_trait(format!("entity{i}"), "trt", format!("name{i}"))?;
+        test_index.wait_operations_committed(&[op]);
+        test_index.handle_engine_events()?;
+    }
+
+    // only the allowed mutation pages are gone through
+    let query_builder = Q::with_trait::<TestMessage>().order_by_block_offset(true);
+    let res = test_index.index.search(query_builder.clone().build())?;
+    assert_eq!(
+        extract_results_entities_id(&res),
+        vec!["entity0", "entity1", "entity2", "entity3", "entity4", "entity5"]
+    );
+    let ordering_values = res
+        .entities
+        .iter()
+        .map(|res| res.ordering_value.clone().unwrap())
+        .collect_vec();
+    assert!(ordering_values
+        .windows(2)
+        .all(|values| values[0].value != values[1].value && values[0].is_before(&values[1])));
+
+    // mutations before the paging's bounds are skipped instead of filling the allowed
+    // mutation pages
+    let after = res.entities[4].ordering_value.clone().unwrap();
+    let res = test_index.index.search(
+        query_builder
+            .with_paging(Paging {
+                after_ordering_value: Some(after),
+                ..Default::default()
+            })
+            .build(),
+    )?;
+    assert_eq!(
+        extract_results_entities_id(&res),
+        vec!["entity5", "entity6", "entity7"]
+    );
+
+    // mutations that aren't indexed in the chain yet have an offset of 0
+    let op = test_index.put_test_trait("entity8", "trt", "name8")?;
+    test_index.wait_operations_emitted(&[op]);
+    let events = test_index.cluster.drain_received_events(0);
+    let pending_events = events
+        .into_iter()
+        .filter(|event| matches!(event, Event::NewPendingOperation(_)));
+    test_index
+        .index
+        .handle_chain_engine_events(pending_events)?;
+    let res = test_index.index.search(
+        Q::with_trait::<TestMessage>()
+            .order_by_block_offset(true)
+            .count(1)
+            .build(),
+    )?;
+    assert_eq!(extract_results_entities_id(&res), vec!["entity8"]);
+    let ordering_value = res.entities[0].ordering_value.as_ref().unwrap();
+    assert_eq!(ordering_value.value, value_from_u64(0, 0).value);
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn query_multiple_mutations_paging() -> anyhow::Result<()> {
     let config = TestEntityIndex::test_config();
@@ -804,6 +1006,116 @@ async fn query_projection() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn query_highlighting() -> anyhow::Result<()> {
+    let config = TestEntityIndex::test_config();
+    let mut test_index = TestEntityIndex::new_with_config(config).await?;
+
+    let op1 = test_index.put_test_trait("entity1", "trait1", "some name")?;
+    test_index.wait_operations_committed(&[op1]);
+    test_index.handle_engine_events()?;
+
+    let op2 = test_index.put_test_trait("entity2", "trait1", "other name")?;
+    test_index.wait_operations_emitted(&[op2]);
+    test_index.handle_engine_events()?;
+
+    {
+        // no snippets unless requested
+        let res = test_index.index.search(Q::matches("name").build())?;
+        assert!(res.entities.iter().all(|e| e.snippets.is_empty()));
+    }
+
+    {
+        // snippets from both chain and pending results
+        let res = test_index
+            .index
+            .search(Q::matches("name").highlight().build())?;
+        assert_eq!(res.entities.len(), 2);
+        for entity in &res.entities {
+            assert_eq!(entity.snippets.len(), 1);
+            let snippet = &entity.snippets[0];
+            assert_eq!(snippet.trait_id, "trait1");
+            assert_eq!(snippet.field, "string1");
+            assert_eq!(snippet.highlights.len(), 1);
+        }
+    }
+
+    {
+        // snippets are generated even if the field is excluded by projection
+        let proj = ProjectionBuilder::for_trait::<TestMessage>().return_fields(vec![2]);
+        let query = Q::matches("some").project(proj).highlight().build();
+        let res = test_index.index.search(query)?;
+        assert_eq!(res.entities[0].snippets[0].fragment, "some name");
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn query_sort_by_multiple_fields_paging() -> anyhow::Result<()> {
+    let config = TestEntityIndex::test_config();
+    let mut test_index = TestEntityIndex::new_with_config(config).await?;
+
+    fn put_traits(
+        test_index: &mut TestEntityIndex,
+        range: std::ops::Range<u32>,
+    ) -> anyhow::Result<Vec<OperationId>> {
+        range
+            .map(|i| {
+                test_index.put_trait_message(
+                    format!("entity{i}"),
+                    "trait1",
+                    TestMessage {
+                        uint3: i % 3,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect()
+    }
+
+    // first half in chain, second half in pending
+    let chain_ops = put_traits(&mut test_index, 0..6)?;
+    test_index.wait_operations_committed(&chain_ops);
+    test_index.handle_engine_events()?;
+
+    let pending_ops = put_traits(&mut test_index, 6..12)?;
+    test_index.wait_operations_emitted(&pending_ops);
+    test_index.handle_engine_events()?;
+
+    let query = Q::with_trait::<TestMessage>()
+        .order_by_field("uint3", false)
+        .then_order_by_operations(true)
+        .count(4)
+        .build();
+
+    let mut entities = Vec::new();
+    let mut query_paging = query.paging.clone();
+    loop {
+        let res = test_index.index.search(EntityQuery {
+            paging: query_paging,
+            ..query.clone()
+        })?;
+        if res.entities.is_empty() {
+            break;
+        }
+        entities.extend(
+            extract_results_entities_id(&res)
+                .into_iter()
+                .map(|id| id.to_string()),
+        );
+        query_paging = res.next_page;
+    }
+
+    let expected = [2, 5, 8, 11, 1, 4, 7, 10, 0, 3, 6, 9]
+        .iter()
+        .map(|i| format!("entity{i}"))
+        .collect_vec();
+    assert_eq!(entities, expected);
+
+    Ok(())
+}
+
 fn count_results_source(results: &EntityResults, source: EntityResultSource) -> usize {
     results
         .entities
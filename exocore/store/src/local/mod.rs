@@ -16,8 +16,8 @@ pub use entity_index::{
 #[cfg(feature = "local")]
 pub use store::{Store, StoreHandle};
 
-#[cfg(test)]
+#[cfg(any(test, feature = "tests-utils"))]
 mod test_store;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "tests-utils"))]
 pub use test_store::TestStore;
@@ -0,0 +1,357 @@
+use std::{fmt, str::FromStr};
+
+use tantivy::tokenizer::{
+    AsciiFoldingFilter, BoxTokenStream, Language, LowerCaser, RemoveLongFilter, SimpleTokenizer,
+    Stemmer, TextAnalyzer, Token, TokenFilter, TokenStream,
+};
+
+use crate::error::Error;
+
+const MAX_TOKEN_LENGTH: usize = 40;
+const NGRAM_MIN_LENGTH: usize = 2;
+const NGRAM_MAX_LENGTH: usize = 15;
+
+/// Analyzer used to tokenize full-text fields, both at indexation and query
+/// time.
+///
+/// The analyzer of the index is configured via `MutationIndexConfig`, but can
+/// be overridden per field via the `text_analyzer` field option (see
+/// `exocore/store/options.proto`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TextAnalyzerKind {
+    /// Splits on non-alphanumeric characters and lowercases tokens.
+    #[default]
+    Default,
+
+    /// Same as `Default`, but also folds accented characters to their ASCII
+    /// equivalent (ex: "élève" -> "eleve").
+    Ascii,
+
+    /// Same as `Ascii`, but also stems tokens using the given language's
+    /// stemmer (ex: "mangeaient" -> "mang").
+    Stemmed(Language),
+
+    /// Same as `Ascii`, but also indexes prefixes of each token to allow
+    /// prefix search (ex: "note" -> "no", "not", "note").
+    Ngram,
+}
+
+impl TextAnalyzerKind {
+    /// Name under which the analyzer is registered in the Tantivy index.
+    pub fn tokenizer_name(&self) -> &'static str {
+        match self {
+            TextAnalyzerKind::Default => "default",
+            TextAnalyzerKind::Ascii => "ascii",
+            TextAnalyzerKind::Ngram => "ngram",
+            TextAnalyzerKind::Stemmed(lang) => match lang {
+                Language::Arabic => "stem_arabic",
+                Language::Danish => "stem_danish",
+                Language::Dutch => "stem_dutch",
+                Language::English => "stem_english",
+                Language::Finnish => "stem_finnish",
+                Language::French => "stem_french",
+                Language::German => "stem_german",
+                Language::Greek => "stem_greek",
+                Language::Hungarian => "stem_hungarian",
+                Language::Italian => "stem_italian",
+                Language::Norwegian => "stem_norwegian",
+                Language::Portuguese => "stem_portuguese",
+                Language::Romanian => "stem_romanian",
+                Language::Russian => "stem_russian",
+                Language::Spanish => "stem_spanish",
+                Language::Swedish => "stem_swedish",
+                Language::Tamil => "stem_tamil",
+                Language::Turkish => "stem_turkish",
+            },
+        }
+    }
+
+    /// Analyzer used to tokenize text at indexation time.
+    pub fn index_analyzer(&self) -> TextAnalyzer {
+        match self {
+            TextAnalyzerKind::Default => base_analyzer(),
+            TextAnalyzerKind::Ascii => base_analyzer().filter(AsciiFoldingFilter),
+            TextAnalyzerKind::Stemmed(lang) => base_analyzer()
+                .filter(Stemmer::new(*lang))
+                .filter(AsciiFoldingFilter),
+            TextAnalyzerKind::Ngram => base_analyzer()
+                .filter(AsciiFoldingFilter)
+                .filter(EdgeNgramFilter::new(NGRAM_MIN_LENGTH, NGRAM_MAX_LENGTH)),
+        }
+    }
+
+    /// Analyzer used to tokenize text at query time.
+    ///
+    /// Same as indexation, except for n-grams for which the query tokens are
+    /// matched as is against indexed prefixes. Since prefixes are only indexed
+    /// up to `NGRAM_MAX_LENGTH` characters, longer query tokens are truncated to
+    /// match the longest indexed prefix.
+    pub fn query_analyzer(&self) -> TextAnalyzer {
+        match self {
+            TextAnalyzerKind::Ngram => base_analyzer()
+                .filter(AsciiFoldingFilter)
+                .filter(TruncateFilter::new(NGRAM_MAX_LENGTH)),
+            other => other.index_analyzer(),
+        }
+    }
+
+    /// Returns the analyzer registered under the given tokenizer name.
+    pub fn from_tokenizer_name(name: &str) -> Option<TextAnalyzerKind> {
+        match name {
+            "default" => Some(TextAnalyzerKind::Default),
+            other => other.trim_start_matches("stem_").parse().ok(),
+        }
+    }
+}
+
+impl FromStr for TextAnalyzerKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lang = match s.to_lowercase().as_str() {
+            "" | "default" => return Ok(TextAnalyzerKind::Default),
+            "ascii" => return Ok(TextAnalyzerKind::Ascii),
+            "ngram" => return Ok(TextAnalyzerKind::Ngram),
+            "ar" | "arabic" => Language::Arabic,
+            "da" | "danish" => Language::Danish,
+            "nl" | "dutch" => Language::Dutch,
+            "en" | "english" => Language::English,
+            "fi" | "finnish" => Language::Finnish,
+            "fr" | "french" => Language::French,
+            "de" | "german" => Language::German,
+            "el" | "greek" => Language::Greek,
+            "hu" | "hungarian" => Language::Hungarian,
+            "it" | "italian" => Language::Italian,
+            "no" | "norwegian" => Language::Norwegian,
+            "pt" | "portuguese" => Language::Portuguese,
+            "ro" | "romanian" => Language::Romanian,
+            "ru" | "russian" => Language::Russian,
+            "es" | "spanish" => Language::Spanish,
+            "sv" | "swedish" => Language::Swedish,
+            "ta" | "tamil" => Language::Tamil,
+            "tr" | "turkish" => Language::Turkish,
+            other => {
+                return Err(Error::Other(anyhow!("Unknown text analyzer '{}'", other)));
+            }
+        };
+
+        Ok(TextAnalyzerKind::Stemmed(lang))
+    }
+}
+
+impl fmt::Display for TextAnalyzerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.tokenizer_name())
+    }
+}
+
+fn base_analyzer() -> TextAnalyzer {
+    TextAnalyzer::from(SimpleTokenizer)
+        .filter(RemoveLongFilter::limit(MAX_TOKEN_LENGTH))
+        .filter(LowerCaser)
+}
+
+/// Token filter that emits the prefixes of each token, from `min` to `max`
+/// characters long. Tokens shorter than `min` are emitted as is.
+#[derive(Clone)]
+struct EdgeNgramFilter {
+    min: usize,
+    max: usize,
+}
+
+impl EdgeNgramFilter {
+    fn new(min: usize, max: usize) -> EdgeNgramFilter {
+        EdgeNgramFilter { min, max }
+    }
+}
+
+impl TokenFilter for EdgeNgramFilter {
+    fn transform<'a>(&self, token_stream: BoxTokenStream<'a>) -> BoxTokenStream<'a> {
+        BoxTokenStream::from(EdgeNgramTokenStream {
+            tail: token_stream,
+            min: self.min,
+            max: self.max,
+            token: Token::default(),
+            prefix_ends: Vec::new(),
+            next_prefix: 0,
+        })
+    }
+}
+
+struct EdgeNgramTokenStream<'a> {
+    tail: BoxTokenStream<'a>,
+    min: usize,
+    max: usize,
+    token: Token,
+    prefix_ends: Vec<usize>,
+    next_prefix: usize,
+}
+
+impl<'a> TokenStream for EdgeNgramTokenStream<'a> {
+    fn advance(&mut self) -> bool {
+        loop {
+            if let Some(end) = self.prefix_ends.get(self.next_prefix) {
+                self.next_prefix += 1;
+
+                let tail_token = self.tail.token();
+                self.token.text.clear();
+                self.token.text.push_str(&tail_token.text[..*end]);
+                self.token.offset_from = tail_token.offset_from;
+                self.token.offset_to = tail_token.offset_to;
+                self.token.position = tail_token.position;
+                self.token.position_length = tail_token.position_length;
+                return true;
+            }
+
+            if !self.tail.advance() {
+                return false;
+            }
+
+            let text = &self.tail.token().text;
+            self.prefix_ends = text
+                .char_indices()
+                .map(|(idx, chr)| idx + chr.len_utf8())
+                .skip(self.min - 1)
+                .take(self.max - self.min + 1)
+                .collect();
+            if self.prefix_ends.is_empty() {
+                self.prefix_ends.push(text.len());
+            }
+            self.next_prefix = 0;
+        }
+    }
+
+    fn token(&self) -> &Token {
+        &self.token
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.token
+    }
+}
+
+/// Token filter that truncates tokens to at most `max` characters.
+#[derive(Clone)]
+struct TruncateFilter {
+    max: usize,
+}
+
+impl TruncateFilter {
+    fn new(max: usize) -> TruncateFilter {
+        TruncateFilter { max }
+    }
+}
+
+impl TokenFilter for TruncateFilter {
+    fn transform<'a>(&self, token_stream: BoxTokenStream<'a>) -> BoxTokenStream<'a> {
+        BoxTokenStream::from(TruncateTokenStream {
+            tail: token_stream,
+            max: self.max,
+        })
+    }
+}
+
+struct TruncateTokenStream<'a> {
+    tail: BoxTokenStream<'a>,
+    max: usize,
+}
+
+impl<'a> TokenStream for TruncateTokenStream<'a> {
+    fn advance(&mut self) -> bool {
+        if !self.tail.advance() {
+            return false;
+        }
+
+        let token = self.tail.token_mut();
+        if let Some((end, _)) = token.text.char_indices().nth(self.max) {
+            token.text.truncate(end);
+        }
+
+        true
+    }
+
+    fn token(&self) -> &Token {
+        self.tail.token()
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        self.tail.token_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_analyzer() {
+        assert_eq!(
+            TextAnalyzerKind::Default,
+            "".parse::<TextAnalyzerKind>().unwrap()
+        );
+        assert_eq!(
+            TextAnalyzerKind::Stemmed(Language::French),
+            "fr".parse::<TextAnalyzerKind>().unwrap()
+        );
+        assert_eq!(
+            TextAnalyzerKind::Stemmed(Language::French),
+            "French".parse::<TextAnalyzerKind>().unwrap()
+        );
+        assert!("klingon".parse::<TextAnalyzerKind>().is_err());
+
+        for kind in [
+            TextAnalyzerKind::Default,
+            TextAnalyzerKind::Ascii,
+            TextAnalyzerKind::Ngram,
+            TextAnalyzerKind::Stemmed(Language::English),
+        ] {
+            assert_eq!(
+                Some(kind),
+                TextAnalyzerKind::from_tokenizer_name(kind.tokenizer_name())
+            );
+        }
+    }
+
+    #[test]
+    fn analyzers_tokens() {
+        assert_eq!(
+            vec!["élève", "studieux"],
+            tokens(TextAnalyzerKind::Default.index_analyzer(), "Élève studieux")
+        );
+        assert_eq!(
+            vec!["eleve", "studieux"],
+            tokens(TextAnalyzerKind::Ascii.index_analyzer(), "Élève studieux")
+        );
+        assert_eq!(
+            vec!["elev", "studieux"],
+            tokens(
+                TextAnalyzerKind::Stemmed(Language::French).index_analyzer(),
+                "Élèves studieux"
+            )
+        );
+        assert_eq!(
+            vec!["a", "no", "not", "note"],
+            tokens(TextAnalyzerKind::Ngram.index_analyzer(), "a Note")
+        );
+        assert_eq!(
+            vec!["a", "note"],
+            tokens(TextAnalyzerKind::Ngram.query_analyzer(), "a Note")
+        );
+        assert_eq!(
+            vec!["internationaliz"],
+            tokens(
+                TextAnalyzerKind::Ngram.query_analyzer(),
+                "internationalization"
+            )
+        );
+    }
+
+    fn tokens(analyzer: TextAnalyzer, text: &str) -> Vec<String> {
+        let mut tokens = Vec::new();
+        let mut stream = analyzer.token_stream(text);
+        while stream.advance() {
+            tokens.push(stream.token().text.clone());
+        }
+        tokens
+    }
+}
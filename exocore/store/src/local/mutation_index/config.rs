@@ -1,4 +1,7 @@
 use exocore_protos::generated::exocore_core::MutationIndexConfig as ProtoMutationIndexConfig;
+
+use super::TextAnalyzerKind;
+
 /// Trait index configuration
 #[derive(Clone, Copy, Debug)]
 pub struct MutationIndexConfig {
@@ -14,12 +17,29 @@ pub struct MutationIndexConfig {
     /// Maximum number of pages returned by results iterator.
     pub iterator_max_pages: usize,
 
+    /// Maximum number of pages returned by results iterator for entity
+    /// queries. Since matching mutations may get discarded if they were
+    /// overridden, entity queries need to go through more pages to fill their
+    /// page. Queries counting their exact total go through all pages.
+    pub entity_query_max_pages: usize,
+
+    /// Maximum number of embeddings compared by a similarity query. Since
+    /// embeddings aren't indexed in a nearest neighbour structure, this bounds
+    /// the cost of a query on large indices. Queries that would need to
+    /// compare more embeddings fail instead of returning incomplete results.
+    pub similarity_max_embeddings: usize,
+
     /// Size of the entity mutations cache in bytes.
     pub entity_mutations_cache_size: usize,
 
+    /// Analyzer used for full-text fields that don't specify one via the
+    /// `text_analyzer` field option.
+    pub text_analyzer: TextAnalyzerKind,
+
     pub dynamic_reference_fields: u32,
     pub dynamic_string_fields: u32,
     pub dynamic_text_fields: u32,
+    pub dynamic_analyzed_text_fields: u32,
     pub dynamic_i64_fields: u32,
     pub dynamic_i64_sortable_fields: u32,
     pub dynamic_u64_fields: u32,
@@ -33,11 +53,16 @@ impl Default for MutationIndexConfig {
             indexer_heap_size_bytes: 30_000_000,
             iterator_page_size: 1000,
             iterator_max_pages: 5,
+            entity_query_max_pages: 50,
             entity_mutations_cache_size: 5000,
+            similarity_max_embeddings: 100_000,
+
+            text_analyzer: TextAnalyzerKind::Default,
 
             dynamic_reference_fields: 10,
             dynamic_string_fields: 10,
             dynamic_text_fields: 10,
+            dynamic_analyzed_text_fields: 3,
             dynamic_i64_fields: 10,
             dynamic_i64_sortable_fields: 10,
             dynamic_u64_fields: 10,
@@ -62,6 +87,17 @@ impl From<ProtoMutationIndexConfig> for MutationIndexConfig {
             config.entity_mutations_cache_size = v as usize;
         }
 
+        if let Some(v) = proto.entity_query_max_pages {
+            config.entity_query_max_pages = v as usize;
+        }
+
+        if let Some(v) = proto.text_analyzer {
+            match v.parse() {
+                Ok(analyzer) => config.text_analyzer = analyzer,
+                Err(err) => error!("Invalid text analyzer in index config: {}", err),
+            }
+        }
+
         config
     }
 }
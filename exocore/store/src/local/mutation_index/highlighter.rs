@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, HashMap};
+
+use exocore_protos::{
+    reflect,
+    reflect::{DynamicMessage, FieldValue, ReflectMessage},
+    registry::Registry,
+    store::{snippet::Highlight, Snippet, Trait},
+};
+use tantivy::{schema::Field, SnippetGenerator};
+
+use super::schema::MutationIndexSchema;
+
+const DEFAULT_MAX_CHARS: usize = 150;
+
+/// Generates snippets of traits' full-text fields in which the terms matching
+/// a query are highlighted.
+///
+/// Since traits' text isn't stored in the index, snippets are generated from
+/// the traits fetched from the chain once results have been collected.
+pub struct Highlighter<'i> {
+    schema: &'i MutationIndexSchema,
+    registry: &'i Registry,
+    generators: HashMap<Field, SnippetGenerator>,
+}
+
+impl<'i> Highlighter<'i> {
+    pub(super) fn new(
+        schema: &'i MutationIndexSchema,
+        registry: &'i Registry,
+        index: &tantivy::Index,
+        query: &dyn tantivy::query::Query,
+        max_chars: u32,
+    ) -> Result<Highlighter<'i>, crate::error::Error> {
+        let max_chars = if max_chars > 0 {
+            max_chars as usize
+        } else {
+            DEFAULT_MAX_CHARS
+        };
+
+        // all terms are considered equivalent since their frequency differs between
+        // the chain and pending indices
+        let mut fields_terms = HashMap::<Field, BTreeMap<String, f32>>::new();
+        query.query_terms(&mut |term, _need_positions| {
+            if let Some(text) = term.as_str() {
+                fields_terms
+                    .entry(term.field())
+                    .or_default()
+                    .insert(text.to_string(), 1.0);
+            }
+        });
+
+        let mut generators = HashMap::new();
+        for (field, terms) in fields_terms {
+            let tokenizer = index.tokenizer_for_field(field)?;
+            let generator = SnippetGenerator::new(terms, tokenizer, field, max_chars);
+            generators.insert(field, generator);
+        }
+
+        Ok(Highlighter {
+            schema,
+            registry,
+            generators,
+        })
+    }
+
+    /// Generates snippets for the full-text fields of the trait that contain
+    /// terms matching the query.
+    pub fn trait_snippets(&self, trt: &Trait) -> Vec<Snippet> {
+        let mut snippets = Vec::new();
+        if self.generators.is_empty() {
+            return snippets;
+        }
+
+        let Some(message_any) = trt.message.as_ref() else {
+            return snippets;
+        };
+
+        let dyn_message = match reflect::from_prost_any(self.registry, message_any) {
+            Ok(dyn_message) => dyn_message,
+            Err(err) => {
+                debug!(
+                    "Couldn't reflect trait {} for highlighting: {}",
+                    trt.id, err
+                );
+                return snippets;
+            }
+        };
+
+        let trait_name = dyn_message.full_name().to_string();
+        self.message_snippets(&trait_name, &trt.id, &dyn_message, None, &mut snippets);
+
+        snippets
+    }
+
+    fn message_snippets(
+        &self,
+        trait_name: &str,
+        trait_id: &str,
+        dyn_message: &DynamicMessage,
+        prefix: Option<&str>,
+        snippets: &mut Vec<Snippet>,
+    ) {
+        for field_desc in dyn_message.fields().values() {
+            let field_name = if let Some(prefix) = prefix {
+                format!("{}.{}", prefix, field_desc.name)
+            } else {
+                field_desc.name.to_string()
+            };
+
+            match dyn_message.get_field_value(field_desc.id) {
+                Ok(FieldValue::String(text)) if field_desc.text_flag => {
+                    if let Some(snippet) = self.field_snippet(trait_name, &field_name, &text) {
+                        snippets.push(Snippet {
+                            trait_id: trait_id.to_string(),
+                            ..snippet
+                        });
+                    }
+                }
+                Ok(field_value @ FieldValue::Message(_, _)) => {
+                    if let Ok(sub_message) = field_value.into_message(self.registry) {
+                        self.message_snippets(
+                            trait_name,
+                            trait_id,
+                            &sub_message,
+                            Some(&field_name),
+                            snippets,
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn field_snippet(&self, trait_name: &str, field_name: &str, text: &str) -> Option<Snippet> {
+        // terms may have matched the field directly (ex: via query string), or via the
+        // full-text field in which all text fields are indexed
+        let field_generator = self
+            .schema
+            .get_dynamic_trait_field(trait_name, field_name)
+            .ok()
+            .and_then(|mapped_field| self.generators.get(&mapped_field.field));
+        let all_text_generator = self.generators.get(&self.schema.all_text);
+
+        let snippet = field_generator
+            .into_iter()
+            .chain(all_text_generator)
+            .map(|generator| generator.snippet(text))
+            .find(|snippet| !snippet.highlighted().is_empty())?;
+
+        let fragment = snippet.fragment();
+        let highlights = snippet
+            .highlighted()
+            .iter()
+            .map(|range| Highlight {
+                start: fragment[..range.start].chars().count() as u32,
+                end: fragment[..range.end].chars().count() as u32,
+            })
+            .collect();
+
+        Some(Snippet {
+            trait_id: String::new(),
+            field: field_name.to_string(),
+            fragment: fragment.to_string(),
+            highlights,
+        })
+    }
+}
@@ -9,6 +9,7 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+pub use analyzer::TextAnalyzerKind;
 use chrono::{TimeZone, Utc};
 pub use config::*;
 use entity_cache::EntityMutationsCache;
@@ -23,15 +24,17 @@ use exocore_protos::{
     reflect::{DynamicMessage, FieldDescriptor, FieldValue, ReflectMessage},
     registry::Registry,
 };
+pub use highlighter::Highlighter;
 pub use operations::*;
 pub use results::*;
 use tantivy::{
-    collector::{Collector, Count, MultiCollector, TopDocs},
+    collector::{Collector, Count, FilterCollector, MultiCollector, TopDocs},
     directory::MmapDirectory,
+    fastfield::Column,
     query::{AllQuery, TermQuery},
     schema::{Field, IndexRecordOption},
-    DocAddress, Document, Index as TantivyIndex, IndexReader, IndexSettings, IndexSortByField,
-    IndexWriter, Order, ReloadPolicy, Searcher, SegmentReader, Term,
+    DocAddress, DocId, Document, Index as TantivyIndex, IndexReader, IndexSettings,
+    IndexSortByField, IndexWriter, Order, ReloadPolicy, Searcher, SegmentReader, Term,
 };
 
 use self::{
@@ -42,12 +45,15 @@ use crate::{
     entity::EntityIdRef, error::Error, mutation::OperationId, ordering::OrderingValueWrapper,
 };
 
+mod analyzer;
 mod config;
 mod entity_cache;
+mod highlighter;
 mod operations;
 mod query;
 mod results;
 mod schema;
+mod similarity;
 #[cfg(test)]
 mod tests;
 
@@ -87,11 +93,27 @@ impl MutationIndex {
     ) -> Result<MutationIndex, Error> {
         let schema = MutationIndexSchema::new(config, schema_registry.as_ref());
 
-        let directory = MmapDirectory::open(directory)?;
+        // schema changes if analyzers or registered messages' fields change, in which
+        // case we need to wipe the index so that it gets fully re-indexed
+        let mut mmap_directory = MmapDirectory::open(directory)?;
+        if TantivyIndex::exists(&mmap_directory).map_err(tantivy::TantivyError::from)? {
+            let existing_index = TantivyIndex::open(mmap_directory.clone())?;
+            if existing_index.schema() != schema.tantivy {
+                warn!(
+                    "Index schema has changed in {:?}. Wiping it for re-indexation.",
+                    directory
+                );
+                drop(existing_index);
+                std::fs::remove_dir_all(directory)?;
+                std::fs::create_dir_all(directory)?;
+                mmap_directory = MmapDirectory::open(directory)?;
+            }
+        }
+
         let index = TantivyIndex::builder()
             .schema(schema.tantivy.clone())
             .settings(index_settings())
-            .open_or_create(directory)?;
+            .open_or_create(mmap_directory)?;
         schema.register_tokenizers(&index);
 
         let index_reader = index
@@ -307,6 +329,28 @@ impl MutationIndex {
         Ok(results)
     }
 
+    /// Create a highlighter that generates snippets of the traits' text fields
+    /// matching the given query.
+    pub fn highlighter(
+        &self,
+        query: &EntityQuery,
+        max_chars: u32,
+    ) -> Result<Highlighter<'_>, Error> {
+        let parsed_query = QueryParser::parse(&self.index, &self.schema, &self.config, query)?;
+
+        Highlighter::new(
+            &self.schema,
+            self.schema_registry.as_ref(),
+            &self.index,
+            parsed_query.tantivy.as_ref(),
+            max_chars,
+        )
+    }
+
+    pub fn config(&self) -> &MutationIndexConfig {
+        &self.config
+    }
+
     /// Execute a query on the index and return an iterator over all matching
     /// mutations.
     pub fn search_iter<Q: Borrow<EntityQuery>>(
@@ -359,6 +403,7 @@ impl MutationIndex {
             paging,
             ordering,
             trait_name: None,
+            similarity_exhausted: None,
         };
 
         let mut results = self.execute_tantivy_query_with_paging(&searcher, parsed_query)?;
@@ -399,6 +444,9 @@ impl MutationIndex {
         if let Some(block_offset) = operation.block_offset {
             doc.add_u64(self.schema.block_offset, block_offset);
         }
+        if let Some(idempotency_key) = operation.idempotency_key {
+            doc.add_u64(self.schema.idempotency_key, idempotency_key);
+        }
 
         if let Some(creation_date) = &operation.trt.creation_date {
             doc.add_u64(
@@ -439,6 +487,8 @@ impl MutationIndex {
             }
         };
 
+        self.add_trait_message_embedding(doc, &dyn_message);
+
         let Some(message_mappings) = self.schema.dynamic_fields.get(dyn_message.full_name()) else {
             // field is not indexed if we don't have a mapping for it
             return;
@@ -466,6 +516,48 @@ impl MutationIndex {
         }
     }
 
+    /// Adds the embedding fields of the message to the document so that it can
+    /// be matched by similarity queries.
+    fn add_trait_message_embedding(&self, doc: &mut Document, dyn_message: &DynamicMessage) {
+        let mut fields = dyn_message
+            .fields()
+            .values()
+            .filter(|field| field.embedding_flag)
+            .collect::<Vec<_>>();
+        fields.sort_by_key(|field| field.id);
+
+        let mut vectors = Vec::new();
+        for field_desc in fields {
+            let values = match dyn_message.get_field_value(field_desc.id) {
+                Ok(FieldValue::Repeated(values)) => values,
+                Ok(other) => {
+                    warn!(
+                        "Unsupported embedding field type / value: type={:?} value={:?}",
+                        field_desc.field_type, other
+                    );
+                    continue;
+                }
+                Err(err) => {
+                    trace!("Couldn't get value of field {}: {}", field_desc.name, err);
+                    continue;
+                }
+            };
+
+            let vector = values
+                .into_iter()
+                .filter_map(|value| match value {
+                    FieldValue::Float(value) => Some(value),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            vectors.push(vector);
+        }
+
+        if let Some(bytes) = similarity::encode_embeddings(vectors.iter().map(Vec::as_slice)) {
+            doc.add_bytes(self.schema.embedding, bytes);
+        }
+    }
+
     fn add_trait_message_document_field(
         &self,
         doc: &mut Document,
@@ -574,6 +666,9 @@ impl MutationIndex {
         if let Some(block_offset) = operation.block_offset {
             doc.add_u64(self.schema.block_offset, block_offset);
         }
+        if let Some(idempotency_key) = operation.idempotency_key {
+            doc.add_u64(self.schema.idempotency_key, idempotency_key);
+        }
 
         doc.add_u64(self.schema.document_type, MutationType::TRAIT_TOMBSTONE_ID);
 
@@ -590,6 +685,9 @@ impl MutationIndex {
         if let Some(block_offset) = operation.block_offset {
             doc.add_u64(self.schema.block_offset, block_offset);
         }
+        if let Some(idempotency_key) = operation.idempotency_key {
+            doc.add_u64(self.schema.idempotency_key, idempotency_key);
+        }
 
         doc.add_u64(self.schema.document_type, MutationType::ENTITY_TOMBSTONE_ID);
 
@@ -626,13 +724,16 @@ impl MutationIndex {
         let ordering_value = query
             .ordering
             .value
+            .as_ref()
             .ok_or(Error::ProtoFieldExpected("ordering.value"))?;
+        let then_fields = self.then_sort_fields(&query.ordering, query.trait_name.as_deref())?;
         let (mutations, total) = match ordering_value {
             ordering::Value::Score(_) => {
                 let collector = self.match_score_collector(
                     &query.paging,
                     query.ordering.ascending,
                     query.ordering.no_recency_boost,
+                    then_fields,
                 );
                 self.execute_tantity_query_with_collector(
                     searcher,
@@ -640,76 +741,55 @@ impl MutationIndex {
                     collector,
                 )?
             }
-            ordering::Value::OperationId(_) => {
-                let sort_field = self.schema.operation_id;
+            other => {
+                let sort_field = self.ordering_sort_field(other, query.trait_name.as_deref())?;
                 let collector = self.sorted_field_collector(
                     &query.paging,
                     sort_field,
                     query.ordering.ascending,
+                    then_fields,
                 );
-                self.execute_tantity_query_with_collector(
-                    searcher,
-                    query.tantivy.as_ref(),
-                    collector,
-                )?
-            }
-            ordering::Value::CreatedAt(_) => {
-                let collector = self.sorted_field_collector(
-                    &query.paging,
-                    self.schema.creation_date,
-                    query.ordering.ascending,
-                );
-                self.execute_tantity_query_with_collector(
-                    searcher,
-                    query.tantivy.as_ref(),
-                    collector,
-                )?
-            }
-            ordering::Value::UpdatedAt(_) => {
-                let collector = self.sorted_field_collector(
-                    &query.paging,
-                    self.schema.modification_date,
-                    query.ordering.ascending,
-                );
-                self.execute_tantity_query_with_collector(
-                    searcher,
-                    query.tantivy.as_ref(),
-                    collector,
-                )?
-            }
-            ordering::Value::Field(field_name) => {
-                let trait_name = query.trait_name.ok_or_else(|| {
-                    Error::QueryParsing(anyhow!("Ordering by field only supported in trait query",))
-                })?;
 
-                let sort_field = self
-                    .schema
-                    .get_dynamic_trait_field(&trait_name, &field_name)?;
-                if !sort_field.is_fast_field {
-                    return Err(Error::QueryParsing(anyhow!(
-                        "Cannot sort by field '{}' as it's not sortable in  trait '{}'",
-                        field_name,
-                        trait_name,
-                    )));
+                // mutations out of the paging's bounds are skipped instead of being paged
+                // through, which would otherwise require going through all of them
+                if let Some((min, max)) = paging_sort_value_bounds(&query.paging) {
+                    let collector = FilterCollector::new(
+                        sort_field,
+                        move |value: u64| value >= min && value <= max,
+                        collector,
+                    );
+                    self.execute_tantity_query_with_collector(
+                        searcher,
+                        query.tantivy.as_ref(),
+                        collector,
+                    )?
+                } else {
+                    self.execute_tantity_query_with_collector(
+                        searcher,
+                        query.tantivy.as_ref(),
+                        collector,
+                    )?
                 }
-
-                let collector = self.sorted_field_collector(
-                    &query.paging,
-                    sort_field.field,
-                    query.ordering.ascending,
-                );
-                self.execute_tantity_query_with_collector(
-                    searcher,
-                    query.tantivy.as_ref(),
-                    collector,
-                )?
             }
         };
 
+        let similarity_exhausted = query
+            .similarity_exhausted
+            .as_ref()
+            .is_some_and(|exhausted| exhausted.load(std::sync::atomic::Ordering::Relaxed));
+        if similarity_exhausted {
+            return Err(Error::Other(anyhow!(
+                "Similarity query reached its maximum of {} compared embeddings",
+                self.config.similarity_max_embeddings
+            )));
+        }
+
         let next_page = if mutations.len() >= query.paging.count as usize {
             Some(Paging {
                 count: query.paging.count,
                 offset: query.paging.offset + mutations.len() as u32,
+                after_ordering_value: query.paging.after_ordering_value.clone(),
+                before_ordering_value: query.paging.before_ordering_value.clone(),
                 ..Default::default()
             })
         } else {
@@ -725,6 +805,72 @@ impl MutationIndex {
         })
     }
 
+    /// Returns the fast field by which results are sorted for the given
+    /// ordering value.
+    fn ordering_sort_field(
+        &self,
+        ordering_value: &ordering::Value,
+        trait_name: Option<&str>,
+    ) -> Result<Field, Error> {
+        match ordering_value {
+            ordering::Value::Score(_) => Err(Error::QueryParsing(anyhow!(
+                "Ordering by score is only supported as primary ordering"
+            ))),
+            ordering::Value::OperationId(_) => Ok(self.schema.operation_id),
+            ordering::Value::CreatedAt(_) => Ok(self.schema.creation_date),
+            ordering::Value::UpdatedAt(_) => Ok(self.schema.modification_date),
+            ordering::Value::BlockOffset(_) => Ok(self.schema.block_offset),
+            ordering::Value::Field(field_name) => {
+                let trait_name = trait_name.ok_or_else(|| {
+                    Error::QueryParsing(anyhow!("Ordering by field only supported in trait query",))
+                })?;
+
+                let sort_field = self
+                    .schema
+                    .get_dynamic_trait_field(trait_name, field_name)?;
+                if !sort_field.is_fast_field {
+                    return Err(Error::QueryParsing(anyhow!(
+                        "Cannot sort by field '{}' as it's not sortable in  trait '{}'",
+                        field_name,
+                        trait_name,
+                    )));
+                }
+
+                Ok(sort_field.field)
+            }
+        }
+    }
+
+    /// Returns the fast fields by which results with equal primary ordering
+    /// values are sorted (see `Ordering.then`).
+    fn then_sort_fields(
+        &self,
+        ordering: &Ordering,
+        trait_name: Option<&str>,
+    ) -> Result<Vec<ThenSortField>, Error> {
+        ordering
+            .then
+            .iter()
+            .map(|then| {
+                if !then.then.is_empty() {
+                    return Err(Error::QueryParsing(anyhow!(
+                        "Nested `then` orderings are not supported"
+                    )));
+                }
+
+                let value = then
+                    .value
+                    .as_ref()
+                    .ok_or(Error::ProtoFieldExpected("ordering.then.value"))?;
+
+                Ok(ThenSortField {
+                    field: self.ordering_sort_field(value, trait_name)?,
+                    reverse: then.ascending != ordering.ascending,
+                })
+            })
+            .collect()
+    }
+
     /// Execute query on Tantivy index and build mutations metadata results.
     fn execute_tantity_query_with_collector<S, C>(
         &self,
@@ -768,10 +914,14 @@ impl MutationIndex {
                             .unwrap_or(false);
                 }
 
+                let idempotency_key =
+                    schema::get_doc_opt_u64_value(&doc, self.schema.idempotency_key);
+
                 let result = MutationMetadata {
                     operation_id,
                     block_offset,
                     entity_id,
+                    idempotency_key,
                     mutation_type,
                     sort_value,
                 };
@@ -785,12 +935,14 @@ impl MutationIndex {
     }
 
     /// Creates a Tantivy top document collectors that sort by the given fast
-    /// field and limits the result by the requested paging.
+    /// field, then by the `then` fields, and limits the result by the
+    /// requested paging.
     fn sorted_field_collector(
         &self,
         paging: &Paging,
         sort_field: Field,
         ascending: bool,
+        then_fields: Vec<ThenSortField>,
     ) -> impl Collector<Fruit = Vec<(OrderingValueWrapper, DocAddress)>> {
         let operation_id_field = self.schema.operation_id;
         TopDocs::with_limit(paging.count as usize)
@@ -805,12 +957,17 @@ impl MutationIndex {
                     .fast_fields()
                     .u64(sort_field)
                     .expect("Field requested is not a i64/u64 fast field.");
+
+                let then_readers = ThenSortReaders::new(segment_reader, &then_fields);
+
                 move |doc_id| OrderingValueWrapper {
                     value: OrderingValue {
                         value: Some(ordering_value::Value::Uint64(
                             sort_fast_field.get_val(doc_id),
                         )),
                         operation_id: operation_id_reader.get_val(doc_id),
+                        then: then_readers.values(doc_id),
+                        ..Default::default()
                     },
                     reverse: ascending,
                     ignore: false,
@@ -825,6 +982,7 @@ impl MutationIndex {
         paging: &Paging,
         ascending: bool,
         no_recency_boost: bool,
+        then_fields: Vec<ThenSortField>,
     ) -> impl Collector<Fruit = Vec<(OrderingValueWrapper, DocAddress)>> {
         let now = Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64;
         let operation_id_field = self.schema.operation_id;
@@ -843,6 +1001,8 @@ impl MutationIndex {
                     .u64(modification_date_field)
                     .unwrap();
 
+                let then_readers = ThenSortReaders::new(segment_reader, &then_fields);
+
                 move |doc_id, score| {
                     let operation_id = operation_id_reader.get_val(doc_id);
 
@@ -861,6 +1021,8 @@ impl MutationIndex {
                         value: OrderingValue {
                             value: Some(ordering_value::Value::Float(score)),
                             operation_id,
+                            then: then_readers.values(doc_id),
+                            ..Default::default()
                         },
                         reverse: ascending,
                         ignore: false,
@@ -870,6 +1032,46 @@ impl MutationIndex {
     }
 }
 
+/// Fast field by which results with equal primary ordering values are sorted.
+#[derive(Clone, Copy)]
+struct ThenSortField {
+    field: Field,
+    reverse: bool,
+}
+
+/// Readers of a segment's `then` sort fast fields.
+struct ThenSortReaders {
+    readers: Vec<(Arc<dyn Column<u64>>, bool)>,
+}
+
+impl ThenSortReaders {
+    fn new(segment_reader: &SegmentReader, then_fields: &[ThenSortField]) -> ThenSortReaders {
+        let readers = then_fields
+            .iter()
+            .map(|then| {
+                let reader = segment_reader
+                    .fast_fields()
+                    .u64(then.field)
+                    .expect("Field requested is not a i64/u64 fast field.");
+                (reader, then.reverse)
+            })
+            .collect();
+
+        ThenSortReaders { readers }
+    }
+
+    fn values(&self, doc_id: DocId) -> Vec<OrderingValue> {
+        self.readers
+            .iter()
+            .map(|(reader, reverse)| OrderingValue {
+                value: Some(ordering_value::Value::Uint64(reader.get_val(doc_id))),
+                reverse: *reverse,
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
 fn dedup_results(storage: &Storage, results: &mut MutationResults) {
     let mut i = 0;
     let mut prev_operation = None;
@@ -890,6 +1092,25 @@ fn dedup_results(storage: &Storage, results: &mut MutationResults) {
     }
 }
 
+/// Returns the range of sort values of the mutations that can be within the
+/// ordering value bounds of the paging, if any. The range is inclusive since
+/// mutations with equal values are then ordered by other values, which are
+/// compared against the bounds once results are aggregated per entity.
+fn paging_sort_value_bounds(paging: &Paging) -> Option<(u64, u64)> {
+    let bound = |value: Option<&OrderingValue>| match value.and_then(|value| value.value.as_ref()) {
+        Some(ordering_value::Value::Uint64(value)) => Some(*value),
+        _ => None,
+    };
+
+    let min = bound(paging.after_ordering_value.as_ref());
+    let max = bound(paging.before_ordering_value.as_ref());
+    if min.is_none() && max.is_none() {
+        return None;
+    }
+
+    Some((min.unwrap_or(u64::MIN), max.unwrap_or(u64::MAX)))
+}
+
 fn index_settings() -> IndexSettings {
     IndexSettings {
         sort_by_field: Some(IndexSortByField {
@@ -39,6 +39,7 @@ pub struct PutTraitMutation {
     pub block_offset: Option<BlockOffset>,
     pub operation_id: OperationId,
     pub entity_id: EntityId,
+    pub idempotency_key: Option<u64>,
     pub trt: Trait,
 }
 
@@ -46,6 +47,7 @@ pub struct PutTraitTombstoneMutation {
     pub block_offset: Option<BlockOffset>,
     pub operation_id: OperationId,
     pub entity_id: EntityId,
+    pub idempotency_key: Option<u64>,
     pub trait_id: TraitId,
 }
 
@@ -53,6 +55,13 @@ pub struct PutEntityTombstoneMutation {
     pub block_offset: Option<BlockOffset>,
     pub operation_id: OperationId,
     pub entity_id: EntityId,
+    pub idempotency_key: Option<u64>,
+}
+
+/// Returns the key identifying an entity mutation that may get written more
+/// than once, if it has one (see `EntityMutation.idempotency_key`).
+pub fn idempotency_key(entity_mutation: &EntityMutation) -> Option<u64> {
+    Some(entity_mutation.idempotency_key).filter(|key| *key != 0)
 }
 
 impl IndexOperation {
@@ -65,6 +74,7 @@ impl IndexOperation {
             return smallvec![];
         };
 
+        let idempotency_key = idempotency_key(&entity_mutation);
         let Some(mutation) = entity_mutation.mutation else {
             return smallvec![];
         };
@@ -79,6 +89,7 @@ impl IndexOperation {
                     block_offset: None,
                     operation_id: operation.operation_id,
                     entity_id: entity_mutation.entity_id,
+                    idempotency_key,
                     trt,
                 })]
             }
@@ -87,6 +98,7 @@ impl IndexOperation {
                     block_offset: None,
                     operation_id: operation.operation_id,
                     entity_id: entity_mutation.entity_id,
+                    idempotency_key,
                     trait_id: trt_del.trait_id,
                 }
             )],
@@ -95,6 +107,7 @@ impl IndexOperation {
                     block_offset: None,
                     operation_id: operation.operation_id,
                     entity_id: entity_mutation.entity_id,
+                    idempotency_key,
                 }
             )],
             Mutation::DeleteOperations(_) => {
@@ -132,6 +145,7 @@ impl IndexOperation {
         operation_id: OperationId,
         block_offset: BlockOffset,
     ) -> SmallVec<[IndexOperation; 1]> {
+        let idempotency_key = idempotency_key(&entity_mutation);
         let Some(mutation) = entity_mutation.mutation else {
             return smallvec![];
         };
@@ -146,6 +160,7 @@ impl IndexOperation {
                     block_offset: Some(block_offset),
                     operation_id,
                     entity_id: entity_mutation.entity_id,
+                    idempotency_key,
                     trt,
                 })]
             }
@@ -154,6 +169,7 @@ impl IndexOperation {
                     block_offset: Some(block_offset),
                     operation_id,
                     entity_id: entity_mutation.entity_id,
+                    idempotency_key,
                     trait_id: trt_del.trait_id,
                 }
             )],
@@ -162,6 +178,7 @@ impl IndexOperation {
                     block_offset: Some(block_offset),
                     operation_id,
                     entity_id: entity_mutation.entity_id,
+                    idempotency_key,
                 }
             )],
             Mutation::DeleteOperations(del_mut) => {
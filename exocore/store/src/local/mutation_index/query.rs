@@ -1,6 +1,8 @@
+use std::sync::{atomic::AtomicBool, Arc};
+
 use exocore_protos::store::{
     boolean_predicate, entity_query::Predicate, ordering, trait_field_predicate, trait_query,
-    EntityQuery, MatchPredicate, Ordering, Paging, TraitFieldPredicate,
+    EntityQuery, MatchPredicate, Ordering, Paging, SimilarityPredicate, TraitFieldPredicate,
     TraitFieldReferencePredicate,
 };
 use tantivy::{
@@ -9,7 +11,7 @@ use tantivy::{
     Index, Term,
 };
 
-use super::{schema::MutationIndexSchema, MutationIndexConfig};
+use super::{schema::MutationIndexSchema, similarity::SimilarityQuery, MutationIndexConfig};
 use crate::error::Error;
 
 pub(crate) struct ParsedQuery {
@@ -17,6 +19,11 @@ pub(crate) struct ParsedQuery {
     pub paging: Paging,
     pub ordering: Ordering,
     pub trait_name: Option<String>,
+
+    /// Set if a similarity predicate of the query reached its maximum number of
+    /// compared embeddings during the search, in which case results are
+    /// incomplete.
+    pub similarity_exhausted: Option<Arc<AtomicBool>>,
 }
 
 pub(crate) struct QueryParser<'s> {
@@ -28,6 +35,7 @@ pub(crate) struct QueryParser<'s> {
     paging: Paging,
     ordering: Ordering,
     trait_name: Option<String>,
+    similarity_exhausted: Option<Arc<AtomicBool>>,
 }
 
 impl<'s> QueryParser<'s> {
@@ -46,6 +54,7 @@ impl<'s> QueryParser<'s> {
             paging: Default::default(),
             ordering: Default::default(),
             trait_name: None,
+            similarity_exhausted: None,
         };
 
         parser.inner_parse()?;
@@ -61,6 +70,7 @@ impl<'s> QueryParser<'s> {
             paging: parser.paging,
             ordering: parser.ordering,
             trait_name: parser.trait_name,
+            similarity_exhausted: parser.similarity_exhausted,
         })
     }
 
@@ -71,12 +81,10 @@ impl<'s> QueryParser<'s> {
             .as_ref()
             .ok_or(Error::ProtoFieldExpected("predicate"))?;
 
-        self.paging = self.proto.paging.unwrap_or(Paging {
-            after_ordering_value: None,
-            before_ordering_value: None,
-            count: self.config.iterator_page_size,
-            offset: 0,
-        });
+        self.paging = self.proto.paging.clone().unwrap_or_default();
+        if self.paging.count == 0 {
+            self.paging.count = self.config.iterator_page_size;
+        }
         self.ordering = self.proto.ordering.clone().unwrap_or_default();
         self.tantivy = Some(self.parse_predicate(predicate)?);
 
@@ -93,6 +101,7 @@ impl<'s> QueryParser<'s> {
             Predicate::All(_all_pred) => self.parse_all_pred(),
             Predicate::Boolean(bool_pred) => self.parse_bool_pred(bool_pred),
             Predicate::QueryString(query_pred) => self.query_string_pred(query_pred),
+            Predicate::Similarity(sim_pred) => self.parse_similarity_pred(sim_pred),
             Predicate::Test(_) => Err(anyhow!("Query failed for tests").into()),
         }
     }
@@ -108,6 +117,32 @@ impl<'s> QueryParser<'s> {
         Ok(Box::new(self.new_fuzzy_match_query(field, text, no_fuzzy)?))
     }
 
+    fn parse_similarity_pred(
+        &mut self,
+        sim_pred: &SimilarityPredicate,
+    ) -> Result<Box<dyn Query>, Error> {
+        if self.ordering.value.is_none() {
+            self.ordering.value = Some(ordering::Value::Score(true));
+        }
+
+        let query = SimilarityQuery::new(
+            self.fields.embedding,
+            &sim_pred.vector,
+            sim_pred.min_similarity,
+            self.config.similarity_max_embeddings,
+            self.similarity_exhausted
+                .get_or_insert_with(Arc::default)
+                .clone(),
+        )
+        .ok_or_else(|| {
+            Error::QueryParsing(anyhow!(
+                "similarity predicate's vector needs to be non-empty and non-null"
+            ))
+        })?;
+
+        Ok(Box::new(query))
+    }
+
     fn parse_trait_pred(
         &mut self,
         trait_pred: &exocore_protos::store::TraitPredicate,
@@ -319,6 +354,7 @@ impl<'s> QueryParser<'s> {
                 SubPredicate::Operations(op_pred) => self.parse_operation_pred(op_pred)?,
                 SubPredicate::All(_all_pred) => self.parse_all_pred()?,
                 SubPredicate::Boolean(bool_pred) => self.parse_bool_pred(bool_pred)?,
+                SubPredicate::Similarity(sim_pred) => self.parse_similarity_pred(sim_pred)?,
             };
 
             let tantivy_occur = match ProtoOccur::try_from(sub_query.occur) {
@@ -439,7 +475,7 @@ impl<'s> QueryParser<'s> {
         text: &str,
         no_fuzzy: bool,
     ) -> Result<BooleanQuery, Error> {
-        let tok = self.index.tokenizer_for_field(field)?;
+        let tok = self.fields.query_tokenizer_for_field(self.index, field)?;
         let mut queries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
         let mut stream = tok.token_stream(text);
 
@@ -466,7 +502,7 @@ impl<'s> QueryParser<'s> {
     }
 
     fn new_phrase_query(&self, field: Field, text: &str) -> Result<Box<dyn Query>, Error> {
-        let tok = self.index.tokenizer_for_field(field)?;
+        let tok = self.fields.query_tokenizer_for_field(self.index, field)?;
         let mut terms = Vec::new();
         let mut stream = tok.token_stream(text);
 
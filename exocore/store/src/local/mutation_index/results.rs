@@ -31,7 +31,7 @@ impl<Q: Borrow<EntityQuery>> Iterator for MutationResultIterator<'_, Q> {
             Some(next_result)
         } else {
             let mut query = self.query.borrow().clone();
-            query.paging = Some(self.next_page?);
+            query.paging = Some(self.next_page.clone()?);
 
             if self.max_pages == 0 {
                 debug!(
@@ -73,6 +73,7 @@ pub struct MutationMetadata {
     pub operation_id: OperationId,
     pub block_offset: Option<BlockOffset>,
     pub entity_id: EntityId,
+    pub idempotency_key: Option<u64>,
     pub mutation_type: MutationType,
     pub sort_value: OrderingValueWrapper,
 }
@@ -6,7 +6,7 @@ use exocore_protos::{
 };
 use tantivy::{schema::*, tokenizer::*, Document};
 
-use super::MutationIndexConfig;
+use super::{MutationIndexConfig, TextAnalyzerKind};
 use crate::error::Error;
 
 /// Schema that contains Tantivy fields for mutations and indexed messages.
@@ -27,18 +27,22 @@ pub(crate) struct MutationIndexSchema {
     pub block_offset: Field,
     pub operation_id: Field,
     pub document_type: Field,
+    pub idempotency_key: Field,
 
     pub all_text: Field,
     pub all_refs: Field,
 
     pub has_reference: Field,
 
+    pub embedding: Field,
+
     // mapping for indexed/sorted fields of messages in registry
     // message type -> field name -> tantivy field
     pub dynamic_fields: DynamicFieldsMapping,
     pub short_names: HashMap<String, String>,
 
     pub references_tokenizer: TextAnalyzer,
+    pub text_analyzers: Vec<TextAnalyzerKind>,
 }
 
 impl MutationIndexSchema {
@@ -60,6 +64,7 @@ impl MutationIndexSchema {
                 .set_fast(Cardinality::SingleValue),
         );
         let document_type = schema_builder.add_u64_field("document_type", STORED);
+        let idempotency_key = schema_builder.add_u64_field("idempotency_key", STORED);
 
         // Tokenize references by space, but no stemming, case folding or length limit
         let references_tokenizer = TextAnalyzer::from(SimpleTokenizer);
@@ -71,15 +76,20 @@ impl MutationIndexSchema {
             )
             .set_stored();
 
-        let all_text = schema_builder.add_text_field("all_text", TEXT);
+        let all_text =
+            schema_builder.add_text_field("all_text", text_options(config.text_analyzer));
         let all_refs = schema_builder.add_text_field("all_refs", references_options.clone());
         let has_reference = schema_builder.add_u64_field("has_reference", STORED);
+        let embedding =
+            schema_builder.add_bytes_field("embedding", BytesOptions::default().set_fast());
 
+        let text_analyzers = registry_text_analyzers(&config, registry);
         let dynamic_fields = build_dynamic_fields_tantivy_schema(
             &config,
             registry,
             &mut schema_builder,
             references_options,
+            &text_analyzers,
         );
 
         let short_names = build_schema_short_type_mapping(registry);
@@ -96,16 +106,20 @@ impl MutationIndexSchema {
             block_offset,
             operation_id,
             document_type,
+            idempotency_key,
 
             all_text,
             all_refs,
 
             has_reference,
 
+            embedding,
+
             dynamic_fields,
             short_names,
 
             references_tokenizer,
+            text_analyzers,
         }
     }
 
@@ -148,6 +162,31 @@ impl MutationIndexSchema {
         index
             .tokenizers()
             .register("references", self.references_tokenizer.clone());
+
+        for analyzer in &self.text_analyzers {
+            index
+                .tokenizers()
+                .register(analyzer.tokenizer_name(), analyzer.index_analyzer());
+        }
+    }
+
+    /// Returns the analyzer to use to tokenize query text for the given field.
+    pub fn query_tokenizer_for_field(
+        &self,
+        index: &tantivy::Index,
+        field: Field,
+    ) -> Result<TextAnalyzer, Error> {
+        let tokenizer_name = match self.tantivy.get_field_entry(field).field_type() {
+            tantivy::schema::FieldType::Str(options) => options
+                .get_indexing_options()
+                .map(|indexing| indexing.tokenizer()),
+            _ => None,
+        };
+
+        match tokenizer_name.and_then(TextAnalyzerKind::from_tokenizer_name) {
+            Some(analyzer) => Ok(analyzer.query_analyzer()),
+            None => Ok(index.tokenizer_for_field(field)?),
+        }
     }
 
     pub fn get_message_name_from_short(&self, short_name: &str) -> Option<&str> {
@@ -180,6 +219,7 @@ pub(crate) type DynamicFieldsMapping = HashMap<String, BTreeMap<String, MappedDy
 pub(crate) struct DynamicFields {
     reference: Vec<Field>,
     text: Vec<Field>,
+    analyzed_text: HashMap<&'static str, Vec<Field>>,
     string: Vec<Field>,
     i64: Vec<Field>,
     i64_fast: Vec<Field>,
@@ -192,6 +232,7 @@ impl DynamicFields {
         index_config: &MutationIndexConfig,
         schema_builder: &mut SchemaBuilder,
         references_options: TextOptions,
+        text_analyzers: &[TextAnalyzerKind],
     ) -> DynamicFields {
         let mut dyn_fields = DynamicFields::default();
 
@@ -202,9 +243,24 @@ impl DynamicFields {
             );
         }
         for i in 0..index_config.dynamic_text_fields {
-            dyn_fields
-                .text
-                .push(schema_builder.add_text_field(&format!("text_{}", i), TEXT));
+            dyn_fields.text.push(schema_builder.add_text_field(
+                &format!("text_{}", i),
+                text_options(index_config.text_analyzer),
+            ));
+        }
+        for analyzer in text_analyzers {
+            if *analyzer == index_config.text_analyzer {
+                continue;
+            }
+
+            let name = analyzer.tokenizer_name();
+            let fields = (0..index_config.dynamic_analyzed_text_fields)
+                .map(|i| {
+                    schema_builder
+                        .add_text_field(&format!("text_{}_{}", name, i), text_options(*analyzer))
+                })
+                .collect();
+            dyn_fields.analyzed_text.insert(name, fields);
         }
         for i in 0..index_config.dynamic_string_fields {
             dyn_fields
@@ -251,8 +307,14 @@ fn build_dynamic_fields_tantivy_schema(
     registry: &Registry,
     schema_builder: &mut SchemaBuilder,
     references_options: TextOptions,
+    text_analyzers: &[TextAnalyzerKind],
 ) -> DynamicFieldsMapping {
-    let mut dyn_fields = DynamicFields::new(index_config, schema_builder, references_options);
+    let mut dyn_fields = DynamicFields::new(
+        index_config,
+        schema_builder,
+        references_options,
+        text_analyzers,
+    );
 
     // map fields of each message in registry that need to be indexed / sortable to
     // dynamic fields
@@ -262,7 +324,14 @@ fn build_dynamic_fields_tantivy_schema(
         let mut msg_fields = MsgFields::default();
 
         for field in message_descriptor.fields.values() {
-            msg_fields.add_field(None, registry, &mut dyn_fields, &message_descriptor, field);
+            msg_fields.add_field(
+                None,
+                index_config,
+                registry,
+                &mut dyn_fields,
+                &message_descriptor,
+                field,
+            );
         }
 
         if !msg_fields.mapping.is_empty() {
@@ -273,6 +342,58 @@ fn build_dynamic_fields_tantivy_schema(
     dyn_mappings
 }
 
+/// Collects the analyzers needed by the index: the index's analyzer and the
+/// analyzers explicitly specified by text fields of registered messages.
+///
+/// Since each analyzer requires its own set of dynamic fields, analyzers are
+/// sorted to keep the Tantivy schema stable for a given registry.
+fn registry_text_analyzers(
+    index_config: &MutationIndexConfig,
+    registry: &Registry,
+) -> Vec<TextAnalyzerKind> {
+    let mut analyzers = vec![index_config.text_analyzer];
+    for message_descriptor in registry.message_descriptors() {
+        for field in message_descriptor.fields.values() {
+            if let Some(analyzer) = field_text_analyzer(field) {
+                if !analyzers.contains(&analyzer) {
+                    analyzers.push(analyzer);
+                }
+            }
+        }
+    }
+    analyzers.sort_by_key(|analyzer| analyzer.tokenizer_name());
+    analyzers
+}
+
+/// Returns the analyzer explicitly specified by a text field, if any.
+fn field_text_analyzer(field_desc: &FieldDescriptor) -> Option<TextAnalyzerKind> {
+    if !field_desc.text_flag {
+        return None;
+    }
+
+    let analyzer = field_desc.text_analyzer.as_ref()?;
+    match analyzer.parse() {
+        Ok(analyzer) => Some(analyzer),
+        Err(err) => {
+            error!(
+                "Invalid text analyzer for field {}: {}. Using index's analyzer.",
+                field_desc.name, err
+            );
+            None
+        }
+    }
+}
+
+/// Full-text indexing options for the given analyzer. Equivalent to
+/// `TEXT`, but with the analyzer's tokenizer.
+fn text_options(analyzer: TextAnalyzerKind) -> TextOptions {
+    TextOptions::default().set_indexing_options(
+        TextFieldIndexing::default()
+            .set_tokenizer(analyzer.tokenizer_name())
+            .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+    )
+}
+
 /// Creates a map of short type names to their full type names.
 fn build_schema_short_type_mapping(registry: &Registry) -> HashMap<String, String> {
     let mut mapping = HashMap::new();
@@ -299,6 +420,7 @@ struct MsgFields {
     mapping: BTreeMap<String, MappedDynamicField>,
     ref_count: usize,
     text_count: usize,
+    analyzed_text_count: HashMap<&'static str, usize>,
     string_count: usize,
     i64_count: usize,
     i64_fast_count: usize,
@@ -310,6 +432,7 @@ impl MsgFields {
     fn add_field(
         &mut self,
         prefix: Option<&str>,
+        index_config: &MutationIndexConfig,
         registry: &Registry,
         dyn_fields: &mut DynamicFields,
         msg_desc: &ReflectMessageDescriptor,
@@ -385,7 +508,23 @@ impl MsgFields {
             }
 
             FieldType::String => {
-                if field_desc.text_flag && self.text_count < dyn_fields.text.len() {
+                let analyzer = field_text_analyzer(field_desc)
+                    .filter(|analyzer| *analyzer != index_config.text_analyzer);
+                if let Some(analyzer) = analyzer {
+                    let name = analyzer.tokenizer_name();
+                    let count = self.analyzed_text_count.entry(name).or_default();
+                    let fields = &dyn_fields.analyzed_text[name];
+                    if *count < fields.len() {
+                        let mapped_field = MappedDynamicField {
+                            field: fields[*count],
+                            field_type: ft,
+                            is_fast_field: false,
+                        };
+                        self.mapping.insert(field_name, mapped_field);
+                        *count += 1;
+                        return;
+                    }
+                } else if field_desc.text_flag && self.text_count < dyn_fields.text.len() {
                     let mapped_field = MappedDynamicField {
                         field: dyn_fields.text[self.text_count],
                         field_type: ft,
@@ -411,6 +550,7 @@ impl MsgFields {
                     for field in sub_msg_desc.fields.values() {
                         self.add_field(
                             Some(&field_name),
+                            index_config,
                             registry,
                             dyn_fields,
                             msg_desc, /* we add field onto main msg mapping so that we can
@@ -425,7 +565,7 @@ impl MsgFields {
                 }
             },
 
-            FieldType::Repeated(_) => {
+            FieldType::Float | FieldType::Repeated(_) => {
                 // not supported
             }
         }
@@ -0,0 +1,304 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+use tantivy::{
+    fastfield::BytesFastFieldReader,
+    query::{EnableScoring, Explanation, Query, Scorer, Weight},
+    schema::Field,
+    DocId, DocSet, Score, SegmentReader, TantivyError, TERMINATED,
+};
+
+const F32_SIZE: usize = std::mem::size_of::<f32>();
+const DIMENSIONS_SIZE: usize = std::mem::size_of::<u32>();
+
+/// Encodes the embedding vectors of a trait to be stored in the embedding fast
+/// field of the index.
+///
+/// Each vector is prefixed by its number of dimensions, and is normalized so
+/// that cosine similarity can be computed with a simple dot product at query
+/// time. Vectors that are empty or have a null norm are skipped since they
+/// can't be compared. Returns `None` if no vector can be compared.
+pub(crate) fn encode_embeddings<'v>(vectors: impl Iterator<Item = &'v [f32]>) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for vector in vectors {
+        let Some(normalized) = normalize(vector) else {
+            continue;
+        };
+
+        bytes.extend_from_slice(&(normalized.len() as u32).to_le_bytes());
+        for value in normalized {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    if bytes.is_empty() {
+        return None;
+    }
+
+    Some(bytes)
+}
+
+/// Iterates over the embeddings encoded by `encode_embeddings`, as slices of
+/// little-endian `f32` bytes.
+fn decode_embeddings(mut bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    std::iter::from_fn(move || {
+        if bytes.len() < DIMENSIONS_SIZE {
+            return None;
+        }
+
+        let (dims, rest) = bytes.split_at(DIMENSIONS_SIZE);
+        let size = u32::from_le_bytes(dims.try_into().unwrap()) as usize * F32_SIZE;
+        if rest.len() < size {
+            return None;
+        }
+
+        let (embedding, rest) = rest.split_at(size);
+        bytes = rest;
+        Some(embedding)
+    })
+}
+
+fn normalize(vector: &[f32]) -> Option<Vec<f32>> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if vector.is_empty() || !norm.is_normal() {
+        return None;
+    }
+
+    Some(vector.iter().map(|v| v / norm).collect())
+}
+
+/// Tantivy query that matches documents having an embedding vector similar to
+/// the query's vector.
+///
+/// Documents are scored by the highest cosine similarity of their embeddings
+/// with the query vector.
+///
+/// There is no approximate nearest neighbour structure: embeddings are
+/// compared one by one, which is fine for the size of a personal index. To
+/// bound the cost of a query, at most `max_embeddings` embeddings are compared
+/// per search. When this maximum is reached, the search stops and the
+/// `exhausted` flag is set so that the search can be failed instead of
+/// returning incomplete results.
+#[derive(Clone, Debug)]
+pub(crate) struct SimilarityQuery {
+    field: Field,
+    vector: Arc<Vec<f32>>,
+    min_similarity: f32,
+    max_embeddings: usize,
+    exhausted: Arc<AtomicBool>,
+}
+
+impl SimilarityQuery {
+    pub fn new(
+        field: Field,
+        vector: &[f32],
+        min_similarity: f32,
+        max_embeddings: usize,
+        exhausted: Arc<AtomicBool>,
+    ) -> Option<SimilarityQuery> {
+        Some(SimilarityQuery {
+            field,
+            vector: Arc::new(normalize(vector)?),
+            min_similarity,
+            max_embeddings,
+            exhausted,
+        })
+    }
+}
+
+impl Query for SimilarityQuery {
+    fn weight(&self, _enable_scoring: EnableScoring<'_>) -> tantivy::Result<Box<dyn Weight>> {
+        Ok(Box::new(SimilarityWeight {
+            query: self.clone(),
+            remaining: Arc::new(AtomicUsize::new(self.max_embeddings)),
+        }))
+    }
+}
+
+struct SimilarityWeight {
+    query: SimilarityQuery,
+
+    // number of embeddings that can still be compared, shared by the scorers of
+    // all segments of the search
+    remaining: Arc<AtomicUsize>,
+}
+
+impl SimilarityWeight {
+    fn new_scorer(
+        &self,
+        reader: &SegmentReader,
+        boost: Score,
+        remaining: Arc<AtomicUsize>,
+        exhausted: Arc<AtomicBool>,
+    ) -> tantivy::Result<SimilarityScorer> {
+        let embeddings = reader.fast_fields().bytes(self.query.field)?;
+        let mut scorer = SimilarityScorer {
+            embeddings,
+            vector: self.query.vector.clone(),
+            min_similarity: self.query.min_similarity,
+            remaining,
+            exhausted,
+            boost,
+            max_doc: reader.max_doc(),
+            doc: 0,
+            score: 0.0,
+        };
+        scorer.seek_match(0);
+
+        Ok(scorer)
+    }
+}
+
+impl Weight for SimilarityWeight {
+    fn scorer(&self, reader: &SegmentReader, boost: Score) -> tantivy::Result<Box<dyn Scorer>> {
+        let scorer = self.new_scorer(
+            reader,
+            boost,
+            self.remaining.clone(),
+            self.query.exhausted.clone(),
+        )?;
+        Ok(Box::new(scorer))
+    }
+
+    fn explain(&self, reader: &SegmentReader, doc: DocId) -> tantivy::Result<Explanation> {
+        // explaining a single document doesn't count against the search's budget
+        let remaining = Arc::new(AtomicUsize::new(usize::MAX));
+        let mut scorer = self.new_scorer(reader, 1.0, remaining, Arc::default())?;
+        if scorer.seek(doc) != doc {
+            return Err(TantivyError::InvalidArgument(format!(
+                "Document #({doc}) does not match"
+            )));
+        }
+
+        Ok(Explanation::new("Cosine similarity", scorer.score()))
+    }
+}
+
+struct SimilarityScorer {
+    embeddings: BytesFastFieldReader,
+    vector: Arc<Vec<f32>>,
+    min_similarity: f32,
+    remaining: Arc<AtomicUsize>,
+    exhausted: Arc<AtomicBool>,
+    boost: Score,
+    max_doc: DocId,
+    doc: DocId,
+    score: Score,
+}
+
+impl SimilarityScorer {
+    /// Positions the scorer on the first matching document starting at the
+    /// given document.
+    fn seek_match(&mut self, from: DocId) -> DocId {
+        let mut doc = from;
+        while doc < self.max_doc {
+            if !self.consume_budget(doc) {
+                self.exhausted.store(true, Ordering::Relaxed);
+                break;
+            }
+
+            if let Some(similarity) = self.similarity(doc) {
+                if similarity > self.min_similarity {
+                    self.doc = doc;
+                    self.score = similarity * self.boost;
+                    return doc;
+                }
+            }
+            doc += 1;
+        }
+
+        self.doc = TERMINATED;
+        TERMINATED
+    }
+
+    /// Consumes the shared budget of compared embeddings for the given
+    /// document. Returns `false` if the budget is exhausted.
+    fn consume_budget(&self, doc: DocId) -> bool {
+        let count = decode_embeddings(self.embeddings.get_bytes(doc)).count();
+        if count == 0 {
+            return true;
+        }
+
+        self.remaining
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |remaining| {
+                remaining.checked_sub(count)
+            })
+            .is_ok()
+    }
+
+    fn similarity(&self, doc: DocId) -> Option<f32> {
+        let size = self.vector.len() * F32_SIZE;
+        decode_embeddings(self.embeddings.get_bytes(doc))
+            .filter(|embedding| embedding.len() == size) // ignore other dimensions
+            .map(|embedding| {
+                embedding
+                    .chunks_exact(F32_SIZE)
+                    .zip(self.vector.iter())
+                    .map(|(chunk, value)| {
+                        let embedding_value = f32::from_le_bytes(chunk.try_into().unwrap());
+                        embedding_value * value
+                    })
+                    .sum::<f32>()
+            })
+            .reduce(f32::max)
+    }
+}
+
+impl DocSet for SimilarityScorer {
+    fn advance(&mut self) -> DocId {
+        if self.doc == TERMINATED {
+            return TERMINATED;
+        }
+
+        self.seek_match(self.doc + 1)
+    }
+
+    fn seek(&mut self, target: DocId) -> DocId {
+        if self.doc >= target {
+            return self.doc;
+        }
+
+        self.seek_match(target)
+    }
+
+    fn doc(&self) -> DocId {
+        self.doc
+    }
+
+    fn size_hint(&self) -> u32 {
+        self.max_doc
+    }
+}
+
+impl Scorer for SimilarityScorer {
+    fn score(&mut self) -> Score {
+        self.score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_normalized_embeddings() {
+        let vectors: [&[f32]; 3] = [&[3.0, 4.0], &[0.0, 0.0], &[2.0]];
+        let bytes = encode_embeddings(vectors.into_iter()).unwrap();
+        let embeddings = decode_embeddings(&bytes)
+            .map(|embedding| {
+                embedding
+                    .chunks_exact(F32_SIZE)
+                    .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(embeddings, vec![vec![0.6, 0.8], vec![1.0]]);
+
+        let empty: [&[f32]; 1] = [&[]];
+        assert!(encode_embeddings(empty.into_iter()).is_none());
+        let null: [&[f32]; 1] = [&[0.0, 0.0]];
+        assert!(encode_embeddings(null.into_iter()).is_none());
+    }
+}
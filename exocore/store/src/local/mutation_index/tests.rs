@@ -6,7 +6,10 @@ use exocore_protos::{
         exocore_test::{TestMessage, TestMessage2},
     },
     prost::{Any, ProstAnyPackMessageExt, ProstDateTimeExt},
-    store::{entity_query, MatchPredicate, TraitDetails},
+    store::{
+        boolean_predicate, entity_query, BooleanPredicate, IdsPredicate, MatchPredicate,
+        SimilarityPredicate, TraitDetails,
+    },
     test::TestStruct,
 };
 use itertools::Itertools;
@@ -27,6 +30,7 @@ fn fetch_entity_mutations() -> anyhow::Result<()> {
         block_offset: Some(1),
         operation_id: 10,
         entity_id: "entity_id1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "foo1".to_string(),
             message: Some(
@@ -44,6 +48,7 @@ fn fetch_entity_mutations() -> anyhow::Result<()> {
         block_offset: Some(2),
         operation_id: 20,
         entity_id: "entity_id2".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "foo2".to_string(),
             message: Some(
@@ -61,6 +66,7 @@ fn fetch_entity_mutations() -> anyhow::Result<()> {
         block_offset: Some(3),
         operation_id: 21,
         entity_id: "entity_id2".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "foo3".to_string(),
             message: Some(
@@ -110,6 +116,7 @@ fn search_query_matches() -> anyhow::Result<()> {
         block_offset: Some(1),
         operation_id: 10,
         entity_id: "entity_id1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "foo1".to_string(),
             modification_date: Some(now.to_proto_timestamp()),
@@ -128,6 +135,7 @@ fn search_query_matches() -> anyhow::Result<()> {
         block_offset: Some(2),
         operation_id: 20,
         entity_id: "entity_id2".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "foo2".to_string(),
             modification_date: Some(now.to_proto_timestamp()),
@@ -181,6 +189,7 @@ fn search_query_matches_no_fuzzy() -> anyhow::Result<()> {
         block_offset: Some(1),
         operation_id: 10,
         entity_id: "entity_id1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "foo1".to_string(),
             message: Some(
@@ -232,6 +241,7 @@ fn search_query_matches_recent_boost() -> anyhow::Result<()> {
         block_offset: Some(1),
         operation_id: 10,
         entity_id: "entity_id1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "foo1".to_string(),
             modification_date: Some(now.to_proto_timestamp()),
@@ -251,6 +261,7 @@ fn search_query_matches_recent_boost() -> anyhow::Result<()> {
         block_offset: Some(2),
         operation_id: 11,
         entity_id: "entity_id2".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "foo2".to_string(),
             modification_date: Some(two_year.to_proto_timestamp()),
@@ -270,6 +281,7 @@ fn search_query_matches_recent_boost() -> anyhow::Result<()> {
         block_offset: Some(3),
         operation_id: 12,
         entity_id: "entity_id3".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "foo3".to_string(),
             modification_date: Some(one_year.to_proto_timestamp()),
@@ -316,6 +328,7 @@ fn search_query_string() -> anyhow::Result<()> {
         block_offset: Some(1),
         operation_id: 10,
         entity_id: "entity_id1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "foo1".to_string(),
             modification_date: Some(now.to_proto_timestamp()),
@@ -334,6 +347,7 @@ fn search_query_string() -> anyhow::Result<()> {
         block_offset: Some(2),
         operation_id: 20,
         entity_id: "entity_id2".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "foo2".to_string(),
             modification_date: Some(now.to_proto_timestamp()),
@@ -410,6 +424,7 @@ fn search_query_matches_paging() -> anyhow::Result<()> {
             block_offset: Some(i),
             operation_id: i,
             entity_id: format!("entity_id{}", i),
+            idempotency_key: None,
             trt: Trait {
                 id: format!("id{}", i),
                 message: Some(
@@ -428,7 +443,7 @@ fn search_query_matches_paging() -> anyhow::Result<()> {
 
     let query = Q::matches("foo").count(10).build();
     let res1 = index.search(query)?;
-    let res1_next_page = res1.next_page.unwrap();
+    let res1_next_page = res1.next_page.clone().unwrap();
     assert_eq!(res1.total, 30);
     assert_eq!(res1.mutations.len(), 10);
     assert_eq!(res1.remaining, 20);
@@ -438,7 +453,7 @@ fn search_query_matches_paging() -> anyhow::Result<()> {
 
     let query = Q::matches("foo").with_paging(res1_next_page).build();
     let res2 = index.search(query)?;
-    let res2_next_page = res2.next_page.unwrap();
+    let res2_next_page = res2.next_page.clone().unwrap();
     assert_eq!(res2.total, 30);
     assert_eq!(res2.mutations.len(), 10);
     assert_eq!(res2.remaining, 10);
@@ -486,6 +501,7 @@ fn search_query_by_trait_type() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 1,
         entity_id: "entity_id1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trait1".to_string(),
             message: Some(
@@ -503,6 +519,7 @@ fn search_query_by_trait_type() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 2,
         entity_id: "entity_id2".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trait2".to_string(),
             message: Some(
@@ -520,6 +537,7 @@ fn search_query_by_trait_type() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 3,
         entity_id: "entity_id3".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trait3".to_string(),
             message: Some(
@@ -537,6 +555,7 @@ fn search_query_by_trait_type() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 4,
         entity_id: "entity_id4".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trait4".to_string(),
             message: Some(
@@ -598,6 +617,7 @@ fn search_query_by_trait_type_paging() -> anyhow::Result<()> {
             block_offset: Some(i),
             operation_id: 30 - i,
             entity_id: format!("entity_id{}", i),
+            idempotency_key: None,
             trt: Trait {
                 id: format!("entity_id{}", i),
                 message: Some(
@@ -663,6 +683,7 @@ fn sort_by_field() -> anyhow::Result<()> {
             block_offset: Some(i),
             operation_id: 20 - i,
             entity_id: format!("entity_id{}", i),
+            idempotency_key: None,
             trt: Trait {
                 id: format!("trait{}", i),
                 message: Some(
@@ -703,6 +724,74 @@ fn sort_by_field() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn sort_by_multiple_fields() -> anyhow::Result<()> {
+    let registry = Arc::new(Registry::new_with_exocore_types());
+    let config = test_config();
+    let index = MutationIndex::create_in_memory(config, registry)?;
+
+    // groups of 5 traits with the same `uint3`, with `date3` in the inverse order of
+    // operations within a group
+    let now = Utc::now();
+    let traits = (0..20).map(|i| {
+        IndexOperation::PutTrait(PutTraitMutation {
+            block_offset: Some(i),
+            operation_id: 20 - i,
+            entity_id: format!("entity_id{}", i),
+            idempotency_key: None,
+            trt: Trait {
+                id: format!("trait{}", i),
+                message: Some(
+                    TestMessage {
+                        uint3: (i / 5) as u32,
+                        date3: Some(
+                            (now - Duration::try_days((i % 5) as i64).unwrap())
+                                .to_proto_timestamp(),
+                        ),
+                        ..Default::default()
+                    }
+                    .pack_to_any()
+                    .unwrap(),
+                ),
+                ..Default::default()
+            },
+        })
+    });
+    index.apply_operations(traits)?;
+
+    let query = Q::with_trait::<TestMessage>()
+        .order_by_field("uint3", false)
+        .then_order_by_field("date3", true)
+        .count(7);
+
+    let mut query_paging = query.paging_or_default();
+    let mut traits = Vec::new();
+    loop {
+        let res = index.search(query.clone().with_paging(query_paging).build())?;
+        traits.extend(extract_traits_id(&res));
+
+        match res.next_page {
+            Some(next_page) => query_paging = next_page,
+            None => break,
+        }
+    }
+
+    let expected = (0..4)
+        .rev()
+        .flat_map(|group| (0..5).rev().map(move |i| format!("trait{}", group * 5 + i)))
+        .collect_vec();
+    assert_eq!(traits, expected);
+
+    // score can only be used as primary ordering
+    let query = Q::with_trait::<TestMessage>()
+        .order_by_field("uint3", false)
+        .then_order_by(ordering::Value::Score(true), false)
+        .build();
+    assert!(index.search(query).is_err());
+
+    Ok(())
+}
+
 #[test]
 fn search_by_reference() -> anyhow::Result<()> {
     let registry = Arc::new(Registry::new_with_exocore_types());
@@ -713,6 +802,7 @@ fn search_by_reference() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 1,
         entity_id: "et1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt1".to_string(),
             message: Some(
@@ -733,6 +823,7 @@ fn search_by_reference() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 2,
         entity_id: "et2".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt2".to_string(),
             message: Some(
@@ -755,6 +846,7 @@ fn search_by_reference() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 3,
         entity_id: long_id.clone(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt3".to_string(),
             message: Some(
@@ -852,6 +944,7 @@ fn search_by_operations() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 1,
         entity_id: "et1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt1".to_string(),
             message: Some(
@@ -872,6 +965,7 @@ fn search_by_operations() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 2,
         entity_id: "et2".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt2".to_string(),
             message: Some(
@@ -933,6 +1027,7 @@ fn search_by_ids() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 1,
         entity_id: "et1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt1".to_string(),
             message: Some(
@@ -953,6 +1048,7 @@ fn search_by_ids() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 2,
         entity_id: "et2".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt2".to_string(),
             message: Some(
@@ -1010,6 +1106,7 @@ fn search_by_trait_field() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 1,
         entity_id: "et1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt1".to_string(),
             message: Some(
@@ -1026,6 +1123,7 @@ fn search_by_trait_field() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 2,
         entity_id: "et2".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt2".to_string(),
             message: Some(
@@ -1059,6 +1157,404 @@ fn search_by_trait_field() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn search_with_index_text_analyzer() -> anyhow::Result<()> {
+    let registry = Arc::new(Registry::new_with_exocore_types());
+    let config = MutationIndexConfig {
+        text_analyzer: "french".parse()?,
+        ..test_config()
+    };
+    let index = MutationIndex::create_in_memory(config, registry)?;
+
+    let et1 = IndexOperation::PutTrait(PutTraitMutation {
+        block_offset: None,
+        operation_id: 1,
+        entity_id: "et1".to_string(),
+        idempotency_key: None,
+        trt: Trait {
+            id: "trt1".to_string(),
+            message: Some(
+                TestMessage {
+                    string1: "Les élèves mangeaient des pommes".to_string(),
+                    ..Default::default()
+                }
+                .pack_to_any()?,
+            ),
+            ..Default::default()
+        },
+    });
+    index.apply_operations(vec![et1].into_iter())?;
+
+    // accents are folded and words stemmed, without relying on fuzzy matching
+    let res = index.search(no_fuzzy_match_query("eleve"))?;
+    assert_eq!(res.mutations.len(), 1);
+
+    let res = index.search(no_fuzzy_match_query("mangé pomme"))?;
+    assert_eq!(res.mutations.len(), 1);
+
+    let res = index.search(no_fuzzy_match_query("poire"))?;
+    assert_eq!(res.mutations.len(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn search_with_field_text_analyzer() -> anyhow::Result<()> {
+    let registry = Arc::new(Registry::new_with_exocore_types());
+    let config = test_config();
+    let index = MutationIndex::create_in_memory(config, registry)?;
+
+    // `string4` uses the french analyzer, while `string1` uses the index's default
+    let et1 = IndexOperation::PutTrait(PutTraitMutation {
+        block_offset: None,
+        operation_id: 1,
+        entity_id: "et1".to_string(),
+        idempotency_key: None,
+        trt: Trait {
+            id: "trt1".to_string(),
+            message: Some(
+                TestMessage {
+                    string1: "Les élèves".to_string(),
+                    string4: "Les élèves".to_string(),
+                    ..Default::default()
+                }
+                .pack_to_any()?,
+            ),
+            ..Default::default()
+        },
+    });
+    index.apply_operations(vec![et1].into_iter())?;
+
+    let res = index.search(Q::from_query_string("type:test +string4:eleve").build())?;
+    assert_eq!(res.mutations.len(), 1);
+
+    let res = index.search(Q::from_query_string("type:test +string1:eleve").build())?;
+    assert_eq!(res.mutations.len(), 0);
+
+    // text of the field is also indexed in the full-text field with index's analyzer
+    let res = index.search(no_fuzzy_match_query("élèves"))?;
+    assert_eq!(res.mutations.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn highlight_trait_snippets() -> anyhow::Result<()> {
+    let registry = Arc::new(Registry::new_with_exocore_types());
+    let config = test_config();
+    let index = MutationIndex::create_in_memory(config, registry)?;
+
+    let trt = Trait {
+        id: "trt1".to_string(),
+        message: Some(
+            TestMessage {
+                string1: "Élève studieux en Rust".to_string(),
+                string2: "Rust is not indexed as text".to_string(),
+                string4: "Les élèves".to_string(),
+                struct1: Some(TestStruct {
+                    string1: "Nested rust".to_string(),
+                }),
+                ..Default::default()
+            }
+            .pack_to_any()?,
+        ),
+        ..Default::default()
+    };
+
+    let highlighter = index.highlighter(&no_fuzzy_match_query("rust"), 0)?;
+    let snippets = highlighter.trait_snippets(&trt);
+    assert_eq!(snippets.len(), 2);
+
+    let snippet = snippets.iter().find(|s| s.field == "string1").unwrap();
+    assert_eq!(snippet.trait_id, "trt1");
+    assert_eq!(snippet.fragment, "Élève studieux en Rust");
+    assert_eq!(snippet.highlights.len(), 1);
+    assert_eq!(snippet.highlights[0].start, 18); // offsets are in chars, not bytes
+    assert_eq!(snippet.highlights[0].end, 22);
+
+    let snippet = snippets
+        .iter()
+        .find(|s| s.field == "struct1.string1")
+        .unwrap();
+    assert_eq!(snippet.fragment, "Nested rust");
+
+    // terms matched on a specific field use that field's analyzer
+    let query = Q::from_query_string("type:test +string4:eleve").build();
+    let snippets = index.highlighter(&query, 0)?.trait_snippets(&trt);
+    assert_eq!(snippets.len(), 1);
+    assert_eq!(snippets[0].field, "string4");
+    assert_eq!(snippets[0].highlights[0].start, 4);
+    assert_eq!(snippets[0].highlights[0].end, 10);
+
+    // no snippets if nothing matches
+    let snippets = index
+        .highlighter(&no_fuzzy_match_query("python"), 0)?
+        .trait_snippets(&trt);
+    assert!(snippets.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn search_similarity() -> anyhow::Result<()> {
+    let registry = Arc::new(Registry::new_with_exocore_types());
+    let config = test_config();
+    let index = MutationIndex::create_in_memory(config, registry)?;
+
+    let embeddings = vec![
+        ("et1", vec![1.0, 0.0, 0.0]),
+        ("et2", vec![0.9, 0.1, 0.0]),
+        ("et3", vec![0.0, 1.0, 0.0]),
+        ("et4", vec![1.0, 0.0]), // different dimension
+        ("et5", vec![]),
+    ];
+    let ops = embeddings
+        .into_iter()
+        .enumerate()
+        .map(
+            |(i, (entity_id, embedding1))| -> anyhow::Result<IndexOperation> {
+                Ok(IndexOperation::PutTrait(PutTraitMutation {
+                    block_offset: None,
+                    operation_id: i as u64 + 1,
+                    entity_id: entity_id.to_string(),
+                    idempotency_key: None,
+                    trt: Trait {
+                        id: "trt1".to_string(),
+                        message: Some(
+                            TestMessage {
+                                embedding1,
+                                ..Default::default()
+                            }
+                            .pack_to_any()?,
+                        ),
+                        ..Default::default()
+                    },
+                }))
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()?;
+    index.apply_operations(ops.into_iter())?;
+
+    let query = Q::similar_to(vec![1.0, 0.0, 0.0])
+        .order_by_score(false, false, false)
+        .build();
+    let res = index.search(query)?;
+    assert_eq!(extract_entities_id(&res), vec!["et1", "et2"]);
+
+    let query = Q::similar_to_min(vec![2.0, 0.0, 0.0], 0.999)
+        .order_by_score(false, false, false)
+        .build();
+    let res = index.search(query)?;
+    assert_eq!(extract_entities_id(&res), vec!["et1"]);
+
+    // can be combined with other predicates
+    let query = EntityQuery {
+        predicate: Some(entity_query::Predicate::Boolean(BooleanPredicate {
+            queries: vec![
+                boolean_predicate::SubQuery {
+                    occur: boolean_predicate::Occur::Must.into(),
+                    predicate: Some(boolean_predicate::sub_query::Predicate::Similarity(
+                        SimilarityPredicate {
+                            vector: vec![1.0, 0.0, 0.0],
+                            min_similarity: 0.0,
+                        },
+                    )),
+                },
+                boolean_predicate::SubQuery {
+                    occur: boolean_predicate::Occur::MustNot.into(),
+                    predicate: Some(boolean_predicate::sub_query::Predicate::Ids(IdsPredicate {
+                        ids: vec!["et1".to_string()],
+                    })),
+                },
+            ],
+        })),
+        ..Default::default()
+    };
+    let res = index.search(query)?;
+    assert_eq!(extract_entities_id(&res), vec!["et2"]);
+
+    // vector needs to be comparable
+    assert!(index.search(Q::similar_to(vec![0.0, 0.0]).build()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn search_similarity_multiple_embeddings() -> anyhow::Result<()> {
+    let registry = Arc::new(Registry::new_with_exocore_types());
+    let index = MutationIndex::create_in_memory(test_config(), registry)?;
+    index.apply_operations(
+        similarity_operations(vec![
+            ("et1", vec![1.0, 0.0], vec![0.0, 1.0, 0.0]),
+            ("et2", vec![0.0, 1.0], vec![]),
+        ])?
+        .into_iter(),
+    )?;
+
+    // all embedding fields of a trait are indexed
+    let res = index.search(Q::similar_to(vec![0.0, 1.0, 0.0]).build())?;
+    assert_eq!(extract_entities_id(&res), vec!["et1"]);
+
+    let res = index.search(Q::similar_to(vec![1.0, 0.0]).build())?;
+    assert_eq!(extract_entities_id(&res), vec!["et1"]);
+
+    Ok(())
+}
+
+#[test]
+fn search_similarity_max_embeddings() -> anyhow::Result<()> {
+    let registry = Arc::new(Registry::new_with_exocore_types());
+    let config = MutationIndexConfig {
+        similarity_max_embeddings: 2,
+        ..test_config()
+    };
+    let index = MutationIndex::create_in_memory(config, registry.clone())?;
+    index.apply_operations(
+        similarity_operations(vec![
+            ("et1", vec![1.0, 0.0], vec![]),
+            ("et2", vec![1.0, 0.0], vec![]),
+            ("et3", vec![1.0, 0.0], vec![]),
+        ])?
+        .into_iter(),
+    )?;
+
+    // comparing more than 2 embeddings fails instead of returning incomplete results
+    assert!(index.search(Q::similar_to(vec![1.0, 0.0]).build()).is_err());
+
+    let index = MutationIndex::create_in_memory(config, registry)?;
+    index.apply_operations(
+        similarity_operations(vec![
+            ("et1", vec![1.0, 0.0], vec![]),
+            ("et2", vec![1.0, 0.0], vec![]),
+        ])?
+        .into_iter(),
+    )?;
+    let res = index.search(Q::similar_to(vec![1.0, 0.0]).build())?;
+    assert_eq!(res.mutations.len(), 2);
+
+    Ok(())
+}
+
+fn similarity_operations(
+    embeddings: Vec<(&str, Vec<f32>, Vec<f32>)>,
+) -> anyhow::Result<Vec<IndexOperation>> {
+    embeddings
+        .into_iter()
+        .enumerate()
+        .map(|(i, (entity_id, embedding1, embedding2))| {
+            Ok(IndexOperation::PutTrait(PutTraitMutation {
+                block_offset: None,
+                operation_id: i as u64 + 1,
+                entity_id: entity_id.to_string(),
+                idempotency_key: None,
+                trt: Trait {
+                    id: "trt1".to_string(),
+                    message: Some(
+                        TestMessage {
+                            embedding1,
+                            embedding2,
+                            ..Default::default()
+                        }
+                        .pack_to_any()?,
+                    ),
+                    ..Default::default()
+                },
+            }))
+        })
+        .collect()
+}
+
+#[test]
+fn search_with_ngram_text_analyzer() -> anyhow::Result<()> {
+    let registry = Arc::new(Registry::new_with_exocore_types());
+    let config = MutationIndexConfig {
+        text_analyzer: TextAnalyzerKind::Ngram,
+        ..test_config()
+    };
+    let index = MutationIndex::create_in_memory(config, registry)?;
+
+    let et1 = IndexOperation::PutTrait(PutTraitMutation {
+        block_offset: None,
+        operation_id: 1,
+        entity_id: "et1".to_string(),
+        idempotency_key: None,
+        trt: Trait {
+            id: "trt1".to_string(),
+            message: Some(
+                TestMessage {
+                    string1: "Programming notes on internationalization".to_string(),
+                    ..Default::default()
+                }
+                .pack_to_any()?,
+            ),
+            ..Default::default()
+        },
+    });
+    index.apply_operations(vec![et1].into_iter())?;
+
+    let res = index.search(no_fuzzy_match_query("prog"))?;
+    assert_eq!(res.mutations.len(), 1);
+
+    let res = index.search(no_fuzzy_match_query("notes"))?;
+    assert_eq!(res.mutations.len(), 1);
+
+    let res = index.search(no_fuzzy_match_query("rogram"))?;
+    assert_eq!(res.mutations.len(), 0);
+
+    // tokens longer than the indexed prefixes still match
+    let res = index.search(no_fuzzy_match_query("internationalization"))?;
+    assert_eq!(res.mutations.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn reopen_with_changed_schema() -> anyhow::Result<()> {
+    let registry = Arc::new(Registry::new_with_exocore_types());
+    let dir = tempfile::tempdir()?;
+
+    {
+        let index =
+            MutationIndex::open_or_create_mmap(test_config(), registry.clone(), dir.path())?;
+        index.apply_operation(IndexOperation::PutTrait(PutTraitMutation {
+            block_offset: Some(1),
+            operation_id: 1,
+            entity_id: "et1".to_string(),
+            idempotency_key: None,
+            trt: Trait {
+                id: "trt1".to_string(),
+                message: Some(
+                    TestMessage {
+                        string1: "Some Subject".to_string(),
+                        ..Default::default()
+                    }
+                    .pack_to_any()?,
+                ),
+                ..Default::default()
+            },
+        }))?;
+    }
+
+    {
+        // same schema, index is kept
+        let index =
+            MutationIndex::open_or_create_mmap(test_config(), registry.clone(), dir.path())?;
+        assert_eq!(index.highest_indexed_block()?, Some(1));
+    }
+
+    {
+        // changing analyzer changes schema, index needs to be re-indexed
+        let config = MutationIndexConfig {
+            text_analyzer: TextAnalyzerKind::Ascii,
+            ..test_config()
+        };
+        let index = MutationIndex::open_or_create_mmap(config, registry, dir.path())?;
+        assert_eq!(index.highest_indexed_block()?, None);
+    }
+
+    Ok(())
+}
+
 #[test]
 fn search_by_trait_sub_message_fields() -> anyhow::Result<()> {
     let registry = Arc::new(Registry::new_with_exocore_types());
@@ -1069,6 +1565,7 @@ fn search_by_trait_sub_message_fields() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 1,
         entity_id: "et1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt1".to_string(),
             message: Some(
@@ -1087,6 +1584,7 @@ fn search_by_trait_sub_message_fields() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 2,
         entity_id: "et2".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt2".to_string(),
             message: Some(
@@ -1158,6 +1656,7 @@ fn search_all() -> anyhow::Result<()> {
             block_offset: None,
             operation_id: i,
             entity_id: format!("et{}", i),
+            idempotency_key: None,
             trt: Trait {
                 id: "trt1".to_string(),
                 message: Some(TestMessage::default().pack_to_any()?),
@@ -1202,6 +1701,7 @@ fn highest_indexed_block() -> anyhow::Result<()> {
         block_offset: Some(1234),
         operation_id: 1,
         entity_id: "et1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt1".to_string(),
             message: Some(
@@ -1220,6 +1720,7 @@ fn highest_indexed_block() -> anyhow::Result<()> {
         block_offset: Some(120),
         operation_id: 2,
         entity_id: "et1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt2".to_string(),
             message: Some(
@@ -1238,6 +1739,7 @@ fn highest_indexed_block() -> anyhow::Result<()> {
         block_offset: Some(9999),
         operation_id: 3,
         entity_id: "et1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt1".to_string(),
             message: Some(
@@ -1267,6 +1769,7 @@ fn put_unregistered_trait() -> anyhow::Result<()> {
         block_offset: Some(1234),
         operation_id: 1,
         entity_id: "et1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "trt1".to_string(),
             message: Some(Any {
@@ -1294,6 +1797,7 @@ fn delete_entity_operation_id_mutation() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 1234,
         entity_id: "entity_id1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "foo1".to_string(),
             message: Some(
@@ -1334,6 +1838,7 @@ fn put_trait_tombstone() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 1234,
         entity_id: "entity_id1".to_string(),
+        idempotency_key: None,
         trait_id: "foo1".to_string(),
     });
     index.apply_operation(contact_mutation)?;
@@ -1342,6 +1847,7 @@ fn put_trait_tombstone() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 2345,
         entity_id: "entity_id2".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "foo2".to_string(),
             message: Some(
@@ -1377,6 +1883,7 @@ fn put_entity_tombstone() -> anyhow::Result<()> {
         block_offset: None,
         operation_id: 1234,
         entity_id: "entity_id1".to_string(),
+        idempotency_key: None,
     });
     index.apply_operation(trait1)?;
 
@@ -1400,6 +1907,7 @@ fn trait_dates() -> anyhow::Result<()> {
         block_offset: Some(1),
         operation_id: 10,
         entity_id: "entity_id1".to_string(),
+        idempotency_key: None,
         trt: Trait {
             id: "foo1".to_string(),
             message: Some(
@@ -1435,6 +1943,24 @@ fn test_config() -> MutationIndexConfig {
     }
 }
 
+fn no_fuzzy_match_query(query: &str) -> EntityQuery {
+    EntityQuery {
+        predicate: Some(entity_query::Predicate::Match(MatchPredicate {
+            query: query.to_string(),
+            no_fuzzy: true,
+        })),
+        ..Default::default()
+    }
+}
+
+fn extract_entities_id(results: &MutationResults) -> Vec<&str> {
+    results
+        .mutations
+        .iter()
+        .map(|m| m.entity_id.as_str())
+        .collect()
+}
+
 fn find_put_trait<'r>(
     results: &'r MutationResults,
     trait_id: &str,
@@ -30,7 +30,7 @@ use super::{entity_index::EntityIndex, StoreConfig};
 use crate::{
     error::Error,
     local::{mutation_tracker::MutationTracker, watched_queries::WatchedQueries},
-    mutation::MutationRequestLike,
+    mutation::{generate_missing_ids, MutationRequestLike},
     query::WatchToken,
 };
 
@@ -346,32 +346,18 @@ where
     ) -> Result<oneshot::Receiver<Result<MutationResult, Error>>, Error> {
         let (sender, receiver) = oneshot::channel();
 
-        let mut operation_ids = Vec::new();
-        let mut last_entity_id = None;
-        for mutation in &mut request.mutations {
-            if let Some(Mutation::Test(_mutation)) = &mutation.mutation {
-                return Err(Error::ProtoFieldExpected("mutation"));
-            }
-
-            // if mutation doesn't have an entity id, generate one
-            if mutation.entity_id.is_empty() {
-                if request.common_entity_id && last_entity_id.is_some() {
-                    mutation.entity_id = last_entity_id.unwrap_or_default();
-                } else {
-                    mutation.entity_id = exocore_core::utils::id::generate_prefixed_id("et");
-                }
-            }
-            last_entity_id = Some(mutation.entity_id.clone());
+        let test_mutation = request
+            .mutations
+            .iter()
+            .any(|mutation| matches!(mutation.mutation, Some(Mutation::Test(_))));
+        if test_mutation {
+            return Err(Error::ProtoFieldExpected("mutation"));
+        }
 
-            // if a put mutation doesn't have a trait id, generate one
-            if let Some(Mutation::PutTrait(put_mutation)) = &mut mutation.mutation {
-                if let Some(trt) = &mut put_mutation.r#trait {
-                    if trt.id.is_empty() {
-                        trt.id = exocore_core::utils::id::generate_prefixed_id("trt");
-                    }
-                }
-            }
+        generate_missing_ids(&mut request);
 
+        let mut operation_ids = Vec::new();
+        for mutation in &request.mutations {
             let encoded = mutation.encode_to_vec();
             let operation_id = self.chain_handle.write_entry_operation(&encoded)?;
 
@@ -764,6 +750,39 @@ pub mod tests {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn store_mutate_idempotent() -> anyhow::Result<()> {
+        let mut test_store = TestStore::new().await?;
+        test_store.start_store().await?;
+
+        let mut mutation = test_store
+            .create_put_contact_mutation("et1", "trt1", "Hello World")
+            .build();
+        mutation.mutations[0].idempotency_key = 42;
+        mutation.wait_indexed = true;
+        test_store.mutate(mutation.clone()).await?;
+
+        let mut other_mutation = test_store
+            .create_put_contact_mutation("et1", "trt1", "Bonjour")
+            .build();
+        other_mutation.wait_indexed = true;
+        test_store.mutate(other_mutation).await?;
+
+        // writing the first mutation again (ex: replayed to another node) doesn't apply it
+        // again, which would have overridden the mutation made after it
+        test_store.mutate(mutation).await?;
+
+        let query = QueryBuilder::matches("hello").build();
+        let results = test_store.query(query).await?;
+        assert!(results.entities.is_empty());
+
+        let query = QueryBuilder::matches("bonjour").build();
+        let results = test_store.query(query).await?;
+        assert_eq!(results.entities.len(), 1);
+
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn query_error_propagating() -> anyhow::Result<()> {
         let mut test_store = TestStore::new().await?;
@@ -8,12 +8,13 @@ use exocore_protos::{
         exocore_test::TestMessage,
     },
     prost::{ProstAnyPackMessageExt, ProstDateTimeExt},
+    reflect::FileDescriptorSet,
     registry::Registry,
     store::TraitDetails,
 };
 use tempfile::TempDir;
 
-use super::{entity_index::test_index::TestEntityIndex, *};
+use super::*;
 use crate::{
     local::{mutation_index::MutationIndexConfig, store::StoreHandle, EntityIndexConfig},
     mutation::{MutationBuilder, MutationRequestLike},
@@ -41,9 +42,25 @@ impl TestStore {
     pub async fn new_with_config(
         store_config: StoreConfig,
         index_config: EntityIndexConfig,
+    ) -> Result<TestStore, anyhow::Error> {
+        Self::new_with_schemas(store_config, index_config, &[]).await
+    }
+
+    /// Creates a store whose cell has the given schemas registered, which is
+    /// needed for their indexed and sorted fields to be indexed.
+    pub async fn new_with_schemas(
+        store_config: StoreConfig,
+        index_config: EntityIndexConfig,
+        schemas: &[FileDescriptorSet],
     ) -> Result<TestStore, anyhow::Error> {
         let cluster = TestChainCluster::new_single_and_start().await?;
 
+        // the index schema is built from the registry when the index gets created
+        let cell_schemas = cluster.cells[0].cell().schemas();
+        for fd_set in schemas {
+            cell_schemas.register_file_descriptor_set(fd_set);
+        }
+
         let temp_dir = tempfile::tempdir()?;
         let registry = Arc::new(Registry::new_with_exocore_types());
 
@@ -74,6 +91,8 @@ impl TestStore {
 
     pub fn test_index_config() -> EntityIndexConfig {
         EntityIndexConfig {
+            chain_index_in_memory: true,
+            chain_index_depth_leeway: 0, // for tests, we want to index as soon as possible
             pending_index_config: MutationIndexConfig {
                 indexer_num_threads: Some(1),
                 ..MutationIndexConfig::default()
@@ -82,13 +101,13 @@ impl TestStore {
                 indexer_num_threads: Some(1),
                 ..MutationIndexConfig::default()
             },
-            ..TestEntityIndex::test_config()
+            ..EntityIndexConfig::default()
         }
     }
 
     pub async fn start_store(&mut self) -> anyhow::Result<()> {
         let store = self.store.take().unwrap();
-        tokio::spawn(async move {
+        exocore_core::futures::spawn_future(async move {
             match store.run().await {
                 Ok(_) => {}
                 Err(err) => error!("Error running store: {}", err),
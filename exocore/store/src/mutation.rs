@@ -29,6 +29,7 @@ impl MutationBuilder {
     pub fn put_trait<E: Into<EntityId>>(mut self, entity_id: E, trt: Trait) -> MutationBuilder {
         self.request.mutations.push(EntityMutation {
             entity_id: entity_id.into(),
+            idempotency_key: 0,
             mutation: Some(Mutation::PutTrait(PutTraitMutation { r#trait: Some(trt) })),
         });
 
@@ -42,6 +43,7 @@ impl MutationBuilder {
     ) -> MutationBuilder {
         self.request.mutations.push(EntityMutation {
             entity_id: entity_id.into(),
+            idempotency_key: 0,
             mutation: Some(Mutation::DeleteTrait(DeleteTraitMutation {
                 trait_id: trait_id.into(),
             })),
@@ -53,6 +55,7 @@ impl MutationBuilder {
     pub fn delete_entity<E: Into<EntityId>>(mut self, entity_id: E) -> MutationBuilder {
         self.request.mutations.push(EntityMutation {
             entity_id: entity_id.into(),
+            idempotency_key: 0,
             mutation: Some(Mutation::DeleteEntity(DeleteEntityMutation {})),
         });
 
@@ -78,6 +81,7 @@ impl MutationBuilder {
     ) -> MutationBuilder {
         self.request.mutations.push(EntityMutation {
             entity_id: entity_id.into(),
+            idempotency_key: 0,
             mutation: Some(Mutation::DeleteOperations(DeleteOperationsMutation {
                 operation_ids,
             })),
@@ -90,6 +94,7 @@ impl MutationBuilder {
     pub(crate) fn fail_mutation<E: Into<EntityId>>(mut self, entity_id: E) -> MutationBuilder {
         self.request.mutations.push(EntityMutation {
             entity_id: entity_id.into(),
+            idempotency_key: 0,
             mutation: Some(Mutation::Test(
                 exocore_protos::generated::exocore_store::TestMutation { success: false },
             )),
@@ -109,6 +114,32 @@ impl Default for MutationBuilder {
     }
 }
 
+/// Generates ids for the entities and traits of the request's mutations that
+/// don't specify one. If the request has `common_entity_id`, mutations without
+/// an entity id reuse the entity id of the previous mutation.
+#[cfg(any(feature = "local", feature = "remote"))]
+pub(crate) fn generate_missing_ids(request: &mut MutationRequest) {
+    let mut last_entity_id = None;
+    for mutation in &mut request.mutations {
+        if mutation.entity_id.is_empty() {
+            if request.common_entity_id && last_entity_id.is_some() {
+                mutation.entity_id = last_entity_id.unwrap_or_default();
+            } else {
+                mutation.entity_id = exocore_core::utils::id::generate_prefixed_id("et");
+            }
+        }
+        last_entity_id = Some(mutation.entity_id.clone());
+
+        if let Some(Mutation::PutTrait(put_mutation)) = &mut mutation.mutation {
+            if let Some(trt) = &mut put_mutation.r#trait {
+                if trt.id.is_empty() {
+                    trt.id = exocore_core::utils::id::generate_prefixed_id("trt");
+                }
+            }
+        }
+    }
+}
+
 pub struct MutationRequestLike(pub MutationRequest);
 
 impl From<MutationRequest> for MutationRequestLike {
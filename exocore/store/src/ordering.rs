@@ -92,6 +92,21 @@ impl OrderingValueExt for OrderingValue {
         use ordering_value::Value as V;
 
         if self.value == other.value {
+            // values of `then` orderings are compared in order before falling back on
+            // operation id
+            for (self_then, other_then) in self.then.iter().zip(other.then.iter()) {
+                if self_then.value == other_then.value {
+                    continue;
+                }
+
+                let cmp = self_then.partial_cmp(other_then)?;
+                return if self_then.reverse {
+                    Some(cmp.reverse())
+                } else {
+                    Some(cmp)
+                };
+            }
+
             return self.operation_id.partial_cmp(&other.operation_id);
         }
 
@@ -142,6 +157,7 @@ pub fn value_from_u64(value: u64, operation_id: OperationId) -> OrderingValue {
     OrderingValue {
         value: Some(ordering_value::Value::Uint64(value)),
         operation_id,
+        ..Default::default()
     }
 }
 
@@ -149,19 +165,20 @@ pub fn value_from_f32(value: f32, operation_id: OperationId) -> OrderingValue {
     OrderingValue {
         value: Some(ordering_value::Value::Float(value)),
         operation_id,
+        ..Default::default()
     }
 }
 
 pub fn value_max() -> OrderingValue {
     OrderingValue {
         value: Some(ordering_value::Value::Max(true)),
-        operation_id: 0,
+        ..Default::default()
     }
 }
 
 pub fn value_min() -> OrderingValue {
     OrderingValue {
         value: Some(ordering_value::Value::Min(true)),
-        operation_id: 0,
+        ..Default::default()
     }
 }
@@ -7,8 +7,8 @@ use exocore_protos::{
     message::NamedMessage,
     reflect::FieldId,
     store::{
-        AllPredicate, IdsPredicate, OperationsPredicate, Projection, QueryStringPredicate,
-        Reference,
+        AllPredicate, Highlighting, IdsPredicate, OperationsPredicate, Projection,
+        QueryStringPredicate, Reference, SimilarityPredicate,
     },
 };
 
@@ -130,6 +130,22 @@ impl QueryBuilder {
         }
     }
 
+    pub fn similar_to<V: Into<Vec<f32>>>(vector: V) -> QueryBuilder {
+        Self::similar_to_min(vector, 0.0)
+    }
+
+    pub fn similar_to_min<V: Into<Vec<f32>>>(vector: V, min_similarity: f32) -> QueryBuilder {
+        QueryBuilder {
+            query: EntityQuery {
+                predicate: Some(entity_query::Predicate::Similarity(SimilarityPredicate {
+                    vector: vector.into(),
+                    min_similarity,
+                })),
+                ..Default::default()
+            },
+        }
+    }
+
     pub fn all() -> QueryBuilder {
         QueryBuilder {
             query: EntityQuery {
@@ -169,6 +185,27 @@ impl QueryBuilder {
         self
     }
 
+    /// Requests the page following the given cursor, as returned in the
+    /// `next_page` of previous results.
+    pub fn after_cursor<C: Into<String>>(mut self, cursor: C) -> Self {
+        match self.query.paging.as_mut() {
+            Some(paging) => paging.cursor = cursor.into(),
+            None => {
+                self.query.paging = Some(Paging {
+                    cursor: cursor.into(),
+                    ..default_paging()
+                })
+            }
+        }
+
+        self
+    }
+
+    pub fn count_total(mut self) -> Self {
+        self.query.count_total = true;
+        self
+    }
+
     pub fn project<P: Into<ProjectionWrapper>>(mut self, projection: P) -> Self {
         self.query.projections.push(projection.into().0);
         self
@@ -189,7 +226,7 @@ impl QueryBuilder {
     }
 
     pub fn paging_or_default(&self) -> Paging {
-        self.query.paging.unwrap_or_else(default_paging)
+        self.query.paging.clone().unwrap_or_else(default_paging)
     }
 
     pub fn with_watch_token(mut self, token: WatchToken) -> Self {
@@ -211,6 +248,13 @@ impl QueryBuilder {
         })
     }
 
+    pub fn order_by_block_offset(self, ascending: bool) -> Self {
+        self.mapped_ordering(|ordering| {
+            ordering.value = Some(ordering::Value::BlockOffset(true));
+            ordering.ascending = ascending;
+        })
+    }
+
     pub fn order_by_score(
         self,
         ascending: bool,
@@ -225,6 +269,30 @@ impl QueryBuilder {
         })
     }
 
+    /// Orders results that have equal values for the previous orderings by the
+    /// given field.
+    pub fn then_order_by_field<F: Into<String>>(self, field: F, ascending: bool) -> Self {
+        self.then_order_by(ordering::Value::Field(field.into()), ascending)
+    }
+
+    pub fn then_order_by_operations(self, ascending: bool) -> Self {
+        self.then_order_by(ordering::Value::OperationId(true), ascending)
+    }
+
+    pub fn then_order_by_updated_at(self, ascending: bool) -> Self {
+        self.then_order_by(ordering::Value::UpdatedAt(true), ascending)
+    }
+
+    pub fn then_order_by(self, value: ordering::Value, ascending: bool) -> Self {
+        self.mapped_ordering(|ordering| {
+            ordering.then.push(Ordering {
+                value: Some(value),
+                ascending,
+                ..Default::default()
+            });
+        })
+    }
+
     pub fn order_ascending(self, ascending: bool) -> Self {
         self.mapped_ordering(|ordering| ordering.ascending = ascending)
     }
@@ -252,6 +320,15 @@ impl QueryBuilder {
         self
     }
 
+    pub fn highlight(self) -> Self {
+        self.highlight_max_chars(0)
+    }
+
+    pub fn highlight_max_chars(mut self, max_chars: u32) -> Self {
+        self.query.highlighting = Some(Highlighting { max_chars });
+        self
+    }
+
     pub fn build(self) -> EntityQuery {
         self.query
     }
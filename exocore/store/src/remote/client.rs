@@ -1,5 +1,6 @@
 use std::{
     collections::HashMap,
+    path::PathBuf,
     pin::Pin,
     sync::{Arc, Mutex, RwLock, Weak},
     task::{Context, Poll},
@@ -8,14 +9,14 @@ use std::{
 
 use async_trait::async_trait;
 use exocore_core::{
-    cell::{Cell, CellNodeRole, Node, NodeId},
+    cell::{Cell, CellNodeRole, CellNodes, Node, NodeId},
     framing::CapnpFrameBuilder,
     futures::interval,
     time::{Clock, ConsistentTimestamp, Instant},
     utils::handle_set::{Handle, HandleSet},
 };
 use exocore_protos::generated::{
-    exocore_store::{EntityQuery, EntityResults, MutationRequest, MutationResult},
+    exocore_store::{EntityQuery, EntityResults, MutationRequest, MutationResult, RefusedMutation},
     store_transport_capnp::{
         mutation_response, query_response, unwatch_query_request, watched_query_response,
     },
@@ -30,14 +31,31 @@ use futures::{
     prelude::*,
 };
 
-use super::seri::{
-    mutation_result_from_response_frame, mutation_to_request_frame,
-    query_results_from_response_frame, query_to_request_frame, watched_query_to_request_frame,
+use super::{
+    offline::{OfflineMutationQueue, QueryCache},
+    seri::{
+        mutation_result_from_response_frame, mutation_to_request_frame,
+        query_results_from_response_frame, query_to_request_frame, watched_query_to_request_frame,
+    },
+};
+use crate::{
+    error::Error,
+    mutation::{generate_missing_ids, MutationRequestLike},
+    query::WatchToken,
 };
-use crate::{error::Error, mutation::MutationRequestLike, query::WatchToken};
 
 /// This implementation of the AsyncStore allow sending all queries and
 /// mutations to a remote node's local store running the `Server` component.
+///
+/// The client tracks the latency and health of all the nodes of the cell with
+/// the `Store` role. Mutations and watched queries are sent to a primary store
+/// node, while queries are spread among healthy nodes and retried on another
+/// node if they time out. If the primary store node becomes unhealthy, the
+/// client fails over to another node and re-registers watched queries on it.
+///
+/// In offline mode, mutations made while no store node is reachable are
+/// persisted in the cell's directory and replayed in order once a store node is
+/// reachable, and queries are answered from a cache of recent results.
 pub struct Client<T>
 where
     T: TransportServiceHandle,
@@ -58,6 +76,16 @@ where
         clock: Clock,
         transport_handle: T,
     ) -> Result<Client<T>, Error> {
+        let (offline_mutations, query_cache) = if config.offline_mode {
+            let dir = cell.directory().scope(PathBuf::from(OFFLINE_DIRECTORY));
+            (
+                Some(OfflineMutationQueue::load(dir)?),
+                Some(QueryCache::new(config.offline_cache_size)),
+            )
+        } else {
+            (None, None)
+        };
+
         let inner = Arc::new(RwLock::new(Inner {
             config,
             cell,
@@ -66,9 +94,13 @@ where
             store_node: None,
             store_node_message_queue: Mutex::new(Vec::new()),
             nodes_status: HashMap::new(),
+            nodes_health: HashMap::new(),
             pending_queries: HashMap::new(),
             watched_queries: HashMap::new(),
             pending_mutations: HashMap::new(),
+            offline_mutations,
+            replaying_mutation: None,
+            query_cache,
         }));
 
         Ok(Client {
@@ -169,6 +201,25 @@ pub struct ClientConfiguration {
     pub watched_register_interval: Duration,
     pub watched_channel_size: usize,
     pub watched_re_register_remote_dropped: bool,
+
+    /// Maximum number of store nodes on which a query is tried before failing
+    /// with a timeout.
+    pub query_max_attempts: usize,
+
+    /// Number of consecutive timed out requests after which a store node is
+    /// considered unhealthy, making the client fail over to another node.
+    pub node_unhealthy_timeouts: usize,
+
+    /// Duration during which an unhealthy store node isn't used if other
+    /// healthy nodes are available.
+    pub node_unhealthy_duration: Duration,
+
+    /// Queues mutations while no store node is reachable and answers queries
+    /// from a cache of recent results. See `Client`.
+    pub offline_mode: bool,
+
+    /// Number of query results kept in the cache in offline mode.
+    pub offline_cache_size: usize,
 }
 
 /// Keep in sync with application SDK store.
@@ -181,10 +232,19 @@ impl Default for ClientConfiguration {
             management_interval: Duration::from_secs(1),
             watched_channel_size: 1000,
             watched_re_register_remote_dropped: true,
+            query_max_attempts: 2,
+            node_unhealthy_timeouts: 2,
+            node_unhealthy_duration: Duration::from_secs(60),
+            offline_mode: false,
+            offline_cache_size: 100,
         }
     }
 }
 
+/// Directory, within the cell's directory, in which the offline mutations queue
+/// is persisted.
+const OFFLINE_DIRECTORY: &str = "store_client";
+
 pub(super) struct Inner {
     config: ClientConfiguration,
     cell: Cell,
@@ -193,9 +253,13 @@ pub(super) struct Inner {
     store_node: Option<Node>,
     store_node_message_queue: Mutex<Vec<OutMessage>>,
     nodes_status: HashMap<NodeId, ConnectionStatus>,
-    pending_queries: HashMap<ConsistentTimestamp, PendingRequest<EntityResults>>,
+    nodes_health: HashMap<NodeId, NodeHealth>,
+    pending_queries: HashMap<ConsistentTimestamp, PendingQuery>,
     watched_queries: HashMap<ConsistentTimestamp, WatchedQueryRequest>,
     pending_mutations: HashMap<ConsistentTimestamp, PendingRequest<MutationResult>>,
+    offline_mutations: Option<OfflineMutationQueue>,
+    replaying_mutation: Option<ConsistentTimestamp>,
+    query_cache: Option<QueryCache>,
 }
 
 impl Inner {
@@ -207,7 +271,11 @@ impl Inner {
         let inner = weak_inner.upgrade().ok_or(Error::Dropped)?;
         let mut inner = inner.write()?;
 
-        inner.nodes_status.insert(node_id, node_new_status);
+        inner.nodes_status.insert(node_id.clone(), node_new_status);
+        if node_new_status == ConnectionStatus::Connected {
+            // give a fresh start to a node that reconnects
+            inner.nodes_health.remove(&node_id);
+        }
 
         let was_already_connected = inner.store_node.is_some();
 
@@ -224,6 +292,7 @@ impl Inner {
                 // queries are still registered
                 if node_new_status == ConnectionStatus::Connected {
                     inner.send_watched_queries_keepalive(true);
+                    inner.replay_offline_mutations()?;
                 }
 
                 return Ok(());
@@ -231,16 +300,7 @@ impl Inner {
         }
 
         // otherwise we try to find a new store node that is connected
-        let new_store_node = {
-            let cell_nodes = inner.cell.nodes();
-            let cell_nodes_iter = cell_nodes.iter();
-
-            let store_node = cell_nodes_iter
-                .with_role(CellNodeRole::Store)
-                .find(|n| node_is_connected(n.node().id()));
-
-            store_node.map(|n| n.node()).cloned()
-        };
+        let new_store_node = inner.select_store_node(&[]);
         if let Some(new_store_node) = new_store_node {
             info!("Switching store server to {}", new_store_node);
             inner.store_node = Some(new_store_node);
@@ -251,6 +311,7 @@ impl Inner {
         }
 
         inner.send_watched_queries_keepalive(true);
+        inner.replay_offline_mutations()?;
 
         Ok(())
     }
@@ -262,11 +323,17 @@ impl Inner {
         let inner = weak_inner.upgrade().ok_or(Error::Dropped)?;
         let mut inner = inner.write()?;
 
-        if let Some(store_node) = &inner.store_node {
-            if in_message.source.id() != store_node.id() {
-                warn!("Got message from a node other than store node (from {} != current {}). Dropping it.", in_message.source, store_node);
-                return Ok(());
-            }
+        let is_store_node = inner
+            .cell
+            .nodes()
+            .get(in_message.source.id())
+            .is_some_and(|node| node.has_role(CellNodeRole::Store));
+        if !is_store_node {
+            warn!(
+                "Got message from a node that isn't a store node (from {}). Dropping it.",
+                in_message.source
+            );
+            return Ok(());
         }
 
         let Some(rendez_vous_id) = in_message.rendez_vous_id else {
@@ -281,7 +348,9 @@ impl Inner {
         match IncomingMessage::parse_incoming_message(&in_message) {
             Ok(IncomingMessage::MutationResponse(mutation)) => {
                 if let Some(pending_request) = inner.pending_mutations.remove(&rendez_vous_id) {
+                    inner.record_node_response(in_message.source.id(), &pending_request);
                     let _ = pending_request.result_sender.send(Ok(mutation));
+                    inner.on_offline_mutation_replayed(rendez_vous_id, None)?;
                 } else {
                     return Err(anyhow!(
                         "Couldn't find pending mutation for mutation response (request_id={:?} type={:?} from={})",
@@ -290,10 +359,14 @@ impl Inner {
                 }
             }
             Ok(IncomingMessage::QueryResponse(result)) => {
-                if let Some(pending_request) = inner.pending_queries.remove(&rendez_vous_id) {
-                    let _ = pending_request.result_sender.send(Ok(result));
+                if let Some(pending_query) = inner.pending_queries.remove(&rendez_vous_id) {
+                    inner.record_node_response(in_message.source.id(), &pending_query.request);
+                    if let Some(query_cache) = &mut inner.query_cache {
+                        query_cache.put(&pending_query.query, &result);
+                    }
+                    let _ = pending_query.request.result_sender.send(Ok(*result));
                 } else if let Some(watched_query) = inner.watched_queries.get_mut(&rendez_vous_id) {
-                    let _ = watched_query.result_sender.try_send(Ok(result));
+                    let _ = watched_query.result_sender.try_send(Ok(*result));
                 } else {
                     return Err(anyhow!(
                         "Couldn't find pending query for query response (request_id={:?} type={:?} from={})",
@@ -312,14 +385,17 @@ impl Inner {
             }
             Err(err) => {
                 if let Some(pending_request) = inner.pending_mutations.remove(&rendez_vous_id) {
+                    // the store node refused the mutation, which would be refused again if we
+                    // retried it
+                    let error = err.to_string();
                     let _ = pending_request.result_sender.send(Err(err));
+                    inner.on_offline_mutation_replayed(rendez_vous_id, Some(error))?;
                 } else if let Some(mut watched_query) =
                     inner.watched_queries.remove(&rendez_vous_id)
                 {
                     let _ = watched_query.result_sender.try_send(Err(err));
-                } else if let Some(pending_request) = inner.pending_queries.remove(&rendez_vous_id)
-                {
-                    let _ = pending_request.result_sender.send(Err(err));
+                } else if let Some(pending_query) = inner.pending_queries.remove(&rendez_vous_id) {
+                    let _ = pending_query.request.result_sender.send(Err(err));
                 }
             }
         }
@@ -331,11 +407,23 @@ impl Inner {
         let inner = weak_inner.upgrade().ok_or(Error::Dropped)?;
         let mut inner = inner.write()?;
 
-        let query_timeout = inner.config.query_timeout;
-        Inner::check_map_requests_timeouts(&mut inner.pending_queries, query_timeout);
+        inner.check_queries_timeouts();
 
         let mutation_timeout = inner.config.mutation_timeout;
-        Inner::check_map_requests_timeouts(&mut inner.pending_mutations, mutation_timeout);
+        let now = inner.clock.instant();
+        let timed_out_nodes =
+            Inner::check_map_requests_timeouts(&mut inner.pending_mutations, now, mutation_timeout);
+        for node_id in timed_out_nodes.into_iter().flatten() {
+            inner.record_node_timeout(&node_id);
+        }
+
+        // a replayed offline mutation that timed out is retried
+        if let Some(request_id) = inner.replaying_mutation {
+            if !inner.pending_mutations.contains_key(&request_id) {
+                inner.replaying_mutation = None;
+            }
+        }
+        inner.replay_offline_mutations()?;
 
         inner.send_watched_queries_keepalive(false);
 
@@ -348,6 +436,48 @@ impl Inner {
     ) -> Result<oneshot::Receiver<Result<MutationResult, Error>>, Error> {
         let (result_sender, receiver) = oneshot::channel();
 
+        // in offline mode, mutations are queued if no store node is reachable, or if
+        // earlier mutations are still queued since they need to be applied in order
+        let store_node_reachable = self.is_store_node_reachable();
+        if let Some(offline_mutations) = &mut self.offline_mutations {
+            if !offline_mutations.is_empty() || !store_node_reachable {
+                debug!("Store node isn't reachable, queueing mutation");
+
+                // ids and idempotency keys are generated upfront so that a replayed mutation
+                // only gets applied once, even if it gets written by more than one store node
+                // (ex: its response got lost and it got replayed to another node). since the
+                // caller already got its result, there is no point waiting for the mutation to
+                // be indexed.
+                let mut request = request;
+                generate_missing_ids(&mut request);
+                let local_node = self.cell.local_node();
+                for mutation in &mut request.mutations {
+                    mutation.idempotency_key = self.clock.consistent_time(local_node).into();
+                }
+                request.wait_indexed = false;
+                request.return_entities = false;
+
+                offline_mutations.push(request)?;
+                self.replay_offline_mutations()?;
+
+                let _ = result_sender.send(Ok(MutationResult {
+                    queued: true,
+                    ..Default::default()
+                }));
+                return Ok(receiver);
+            }
+        }
+
+        self.send_mutation_request(request, result_sender)?;
+
+        Ok(receiver)
+    }
+
+    fn send_mutation_request(
+        &mut self,
+        request: MutationRequest,
+        result_sender: oneshot::Sender<Result<MutationResult, Error>>,
+    ) -> Result<ConsistentTimestamp, Error> {
         let request_id = self.clock.consistent_time(self.cell.local_node());
         let request_frame = mutation_to_request_frame(request)?;
         let message =
@@ -361,11 +491,71 @@ impl Inner {
             PendingRequest {
                 request_id,
                 result_sender,
-                send_time: Instant::now(),
+                send_time: self.clock.instant(),
+                node_id: self.store_node.as_ref().map(|node| node.id().clone()),
             },
         );
 
-        Ok(receiver)
+        Ok(request_id)
+    }
+
+    fn is_store_node_reachable(&self) -> bool {
+        self.store_node.as_ref().is_some_and(|node| {
+            self.nodes_status.get(node.id()) == Some(&ConnectionStatus::Connected)
+        })
+    }
+
+    /// Sends the first mutation of the offline queue to the store node, unless
+    /// one is already being sent. Queued mutations are sent one at a time, so
+    /// that they are applied in the order they were made.
+    fn replay_offline_mutations(&mut self) -> Result<(), Error> {
+        if self.replaying_mutation.is_some() || !self.is_store_node_reachable() {
+            return Ok(());
+        }
+
+        let Some(request) = self
+            .offline_mutations
+            .as_ref()
+            .and_then(|queue| queue.front().cloned())
+        else {
+            return Ok(());
+        };
+
+        debug!("Replaying queued offline mutation");
+        let (result_sender, _receiver) = oneshot::channel();
+        let request_id = self.send_mutation_request(request, result_sender)?;
+        self.replaying_mutation = Some(request_id);
+
+        Ok(())
+    }
+
+    /// Removes a replayed mutation from the offline queue once the store node
+    /// answered it, and replays the next one. If the store node refused it,
+    /// the mutation is kept with the refused mutations so that it can be
+    /// taken via `ClientHandle::take_refused_offline_mutations`.
+    fn on_offline_mutation_replayed(
+        &mut self,
+        request_id: ConsistentTimestamp,
+        error: Option<String>,
+    ) -> Result<(), Error> {
+        if self.replaying_mutation != Some(request_id) {
+            return Ok(());
+        }
+
+        self.replaying_mutation = None;
+        if let Some(offline_mutations) = &mut self.offline_mutations {
+            if let Some(error) = error {
+                error!(
+                    "Queued offline mutation got refused by store node: {}",
+                    error
+                );
+                offline_mutations.refuse_front(error)?;
+            } else {
+                offline_mutations.pop_front()?;
+            }
+        }
+
+        self.replay_offline_mutations()
     }
 
     fn send_query(
@@ -374,26 +564,59 @@ impl Inner {
     ) -> Result<oneshot::Receiver<Result<EntityResults, Error>>, Error> {
         let (result_sender, receiver) = oneshot::channel();
 
+        // in offline mode, queries are answered from cache if no store node is
+        // reachable
+        if let (false, Some(query_cache)) = (self.is_store_node_reachable(), &self.query_cache) {
+            let results = query_cache.get(&query).ok_or(Error::Offline);
+            let _ = result_sender.send(results);
+            return Ok(receiver);
+        }
+
         let request_id = self.clock.consistent_time(self.cell.local_node());
-        let request_frame = query_to_request_frame(&query)?;
-        let message =
-            OutMessage::from_framed_message(&self.cell, ServiceType::Store, request_frame)?
-                .with_expiration(Some(Instant::now() + self.config.query_timeout))
-                .with_rdv(request_id);
-        self.send_store_node_message(message)?;
+        let node_id = self.send_query_message(request_id, &query, &[])?;
 
         self.pending_queries.insert(
             request_id,
-            PendingRequest {
-                request_id,
-                result_sender,
-                send_time: Instant::now(),
+            PendingQuery {
+                request: PendingRequest {
+                    request_id,
+                    result_sender,
+                    send_time: self.clock.instant(),
+                    node_id,
+                },
+                query,
+                tried_nodes: Vec::new(),
             },
         );
 
         Ok(receiver)
     }
 
+    /// Sends a query to the best store node that isn't in the excluded nodes,
+    /// or queues it until a store node is available. Returns the id of the
+    /// node to which it was sent, if any.
+    fn send_query_message(
+        &self,
+        request_id: ConsistentTimestamp,
+        query: &EntityQuery,
+        exclude: &[NodeId],
+    ) -> Result<Option<NodeId>, Error> {
+        let request_frame = query_to_request_frame(query)?;
+        let message =
+            OutMessage::from_framed_message(&self.cell, ServiceType::Store, request_frame)?
+                .with_expiration(Some(Instant::now() + self.config.query_timeout))
+                .with_rdv(request_id);
+
+        if let Some(node) = self.select_store_node(exclude) {
+            let node_id = node.id().clone();
+            self.send_node_message(message, node)?;
+            Ok(Some(node_id))
+        } else {
+            self.send_store_node_message(message)?;
+            Ok(self.store_node.as_ref().map(|node| node.id().clone()))
+        }
+    }
+
     fn watch_query(
         &mut self,
         query: EntityQuery,
@@ -410,7 +633,7 @@ impl Inner {
             request_id,
             result_sender,
             query,
-            last_register: Some(Instant::now()),
+            last_register: Some(self.clock.instant()),
         };
 
         self.send_watch_query(&watched_query)?;
@@ -429,45 +652,252 @@ impl Inner {
     }
 
     fn send_unwatch_query(&self, token: WatchToken) -> Result<(), Error> {
+        let message = self.unwatch_query_message(token)?;
+        self.send_store_node_message(message)
+    }
+
+    fn send_unwatch_query_to(&self, token: WatchToken, node: Node) -> Result<(), Error> {
+        let message = self.unwatch_query_message(token)?;
+        self.send_node_message(message, node)
+    }
+
+    fn unwatch_query_message(&self, token: WatchToken) -> Result<OutMessage, Error> {
         let mut frame_builder = CapnpFrameBuilder::<unwatch_query_request::Owned>::new();
         let mut message_builder = frame_builder.get_builder();
         message_builder.set_token(token);
 
-        let message =
-            OutMessage::from_framed_message(&self.cell, ServiceType::Store, frame_builder)?;
-
-        self.send_store_node_message(message)
+        Ok(OutMessage::from_framed_message(
+            &self.cell,
+            ServiceType::Store,
+            frame_builder,
+        )?)
     }
 
+    /// Fails requests that timed out, and returns the nodes to which they were
+    /// sent.
     fn check_map_requests_timeouts<T>(
         requests: &mut HashMap<ConsistentTimestamp, PendingRequest<T>>,
+        now: Instant,
         timeout: Duration,
-    ) {
+    ) -> Vec<Option<NodeId>> {
         let mut timed_out_requests = Vec::new();
         for request in requests.values() {
-            if request.send_time.elapsed() > timeout {
+            if now - request.send_time > timeout {
                 timed_out_requests.push(request.request_id);
             }
         }
 
+        let mut timed_out_nodes = Vec::new();
         for request_id in timed_out_requests {
             if let Some(request) = requests.remove(&request_id) {
                 let _ = request
                     .result_sender
-                    .send(Err(Error::Timeout(request.send_time.elapsed(), timeout)));
+                    .send(Err(Error::Timeout(now - request.send_time, timeout)));
+                timed_out_nodes.push(request.node_id);
             }
         }
+
+        timed_out_nodes
+    }
+
+    /// Retries queries that timed out on another store node, or fails them if
+    /// they were tried on too many nodes or if no other node is available.
+    /// Queries are idempotent, so a late response from the node on which the
+    /// query timed out is still accepted.
+    fn check_queries_timeouts(&mut self) {
+        let timeout = self.config.query_timeout;
+        let now = self.clock.instant();
+        let timed_out_queries = self
+            .pending_queries
+            .values()
+            .filter(|query| now - query.request.send_time > timeout)
+            .map(|query| query.request.request_id)
+            .collect::<Vec<_>>();
+
+        for request_id in timed_out_queries {
+            let Some(mut pending_query) = self.pending_queries.remove(&request_id) else {
+                continue;
+            };
+
+            if let Some(node_id) = pending_query.request.node_id.take() {
+                self.record_node_timeout(&node_id);
+                pending_query.tried_nodes.push(node_id);
+            }
+
+            if pending_query.tried_nodes.len() < self.config.query_max_attempts {
+                let retry_node = self.select_store_node(&pending_query.tried_nodes);
+                if let Some(retry_node) = retry_node {
+                    info!(
+                        "Query {:?} timed out. Retrying on {}",
+                        request_id, retry_node
+                    );
+                    match self.send_query_message(
+                        request_id,
+                        &pending_query.query,
+                        &pending_query.tried_nodes,
+                    ) {
+                        Ok(node_id) => {
+                            pending_query.request.node_id = node_id;
+                            pending_query.request.send_time = now;
+                            self.pending_queries.insert(request_id, pending_query);
+                            continue;
+                        }
+                        Err(err) => {
+                            error!("Couldn't retry query on another node: {}", err);
+                        }
+                    }
+                }
+            }
+
+            let request = pending_query.request;
+            let cached = self
+                .query_cache
+                .as_ref()
+                .and_then(|cache| cache.get(&pending_query.query));
+            if let Some(cached) = cached {
+                info!("Query {:?} timed out. Returning cached results", request_id);
+                let _ = request.result_sender.send(Ok(cached));
+                continue;
+            }
+
+            let _ = request
+                .result_sender
+                .send(Err(Error::Timeout(now - request.send_time, timeout)));
+        }
+    }
+
+    /// Records a response from a store node to one of our requests, which
+    /// makes it healthy again and updates its average latency.
+    fn record_node_response<T>(&mut self, node_id: &NodeId, request: &PendingRequest<T>) {
+        let health = self.nodes_health.entry(node_id.clone()).or_default();
+        health.consecutive_timeouts = 0;
+        health.last_timeout = None;
+
+        let latency = self.clock.instant() - request.send_time;
+        health.latency = Some(match health.latency {
+            Some(avg) => (avg * 4 + latency) / 5,
+            None => latency,
+        });
+    }
+
+    /// Records a timed out request on a store node. If the node is our primary
+    /// store node and it became unhealthy, we fail over to another node.
+    fn record_node_timeout(&mut self, node_id: &NodeId) {
+        let health = self.nodes_health.entry(node_id.clone()).or_default();
+        health.consecutive_timeouts += 1;
+        health.last_timeout = Some(self.clock.instant());
+
+        let is_primary = self
+            .store_node
+            .as_ref()
+            .is_some_and(|node| node.id() == node_id);
+        if is_primary && self.is_node_unhealthy(node_id) {
+            self.failover_store_node();
+        }
+    }
+
+    fn is_node_unhealthy(&self, node_id: &NodeId) -> bool {
+        self.nodes_health.get(node_id).is_some_and(|health| {
+            health.consecutive_timeouts >= self.config.node_unhealthy_timeouts
+                && health
+                    .last_timeout
+                    .is_some_and(|i| self.clock.instant() - i < self.config.node_unhealthy_duration)
+        })
+    }
+
+    /// Switches the primary store node to another healthy node, and
+    /// re-registers watched queries on it.
+    fn failover_store_node(&mut self) {
+        let Some(current_node) = self.store_node.clone() else {
+            return;
+        };
+
+        let Some(new_node) = self.select_store_node(&[current_node.id().clone()]) else {
+            warn!(
+                "Store node {} is unhealthy, but no other store node is available",
+                current_node
+            );
+            return;
+        };
+        if self.is_node_unhealthy(new_node.id()) {
+            return;
+        }
+
+        info!(
+            "Store node {} is unhealthy. Failing over to {}",
+            current_node, new_node
+        );
+        for watched_query in self.watched_queries.values() {
+            if let Err(err) =
+                self.send_unwatch_query_to(watched_query.query.watch_token, current_node.clone())
+            {
+                debug!("Couldn't unwatch query on previous store node: {}", err);
+            }
+        }
+
+        self.store_node = Some(new_node);
+        self.send_watched_queries_keepalive(true);
+    }
+
+    /// Selects the connected store node, that isn't in the excluded nodes, to
+    /// which a request should be sent. Healthy nodes are preferred, and among
+    /// them, the one with the lowest average latency weighted by its number of
+    /// in-flight queries, so that queries are spread among nodes.
+    fn select_store_node(&self, exclude: &[NodeId]) -> Option<Node> {
+        let candidates = {
+            let cell_nodes = self.cell.nodes();
+            cell_nodes
+                .iter()
+                .with_role(CellNodeRole::Store)
+                .filter(|cell_node| {
+                    let node_id = cell_node.node().id();
+                    self.nodes_status.get(node_id) == Some(&ConnectionStatus::Connected)
+                        && !exclude.contains(node_id)
+                })
+                .map(|cell_node| cell_node.node().clone())
+                .collect::<Vec<_>>()
+        };
+
+        let (healthy, unhealthy): (Vec<Node>, Vec<Node>) = candidates
+            .into_iter()
+            .partition(|node| !self.is_node_unhealthy(node.id()));
+        let candidates = if !healthy.is_empty() {
+            healthy
+        } else {
+            unhealthy
+        };
+
+        candidates
+            .into_iter()
+            .min_by_key(|node| self.node_load_score(node.id()))
+    }
+
+    fn node_load_score(&self, node_id: &NodeId) -> u128 {
+        let latency = self
+            .nodes_health
+            .get(node_id)
+            .and_then(|health| health.latency)
+            .unwrap_or_default();
+
+        let in_flight = self
+            .pending_queries
+            .values()
+            .filter(|query| query.request.node_id.as_ref() == Some(node_id))
+            .count();
+
+        (latency.as_micros() + 1) * (in_flight as u128 + 1)
     }
 
     fn send_watched_queries_keepalive(&mut self, force: bool) {
         let register_interval = self.config.watched_register_interval;
+        let now = self.clock.instant();
 
         let mut sent_queries = Vec::new();
         for (token, query) in &self.watched_queries {
             if force
                 || query
                     .last_register
-                    .map_or(true, |i| i.elapsed() > register_interval)
+                    .map_or(true, |i| now - i > register_interval)
             {
                 if let Err(err) = self.send_watch_query(query) {
                     error!("Couldn't send watch query: {}", err);
@@ -478,7 +908,7 @@ impl Inner {
 
         for token in &sent_queries {
             let query = self.watched_queries.get_mut(token).unwrap();
-            query.last_register = Some(Instant::now());
+            query.last_register = Some(now);
         }
     }
 
@@ -492,12 +922,16 @@ impl Inner {
             return Ok(());
         };
 
+        self.send_node_message(message, store_node)
+    }
+
+    fn send_node_message(&self, message: OutMessage, node: Node) -> Result<(), Error> {
         let transport = self.transport_out.as_ref().ok_or_else(|| {
             Error::Fatal(anyhow!("Tried to send message, but transport_out was none"))
         })?;
 
         transport
-            .unbounded_send(OutEvent::Message(message.with_destination(store_node)))
+            .unbounded_send(OutEvent::Message(message.with_destination(node)))
             .map_err(|_err| {
                 Error::Fatal(anyhow!(
                     "Tried to send message, but transport_out channel is closed"
@@ -524,7 +958,7 @@ impl Inner {
 /// Parsed incoming message via transport.
 enum IncomingMessage {
     MutationResponse(MutationResult),
-    QueryResponse(EntityResults),
+    QueryResponse(Box<EntityResults>),
 }
 
 impl IncomingMessage {
@@ -539,7 +973,7 @@ impl IncomingMessage {
             | <watched_query_response::Owned as MessageType>::MESSAGE_TYPE => {
                 let query_frame = in_message.get_data_as_framed_message()?;
                 let query_result = query_results_from_response_frame(query_frame)?;
-                Ok(IncomingMessage::QueryResponse(query_result))
+                Ok(IncomingMessage::QueryResponse(Box::new(query_result)))
             }
             other => Err(anyhow!("Received message of unknown type: {}", other).into()),
         }
@@ -551,6 +985,23 @@ struct PendingRequest<T> {
     request_id: ConsistentTimestamp,
     result_sender: oneshot::Sender<Result<T, Error>>,
     send_time: Instant,
+    node_id: Option<NodeId>,
+}
+
+/// Query for which we're waiting a response, which can be retried on other
+/// nodes than the ones it was tried on.
+struct PendingQuery {
+    request: PendingRequest<EntityResults>,
+    query: EntityQuery,
+    tried_nodes: Vec<NodeId>,
+}
+
+/// Latency & health of a store node, based on its responses to our requests.
+#[derive(Default)]
+struct NodeHealth {
+    latency: Option<Duration>,
+    consecutive_timeouts: usize,
+    last_timeout: Option<Instant>,
 }
 
 struct WatchedQueryRequest {
@@ -583,6 +1034,17 @@ impl ClientHandle {
         let inner = inner.read().ok()?;
         inner.store_node.clone()
     }
+
+    /// Takes the mutations that were queued in offline mode, but that got
+    /// refused by the store node once sent, along with the error it returned.
+    pub fn take_refused_offline_mutations(&self) -> Result<Vec<RefusedMutation>, Error> {
+        let inner = self.inner.upgrade().ok_or(Error::Dropped)?;
+        let mut inner = inner.write()?;
+        match &mut inner.offline_mutations {
+            Some(offline_mutations) => offline_mutations.take_refused(),
+            None => Ok(Vec::new()),
+        }
+    }
 }
 
 #[async_trait]
@@ -675,6 +1137,7 @@ mod tests {
         futures::spawn_future,
         tests_utils::expect_eventually,
     };
+    use exocore_protos::generated::store_transport_capnp::{query_request, watched_query_request};
     use exocore_transport::testing::MockTransport;
 
     use super::*;
@@ -761,4 +1224,89 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn query_retried_and_failover_on_timeout() -> anyhow::Result<()> {
+        let client_node = LocalNode::generate();
+        let full_cell = FullCell::generate(client_node.clone())?;
+        let clock = Clock::new();
+        let transport = MockTransport::default();
+
+        let mut server_nodes = Vec::new();
+        let mut server_transports = Vec::new();
+        for _i in 0..2 {
+            let node = LocalNode::generate();
+            let mut cell_nodes = full_cell.cell().nodes_mut();
+
+            cell_nodes.add(node.node().clone());
+            let cell_node = cell_nodes.get_mut(node.id()).unwrap();
+            cell_node.add_role(CellNodeRole::Store);
+
+            server_nodes.push(node.clone());
+
+            let transport = transport
+                .get_transport(node, ServiceType::Store)
+                .into_testable_handle(full_cell.cell().clone());
+
+            server_transports.push(transport);
+        }
+
+        let transport_handle = transport.get_transport(client_node, ServiceType::Store);
+        let config = ClientConfiguration {
+            query_timeout: Duration::from_millis(300),
+            management_interval: Duration::from_millis(50),
+            node_unhealthy_timeouts: 1,
+            ..Default::default()
+        };
+        let client = Client::new(config, full_cell.cell().clone(), clock, transport_handle)?;
+        let client_inner = client.inner.clone();
+        let client_handle = client.get_handle();
+
+        spawn_future(async move {
+            let _ = client.run().await;
+        });
+        client_handle.on_start().await;
+
+        transport.notify_node_connection_status(server_nodes[0].id(), ConnectionStatus::Connected);
+        expect_eventually(|| -> bool {
+            let inner = client_inner.read().unwrap();
+            inner.store_node.is_some()
+        });
+
+        let _watched_query = client_handle.watched_query(QueryBuilder::test(true).build())?;
+        server_transports[0].recv_msg().await;
+
+        // query goes to node 0, which never answers
+        let query_handle = client_handle.clone();
+        let query_result =
+            tokio::spawn(async move { query_handle.query(QueryBuilder::test(true).build()).await });
+        server_transports[0].recv_msg().await;
+
+        transport.notify_node_connection_status(server_nodes[1].id(), ConnectionStatus::Connected);
+
+        // node 0 is now unhealthy, so client fails over to node 1,
+        // re-registers the watched query on it and retries the query on it
+        let watch_request = server_transports[1].recv_msg().await;
+        assert_eq!(
+            watch_request.typ,
+            <watched_query_request::Owned as MessageType>::MESSAGE_TYPE
+        );
+        assert_eq!(
+            client_handle.store_node().unwrap().id(),
+            server_nodes[1].id()
+        );
+
+        let retried_query = server_transports[1].recv_msg().await;
+        assert_eq!(
+            retried_query.typ,
+            <query_request::Owned as MessageType>::MESSAGE_TYPE
+        );
+        let response_frame =
+            super::super::seri::query_results_to_response_frame(Ok(EntityResults::default()))?;
+        let response = retried_query.to_response_message(full_cell.cell(), response_frame)?;
+        server_transports[1].send_message(response).await;
+        assert!(query_result.await?.is_ok());
+
+        Ok(())
+    }
 }
@@ -1,3 +1,4 @@
+mod offline;
 mod seri;
 
 pub mod client;
@@ -0,0 +1,246 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+};
+
+use exocore_core::dir::DynDirectory;
+use exocore_protos::{
+    generated::exocore_store::{
+        EntityQuery, EntityResults, MutationQueue, MutationRequest, RefusedMutation,
+    },
+    prost::Message,
+};
+
+use crate::error::Error;
+
+const MUTATION_QUEUE_FILE: &str = "mutation_queue.pb";
+const MUTATION_QUEUE_TMP_FILE: &str = "mutation_queue.pb.tmp";
+
+/// Mutations that were made while no store node was reachable, persisted in the
+/// client's directory so that they aren't lost if the client is restarted
+/// before a store node becomes reachable again.
+///
+/// Mutations that get refused by the store node once sent are kept aside until
+/// they are taken by the user of the client.
+pub(super) struct OfflineMutationQueue {
+    dir: DynDirectory,
+    mutations: VecDeque<MutationRequest>,
+    refused: Vec<RefusedMutation>,
+}
+
+impl OfflineMutationQueue {
+    /// Loads the queue persisted in the given directory, or creates an empty
+    /// one if none was persisted yet.
+    pub(super) fn load(dir: DynDirectory) -> Result<OfflineMutationQueue, Error> {
+        let mut mutations = VecDeque::new();
+        let mut refused = Vec::new();
+        if dir.exists(Path::new(MUTATION_QUEUE_FILE)) {
+            let mut file = dir
+                .open_read(Path::new(MUTATION_QUEUE_FILE))
+                .map_err(|err| anyhow!("Couldn't open mutation queue: {}", err))?;
+            let mut bytes = Vec::new();
+            std::io::Read::read_to_end(&mut file, &mut bytes)?;
+
+            let queue =
+                MutationQueue::decode(bytes.as_slice()).map_err(exocore_protos::Error::from)?;
+            mutations.extend(queue.mutations);
+            refused = queue.refused;
+        }
+
+        Ok(OfflineMutationQueue {
+            dir,
+            mutations,
+            refused,
+        })
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.mutations.is_empty()
+    }
+
+    pub(super) fn front(&self) -> Option<&MutationRequest> {
+        self.mutations.front()
+    }
+
+    /// Adds a mutation at the end of the queue and persists it.
+    pub(super) fn push(&mut self, request: MutationRequest) -> Result<(), Error> {
+        self.mutations.push_back(request);
+        self.save()
+    }
+
+    /// Removes the first mutation of the queue, once it got sent to a store
+    /// node, and persists the queue.
+    pub(super) fn pop_front(&mut self) -> Result<Option<MutationRequest>, Error> {
+        let request = self.mutations.pop_front();
+        self.save()?;
+        Ok(request)
+    }
+
+    /// Moves the first mutation of the queue to the refused mutations, once it
+    /// got refused by a store node, and persists the queue.
+    pub(super) fn refuse_front(&mut self, error: String) -> Result<(), Error> {
+        if let Some(request) = self.mutations.pop_front() {
+            self.refused.push(RefusedMutation {
+                request: Some(request),
+                error,
+            });
+        }
+        self.save()
+    }
+
+    /// Takes the mutations that got refused by a store node, and persists the
+    /// queue.
+    pub(super) fn take_refused(&mut self) -> Result<Vec<RefusedMutation>, Error> {
+        let refused = std::mem::take(&mut self.refused);
+        if !refused.is_empty() {
+            self.save()?;
+        }
+        Ok(refused)
+    }
+
+    /// Persists the queue to a temporary file that then replaces the previous
+    /// one, so that the queue doesn't get corrupted if the client is killed
+    /// while writing it.
+    fn save(&self) -> Result<(), Error> {
+        let queue = MutationQueue {
+            mutations: self.mutations.iter().cloned().collect(),
+            refused: self.refused.clone(),
+        };
+
+        {
+            let mut file = self
+                .dir
+                .open_create(Path::new(MUTATION_QUEUE_TMP_FILE))
+                .map_err(|err| anyhow!("Couldn't create mutation queue: {}", err))?;
+            std::io::Write::write_all(&mut file, &queue.encode_to_vec())?;
+        }
+
+        self.dir
+            .rename(
+                Path::new(MUTATION_QUEUE_TMP_FILE),
+                Path::new(MUTATION_QUEUE_FILE),
+            )
+            .map_err(|err| anyhow!("Couldn't replace mutation queue: {}", err))?;
+
+        Ok(())
+    }
+}
+
+/// In-memory cache of the results of the most recent queries, used to answer
+/// queries while no store node is reachable.
+pub(super) struct QueryCache {
+    capacity: usize,
+    results: HashMap<Vec<u8>, EntityResults>,
+    order: VecDeque<Vec<u8>>,
+}
+
+impl QueryCache {
+    pub(super) fn new(capacity: usize) -> QueryCache {
+        QueryCache {
+            capacity,
+            results: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Caches the results of a query, evicting the oldest results if the cache
+    /// is full. Results that were skipped because their hash didn't change
+    /// don't contain any entities and aren't cached.
+    pub(super) fn put(&mut self, query: &EntityQuery, results: &EntityResults) {
+        if results.skipped_hash || self.capacity == 0 {
+            return;
+        }
+
+        let key = cache_key(query);
+        if self.results.insert(key.clone(), results.clone()).is_none() {
+            self.order.push_back(key);
+        }
+
+        while self.order.len() > self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.results.remove(&evicted);
+            }
+        }
+    }
+
+    /// Returns the cached results of the query, flagged as stale.
+    pub(super) fn get(&self, query: &EntityQuery) -> Option<EntityResults> {
+        let mut results = self.results.get(&cache_key(query))?.clone();
+        results.stale = true;
+        Some(results)
+    }
+}
+
+/// Queries are identified by their encoded form, without the fields that
+/// differ between executions of the same query.
+fn cache_key(query: &EntityQuery) -> Vec<u8> {
+    let mut query = query.clone();
+    query.watch_token = 0;
+    query.result_hash = 0;
+    query.encode_to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use exocore_core::dir::ram::RamDirectory;
+
+    use super::*;
+    use crate::{mutation::MutationBuilder, query::QueryBuilder};
+
+    #[test]
+    fn mutation_queue_persistence() -> anyhow::Result<()> {
+        let dir: DynDirectory = RamDirectory::new().into();
+
+        let mut queue = OfflineMutationQueue::load(dir.clone())?;
+        assert!(queue.is_empty());
+
+        let mutation1 = MutationBuilder::new().delete_entity("et1").build();
+        let mutation2 = MutationBuilder::new().delete_entity("et2").build();
+        queue.push(mutation1.clone())?;
+        queue.push(mutation2.clone())?;
+
+        let mut queue = OfflineMutationQueue::load(dir.clone())?;
+        assert_eq!(queue.front(), Some(&mutation1));
+        assert_eq!(queue.pop_front()?, Some(mutation1));
+
+        let mut queue = OfflineMutationQueue::load(dir.clone())?;
+        assert_eq!(queue.front(), Some(&mutation2));
+
+        queue.refuse_front("refused".to_string())?;
+        assert!(queue.is_empty());
+
+        let mut queue = OfflineMutationQueue::load(dir.clone())?;
+        assert!(queue.is_empty());
+        let refused = queue.take_refused()?;
+        assert_eq!(refused.len(), 1);
+        assert_eq!(refused[0].request, Some(mutation2));
+        assert_eq!(refused[0].error, "refused");
+
+        let mut queue = OfflineMutationQueue::load(dir)?;
+        assert!(queue.take_refused()?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_cache_eviction() {
+        let mut cache = QueryCache::new(2);
+
+        let query1 = QueryBuilder::matches("1").build();
+        let query2 = QueryBuilder::matches("2").build();
+        let query3 = QueryBuilder::matches("3").build();
+        let results = EntityResults {
+            estimated_count: 1,
+            ..Default::default()
+        };
+
+        cache.put(&query1, &results);
+        cache.put(&query2, &results);
+        assert!(cache.get(&query1).unwrap().stale);
+
+        cache.put(&query3, &results);
+        assert!(cache.get(&query1).is_none());
+        assert!(cache.get(&query2).is_some());
+        assert!(cache.get(&query3).is_some());
+    }
+}
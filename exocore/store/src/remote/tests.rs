@@ -3,10 +3,12 @@ use std::{sync::Arc, time::Duration};
 use exocore_core::{
     cell::{CellNodeRole, LocalNode},
     futures::spawn_future,
-    tests_utils::{assert_equal_res, async_expect_eventually, expect_eventually},
+    tests_utils::{assert_equal_res, assert_res, async_expect_eventually, expect_eventually},
 };
 use exocore_protos::generated::exocore_store::{EntityQuery, EntityResults, MutationResult};
-use exocore_transport::{testing::MockTransportServiceHandle, ServiceType};
+use exocore_transport::{
+    testing::MockTransportServiceHandle, transport::ConnectionStatus, ServiceType,
+};
 use futures::executor::block_on_stream;
 use tokio::sync::Mutex;
 
@@ -331,6 +333,130 @@ async fn client_drop_stops_watched_stream() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn offline_mutations_and_cached_queries() -> anyhow::Result<()> {
+    let client_config = ClientConfiguration {
+        offline_mode: true,
+        ..ClientConfiguration::default()
+    };
+
+    let test_remote_store = Arc::new(Mutex::new(
+        TestRemoteStore::new_with_configuration(Default::default(), client_config).await?,
+    ));
+    {
+        let mut test_remote_store = test_remote_store.lock().await;
+        test_remote_store.start_server().await?;
+        test_remote_store.start_client().await?;
+        expect_eventually(|| test_remote_store.client_handle.store_node().is_some());
+
+        let mutation = test_remote_store
+            .local_store
+            .create_put_contact_mutation("entity1", "trait1", "hello");
+        let result = test_remote_store.send_and_await_mutation(mutation).await?;
+        assert!(!result.queued);
+    }
+
+    // results of queries made while online get cached
+    async_expect_eventually(|| async {
+        let mut test_remote_store = test_remote_store.lock().await;
+        let query = QueryBuilder::matches("hello").build();
+        let results = test_remote_store.send_and_await_query(query).await.unwrap();
+        assert_equal_res(results.entities.len(), 1)
+    })
+    .await;
+
+    test_remote_store
+        .lock()
+        .await
+        .notify_server_status(ConnectionStatus::Disconnected);
+
+    // offline, queries are answered from cache and flagged as stale
+    async_expect_eventually(|| async {
+        let mut test_remote_store = test_remote_store.lock().await;
+        let query = QueryBuilder::matches("hello").build();
+        let results = test_remote_store.send_and_await_query(query).await.unwrap();
+        assert_res(results.stale)?;
+        assert_equal_res(results.entities.len(), 1)
+    })
+    .await;
+
+    // offline, queries that weren't cached fail
+    {
+        let mut store = test_remote_store.lock().await;
+        let query = QueryBuilder::matches("world").build();
+        let result = store.send_and_await_query(query).await;
+        assert!(matches!(result, Err(Error::Offline)));
+    }
+
+    // offline, mutations are queued
+    {
+        let mut store = test_remote_store.lock().await;
+        let mutation = store
+            .local_store
+            .create_put_contact_mutation("entity2", "trait1", "world");
+        let result = store.send_and_await_mutation(mutation).await?;
+        assert!(result.queued);
+
+        // once back online, queued mutations are replayed
+        store.notify_server_status(ConnectionStatus::Connected);
+    }
+
+    async_expect_eventually(|| async {
+        let mut test_remote_store = test_remote_store.lock().await;
+        let query = QueryBuilder::matches("world").build();
+        let results = test_remote_store.send_and_await_query(query).await?;
+        assert_res(!results.stale)?;
+        assert_equal_res(results.entities.len(), 1)
+    })
+    .await;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn offline_refused_mutations() -> anyhow::Result<()> {
+    let client_config = ClientConfiguration {
+        offline_mode: true,
+        ..ClientConfiguration::default()
+    };
+
+    let test_remote_store = Arc::new(Mutex::new(
+        TestRemoteStore::new_with_configuration(Default::default(), client_config).await?,
+    ));
+    {
+        let mut test_remote_store = test_remote_store.lock().await;
+        test_remote_store.start_server().await?;
+        test_remote_store.start_client().await?;
+        expect_eventually(|| test_remote_store.client_handle.store_node().is_some());
+
+        test_remote_store.notify_server_status(ConnectionStatus::Disconnected);
+    }
+
+    // test mutations are refused by store nodes, so they only succeed once queued
+    async_expect_eventually(|| async {
+        let mut test_remote_store = test_remote_store.lock().await;
+        let mutation = MutationBuilder::new().fail_mutation("entity1".to_string());
+        let result = test_remote_store.send_and_await_mutation(mutation).await?;
+        assert_res(result.queued)
+    })
+    .await;
+
+    let test_remote_store = test_remote_store.lock().await;
+    test_remote_store.notify_server_status(ConnectionStatus::Connected);
+
+    let mut refused = Vec::new();
+    expect_eventually(|| {
+        let client_handle = &test_remote_store.client_handle;
+        refused.extend(client_handle.take_refused_offline_mutations().unwrap());
+        !refused.is_empty()
+    });
+    assert_eq!(refused.len(), 1);
+    assert!(refused[0].request.is_some());
+    assert!(!refused[0].error.is_empty());
+
+    Ok(())
+}
+
 struct TestRemoteStore {
     local_store: TestStore,
     server_config: ServerConfiguration,
@@ -403,16 +529,17 @@ impl TestRemoteStore {
         self.client_handle.on_start().await;
 
         // notify that server is online for client to use id
+        self.notify_server_status(ConnectionStatus::Connected);
+
+        Ok(())
+    }
+
+    fn notify_server_status(&self, status: ConnectionStatus) {
         let node_id = self.local_store.cluster.cells[0].cell().local_node().id();
         self.local_store
             .cluster
             .transport_hub
-            .notify_node_connection_status(
-                node_id,
-                exocore_transport::transport::ConnectionStatus::Connected,
-            );
-
-        Ok(())
+            .notify_node_connection_status(node_id, status);
     }
 
     async fn send_and_await_mutation<M: Into<MutationRequestLike> + Send>(
@@ -9,7 +9,7 @@ use crate::{error::Error, mutation::MutationRequestLike};
 /// locally hosted store, while the remote is a store that is on a remote node.
 #[async_trait]
 pub trait Store: Clone + Send + 'static {
-    type WatchedQueryStream: Stream<Item = Result<EntityResults, Error>>;
+    type WatchedQueryStream: Stream<Item = Result<EntityResults, Error>> + Send;
 
     async fn mutate<M: Into<MutationRequestLike> + Send>(
         &self,
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use exocore::{
+    tests_utils::{CellSimulation, CellSimulationConfig, Workload},
+    transport::testing::NetworkConditions,
+};
+
+#[tokio::test(flavor = "current_thread", start_paused = true)]
+async fn workload_converges() -> anyhow::Result<()> {
+    let config = CellSimulationConfig {
+        seed: 1234,
+        ..Default::default()
+    };
+    let simulation = CellSimulation::start(config).await?;
+
+    let workload = Workload::generate(1234, simulation.clients.len(), 50);
+    let report = simulation.run_workload(&workload).await;
+    assert_eq!(report.mutations_failed, 0);
+    assert_eq!(report.queries_failed, 0);
+
+    simulation
+        .wait_converged(&Workload::convergence_queries())
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread", start_paused = true)]
+async fn workload_converges_on_unreliable_network() -> anyhow::Result<()> {
+    let config = CellSimulationConfig {
+        seed: 4321,
+        network: NetworkConditions {
+            latency: Duration::from_millis(2),
+            jitter: Duration::from_millis(5),
+            loss: 0.05,
+            duplication: 0.05,
+            reordering: 0.05,
+        },
+        ..Default::default()
+    };
+    let simulation = CellSimulation::start(config).await?;
+
+    // lost messages may make some requests time out, but nodes should still
+    // converge on the mutations that got through
+    let workload = Workload::generate(4321, simulation.clients.len(), 50);
+    let report = simulation.run_workload(&workload).await;
+    assert!(report.mutations_succeeded > 0);
+    assert!(report.queries_succeeded > 0);
+
+    simulation
+        .wait_converged(&Workload::convergence_queries())
+        .await?;
+
+    let stats = simulation.network_stats();
+    assert!(stats.lost > 0);
+    assert!(stats.duplicated > 0);
+    assert!(stats.reordered > 0);
+
+    Ok(())
+}
@@ -8,6 +8,7 @@ REPO_ROOT="$EXOCORE_ROOT/.."
 # Protobuf descriptors
 protoc -I"$EXOCORE_ROOT/protos/protobuf/" $EXOCORE_ROOT/protos/protobuf/exocore/store/*.proto -o "$EXOCORE_ROOT/protos/src/generated/exocore_store.fd"
 protoc -I"$EXOCORE_ROOT/protos/protobuf/" $EXOCORE_ROOT/protos/protobuf/exocore/test/*.proto -o "$EXOCORE_ROOT/protos/src/generated/exocore_test.fd"
+protoc -I"$EXOCORE_ROOT/protos/protobuf/" $EXOCORE_ROOT/protos/protobuf/exocore/apps/host.proto -o "$EXOCORE_ROOT/protos/src/generated/exocore_apps_host.fd"
 
 # Prost & capnp generation
 export GENERATE_PROTOS=1
@@ -12,6 +12,15 @@ pub struct HttpTransportConfig {
     pub handle_in_channel_size: usize,
     pub handle_out_channel_size: usize,
     pub request_timeout: Duration,
+
+    /// Size of the channel of replies to streaming requests (ex: watched
+    /// queries).
+    pub stream_channel_size: usize,
+
+    /// Interval at which watched queries are registered again to the store
+    /// while their HTTP connection is open. Should be lower than the store
+    /// server's registration timeout.
+    pub watched_query_register_interval: Duration,
 }
 
 impl HttpTransportConfig {
@@ -32,6 +41,8 @@ impl Default for HttpTransportConfig {
             handle_in_channel_size: 1000,
             handle_out_channel_size: 1000,
             request_timeout: Duration::from_secs(5),
+            stream_channel_size: 10,
+            watched_query_register_interval: Duration::from_secs(10),
         }
     }
 }
@@ -0,0 +1,328 @@
+use exocore_protos::{
+    prost::{ProstTimestampExt, Timestamp},
+    reflect::{self, ReflectMessage},
+    registry::Registry,
+    store::{
+        entity_mutation, entity_query, DeleteTraitMutation, Entity, EntityMutation, EntityQuery,
+        EntityResults, IdsPredicate, MatchPredicate, MutationRequest, MutationResult, Paging,
+        PutTraitMutation, QueryStringPredicate, Trait, TraitPredicate,
+    },
+};
+use hyper::{header, Body, Response};
+
+use super::server::RequestError;
+
+/// JSON representation of an entity query.
+///
+/// At most one of `query` (query string), `match` (full-text match), `trait`
+/// (trait type) or `ids` can be specified. All entities are matched if none is.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct JsonQuery {
+    pub query: Option<String>,
+    #[serde(rename = "match")]
+    pub match_text: Option<String>,
+    #[serde(rename = "trait")]
+    pub trait_name: Option<String>,
+    pub ids: Vec<String>,
+    pub count: u32,
+    pub cursor: Option<String>,
+    pub include_deleted: bool,
+    pub count_total: bool,
+}
+
+impl JsonQuery {
+    pub fn into_entity_query(self) -> Result<EntityQuery, RequestError> {
+        let mut predicates = Vec::new();
+        if let Some(query) = self.query {
+            predicates.push(entity_query::Predicate::QueryString(QueryStringPredicate {
+                query,
+            }));
+        }
+        if let Some(query) = self.match_text {
+            predicates.push(entity_query::Predicate::Match(MatchPredicate {
+                query,
+                ..Default::default()
+            }));
+        }
+        if let Some(trait_name) = self.trait_name {
+            predicates.push(entity_query::Predicate::Trait(TraitPredicate {
+                trait_name,
+                query: None,
+            }));
+        }
+        if !self.ids.is_empty() {
+            predicates.push(entity_query::Predicate::Ids(IdsPredicate { ids: self.ids }));
+        }
+
+        if predicates.len() > 1 {
+            return Err(RequestError::InvalidJson(
+                "only one of `query`, `match`, `trait` or `ids` can be specified".to_string(),
+            ));
+        }
+        let predicate = predicates
+            .pop()
+            .unwrap_or(entity_query::Predicate::All(Default::default()));
+
+        Ok(EntityQuery {
+            predicate: Some(predicate),
+            paging: Some(Paging {
+                count: self.count,
+                cursor: self.cursor.unwrap_or_default(),
+                ..Default::default()
+            }),
+            include_deleted: self.include_deleted,
+            count_total: self.count_total,
+            ..Default::default()
+        })
+    }
+}
+
+/// JSON representation of a mutation request.
+///
+/// Mutations use the same format as the one used by the chain entities export
+/// of the CLI, in which traits' messages are encoded as `{"type": "<message
+/// full name>", "value": {<fields>}}`.
+#[derive(Deserialize)]
+pub struct JsonMutationRequest {
+    pub mutations: Vec<JsonMutation>,
+    #[serde(default)]
+    pub wait_indexed: bool,
+    #[serde(default)]
+    pub return_entities: bool,
+    #[serde(default)]
+    pub common_entity_id: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JsonMutation {
+    PutTrait {
+        #[serde(default)]
+        entity_id: String,
+        #[serde(default)]
+        trait_id: String,
+        message: serde_json::Value,
+    },
+    DeleteTrait {
+        entity_id: String,
+        trait_id: String,
+    },
+    DeleteEntity {
+        entity_id: String,
+    },
+}
+
+impl JsonMutationRequest {
+    pub fn into_mutation_request(
+        self,
+        registry: &Registry,
+    ) -> Result<MutationRequest, RequestError> {
+        let mutations = self
+            .mutations
+            .into_iter()
+            .map(|mutation| mutation.into_entity_mutation(registry))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(MutationRequest {
+            mutations,
+            wait_indexed: self.wait_indexed,
+            return_entities: self.return_entities,
+            common_entity_id: self.common_entity_id,
+        })
+    }
+}
+
+impl JsonMutation {
+    fn into_entity_mutation(self, registry: &Registry) -> Result<EntityMutation, RequestError> {
+        use entity_mutation::Mutation;
+
+        Ok(match self {
+            JsonMutation::PutTrait {
+                entity_id,
+                trait_id,
+                message,
+            } => {
+                let message = reflect::from_json(registry, &message)
+                    .and_then(|message| message.encode_to_prost_any())
+                    .map_err(|err| RequestError::InvalidJson(err.to_string()))?;
+
+                EntityMutation {
+                    entity_id,
+                    idempotency_key: 0,
+                    mutation: Some(Mutation::PutTrait(PutTraitMutation {
+                        r#trait: Some(Trait {
+                            id: trait_id,
+                            message: Some(message),
+                            ..Default::default()
+                        }),
+                    })),
+                }
+            }
+            JsonMutation::DeleteTrait {
+                entity_id,
+                trait_id,
+            } => EntityMutation {
+                entity_id,
+                idempotency_key: 0,
+                mutation: Some(Mutation::DeleteTrait(DeleteTraitMutation { trait_id })),
+            },
+            JsonMutation::DeleteEntity { entity_id } => EntityMutation {
+                entity_id,
+                idempotency_key: 0,
+                mutation: Some(Mutation::DeleteEntity(Default::default())),
+            },
+        })
+    }
+}
+
+/// JSON representation of entity query results.
+#[derive(Serialize)]
+pub struct JsonResults {
+    pub entities: Vec<JsonEntity>,
+    pub estimated_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+impl JsonResults {
+    pub fn from_results(
+        registry: &Registry,
+        results: EntityResults,
+        count_total: bool,
+    ) -> JsonResults {
+        let next_cursor = results
+            .next_page
+            .filter(|_| !results.entities.is_empty())
+            .map(|paging| paging.cursor)
+            .filter(|cursor| !cursor.is_empty());
+
+        let entities = results
+            .entities
+            .into_iter()
+            .flat_map(|result| result.entity)
+            .map(|entity| JsonEntity::from_entity(registry, entity))
+            .collect();
+
+        JsonResults {
+            entities,
+            estimated_count: results.estimated_count,
+            total_count: count_total.then_some(results.total_count),
+            next_cursor,
+        }
+    }
+}
+
+/// JSON representation of a mutation result.
+#[derive(Serialize)]
+pub struct JsonMutationResult {
+    pub operation_ids: Vec<u64>,
+    pub entities: Vec<JsonEntity>,
+}
+
+impl JsonMutationResult {
+    pub fn from_result(registry: &Registry, result: MutationResult) -> JsonMutationResult {
+        JsonMutationResult {
+            operation_ids: result.operation_ids,
+            entities: result
+                .entities
+                .into_iter()
+                .map(|entity| JsonEntity::from_entity(registry, entity))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct JsonEntity {
+    pub id: String,
+    pub traits: Vec<JsonTrait>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creation_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modification_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deletion_date: Option<String>,
+}
+
+impl JsonEntity {
+    pub fn from_entity(registry: &Registry, entity: Entity) -> JsonEntity {
+        JsonEntity {
+            id: entity.id,
+            traits: entity
+                .traits
+                .into_iter()
+                .map(|trt| JsonTrait::from_trait(registry, trt))
+                .collect(),
+            creation_date: opt_date_to_json(entity.creation_date),
+            modification_date: opt_date_to_json(entity.modification_date),
+            deletion_date: opt_date_to_json(entity.deletion_date),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct JsonTrait {
+    pub id: String,
+    pub message: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creation_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modification_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deletion_date: Option<String>,
+}
+
+impl JsonTrait {
+    fn from_trait(registry: &Registry, trt: Trait) -> JsonTrait {
+        let message = trt
+            .message
+            .as_ref()
+            .map(|message| {
+                reflect::from_prost_any(registry, message)
+                    .and_then(|dyn_message| dyn_message.encode_json(registry))
+                    .unwrap_or_else(|err| {
+                        // type may not be in the cell's registry, we still return its type
+                        warn!(
+                            "Couldn't encode message of trait {} to json: {}",
+                            trt.id, err
+                        );
+                        serde_json::json!({
+                            "type": reflect::any_url_to_full_name(&message.type_url),
+                        })
+                    })
+            })
+            .unwrap_or_default();
+
+        JsonTrait {
+            id: trt.id,
+            message,
+            creation_date: opt_date_to_json(trt.creation_date),
+            modification_date: opt_date_to_json(trt.modification_date),
+            deletion_date: opt_date_to_json(trt.deletion_date),
+        }
+    }
+}
+
+pub fn from_json_body<'d, T: serde::Deserialize<'d>>(body: &'d [u8]) -> Result<T, RequestError> {
+    serde_json::from_slice(body).map_err(|err| RequestError::InvalidJson(err.to_string()))
+}
+
+pub fn to_json_response<T: serde::Serialize>(value: &T) -> Result<Response<Body>, RequestError> {
+    let body = serde_json::to_vec(value)
+        .map_err(|err| RequestError::Server(format!("Couldn't encode json: {}", err)))?;
+
+    let mut resp = Response::new(Body::from(body));
+    resp.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+
+    Ok(resp)
+}
+
+fn opt_date_to_json(date: Option<Timestamp>) -> Option<String> {
+    date.map(|date| date.to_chrono_datetime().to_rfc3339())
+}
@@ -1,5 +1,6 @@
 mod config;
 mod handles;
+pub mod json;
 mod requests;
 mod server;
 
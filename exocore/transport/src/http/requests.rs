@@ -1,14 +1,18 @@
 use std::{
     collections::HashMap,
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc, Weak,
     },
     time::Duration,
 };
 
 use exocore_core::futures::{block_on, sleep};
-use futures::{channel::oneshot, lock::Mutex, FutureExt};
+use futures::{
+    channel::{mpsc, oneshot},
+    lock::Mutex,
+    FutureExt, Stream, StreamExt,
+};
 
 use super::config::HttpTransportConfig;
 use crate::OutMessage;
@@ -18,7 +22,7 @@ pub type RequestId = u64;
 /// Tracks incoming HTTP requests for which we are waiting a reply from a
 /// service.
 pub struct RequestTracker {
-    requests: Mutex<HashMap<RequestId, oneshot::Sender<OutMessage>>>,
+    requests: Mutex<HashMap<RequestId, ReplySender>>,
     next_id: AtomicU64,
     config: HttpTransportConfig,
 }
@@ -46,19 +50,52 @@ impl RequestTracker {
             receive_timeout: self.config.request_timeout,
         };
 
-        requests.insert(request.id, sender);
+        requests.insert(request.id, ReplySender::Single(sender));
+
+        request
+    }
+
+    /// Pushes a new request for which we'll expect a stream of replies from a
+    /// service (ex: watched query).
+    pub async fn push_stream(self: Arc<Self>) -> TrackedStreamRequest {
+        let mut requests = self.requests.lock().await;
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+
+        let (sender, receiver) = mpsc::channel(self.config.stream_channel_size);
+        let overflowed = Arc::new(AtomicBool::new(false));
+        let request = TrackedStreamRequest {
+            id,
+            requests: Arc::downgrade(&self),
+            receiver,
+            overflowed: overflowed.clone(),
+        };
+
+        requests.insert(request.id, ReplySender::Stream(sender, overflowed));
 
         request
     }
 
     /// Handles a reply from a service to be sent back to a request.
+    ///
+    /// If the channel of a streaming request is full, the request is removed
+    /// and its stream is closed as overflowed instead of silently dropping the
+    /// reply, since we can't block the service while the client catches up.
     pub async fn reply(&self, request_id: RequestId, message: OutMessage) {
-        let sender = {
-            let mut requests = self.requests.lock().await;
-            requests.remove(&request_id)
-        };
+        let mut requests = self.requests.lock().await;
 
-        if let Some(sender) = sender {
+        if let Some(ReplySender::Stream(sender, overflowed)) = requests.get_mut(&request_id) {
+            if let Err(err) = sender.try_send(message) {
+                if err.is_full() {
+                    warn!(
+                        "Error replying message to streaming request {}. Channel is full, closing it.",
+                        request_id
+                    );
+                    overflowed.store(true, Ordering::Relaxed);
+                }
+                requests.remove(&request_id);
+            }
+        } else if let Some(ReplySender::Single(sender)) = requests.remove(&request_id) {
             if sender.send(message).is_err() {
                 warn!(
                     "Error replying message to request {}. Channel got dropped.",
@@ -79,6 +116,11 @@ impl RequestTracker {
     }
 }
 
+enum ReplySender {
+    Single(oneshot::Sender<OutMessage>),
+    Stream(mpsc::Sender<OutMessage>, Arc<AtomicBool>),
+}
+
 /// Receiving end of a the tracked request. This is used in the HTTP request
 /// handler to wait for a reply from a service.
 pub struct TrackedRequest {
@@ -115,3 +157,43 @@ impl Drop for TrackedRequest {
         }
     }
 }
+
+/// Receiving end of a tracked request that expects multiple replies. This is
+/// used in the HTTP request handler to stream replies from a service.
+pub struct TrackedStreamRequest {
+    id: RequestId,
+    requests: Weak<RequestTracker>,
+    receiver: mpsc::Receiver<OutMessage>,
+    overflowed: Arc<AtomicBool>,
+}
+
+impl TrackedStreamRequest {
+    pub fn id(&self) -> RequestId {
+        self.id
+    }
+
+    /// Indicates if the stream got closed because replies weren't consumed
+    /// fast enough.
+    pub fn has_overflowed(&self) -> bool {
+        self.overflowed.load(Ordering::Relaxed)
+    }
+}
+
+impl Stream for TrackedStreamRequest {
+    type Item = OutMessage;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+impl Drop for TrackedStreamRequest {
+    fn drop(&mut self) {
+        if let Some(requests) = self.requests.upgrade() {
+            block_on(requests.remove(self.id));
+        }
+    }
+}
@@ -1,9 +1,10 @@
-use std::{borrow::Cow, net::Ipv4Addr, sync::Arc};
+use std::{borrow::Cow, net::Ipv4Addr, sync::Arc, time::Duration};
 
+use base64::Engine;
 use exocore_core::{
-    cell::{Cell, CellNodes, LocalNode, Node},
+    cell::{Cell, CellId, CellNodes, LocalNode, Node},
     framing::{CapnpFrameBuilder, FrameBuilder},
-    futures::block_on,
+    futures::{block_on, interval, spawn_future},
     sec::auth_token::AuthToken,
     time::Clock,
     utils::handle_set::HandleSet,
@@ -11,18 +12,23 @@ use exocore_core::{
 use exocore_protos::{
     capnp,
     generated::store_transport_capnp::{
-        mutation_request, mutation_response, query_request, query_response,
+        mutation_request, mutation_response, query_request, query_response, unwatch_query_request,
+        watched_query_request,
     },
+    prost::Message,
+    store::{entity_query, EntityQuery, EntityResults, IdsPredicate, MutationResult, Paging},
 };
 use futures::{channel::mpsc, lock::Mutex, FutureExt, StreamExt};
 use hyper::{
+    header,
     service::{make_service_fn, service_fn},
     Body, Request, Response, Server, StatusCode,
 };
 
 use super::{
     handles::{ServiceHandle, ServiceHandles},
-    requests::{RequestTracker, TrackedRequest},
+    json::{self, JsonEntity, JsonMutationRequest, JsonMutationResult, JsonQuery, JsonResults},
+    requests::{RequestId, RequestTracker, TrackedRequest, TrackedStreamRequest},
     HttpTransportConfig, HttpTransportServiceHandle,
 };
 use crate::{transport::ConnectionId, Error, InMessage, OutEvent, OutMessage, ServiceType};
@@ -34,7 +40,13 @@ use crate::{transport::ConnectionId, Error, InMessage, OutEvent, OutMessage, Ser
 /// through a generated `AuthToken` signed by the public key of a node of the
 /// cell.
 ///
-/// At the moment, this transport is only used for entity queries and mutations.
+/// At the moment, this transport is only used for entity queries, watched
+/// queries and mutations. Watched queries results are streamed back using
+/// Server-Sent Events.
+///
+/// Queries and mutations can also be done in JSON via the `/store/json/*`
+/// endpoints, in which case traits are encoded and decoded using the cell's
+/// schemas registry.
 pub struct HttpTransportServer {
     local_node: LocalNode,
     config: HttpTransportConfig,
@@ -117,21 +129,29 @@ impl HttpTransportServer {
                 let request_tracker = request_tracker.clone();
                 let service_handles = self.service_handles.clone();
                 let clock = self.clock.clone();
+                let config = self.config.clone();
 
                 let server = Server::bind(&addr).serve(make_service_fn(move |_socket| {
                     let request_tracker = request_tracker.clone();
                     let service_handles = service_handles.clone();
                     let clock = clock.clone();
+                    let config = config.clone();
                     async move {
                         Ok::<_, hyper::Error>(service_fn(move |req| {
                             let request_tracker = request_tracker.clone();
                             let service_handles = service_handles.clone();
                             let clock = clock.clone();
+                            let config = config.clone();
 
                             async {
-                                let resp =
-                                    handle_request(request_tracker, service_handles, clock, req)
-                                        .await;
+                                let resp = handle_request(
+                                    request_tracker,
+                                    service_handles,
+                                    clock,
+                                    config,
+                                    req,
+                                )
+                                .await;
 
                                 let resp = match resp {
                                     Ok(resp) => resp,
@@ -214,6 +234,7 @@ async fn handle_request(
     request_tracker: Arc<RequestTracker>,
     service_handles: Arc<Mutex<ServiceHandles>>,
     clock: Clock,
+    config: HttpTransportConfig,
     req: Request<Body>,
 ) -> Result<Response<Body>, RequestError> {
     let request_type = RequestType::from_url_path(req.uri().path()).inspect_err(|err| {
@@ -276,7 +297,8 @@ async fn handle_request(
 
             drop(services); // drop handles to release lock while we wait for answer
 
-            Ok(receive_entity_query(&cell, tracked_request).await?)
+            let results = receive_entity_query(&cell, tracked_request).await?;
+            Ok(Response::new(Body::from(results)))
         }
         RequestType::StoreMutation => {
             let body_bytes = hyper::body::to_bytes(req.into_body()).await?;
@@ -294,7 +316,120 @@ async fn handle_request(
 
             drop(services); // drop handles to release lock while we wait for answer
 
-            Ok(receive_entity_mutation(&cell, tracked_request).await?)
+            let result = receive_entity_mutation(&cell, tracked_request).await?;
+            Ok(Response::new(Body::from(result)))
+        }
+        RequestType::StoreJsonQuery => {
+            let body_bytes = hyper::body::to_bytes(req.into_body()).await?;
+            let json_query: JsonQuery = json::from_json_body(&body_bytes)?;
+            let query = json_query.into_entity_query()?;
+            let tracked_request = request_tracker.push().await;
+            let cell = service.cell.clone();
+
+            send_entity_query(
+                &query.encode_to_vec(),
+                &clock,
+                from_node,
+                service,
+                &tracked_request,
+            )
+            .await?;
+
+            drop(services); // drop handles to release lock while we wait for answer
+
+            let results = receive_entity_query(&cell, tracked_request).await?;
+            let results = EntityResults::decode(results.as_slice())
+                .map_err(|err| RequestError::Server(format!("Invalid results: {}", err)))?;
+            let json_results =
+                JsonResults::from_results(cell.schemas(), results, query.count_total);
+            json::to_json_response(&json_results)
+        }
+        RequestType::StoreJsonEntity => {
+            let entity_id = read_query_param(&req, "id").ok_or(RequestError::Query)?;
+            let query = EntityQuery {
+                predicate: Some(entity_query::Predicate::Ids(IdsPredicate {
+                    ids: vec![entity_id],
+                })),
+                paging: Some(Paging {
+                    count: 1,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            let tracked_request = request_tracker.push().await;
+            let cell = service.cell.clone();
+
+            send_entity_query(
+                &query.encode_to_vec(),
+                &clock,
+                from_node,
+                service,
+                &tracked_request,
+            )
+            .await?;
+
+            drop(services); // drop handles to release lock while we wait for answer
+
+            let results = receive_entity_query(&cell, tracked_request).await?;
+            let results = EntityResults::decode(results.as_slice())
+                .map_err(|err| RequestError::Server(format!("Invalid results: {}", err)))?;
+            let entity = results
+                .entities
+                .into_iter()
+                .find_map(|result| result.entity)
+                .ok_or(RequestError::NotFound)?;
+            json::to_json_response(&JsonEntity::from_entity(cell.schemas(), entity))
+        }
+        RequestType::StoreJsonMutation => {
+            let body_bytes = hyper::body::to_bytes(req.into_body()).await?;
+            let json_request: JsonMutationRequest = json::from_json_body(&body_bytes)?;
+            let request = json_request.into_mutation_request(service.cell.schemas())?;
+            let tracked_request = request_tracker.push().await;
+            let cell = service.cell.clone();
+
+            send_entity_mutation(
+                &request.encode_to_vec(),
+                &clock,
+                from_node,
+                service,
+                &tracked_request,
+            )
+            .await?;
+
+            drop(services); // drop handles to release lock while we wait for answer
+
+            let result = receive_entity_mutation(&cell, tracked_request).await?;
+            let result = MutationResult::decode(result.as_slice())
+                .map_err(|err| RequestError::Server(format!("Invalid result: {}", err)))?;
+            json::to_json_response(&JsonMutationResult::from_result(cell.schemas(), result))
+        }
+        RequestType::StoreWatchedQuery => {
+            let query_bytes = read_watched_query(req).await?;
+            let (watch_token, query_bytes) =
+                ensure_watch_token(&query_bytes, &clock, &service.cell)?;
+            let tracked_request = request_tracker.push_stream().await;
+            let cell = service.cell.clone();
+
+            send_watched_query(
+                &query_bytes,
+                &clock,
+                from_node.clone(),
+                service,
+                tracked_request.id(),
+            )?;
+
+            drop(services); // drop handles to release lock while we stream results
+
+            let watched_query = WatchedQuery {
+                cell,
+                from_node,
+                watch_token,
+                query_bytes,
+                service_handles,
+                clock,
+                register_interval: config.watched_query_register_interval,
+            };
+            Ok(stream_watched_query(watched_query, tracked_request))
         }
     }
 }
@@ -327,7 +462,7 @@ async fn send_entity_query(
 async fn receive_entity_query(
     cell: &Cell,
     tracked_request: TrackedRequest,
-) -> Result<Response<Body>, RequestError> {
+) -> Result<Vec<u8>, RequestError> {
     let local_node = cell.local_node().node().clone();
 
     let response_message = tracked_request
@@ -341,8 +476,7 @@ async fn receive_entity_query(
     let result_reader = result_message.get_reader()?;
 
     if !result_reader.has_error() {
-        let body = Body::from(result_reader.get_response()?.to_vec());
-        Ok(Response::new(body))
+        Ok(result_reader.get_response()?.to_vec())
     } else {
         Err(RequestError::Query)
     }
@@ -376,7 +510,7 @@ async fn send_entity_mutation(
 async fn receive_entity_mutation(
     cell: &Cell,
     tracked_request: TrackedRequest,
-) -> Result<Response<Body>, RequestError> {
+) -> Result<Vec<u8>, RequestError> {
     let local_node = cell.local_node().node().clone();
 
     let response_message = tracked_request
@@ -390,27 +524,243 @@ async fn receive_entity_mutation(
     let result_reader = result_message.get_reader()?;
 
     if !result_reader.has_error() {
-        let body = Body::from(result_reader.get_response()?.to_vec());
-        Ok(Response::new(body))
+        Ok(result_reader.get_response()?.to_vec())
+    } else {
+        Err(RequestError::Query)
+    }
+}
+
+/// Reads the watched query from the `query` parameter of the URL, encoded in
+/// URL-safe base64, or from the body of the request if not specified. The URL
+/// parameter allows browsers' `EventSource`, which can't send a body, to watch
+/// queries.
+async fn read_watched_query(request: Request<Body>) -> Result<Vec<u8>, RequestError> {
+    if let Some(query) = request.uri().query() {
+        let params = url::form_urlencoded::parse(query.as_bytes());
+        if let Some(encoded_query) = get_query_param(params, "query") {
+            return base64::engine::general_purpose::URL_SAFE_NO_PAD
+                .decode(encoded_query.trim_end_matches('='))
+                .map_err(|_| RequestError::Query);
+        }
+    }
+
+    let body_bytes = hyper::body::to_bytes(request.into_body()).await?;
+    Ok(body_bytes.to_vec())
+}
+
+/// Makes sure that the watched query has a watch token, since it is needed to
+/// register it again and to unregister it once the connection gets closed.
+fn ensure_watch_token(
+    query_bytes: &[u8],
+    clock: &Clock,
+    cell: &Cell,
+) -> Result<(u64, Vec<u8>), RequestError> {
+    let mut query = EntityQuery::decode(query_bytes).map_err(|_| RequestError::Query)?;
+    if query.watch_token == 0 {
+        query.watch_token = clock.consistent_time(cell.local_node()).into();
+    }
+
+    Ok((query.watch_token, query.encode_to_vec()))
+}
+
+fn send_watched_query(
+    query_bytes: &[u8],
+    clock: &Clock,
+    from_node: Node,
+    service: &mut ServiceHandle,
+    request_id: RequestId,
+) -> Result<(), RequestError> {
+    let local_node = service.cell.local_node().node().clone();
+
+    let mut frame_builder = CapnpFrameBuilder::<watched_query_request::Owned>::new();
+    let mut msg_builder = frame_builder.get_builder();
+    msg_builder.set_request(query_bytes);
+
+    let message =
+        OutMessage::from_framed_message(&service.cell, ServiceType::Store, frame_builder)?
+            .with_destination(local_node)
+            .with_rdv(clock.consistent_time(service.cell.local_node()))
+            .with_connection(ConnectionId::HttpServer(request_id))
+            .to_in_message(from_node)?;
+
+    service.send_message(message)?;
+
+    Ok(())
+}
+
+fn send_unwatch_query(
+    watch_token: u64,
+    from_node: Node,
+    service: &mut ServiceHandle,
+) -> Result<(), RequestError> {
+    let local_node = service.cell.local_node().node().clone();
+
+    let mut frame_builder = CapnpFrameBuilder::<unwatch_query_request::Owned>::new();
+    let mut msg_builder = frame_builder.get_builder();
+    msg_builder.set_token(watch_token);
+
+    let message =
+        OutMessage::from_framed_message(&service.cell, ServiceType::Store, frame_builder)?
+            .with_destination(local_node)
+            .to_in_message(from_node)?;
+
+    service.send_message(message)?;
+
+    Ok(())
+}
+
+/// Watched query for which results are streamed to an HTTP connection.
+struct WatchedQuery {
+    cell: Cell,
+    from_node: Node,
+    watch_token: u64,
+    query_bytes: Vec<u8>,
+    service_handles: Arc<Mutex<ServiceHandles>>,
+    clock: Clock,
+    register_interval: Duration,
+}
+
+impl WatchedQuery {
+    async fn register(&self, request_id: RequestId) -> Result<(), RequestError> {
+        let mut services = self.service_handles.lock().await;
+        let service = get_store_handle(&mut services, self.cell.id())?;
+        send_watched_query(
+            &self.query_bytes,
+            &self.clock,
+            self.from_node.clone(),
+            service,
+            request_id,
+        )
+    }
+
+    async fn unregister(&self) -> Result<(), RequestError> {
+        let mut services = self.service_handles.lock().await;
+        let service = get_store_handle(&mut services, self.cell.id())?;
+        send_unwatch_query(self.watch_token, self.from_node.clone(), service)
+    }
+}
+
+fn get_store_handle<'s>(
+    services: &'s mut ServiceHandles,
+    cell_id: &CellId,
+) -> Result<&'s mut ServiceHandle, RequestError> {
+    services
+        .get_handle(cell_id, ServiceType::Store)
+        .ok_or_else(|| RequestError::Server("Store service handle got dropped".to_string()))
+}
+
+/// Streams results of a watched query as Server-Sent Events. Each `message`
+/// event contains base64 encoded `EntityResults`, sent every time the results
+/// change. An `error` event is sent before closing the stream if the query
+/// failed, or if the client didn't consume results fast enough.
+///
+/// While the connection is open, the query is registered again at interval
+/// since the store unregisters queries that aren't. Once the connection gets
+/// closed, the query is unregistered from the store.
+fn stream_watched_query(
+    watched_query: WatchedQuery,
+    mut tracked_request: TrackedStreamRequest,
+) -> Response<Body> {
+    let (mut body_sender, body) = Body::channel();
+
+    spawn_future(async move {
+        let mut register_interval = interval(watched_query.register_interval);
+        register_interval.tick().await; // first tick is immediate
+
+        loop {
+            futures::select! {
+                message = tracked_request.next().fuse() => {
+                    let Some(message) = message else {
+                        if tracked_request.has_overflowed() {
+                            let event = "event: error\ndata: Results weren't consumed fast enough\n\n";
+                            let _ = body_sender.send_data(event.into()).await;
+                        }
+                        break;
+                    };
+
+                    let (event, is_error) = match watched_query_event(&watched_query.cell, message) {
+                        Ok(event) => (event, false),
+                        Err(err) => (format!("event: error\ndata: {}\n\n", err), true),
+                    };
+
+                    if body_sender.send_data(event.into()).await.is_err() || is_error {
+                        break;
+                    }
+                },
+                _ = register_interval.tick().fuse() => {
+                    // sending a comment allows detecting if the connection got closed
+                    if body_sender.send_data(": keep-alive\n\n".into()).await.is_err() {
+                        break;
+                    }
+
+                    if let Err(err) = watched_query.register(tracked_request.id()).await {
+                        error!("Couldn't register watched query again: {}", err);
+                        break;
+                    }
+                },
+            }
+        }
+
+        debug!(
+            "Watched query with token {} is done. Unregistering it.",
+            watched_query.watch_token
+        );
+        if let Err(err) = watched_query.unregister().await {
+            error!("Couldn't unregister watched query: {}", err);
+        }
+    });
+
+    let mut resp = Response::new(body);
+    let headers = resp.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/event-stream"),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        header::HeaderValue::from_static("no-cache"),
+    );
+
+    resp
+}
+
+fn watched_query_event(cell: &Cell, response_message: OutMessage) -> Result<String, RequestError> {
+    let local_node = cell.local_node().node().clone();
+
+    let message_envelope = response_message.envelope_builder.as_owned_frame();
+    let message = InMessage::from_node_and_frame(local_node, message_envelope)?;
+    let result_message = message.get_data_as_framed_message::<query_response::Owned>()?;
+    let result_reader = result_message.get_reader()?;
+
+    if !result_reader.has_error() {
+        let results = result_reader.get_response()?;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(results);
+        Ok(format!("data: {}\n\n", encoded))
     } else {
         Err(RequestError::Query)
     }
 }
 
+fn read_query_param(request: &Request<Body>, name: &str) -> Option<String> {
+    let query = request.uri().query()?;
+    let params = url::form_urlencoded::parse(query.as_bytes());
+    get_query_param(params, name).map(|value| value.to_string())
+}
+
 fn read_authorization_token(request: &Request<Body>) -> Result<String, RequestError> {
     let pq = request.uri();
     let path_and_query = pq.path_and_query().ok_or(RequestError::Unauthorized)?;
     let query = path_and_query.query().ok_or(RequestError::Unauthorized)?;
 
     let params = url::form_urlencoded::parse(query.as_bytes());
-    let token = get_query_token(params).ok_or(RequestError::Unauthorized)?;
+    let token = get_query_param(params, "token").ok_or(RequestError::Unauthorized)?;
 
     Ok(token.to_string())
 }
 
-fn get_query_token(pairs: url::form_urlencoded::Parse) -> Option<Cow<str>> {
+fn get_query_param<'p>(pairs: url::form_urlencoded::Parse<'p>, name: &str) -> Option<Cow<'p, str>> {
     for (key, value) in pairs {
-        if key == "token" {
+        if key == name {
             return Some(value);
         }
     }
@@ -420,9 +770,14 @@ fn get_query_token(pairs: url::form_urlencoded::Parse) -> Option<Cow<str>> {
 
 /// Type of an incoming HTTP request.
 #[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)]
 enum RequestType {
     StoreQuery,
     StoreMutation,
+    StoreWatchedQuery,
+    StoreJsonQuery,
+    StoreJsonEntity,
+    StoreJsonMutation,
 }
 
 impl RequestType {
@@ -431,6 +786,14 @@ impl RequestType {
             Ok(RequestType::StoreQuery)
         } else if path == "/store/mutate" {
             Ok(RequestType::StoreMutation)
+        } else if path == "/store/watch" {
+            Ok(RequestType::StoreWatchedQuery)
+        } else if path == "/store/json/query" {
+            Ok(RequestType::StoreJsonQuery)
+        } else if path == "/store/json/entity" {
+            Ok(RequestType::StoreJsonEntity)
+        } else if path == "/store/json/mutate" {
+            Ok(RequestType::StoreJsonMutation)
         } else {
             Err(RequestError::InvalidRequestType)
         }
@@ -440,6 +803,10 @@ impl RequestType {
         match self {
             RequestType::StoreQuery => ServiceType::Store,
             RequestType::StoreMutation => ServiceType::Store,
+            RequestType::StoreWatchedQuery => ServiceType::Store,
+            RequestType::StoreJsonQuery => ServiceType::Store,
+            RequestType::StoreJsonEntity => ServiceType::Store,
+            RequestType::StoreJsonMutation => ServiceType::Store,
         }
     }
 }
@@ -451,6 +818,10 @@ pub enum RequestError {
     InvalidRequestType,
     #[error("Request unauthorized")]
     Unauthorized,
+    #[error("Not found")]
+    NotFound,
+    #[error("Invalid JSON: {0}")]
+    InvalidJson(String),
     #[error("Query error")]
     Query,
     #[error("Internal server error: {0}")]
@@ -469,7 +840,9 @@ impl RequestError {
         let status = match self {
             RequestError::InvalidRequestType => StatusCode::NOT_FOUND,
             RequestError::Unauthorized => StatusCode::UNAUTHORIZED,
+            RequestError::NotFound => StatusCode::NOT_FOUND,
             RequestError::Query => StatusCode::BAD_REQUEST,
+            RequestError::InvalidJson(_) => StatusCode::BAD_REQUEST,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
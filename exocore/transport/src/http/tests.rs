@@ -1,3 +1,6 @@
+use std::sync::Arc;
+
+use base64::Engine;
 use exocore_core::{
     cell::{FullCell, LocalNode},
     framing::CapnpFrameBuilder,
@@ -5,9 +8,22 @@ use exocore_core::{
     sec::auth_token::AuthToken,
     time::Clock,
 };
-use exocore_protos::generated::store_transport_capnp::{
-    mutation_request, mutation_response, query_request, query_response,
+use exocore_protos::{
+    generated::{
+        exocore_test::TestMessage,
+        store_transport_capnp::{
+            mutation_request, mutation_response, query_request, query_response,
+            unwatch_query_request, watched_query_request,
+        },
+        MessageType,
+    },
+    prost::{Message, ProstAnyPackMessageExt},
+    store::{
+        entity_mutation, entity_query, Entity, EntityQuery, EntityResult, EntityResults,
+        MutationRequest, MutationResult, Trait,
+    },
 };
+use futures::StreamExt;
 use hyper::{body::Buf, Body, Client, Request, Response, StatusCode};
 
 use super::*;
@@ -128,11 +144,327 @@ async fn entities_mutation() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn entities_watched_query() -> anyhow::Result<()> {
+    let node = LocalNode::generate();
+    let full_cell = FullCell::generate(node.clone())?;
+    let clock = Clock::new();
+
+    let auth_token = AuthToken::new(full_cell.cell(), &clock, None)?;
+    let auth_token = auth_token.encode_base58_string();
+
+    let mut entities_handle = start_server(&full_cell, &clock, 3010).await;
+
+    let query = EntityQuery {
+        watch_token: 1337,
+        ..Default::default()
+    };
+    let url = format!("http://127.0.0.1:3010/store/watch?token={}", auth_token);
+    let resp_chan = send_http_request(url, &query.encode_to_vec());
+
+    let watch_request = entities_handle.recv_msg().await;
+    assert_eq!(
+        watch_request.typ,
+        <watched_query_request::Owned as MessageType>::MESSAGE_TYPE
+    );
+    let watch_frame = watch_request.get_data_as_framed_message::<watched_query_request::Owned>()?;
+    let watched_query = EntityQuery::decode(watch_frame.get_reader()?.get_request()?)?;
+    assert_eq!(watched_query.watch_token, 1337);
+
+    let resp = resp_chan.await??;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "text/event-stream"
+    );
+    let mut body = resp.into_body();
+
+    // each results sent by the store are streamed as an event
+    for response in [b"results1", b"results2"] {
+        let mut frame_builder = CapnpFrameBuilder::<query_response::Owned>::new();
+        let mut b: query_response::Builder = frame_builder.get_builder();
+        b.set_response(response);
+
+        let resp_msg = watch_request.to_response_message(entities_handle.cell(), frame_builder)?;
+        entities_handle.send_message(resp_msg).await;
+
+        let event = read_next_event(&mut body).await;
+        let expected = base64::engine::general_purpose::STANDARD.encode(response);
+        assert_eq!(event, format!("data: {}\n\n", expected));
+    }
+
+    // once the connection is closed, the query gets unwatched
+    drop(body);
+    loop {
+        let msg = entities_handle.recv_msg().await;
+        if msg.typ == <unwatch_query_request::Owned as MessageType>::MESSAGE_TYPE {
+            let frame = msg.get_data_as_framed_message::<unwatch_query_request::Owned>()?;
+            assert_eq!(frame.get_reader()?.get_token(), 1337);
+            break;
+        }
+
+        // otherwise, query got registered again while connection was open
+        assert_eq!(
+            msg.typ,
+            <watched_query_request::Owned as MessageType>::MESSAGE_TYPE
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn stream_request_overflow() -> anyhow::Result<()> {
+    let node = LocalNode::generate();
+    let full_cell = FullCell::generate(node)?;
+
+    let config = HttpTransportConfig {
+        stream_channel_size: 1,
+        ..Default::default()
+    };
+    let tracker = Arc::new(requests::RequestTracker::new(config));
+    let mut request = tracker.clone().push_stream().await;
+
+    let message = || {
+        let mut frame_builder = CapnpFrameBuilder::<query_response::Owned>::new();
+        let mut b: query_response::Builder = frame_builder.get_builder();
+        b.set_response(b"results");
+        crate::OutMessage::from_framed_message(full_cell.cell(), ServiceType::Store, frame_builder)
+    };
+
+    // replies that can't be buffered close the stream instead of being dropped
+    for _ in 0..3 {
+        tracker.reply(request.id(), message()?).await;
+    }
+    let received = request.by_ref().collect::<Vec<_>>().await;
+    assert!(!received.is_empty() && received.len() < 3);
+    assert!(request.has_overflowed());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn entities_json_query() -> anyhow::Result<()> {
+    let node = LocalNode::generate();
+    let full_cell = FullCell::generate(node.clone())?;
+    let clock = Clock::new();
+
+    let auth_token = AuthToken::new(full_cell.cell(), &clock, None)?;
+    let auth_token = auth_token.encode_base58_string();
+
+    let mut entities_handle = start_server(&full_cell, &clock, 3011).await;
+
+    {
+        // only one predicate can be specified
+        let url = format!(
+            "http://127.0.0.1:3011/store/json/query?token={}",
+            auth_token
+        );
+        let resp_chan = send_http_request(url, br#"{"query": "hello", "trait": "test"}"#);
+        let resp = resp_chan.await??;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    let url = format!(
+        "http://127.0.0.1:3011/store/json/query?token={}",
+        auth_token
+    );
+    let resp_chan = send_http_request(
+        url,
+        br#"{"query": "hello", "count": 5, "count_total": true}"#,
+    );
+
+    let query_request = entities_handle.recv_msg().await;
+    let query_frame = query_request.get_data_as_framed_message::<query_request::Owned>()?;
+    let query = EntityQuery::decode(query_frame.get_reader()?.get_request()?)?;
+    assert!(matches!(
+        query.predicate,
+        Some(entity_query::Predicate::QueryString(ref predicate)) if predicate.query == "hello"
+    ));
+    assert_eq!(query.paging.as_ref().unwrap().count, 5);
+    assert!(query.count_total);
+
+    let results = EntityResults {
+        entities: vec![EntityResult {
+            entity: Some(test_entity("et1", "hello world")),
+            ..Default::default()
+        }],
+        estimated_count: 1,
+        total_count: 1,
+        ..Default::default()
+    };
+    let mut frame_builder = CapnpFrameBuilder::<query_response::Owned>::new();
+    let mut b: query_response::Builder = frame_builder.get_builder();
+    b.set_response(&results.encode_to_vec());
+    let resp_msg = query_request.to_response_message(entities_handle.cell(), frame_builder)?;
+    entities_handle.send_message(resp_msg).await;
+
+    let resp = resp_chan.await??;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = read_json_body(resp).await?;
+    assert_eq!(json["total_count"], 1);
+    assert_eq!(json["entities"][0]["id"], "et1");
+    let message = &json["entities"][0]["traits"][0]["message"];
+    assert_eq!(message["type"], "exocore.test.TestMessage");
+    assert_eq!(message["value"]["string1"], "hello world");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn entities_json_entity() -> anyhow::Result<()> {
+    let node = LocalNode::generate();
+    let full_cell = FullCell::generate(node.clone())?;
+    let clock = Clock::new();
+
+    let auth_token = AuthToken::new(full_cell.cell(), &clock, None)?;
+    let auth_token = auth_token.encode_base58_string();
+
+    let mut entities_handle = start_server(&full_cell, &clock, 3012).await;
+
+    for (entity, expected_status) in [
+        (Some(test_entity("et1", "hello")), StatusCode::OK),
+        (None, StatusCode::NOT_FOUND),
+    ] {
+        let url = format!(
+            "http://127.0.0.1:3012/store/json/entity?token={}&id=et1",
+            auth_token
+        );
+        let resp_chan = send_http_request(url, b"");
+
+        let query_request = entities_handle.recv_msg().await;
+        let query_frame = query_request.get_data_as_framed_message::<query_request::Owned>()?;
+        let query = EntityQuery::decode(query_frame.get_reader()?.get_request()?)?;
+        assert!(matches!(
+            query.predicate,
+            Some(entity_query::Predicate::Ids(ref predicate)) if predicate.ids == vec!["et1".to_string()]
+        ));
+
+        let results = EntityResults {
+            entities: entity
+                .into_iter()
+                .map(|entity| EntityResult {
+                    entity: Some(entity),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let mut frame_builder = CapnpFrameBuilder::<query_response::Owned>::new();
+        let mut b: query_response::Builder = frame_builder.get_builder();
+        b.set_response(&results.encode_to_vec());
+        let resp_msg = query_request.to_response_message(entities_handle.cell(), frame_builder)?;
+        entities_handle.send_message(resp_msg).await;
+
+        let resp = resp_chan.await??;
+        assert_eq!(resp.status(), expected_status);
+        if expected_status == StatusCode::OK {
+            let json = read_json_body(resp).await?;
+            assert_eq!(json["id"], "et1");
+            assert_eq!(json["traits"][0]["id"], "trt1");
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn entities_json_mutation() -> anyhow::Result<()> {
+    let node = LocalNode::generate();
+    let full_cell = FullCell::generate(node.clone())?;
+    let clock = Clock::new();
+
+    let auth_token = AuthToken::new(full_cell.cell(), &clock, None)?;
+    let auth_token = auth_token.encode_base58_string();
+
+    let mut entities_handle = start_server(&full_cell, &clock, 3013).await;
+
+    {
+        // unknown message types are rejected
+        let url = format!(
+            "http://127.0.0.1:3013/store/json/mutate?token={}",
+            auth_token
+        );
+        let body = serde_json::json!({
+            "mutations": [{
+                "type": "put_trait",
+                "entity_id": "et1",
+                "trait_id": "trt1",
+                "message": {"type": "exocore.test.DoesNotExist"},
+            }],
+        });
+        let resp_chan = send_http_request(url, &serde_json::to_vec(&body)?);
+        let resp = resp_chan.await??;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    let url = format!(
+        "http://127.0.0.1:3013/store/json/mutate?token={}",
+        auth_token
+    );
+    let body = serde_json::json!({
+        "mutations": [{
+            "type": "put_trait",
+            "entity_id": "et1",
+            "trait_id": "trt1",
+            "message": {
+                "type": "exocore.test.TestMessage",
+                "value": {"string1": "hello"},
+            },
+        }, {
+            "type": "delete_entity",
+            "entity_id": "et2",
+        }],
+        "return_entities": true,
+    });
+    let resp_chan = send_http_request(url, &serde_json::to_vec(&body)?);
+
+    let mutation_request = entities_handle.recv_msg().await;
+    let mutation_frame =
+        mutation_request.get_data_as_framed_message::<mutation_request::Owned>()?;
+    let request = MutationRequest::decode(mutation_frame.get_reader()?.get_request()?)?;
+    assert!(request.return_entities);
+    assert_eq!(request.mutations.len(), 2);
+    match &request.mutations[0].mutation {
+        Some(entity_mutation::Mutation::PutTrait(put)) => {
+            let trt = put.r#trait.as_ref().unwrap();
+            assert_eq!(trt.id, "trt1");
+            let message = TestMessage::decode(trt.message.as_ref().unwrap().value.as_slice())?;
+            assert_eq!(message.string1, "hello");
+        }
+        other => panic!("Expected put trait mutation, got {:?}", other),
+    }
+    assert!(matches!(
+        request.mutations[1].mutation,
+        Some(entity_mutation::Mutation::DeleteEntity(_))
+    ));
+
+    let result = MutationResult {
+        operation_ids: vec![1, 2],
+        entities: vec![test_entity("et1", "hello")],
+        ..Default::default()
+    };
+    let mut frame_builder = CapnpFrameBuilder::<mutation_response::Owned>::new();
+    let mut b: mutation_response::Builder = frame_builder.get_builder();
+    b.set_response(&result.encode_to_vec());
+    let resp_msg = mutation_request.to_response_message(entities_handle.cell(), frame_builder)?;
+    entities_handle.send_message(resp_msg).await;
+
+    let resp = resp_chan.await??;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json = read_json_body(resp).await?;
+    assert_eq!(json["operation_ids"], serde_json::json!([1, 2]));
+    assert_eq!(json["entities"][0]["id"], "et1");
+
+    Ok(())
+}
+
 async fn start_server(full_cell: &FullCell, clock: &Clock, port: u16) -> TestableTransportHandle {
     let listen_addr = format!("http://127.0.0.1:{}", port);
 
     let config = HttpTransportConfig {
         listen_addresses: vec![listen_addr.parse().unwrap()],
+        watched_query_register_interval: std::time::Duration::from_millis(100),
         ..Default::default()
     };
 
@@ -191,3 +523,39 @@ async fn entities_receive_response_query(
 
     Ok(())
 }
+
+async fn read_json_body(resp: Response<Body>) -> anyhow::Result<serde_json::Value> {
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "application/json"
+    );
+    let body = hyper::body::to_bytes(resp.into_body()).await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+fn test_entity(id: &str, string1: &str) -> Entity {
+    let message = TestMessage {
+        string1: string1.to_string(),
+        ..Default::default()
+    };
+
+    Entity {
+        id: id.to_string(),
+        traits: vec![Trait {
+            id: "trt1".to_string(),
+            message: Some(message.pack_to_any().unwrap()),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+async fn read_next_event(body: &mut Body) -> String {
+    loop {
+        let chunk = body.next().await.unwrap().unwrap();
+        let event = String::from_utf8(chunk.to_vec()).unwrap();
+        if !event.starts_with(':') {
+            return event;
+        }
+    }
+}
@@ -2,6 +2,10 @@
 #[macro_use]
 extern crate log;
 
+#[cfg(feature = "http-server")]
+#[macro_use]
+extern crate serde_derive;
+
 pub mod error;
 pub mod messages;
 pub mod streams;
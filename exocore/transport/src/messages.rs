@@ -48,6 +48,31 @@ impl OutMessage {
         })
     }
 
+    /// Creates a message whose data isn't a capnp frame, but bytes of a
+    /// message of the given type encoded by the service (ex: protobuf).
+    pub fn from_data(
+        cell: &Cell,
+        service: ServiceType,
+        message_type: u16,
+        data: &[u8],
+    ) -> OutMessage {
+        let mut envelope_builder = CapnpFrameBuilder::<envelope::Owned>::new();
+        let mut envelope_message_builder = envelope_builder.get_builder();
+        envelope_message_builder.set_service(service.to_code());
+        envelope_message_builder.set_type(message_type);
+        envelope_message_builder.set_cell_id(cell.id().as_bytes());
+        envelope_message_builder.set_from_node_id(cell.local_node().id().to_string().as_str());
+        envelope_message_builder.set_data(data);
+
+        OutMessage {
+            destination: None,
+            expiration: None,
+            connection: None,
+            envelope_builder,
+            stream: None,
+        }
+    }
+
     pub fn with_destination(mut self, node: Node) -> Self {
         self.destination = Some(node);
         self
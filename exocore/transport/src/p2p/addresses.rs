@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use exocore_core::{
+    cell::{AddressBook, Cell, CellId, Node, NodeAddressesAnnounce, NodeId},
+    framing::{CapnpFrameBuilder, FrameBuilder},
+    time::Clock,
+};
+use exocore_protos::generated::common_capnp::envelope;
+use libp2p::{multiaddr::Protocol, Multiaddr};
+
+use super::handles::ServiceHandles;
+use crate::{Error, ServiceType};
+
+/// Type of the messages carrying a signed `NodeAddressesAnnounce`, exchanged
+/// between transports on the `Meta` service.
+pub(super) const ADDRESSES_ANNOUNCE_MESSAGE_TYPE: u16 = 1;
+
+/// Gossips the addresses on which the local node is listening to the other
+/// nodes of its cells, and keeps track of the addresses they announced in an
+/// address book persisted for each cell.
+///
+/// Announces are signed by the announcing node, which allows forwarding the
+/// latest announce of each node to the other nodes of the cell so that a node
+/// can learn the addresses of nodes it can't reach directly.
+pub(super) struct AddressesGossip {
+    clock: Clock,
+    cells: HashMap<CellId, (Cell, AddressBook)>,
+}
+
+impl AddressesGossip {
+    pub(super) fn new(service_handles: &ServiceHandles, clock: Clock) -> AddressesGossip {
+        let mut cells = HashMap::new();
+        for handle in service_handles.service_handles.values() {
+            let cell = &handle.cell;
+            if cells.contains_key(cell.id()) {
+                continue;
+            }
+
+            let book = match AddressBook::load(cell) {
+                Ok(book) => book,
+                Err(err) => {
+                    warn!("Couldn't load address book of cell {}: {}", cell, err);
+                    continue;
+                }
+            };
+            cells.insert(cell.id().clone(), (cell.clone(), book));
+        }
+
+        AddressesGossip { clock, cells }
+    }
+
+    /// Returns the addresses announced by the node in any of our cells, most
+    /// recently announced first.
+    pub(super) fn node_addresses(&self, node_id: &NodeId) -> Vec<Multiaddr> {
+        let mut addresses = Vec::new();
+        for (_cell, book) in self.cells.values() {
+            for address in book.node_addresses(node_id) {
+                if !addresses.contains(&address) {
+                    addresses.push(address);
+                }
+            }
+        }
+        addresses
+    }
+
+    /// Creates the announce messages to be sent to the given nodes, which
+    /// contain a fresh announce of our listen addresses and the latest announce
+    /// we got from the other nodes of the cells.
+    ///
+    /// Unspecified listen addresses (ex: `0.0.0.0`) aren't announced since they
+    /// can't be dialed by other nodes.
+    pub(super) fn announce_messages(
+        &self,
+        listen_addresses: &[Multiaddr],
+        mut should_send: impl FnMut(&Node) -> bool,
+    ) -> Vec<(Node, Bytes)> {
+        let listen_addresses = listen_addresses
+            .iter()
+            .filter(|addr| is_dialable(addr))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut messages = Vec::new();
+        for (cell, book) in self.cells.values() {
+            let local_announce =
+                match NodeAddressesAnnounce::new(cell, &self.clock, listen_addresses.clone()) {
+                    Ok(announce) => announce,
+                    Err(err) => {
+                        warn!("Couldn't create addresses announce for {}: {}", cell, err);
+                        continue;
+                    }
+                };
+
+            let cell_nodes = {
+                let nodes = cell.nodes();
+                nodes
+                    .iter()
+                    .all_except_local()
+                    .map(|cell_node| cell_node.node().clone())
+                    .collect::<Vec<_>>()
+            };
+
+            for node in cell_nodes {
+                if !should_send(&node) {
+                    continue;
+                }
+
+                let announces = std::iter::once(&local_announce).chain(book.announces());
+                for announce in announces {
+                    if announce.node_id() == node.id() {
+                        continue;
+                    }
+
+                    messages.push((node.clone(), build_announce_envelope(cell, announce)));
+                }
+            }
+        }
+
+        messages
+    }
+
+    /// Handles an announce received from another node. If it's valid and newer
+    /// than the latest one we had, the address book gets updated and the id of
+    /// the announcing node is returned so that its new addresses can be dialed.
+    pub(super) fn handle_announce(
+        &mut self,
+        cell_id: &CellId,
+        data: &[u8],
+    ) -> Result<Option<NodeId>, Error> {
+        let Some((cell, book)) = self.cells.get_mut(cell_id) else {
+            return Err(Error::Other(format!(
+                "Got addresses announce for unknown cell {}",
+                cell_id
+            )));
+        };
+
+        let announce = NodeAddressesAnnounce::decode_and_validate(cell, data)
+            .map_err(|err| Error::Other(format!("Invalid addresses announce: {}", err)))?;
+        if announce.node_id() == cell.local_node().id() {
+            return Ok(None);
+        }
+
+        let node_id = announce.node_id().clone();
+        if !book.add_announce(announce) {
+            return Ok(None);
+        }
+
+        debug!(
+            "Got new addresses for node {}: {:?}",
+            node_id,
+            book.node_addresses(&node_id)
+        );
+        if let Err(err) = book.save() {
+            warn!("Couldn't save address book of cell {}: {}", cell, err);
+        }
+
+        Ok(Some(node_id))
+    }
+}
+
+/// Indicates if the address could be dialed by another node, which isn't the
+/// case of unspecified IP addresses.
+pub(super) fn is_dialable(address: &Multiaddr) -> bool {
+    match address.iter().next() {
+        Some(Protocol::Ip4(ip)) => !ip.is_unspecified(),
+        Some(Protocol::Ip6(ip)) => !ip.is_unspecified(),
+        _ => true,
+    }
+}
+
+fn build_announce_envelope(cell: &Cell, announce: &NodeAddressesAnnounce) -> Bytes {
+    let mut envelope_builder = CapnpFrameBuilder::<envelope::Owned>::new();
+    let mut envelope_message_builder = envelope_builder.get_builder();
+    envelope_message_builder.set_service(ServiceType::Meta.to_code());
+    envelope_message_builder.set_type(ADDRESSES_ANNOUNCE_MESSAGE_TYPE);
+    envelope_message_builder.set_cell_id(cell.id().as_bytes());
+    envelope_message_builder.set_from_node_id(cell.local_node().id().to_string().as_str());
+    envelope_message_builder.set_data(&announce.encode_to_vec());
+
+    envelope_builder.as_bytes()
+}
@@ -86,9 +86,17 @@ impl ExocoreBehaviour {
         }
     }
 
-    pub fn add_node(&mut self, node: &Node) {
+    /// Adds or updates a node that we want to be connected to. Addresses
+    /// recently announced by the node are dialed first, followed by the ones
+    /// from its config.
+    pub fn add_node(&mut self, node: &Node, recent_addresses: &[Multiaddr]) {
         let peer_id = *node.peer_id();
-        let addresses = node.p2p_addresses();
+        let mut addresses = recent_addresses.to_vec();
+        for address in node.p2p_addresses() {
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
 
         if let Some(current_peer) = self.peers.get_mut(&peer_id) {
             if current_peer.addresses == addresses {
@@ -96,8 +104,9 @@ impl ExocoreBehaviour {
                 return;
             }
 
-            // update peer addresses
+            // update peer addresses, which may make it reachable again
             current_peer.addresses = addresses;
+            current_peer.dial_failed = false;
         } else {
             self.peers.insert(
                 peer_id,
@@ -107,6 +116,7 @@ impl ExocoreBehaviour {
                     temp_queue: VecDeque::new(),
                     status: PeerStatus::Disconnected,
                     last_dial: None,
+                    dial_failed: false,
                 },
             );
         }
@@ -114,6 +124,22 @@ impl ExocoreBehaviour {
         self.dial_peer(peer_id, true);
     }
 
+    pub fn is_peer_connected(&self, peer_id: &PeerId) -> bool {
+        self.peers
+            .get(peer_id)
+            .is_some_and(|peer| peer.status == PeerStatus::Connected)
+    }
+
+    /// Indicates if the peer is connected, or could be reached by dialing it.
+    /// A peer is considered unreachable if we don't know any of its addresses
+    /// or if its last dial failed.
+    pub fn is_peer_reachable(&self, peer_id: &PeerId) -> bool {
+        self.peers.get(peer_id).is_some_and(|peer| {
+            peer.status == PeerStatus::Connected
+                || (!peer.addresses.is_empty() && !peer.dial_failed)
+        })
+    }
+
     pub fn report_ping_success(&mut self, peer_id: &PeerId, rtt: Duration) {
         if let Some(peer) = self.peers.get(peer_id) {
             debug!("Successfully ping peer {}: {:?}", peer.node, rtt);
@@ -169,6 +195,7 @@ impl ExocoreBehaviour {
 
             info!("Connected to peer {}", peer.node);
             peer.status = PeerStatus::Connected;
+            peer.dial_failed = false;
             self.actions
                 .push_back(ToSwarm::GenerateEvent(ExocoreBehaviourEvent::PeerStatus(
                     *peer_id,
@@ -212,6 +239,7 @@ impl ExocoreBehaviour {
             .peer_id
             .and_then(|peer_id| self.peers.get_mut(&peer_id))
         {
+            peer.dial_failed = true;
             info!(
                 "Failed to connect to peer {}: {:?}. {} messages in queue for node.",
                 peer.node,
@@ -363,6 +391,7 @@ struct Peer {
     temp_queue: VecDeque<QueuedPeerEvent>,
     status: PeerStatus,
     last_dial: Option<Instant>,
+    dial_failed: bool,
 }
 
 impl Peer {
@@ -1,6 +1,7 @@
 use std::time::Duration;
 
 use exocore_core::cell::LocalNode;
+use exocore_protos::core::NodeP2pConfig;
 use libp2p::Multiaddr;
 
 use crate::Error;
@@ -13,6 +14,20 @@ pub struct Libp2pTransportConfig {
     pub handle_out_channel_size: usize,
     pub handles_to_behaviour_channel_size: usize,
     pub swarm_nodes_update_interval: Duration,
+
+    /// If enabled, nodes of the cells are discovered on the local network via
+    /// mDNS. Only available on non-wasm targets.
+    ///
+    /// mDNS records only advertise the peer id of nodes, from which their node
+    /// id is derived. Discovered addresses are therefore only used if the
+    /// discovered node is a member of one of the transport's cells, and are
+    /// forgotten once their records expire.
+    pub mdns_discovery: bool,
+
+    /// Interval at which the addresses on which the node is listening are
+    /// announced to the other connected nodes of the cells, along with the
+    /// latest addresses announced by other nodes.
+    pub addresses_gossip_interval: Duration,
 }
 
 impl Libp2pTransportConfig {
@@ -34,6 +49,17 @@ impl Default for Libp2pTransportConfig {
             handle_out_channel_size: 1000,
             handles_to_behaviour_channel_size: 5000,
             swarm_nodes_update_interval: Duration::from_secs(1),
+            mdns_discovery: false,
+            addresses_gossip_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl From<NodeP2pConfig> for Libp2pTransportConfig {
+    fn from(proto: NodeP2pConfig) -> Self {
+        Libp2pTransportConfig {
+            mdns_discovery: proto.mdns_discovery.unwrap_or(false),
+            ..Default::default()
         }
     }
 }
@@ -1,8 +1,10 @@
+mod addresses;
 mod behaviour;
 mod bytes_channel;
 mod config;
 mod handles;
 mod protocol;
+mod relay;
 mod transport;
 
 #[cfg(test)]
@@ -1,6 +1,6 @@
 use std::{
     collections::VecDeque,
-    io, iter,
+    io,
     task::{Context, Poll},
 };
 
@@ -23,11 +23,29 @@ use super::bytes_channel::BytesChannelSender;
 const MAX_MESSAGE_SIZE: usize = 20 * 1024 * 1024; // 20MB
 const STREAM_BUFFER_SIZE: usize = 1024;
 
+const PROTOCOL_V1: &str = "/exocore/0.1.0";
+const PROTOCOL_V2: &str = "/exocore/0.2.0";
+
+/// Messages smaller than this size can be batched with other messages in a
+/// single frame.
+const BATCH_MESSAGE_MAX_SIZE: usize = 4 * 1024; // 4KB
+
+/// Maximum size of the messages batched in a single frame.
+const BATCH_MAX_SIZE: usize = 64 * 1024; // 64KB
+
+/// Frames smaller than this size are never compressed since compression
+/// wouldn't save enough to be worth it.
+#[cfg(feature = "zstd")]
+const COMPRESSION_MIN_SIZE: usize = 512;
+
+#[cfg(feature = "zstd")]
+const COMPRESSION_LEVEL: i32 = 3;
+
 type HandlerEvent = ConnectionHandlerEvent<ExocoreProtoConfig, (), Result<MessageData, io::Error>>;
 
 // TODO: Remove dyn dispatched future once type_alias_impl_trait lands: https://github.com/rust-lang/rust/issues/63063
 type InboundStreamFuture =
-    BoxFuture<'static, Result<(Option<MessageData>, WrappedStream<Stream>), io::Error>>;
+    BoxFuture<'static, Result<(Vec<MessageData>, WrappedStream<Stream>), io::Error>>;
 type OutboundStreamFuture = BoxFuture<'static, Result<Option<WrappedStream<Stream>>, io::Error>>;
 
 /// Protocol handler for Exocore protocol. This handles protocols and substreams
@@ -43,9 +61,12 @@ type OutboundStreamFuture = BoxFuture<'static, Result<Option<WrappedStream<Strea
 ///   * When an incoming stream is open to us, it reads the incoming message
 ///     from it. Since this is asynchronous, we keep the futures and poll to
 ///     completion.
+///   * If the stream negotiated a protocol version that supports it, small
+///     outgoing messages are batched together, and frames are compressed.
 pub struct ExocoreProtoHandler {
     listen_protocol: SubstreamProtocol<ExocoreProtoConfig, ()>,
     inbound_stream_futures: Vec<InboundStreamFuture>,
+    inbound_messages: VecDeque<MessageData>,
     outbound_dialing: bool,
     outbound_stream_futures: Vec<OutboundStreamFuture>,
     idle_outbound_stream: Option<WrappedStream<Stream>>,
@@ -83,6 +104,7 @@ impl Default for ExocoreProtoHandler {
         ExocoreProtoHandler {
             listen_protocol: SubstreamProtocol::new(ExocoreProtoConfig, ()),
             inbound_stream_futures: Vec::new(),
+            inbound_messages: VecDeque::new(),
             outbound_dialing: false,
             outbound_stream_futures: Vec::new(),
             idle_outbound_stream: None,
@@ -150,6 +172,11 @@ impl ConnectionHandler for ExocoreProtoHandler {
     }
 
     fn poll(&mut self, cx: &mut Context) -> Poll<HandlerEvent> {
+        // messages of a batch are notified to the behaviour one at the time
+        if let Some(message) = self.inbound_messages.pop_front() {
+            return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(Ok(message)));
+        }
+
         // if we have a message to send, but no outgoing streams via which to send it,
         // we request one
         if !self.send_queue.is_empty()
@@ -169,10 +196,10 @@ impl ConnectionHandler for ExocoreProtoHandler {
         // to it and keep the future to poll to completion
         if self.idle_outbound_stream.is_some() && !self.send_queue.is_empty() {
             trace!("Sending message to idle output stream");
-            let message = self.send_queue.pop_front().unwrap();
             let stream = self.idle_outbound_stream.take().unwrap();
+            let messages = pop_messages_batch(&mut self.send_queue, stream.version);
             self.outbound_stream_futures
-                .push(Box::pin(stream.send_message(message)));
+                .push(Box::pin(stream.send_messages(messages)));
         }
 
         // we poll all futures that writes messages to completion. once completed, we
@@ -184,10 +211,12 @@ impl ConnectionHandler for ExocoreProtoHandler {
                     Poll::Ready(Ok(Some(substream))) => {
                         if self.idle_outbound_stream.is_some() {
                             trace!("Successfully sent message. One stream already opening / ongoing. Closing this one");
-                        } else if let Some(message) = self.send_queue.pop_front() {
+                        } else if !self.send_queue.is_empty() {
                             trace!("Successfully sent message. Sending a new one from queue.");
+                            let messages =
+                                pop_messages_batch(&mut self.send_queue, substream.version);
                             self.outbound_stream_futures
-                                .push(Box::pin(substream.send_message(message)));
+                                .push(Box::pin(substream.send_messages(messages)));
                         } else if self.idle_outbound_stream.is_none() {
                             trace!("Successfully sent message. None in queue. Idling");
                             self.idle_outbound_stream = Some(substream);
@@ -214,13 +243,14 @@ impl ConnectionHandler for ExocoreProtoHandler {
             let futures = std::mem::take(&mut self.inbound_stream_futures);
             for mut fut in futures {
                 match fut.as_mut().poll(cx) {
-                    Poll::Ready(Ok((opt_msg, substream))) => {
+                    Poll::Ready(Ok((messages, substream))) => {
                         self.inbound_stream_futures
                             .push(Box::pin(substream.read_next()));
 
                         // we may not always have a message if the substream was currently handling
                         // copying data to a stream consumed by the application
-                        if let Some(message) = opt_msg {
+                        self.inbound_messages.extend(messages);
+                        if let Some(message) = self.inbound_messages.pop_front() {
                             trace!("Successfully read a message on substream");
                             return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(Ok(
                                 message,
@@ -248,6 +278,9 @@ impl ConnectionHandler for ExocoreProtoHandler {
 /// Stream protocol negotiation and upgrading is entirely managed by libp2p.
 /// Once an incoming stream or outgoing stream is upgraded, we wrap it into a
 /// `WrappedStream` that will then be used by `ExocoreProtoHandler`.
+///
+/// Protocol versions are listed by order of preference so that the latest
+/// version supported by both peers gets negotiated.
 #[derive(Clone, Default)]
 pub struct ExocoreProtoConfig;
 
@@ -255,10 +288,56 @@ type UpgradeInfoData = &'static str;
 
 impl UpgradeInfo for ExocoreProtoConfig {
     type Info = UpgradeInfoData;
-    type InfoIter = iter::Once<Self::Info>;
+    type InfoIter = std::vec::IntoIter<Self::Info>;
 
     fn protocol_info(&self) -> Self::InfoIter {
-        iter::once("/exocore/0.1.0")
+        ProtocolVersion::supported()
+            .iter()
+            .map(|version| version.protocol_name())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+/// Version of the protocol negotiated on a stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProtocolVersion {
+    /// Each message is written as its own uncompressed frame.
+    V1,
+
+    /// Small messages are batched in a single frame, and frames are compressed
+    /// using zstd if it makes them smaller.
+    V2,
+}
+
+impl ProtocolVersion {
+    /// Versions supported by this build, latest first. Compression isn't
+    /// available on all targets, in which case we stick to the first version.
+    fn supported() -> &'static [ProtocolVersion] {
+        #[cfg(feature = "zstd")]
+        {
+            &[ProtocolVersion::V2, ProtocolVersion::V1]
+        }
+
+        #[cfg(not(feature = "zstd"))]
+        {
+            &[ProtocolVersion::V1]
+        }
+    }
+
+    fn protocol_name(self) -> &'static str {
+        match self {
+            ProtocolVersion::V1 => PROTOCOL_V1,
+            ProtocolVersion::V2 => PROTOCOL_V2,
+        }
+    }
+
+    fn from_protocol_name(name: &str) -> ProtocolVersion {
+        if name == PROTOCOL_V2 {
+            ProtocolVersion::V2
+        } else {
+            ProtocolVersion::V1
+        }
     }
 }
 
@@ -270,8 +349,9 @@ where
     type Error = io::Error;
     type Future = future::Ready<Result<WrappedStream<TStream>, io::Error>>;
 
-    fn upgrade_inbound(self, socket: TStream, _: Self::Info) -> Self::Future {
-        future::ok(WrappedStream::new(socket))
+    fn upgrade_inbound(self, socket: TStream, info: Self::Info) -> Self::Future {
+        let version = ProtocolVersion::from_protocol_name(info);
+        future::ok(WrappedStream::new(socket, version))
     }
 }
 
@@ -284,8 +364,9 @@ where
     type Future = future::Ready<Result<WrappedStream<TStream>, io::Error>>;
 
     #[inline]
-    fn upgrade_outbound(self, socket: TStream, _: Self::Info) -> Self::Future {
-        future::ok(WrappedStream::new(socket))
+    fn upgrade_outbound(self, socket: TStream, info: Self::Info) -> Self::Future {
+        let version = ProtocolVersion::from_protocol_name(info);
+        future::ok(WrappedStream::new(socket, version))
     }
 }
 
@@ -311,6 +392,7 @@ where
     TStream: AsyncRead + AsyncWrite + Send + Unpin + 'static,
 {
     socket: TStream,
+    version: ProtocolVersion,
     out_stream: Option<BytesChannelSender>,
 }
 
@@ -318,9 +400,10 @@ impl<TStream> WrappedStream<TStream>
 where
     TStream: AsyncRead + AsyncWrite + Send + Unpin + 'static,
 {
-    fn new(socket: TStream) -> Self {
+    fn new(socket: TStream, version: ProtocolVersion) -> Self {
         WrappedStream {
             socket,
+            version,
             out_stream: None,
         }
     }
@@ -330,21 +413,34 @@ impl<TStream> WrappedStream<TStream>
 where
     TStream: AsyncRead + AsyncWrite + Send + Unpin + 'static,
 {
-    async fn send_message(mut self, message: MessageData) -> Result<Option<Self>, io::Error> {
-        let mut size_buf = [0; 4];
-        encode_msg_len(
-            message.message.len(),
-            message.stream.is_some(),
-            &mut size_buf,
-        );
+    /// Sends messages in a single frame. More than one message can only be
+    /// sent if they were batched using `pop_messages_batch`.
+    async fn send_messages(
+        mut self,
+        mut messages: Vec<MessageData>,
+    ) -> Result<Option<Self>, io::Error> {
+        let (body, stream, batch) = if messages.len() == 1 {
+            let message = messages.pop().unwrap();
+            (message.message, message.stream, false)
+        } else {
+            (encode_batch(&messages), None, true)
+        };
+
+        let (body, compressed) = self.maybe_compress(body)?;
+        let header = FrameHeader {
+            len: body.len(),
+            has_stream: stream.is_some(),
+            compressed,
+            batch,
+        };
 
-        // write msg size & msg data
-        self.socket.write_all(&size_buf).await?;
-        self.socket.write_all(&message.message).await?;
+        // write frame header & frame data
+        self.socket.write_all(&header.encode()).await?;
+        self.socket.write_all(&body).await?;
 
         // if we have a stream, copy the stream to socket then drop the substream to
         // notify the end of stream
-        if let Some(stream) = message.stream {
+        if let Some(stream) = stream {
             futures::io::copy(stream, &mut self.socket).await?;
             self.socket.flush().await?;
 
@@ -360,83 +456,247 @@ where
         }
     }
 
-    async fn read_next(mut self) -> Result<(Option<MessageData>, Self), io::Error> {
+    #[cfg(feature = "zstd")]
+    fn maybe_compress(&self, body: Bytes) -> Result<(Bytes, bool), io::Error> {
+        if self.version == ProtocolVersion::V1 || body.len() < COMPRESSION_MIN_SIZE {
+            return Ok((body, false));
+        }
+
+        let compressed = zstd::encode_all(body.as_ref(), COMPRESSION_LEVEL)?;
+        if compressed.len() < body.len() {
+            Ok((compressed.into(), true))
+        } else {
+            Ok((body, false))
+        }
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    fn maybe_compress(&self, body: Bytes) -> Result<(Bytes, bool), io::Error> {
+        Ok((body, false))
+    }
+
+    async fn read_next(mut self) -> Result<(Vec<MessageData>, Self), io::Error> {
         if let Some(mut out_stream) = self.out_stream.take() {
             futures::io::copy(&mut self.socket, &mut out_stream).await?;
-            Ok((None, self))
+            Ok((Vec::new(), self))
         } else {
-            let msg = self.read_new_message().await?;
-            Ok((Some(msg), self))
+            let messages = self.read_new_messages().await?;
+            Ok((messages, self))
         }
     }
 
-    async fn read_new_message(&mut self) -> Result<MessageData, io::Error> {
-        let mut size_buf = [0; 4];
-        self.socket.read_exact(&mut size_buf).await?;
-        let (msg_len, has_stream) = decode_msg_len(&size_buf);
-
-        let message_data = {
-            if msg_len > MAX_MESSAGE_SIZE {
-                warn!(
-                    "Got a message on stream that exceeds maximum size. Dropping stream. ({}>{})",
-                    msg_len, MAX_MESSAGE_SIZE
-                );
+    async fn read_new_messages(&mut self) -> Result<Vec<MessageData>, io::Error> {
+        let mut header_buf = [0; 4];
+        self.socket.read_exact(&mut header_buf).await?;
+        let header = FrameHeader::decode(&header_buf);
+
+        if header.len > MAX_MESSAGE_SIZE {
+            warn!(
+                "Got a message on stream that exceeds maximum size. Dropping stream. ({}>{})",
+                header.len, MAX_MESSAGE_SIZE
+            );
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        if self.version == ProtocolVersion::V1 && (header.compressed || header.batch) {
+            warn!("Got a compressed or batched frame on a stream that doesn't support it");
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        let mut frame_data = vec![0; header.len];
+        self.socket.read_exact(&mut frame_data).await?;
+        let frame_data: Bytes = if header.compressed {
+            decompress(&frame_data)?.into()
+        } else {
+            frame_data.into()
+        };
+
+        if header.batch {
+            if header.has_stream {
+                warn!("Got a batched frame with a stream, which isn't supported");
                 return Err(io::ErrorKind::InvalidData.into());
             }
 
-            let mut message_data = vec![0; msg_len];
-            self.socket.read_exact(&mut message_data).await?;
-            message_data
-        };
+            return decode_batch(frame_data);
+        }
 
-        if has_stream {
+        if header.has_stream {
             let (sender, receiver) = super::bytes_channel::new(STREAM_BUFFER_SIZE);
             self.out_stream = Some(sender);
 
-            Ok(MessageData {
-                message: message_data.into(),
+            Ok(vec![MessageData {
+                message: frame_data,
                 stream: Some(Box::new(receiver)),
-            })
+            }])
         } else {
-            Ok(MessageData {
-                message: message_data.into(),
+            Ok(vec![MessageData {
+                message: frame_data,
                 stream: None,
-            })
+            }])
         }
     }
 }
 
-const STREAM_MASK: usize = 1 << 31;
+/// Pops the next messages to be sent in a single frame from the queue.
+///
+/// On streams supporting it, consecutive small messages without stream get
+/// batched together in a single frame. Otherwise, only one message is popped.
+fn pop_messages_batch(
+    queue: &mut VecDeque<MessageData>,
+    version: ProtocolVersion,
+) -> Vec<MessageData> {
+    let first = match queue.pop_front() {
+        Some(message) => message,
+        None => return Vec::new(),
+    };
+
+    let is_batchable = |message: &MessageData| {
+        message.stream.is_none() && message.message.len() <= BATCH_MESSAGE_MAX_SIZE
+    };
+    if version == ProtocolVersion::V1 || !is_batchable(&first) {
+        return vec![first];
+    }
+
+    let mut batch_size = 4 + first.message.len();
+    let mut messages = vec![first];
+    while let Some(next) = queue.front() {
+        let next_size = 4 + next.message.len();
+        if !is_batchable(next) || batch_size + next_size > BATCH_MAX_SIZE {
+            break;
+        }
 
-/// Encodes message size and uses high bit to indicate that there is a stream
-/// after message.
-fn encode_msg_len(mut len: usize, has_stream: bool, into: &mut [u8]) {
-    if has_stream {
-        len |= STREAM_MASK;
+        batch_size += next_size;
+        messages.push(queue.pop_front().unwrap());
     }
 
-    LittleEndian::write_u32(into, len as u32);
+    messages
 }
 
-/// Decodes message size.
-fn decode_msg_len(bytes: &[u8]) -> (usize, bool) {
-    let mut msg_len = LittleEndian::read_u32(bytes) as usize;
-    let has_stream = if msg_len & STREAM_MASK == STREAM_MASK {
-        msg_len &= STREAM_MASK - 1;
-        true
-    } else {
-        false
-    };
+/// Encodes batched messages, each prefixed by its size.
+fn encode_batch(messages: &[MessageData]) -> Bytes {
+    let size = messages.iter().map(|msg| 4 + msg.message.len()).sum();
+    let mut data = Vec::with_capacity(size);
+    for message in messages {
+        let mut size_buf = [0; 4];
+        LittleEndian::write_u32(&mut size_buf, message.message.len() as u32);
+        data.extend_from_slice(&size_buf);
+        data.extend_from_slice(&message.message);
+    }
 
-    (msg_len, has_stream)
+    data.into()
+}
+
+/// Decodes messages batched by `encode_batch`.
+fn decode_batch(mut data: Bytes) -> Result<Vec<MessageData>, io::Error> {
+    let mut messages = Vec::new();
+    while !data.is_empty() {
+        if data.len() < 4 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        let size = LittleEndian::read_u32(&data) as usize;
+        let _ = data.split_to(4);
+        if data.len() < size {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        messages.push(MessageData {
+            message: data.split_to(size),
+            stream: None,
+        });
+    }
+
+    Ok(messages)
+}
+
+/// Decompresses a frame, making sure that its decompressed size doesn't exceed
+/// the maximum message size.
+#[cfg(feature = "zstd")]
+fn decompress(data: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let decoder = zstd::stream::read::Decoder::new(data)?;
+    let mut decompressed = Vec::new();
+    std::io::Read::read_to_end(
+        &mut std::io::Read::take(decoder, MAX_MESSAGE_SIZE as u64 + 1),
+        &mut decompressed,
+    )?;
+
+    if decompressed.len() > MAX_MESSAGE_SIZE {
+        warn!(
+            "Got a compressed message on stream that exceeds maximum size. Dropping stream. (>{})",
+            MAX_MESSAGE_SIZE
+        );
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+
+    Ok(decompressed)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress(_data: &[u8]) -> Result<Vec<u8>, io::Error> {
+    warn!("Got a compressed message, but compression isn't supported");
+    Err(io::ErrorKind::InvalidData.into())
+}
+
+const STREAM_MASK: u32 = 1 << 31;
+const COMPRESSED_MASK: u32 = 1 << 30;
+const BATCH_MASK: u32 = 1 << 29;
+const LEN_MASK: u32 = BATCH_MASK - 1;
+
+/// Header preceding each frame written to a stream. The frame size is encoded
+/// in the low bits, while the high bits are used as flags. Since the maximum
+/// message size fits in the low bits, headers without flags are identical to
+/// the ones of the first protocol version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FrameHeader {
+    len: usize,
+
+    /// The frame is followed by a stream that lasts until the socket is closed.
+    has_stream: bool,
+
+    /// The frame is compressed using zstd.
+    compressed: bool,
+
+    /// The frame contains multiple messages encoded by `encode_batch`.
+    batch: bool,
+}
+
+impl FrameHeader {
+    fn encode(&self) -> [u8; 4] {
+        let mut value = self.len as u32 & LEN_MASK;
+        if self.has_stream {
+            value |= STREAM_MASK;
+        }
+        if self.compressed {
+            value |= COMPRESSED_MASK;
+        }
+        if self.batch {
+            value |= BATCH_MASK;
+        }
+
+        let mut bytes = [0; 4];
+        LittleEndian::write_u32(&mut bytes, value);
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> FrameHeader {
+        let value = LittleEndian::read_u32(bytes);
+        FrameHeader {
+            len: (value & LEN_MASK) as usize,
+            has_stream: value & STREAM_MASK == STREAM_MASK,
+            compressed: value & COMPRESSED_MASK == COMPRESSED_MASK,
+            batch: value & BATCH_MASK == BATCH_MASK,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use futures::{executor::block_on, io::Cursor};
+
     use super::*;
 
     #[test]
-    fn test_encode_decode_msg_len() {
+    fn test_encode_decode_frame_header() {
         let tv = vec![
             (7, true),
             (7, false),
@@ -449,12 +709,141 @@ mod tests {
         ];
 
         for (len, stream) in tv {
-            let mut size_buf = vec![0; 4];
-            encode_msg_len(len, stream, &mut size_buf);
-
-            let (decoded_len, decoded_stream) = decode_msg_len(&size_buf);
-            assert_eq!(decoded_len, len, "{} {}", len, stream);
-            assert_eq!(stream, decoded_stream, "{} {}", len, stream);
+            for (compressed, batch) in [(false, false), (true, false), (true, true)] {
+                let header = FrameHeader {
+                    len,
+                    has_stream: stream,
+                    compressed,
+                    batch,
+                };
+
+                let decoded = FrameHeader::decode(&header.encode());
+                assert_eq!(header, decoded, "{:?}", header);
+            }
         }
     }
+
+    #[test]
+    fn frame_header_v1_compatible() {
+        let header = FrameHeader {
+            len: 1234,
+            has_stream: true,
+            compressed: false,
+            batch: false,
+        };
+
+        let mut expected = [0; 4];
+        LittleEndian::write_u32(&mut expected, 1234 | (1 << 31));
+        assert_eq!(header.encode(), expected);
+    }
+
+    #[test]
+    fn v1_sends_one_message_per_frame() {
+        let mut queue = test_messages(&[10, 20]);
+        let messages = pop_messages_batch(&mut queue, ProtocolVersion::V1);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(queue.len(), 1);
+
+        let data = block_on(send(ProtocolVersion::V1, messages));
+        let header = FrameHeader::decode(&data[0..4]);
+        assert!(!header.compressed);
+        assert!(!header.batch);
+        assert_eq!(header.len, 10);
+
+        let received = block_on(receive(ProtocolVersion::V1, data));
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].message.len(), 10);
+    }
+
+    #[test]
+    fn v2_batches_small_messages() {
+        let mut queue = test_messages(&[10, 2000, 30, BATCH_MESSAGE_MAX_SIZE + 1, 40]);
+        let messages = pop_messages_batch(&mut queue, ProtocolVersion::V2);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(queue.len(), 2);
+
+        let data = block_on(send(ProtocolVersion::V2, messages));
+        let header = FrameHeader::decode(&data[0..4]);
+        assert!(header.batch);
+
+        let received = block_on(receive(ProtocolVersion::V2, data));
+        let sizes = received
+            .iter()
+            .map(|msg| msg.message.len())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, vec![10, 2000, 30]);
+
+        // large message isn't batched
+        let messages = pop_messages_batch(&mut queue, ProtocolVersion::V2);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn v2_batch_max_size() {
+        let count = BATCH_MAX_SIZE / BATCH_MESSAGE_MAX_SIZE + 2;
+        let mut queue = test_messages(&vec![BATCH_MESSAGE_MAX_SIZE; count]);
+        let messages = pop_messages_batch(&mut queue, ProtocolVersion::V2);
+        let batch_size: usize = messages.iter().map(|msg| 4 + msg.message.len()).sum();
+        assert!(batch_size <= BATCH_MAX_SIZE);
+        assert!(!queue.is_empty());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn v2_compresses_frames() {
+        let message = MessageData {
+            message: vec![42u8; 10_000].into(),
+            stream: None,
+        };
+
+        let data = block_on(send(ProtocolVersion::V2, vec![message]));
+        let header = FrameHeader::decode(&data[0..4]);
+        assert!(header.compressed);
+        assert!(header.len < 10_000);
+
+        let received = block_on(receive(ProtocolVersion::V2, data));
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].message.as_ref(), vec![42u8; 10_000].as_slice());
+
+        // compressed frames are refused on a stream that didn't negotiate them
+        let data = block_on(send(
+            ProtocolVersion::V2,
+            vec![MessageData {
+                message: vec![42u8; 10_000].into(),
+                stream: None,
+            }],
+        ));
+        let mut stream = WrappedStream::new(Cursor::new(data), ProtocolVersion::V1);
+        assert!(block_on(stream.read_new_messages()).is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn v2_decompression_size_limit() {
+        let data = vec![0u8; MAX_MESSAGE_SIZE + 1];
+        let compressed = zstd::encode_all(data.as_slice(), COMPRESSION_LEVEL).unwrap();
+        assert!(decompress(&compressed).is_err());
+    }
+
+    fn test_messages(sizes: &[usize]) -> VecDeque<MessageData> {
+        sizes
+            .iter()
+            .map(|size| MessageData {
+                message: vec![1u8; *size].into(),
+                stream: None,
+            })
+            .collect()
+    }
+
+    async fn send(version: ProtocolVersion, messages: Vec<MessageData>) -> Vec<u8> {
+        let stream = WrappedStream::new(Cursor::new(Vec::new()), version);
+        let stream = stream.send_messages(messages).await.unwrap().unwrap();
+        stream.socket.into_inner()
+    }
+
+    async fn receive(version: ProtocolVersion, data: Vec<u8>) -> Vec<MessageData> {
+        let mut stream = WrappedStream::new(Cursor::new(data), version);
+        stream.read_new_messages().await.unwrap()
+    }
 }
@@ -0,0 +1,168 @@
+use bytes::Bytes;
+use exocore_core::{
+    cell::{Cell, CellId, CellNodeRole, CellNodes, Node, NodeId},
+    framing::{CapnpFrameBuilder, FrameBuilder},
+};
+use exocore_protos::{core::RelayedMessage, generated::common_capnp::envelope, prost::Message};
+use libp2p::PeerId;
+
+use super::handles::ServiceHandles;
+use crate::{Error, ServiceType};
+
+/// Type of the messages carrying a `RelayedMessage`, exchanged between
+/// transports on the `Meta` service.
+pub(super) const RELAYED_MESSAGE_TYPE: u16 = 2;
+
+/// Action to be taken by the transport for a relayed message it received.
+pub(super) enum RelayedMessageAction {
+    /// We are the relay node: the message needs to be forwarded to the given
+    /// destination node, using the given `Meta` envelope.
+    Forward(Node, Bytes),
+
+    /// We are the destination node: the given envelope was sent by the given
+    /// source node and needs to be dispatched to the service handles.
+    Deliver(Node, Bytes),
+}
+
+/// Finds a node with the relay role, to which we are connected, in one of the
+/// cells that the destination node is part of. Returns the cell in which the
+/// relay node was found with the relay node.
+pub(super) fn find_relay_node(
+    inner: &ServiceHandles,
+    destination: &NodeId,
+    is_connected: impl Fn(&PeerId) -> bool,
+) -> Option<(Cell, Node)> {
+    for handle in inner.service_handles.values() {
+        let cell = &handle.cell;
+        let nodes = cell.nodes();
+        if nodes.get(destination).is_none() {
+            continue;
+        }
+
+        let relay_node = nodes
+            .iter()
+            .with_role(CellNodeRole::Relay)
+            .map(|cell_node| cell_node.node())
+            .find(|node| {
+                node.id() != destination
+                    && node.id() != cell.local_node().id()
+                    && is_connected(node.peer_id())
+            })
+            .cloned();
+
+        if let Some(relay_node) = relay_node {
+            return Some((cell.clone(), relay_node));
+        }
+    }
+
+    None
+}
+
+/// Wraps an envelope to be sent to a destination node through a relay node
+/// into a `Meta` envelope.
+///
+/// The source node is only set by the relay node, since the destination can't
+/// authenticate it otherwise.
+pub(super) fn build_relayed_envelope(
+    cell: &Cell,
+    destination: &NodeId,
+    source: Option<&NodeId>,
+    envelope: &[u8],
+) -> Bytes {
+    let relayed = RelayedMessage {
+        destination_node_id: destination.to_bytes(),
+        source_node_id: source.map(|id| id.to_bytes()).unwrap_or_default(),
+        envelope: envelope.to_vec(),
+    };
+
+    let mut envelope_builder = CapnpFrameBuilder::<envelope::Owned>::new();
+    let mut envelope_message_builder = envelope_builder.get_builder();
+    envelope_message_builder.set_service(ServiceType::Meta.to_code());
+    envelope_message_builder.set_type(RELAYED_MESSAGE_TYPE);
+    envelope_message_builder.set_cell_id(cell.id().as_bytes());
+    envelope_message_builder.set_from_node_id(cell.local_node().id().to_string().as_str());
+    envelope_message_builder.set_data(&relayed.encode_to_vec());
+
+    envelope_builder.as_bytes()
+}
+
+/// Handles a relayed message received from the given peer.
+///
+/// If we are the destination, the peer needs to be a relay node of the cell.
+/// Otherwise, we need to be a relay node of the cell, and both the peer and the
+/// destination need to be nodes of the cell.
+pub(super) fn handle_relayed_message(
+    inner: &ServiceHandles,
+    cell_id: &CellId,
+    from: PeerId,
+    data: &[u8],
+) -> Result<RelayedMessageAction, Error> {
+    let cell = inner
+        .service_handles
+        .values()
+        .map(|handle| &handle.cell)
+        .find(|cell| cell.id() == cell_id)
+        .ok_or_else(|| Error::Other(format!("Got relayed message for unknown cell {}", cell_id)))?;
+
+    let relayed = RelayedMessage::decode(data)
+        .map_err(|err| Error::Other(format!("Couldn't decode relayed message: {}", err)))?;
+    let destination = NodeId::from_bytes(relayed.destination_node_id)
+        .map_err(|err| Error::Other(format!("Invalid relayed message destination: {}", err)))?;
+
+    let local_is_relay = cell.local_node_has_role(CellNodeRole::Relay);
+    let nodes = cell.nodes();
+    let from_node = nodes.get(&NodeId::from_peer_id(from)).ok_or_else(|| {
+        Error::Other(format!(
+            "Got relayed message from peer {} that isn't in cell {}",
+            from, cell
+        ))
+    })?;
+
+    if &destination == cell.local_node().id() {
+        if !from_node.has_role(CellNodeRole::Relay) {
+            return Err(Error::Other(format!(
+                "Got relayed message from node {} that isn't a relay",
+                from_node.node()
+            )));
+        }
+
+        let source = NodeId::from_bytes(relayed.source_node_id)
+            .map_err(|err| Error::Other(format!("Invalid relayed message source: {}", err)))?;
+        let source_node = nodes.get(&source).ok_or_else(|| {
+            Error::Other(format!(
+                "Got relayed message from node {} that isn't in cell {}",
+                source, cell
+            ))
+        })?;
+
+        return Ok(RelayedMessageAction::Deliver(
+            source_node.node().clone(),
+            Bytes::from(relayed.envelope),
+        ));
+    }
+
+    if !local_is_relay {
+        return Err(Error::Other(format!(
+            "Got message to relay to {}, but we aren't a relay of cell {}",
+            destination, cell
+        )));
+    }
+
+    let destination_node = nodes.get(&destination).ok_or_else(|| {
+        Error::Other(format!(
+            "Got message to relay to node {} that isn't in cell {}",
+            destination, cell
+        ))
+    })?;
+
+    let envelope = build_relayed_envelope(
+        cell,
+        &destination,
+        Some(from_node.node().id()),
+        &relayed.envelope,
+    );
+    Ok(RelayedMessageAction::Forward(
+        destination_node.node().clone(),
+        envelope,
+    ))
+}
@@ -4,7 +4,7 @@ use exocore_core::{
     cell::{FullCell, LocalNode},
     futures::{sleep, spawn_future},
     tests_utils::{assert_equal_res, assert_res, async_expect_eventually},
-    time::{ConsistentTimestamp, Instant},
+    time::{Clock, ConsistentTimestamp, Instant},
 };
 use futures::{io::Cursor, AsyncRead, AsyncReadExt};
 
@@ -26,7 +26,8 @@ async fn test_integration() -> anyhow::Result<()> {
     n1_cell.cell().nodes_mut().add(n2.node().clone());
     n2_cell.cell().nodes_mut().add(n1.node().clone());
 
-    let mut transport1 = Libp2pTransport::new(n1.clone(), Libp2pTransportConfig::default());
+    let mut transport1 =
+        Libp2pTransport::new(n1.clone(), Libp2pTransportConfig::default(), Clock::new());
     let handle1 = transport1.get_handle(n1_cell.cell().clone(), ServiceType::Chain)?;
     let mut handle1 = TestableTransportHandle::new(handle1, n1_cell.cell().clone());
     spawn_future(async {
@@ -34,7 +35,8 @@ async fn test_integration() -> anyhow::Result<()> {
         info!("Transport done: {:?}", res);
     });
 
-    let mut transport2 = Libp2pTransport::new(n2.clone(), Libp2pTransportConfig::default());
+    let mut transport2 =
+        Libp2pTransport::new(n2.clone(), Libp2pTransportConfig::default(), Clock::new());
     let handle2 = transport2.get_handle(n2_cell.cell().clone(), ServiceType::Chain)?;
     let mut handle2 = TestableTransportHandle::new(handle2, n2_cell.cell().clone());
     spawn_future(async {
@@ -106,7 +108,7 @@ async fn handle_removal_and_transport_kill() -> anyhow::Result<()> {
     n2.add_p2p_address("/ip4/127.0.0.1/tcp/0".parse()?);
     let n2_cell = FullCell::generate(n2)?;
 
-    let mut transport = Libp2pTransport::new(n1, Libp2pTransportConfig::default());
+    let mut transport = Libp2pTransport::new(n1, Libp2pTransportConfig::default(), Clock::new());
     let inner_weak = Arc::downgrade(&transport.get_service_handles());
 
     // we create 2 handles
@@ -148,7 +150,7 @@ async fn should_queue_message_until_connected() -> anyhow::Result<()> {
     n1_cell.cell().nodes_mut().add(n2.node().clone());
     n2_cell.cell().nodes_mut().add(n1.node().clone());
 
-    let mut t2 = Libp2pTransport::new(n1, Libp2pTransportConfig::default());
+    let mut t2 = Libp2pTransport::new(n1, Libp2pTransportConfig::default(), Clock::new());
     let h1 = t2.get_handle(n1_cell.cell().clone(), ServiceType::Chain)?;
     let mut h1 = TestableTransportHandle::new(h1, n1_cell.cell().clone());
     spawn_future(async {
@@ -171,7 +173,7 @@ async fn should_queue_message_until_connected() -> anyhow::Result<()> {
     std::thread::sleep(Duration::from_millis(100));
 
     // we create second node
-    let mut t2 = Libp2pTransport::new(n2.clone(), Libp2pTransportConfig::default());
+    let mut t2 = Libp2pTransport::new(n2.clone(), Libp2pTransportConfig::default(), Clock::new());
     let h2 = t2.get_handle(n2_cell.cell().clone(), ServiceType::Chain)?;
     let mut h2 = TestableTransportHandle::new(h2, n2_cell.cell().clone());
     spawn_future(async {
@@ -192,3 +194,256 @@ async fn should_queue_message_until_connected() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+// uses multicast on the actual network, which may not be available
+#[ignore = "requires multicast on the local network"]
+#[tokio::test(flavor = "multi_thread")]
+async fn mdns_discovery() -> anyhow::Result<()> {
+    // nodes don't have any known addresses, they need to discover each other
+    let n1 = LocalNode::generate();
+    let n1_cell = FullCell::generate(n1.clone())?;
+
+    let n2 = LocalNode::generate();
+    let n2_cell = n1_cell.clone().with_local_node(n2.clone());
+
+    n1_cell.cell().nodes_mut().add(n2.node().clone());
+    n2_cell.cell().nodes_mut().add(n1.node().clone());
+
+    // mDNS doesn't advertise on loopback interfaces, so we need to listen on all
+    let mdns_config = |port: u16| Libp2pTransportConfig {
+        listen_addresses: vec![format!("/ip4/0.0.0.0/tcp/{}", port).parse().unwrap()],
+        mdns_discovery: true,
+        ..Default::default()
+    };
+
+    let mut transport1 = Libp2pTransport::new(n1.clone(), mdns_config(3014), Clock::new());
+    let handle1 = transport1.get_handle(n1_cell.cell().clone(), ServiceType::Chain)?;
+    let mut handle1 = TestableTransportHandle::new(handle1, n1_cell.cell().clone());
+    spawn_future(async {
+        let res = transport1.run().await;
+        info!("Transport done: {:?}", res);
+    });
+
+    let mut transport2 = Libp2pTransport::new(n2.clone(), mdns_config(3015), Clock::new());
+    let handle2 = transport2.get_handle(n2_cell.cell().clone(), ServiceType::Chain)?;
+    let mut handle2 = TestableTransportHandle::new(handle2, n2_cell.cell().clone());
+    spawn_future(async {
+        let res = transport2.run().await;
+        info!("Transport done: {:?}", res);
+    });
+
+    async_expect_eventually(|| async {
+        assert_equal_res(
+            handle1.node_status(n2.id()).await,
+            Some(ConnectionStatus::Connected),
+        )
+    })
+    .await;
+
+    handle1.send_rdv(n2.node().clone(), 1).await;
+    handle2.recv_rdv(1).await;
+
+    Ok(())
+}
+
+#[test]
+fn mdns_discovered_addresses() -> anyhow::Result<()> {
+    let n1 = LocalNode::generate();
+    let n1_cell = FullCell::generate(n1.clone())?;
+
+    let n2 = LocalNode::generate();
+    n1_cell.cell().nodes_mut().add(n2.node().clone());
+
+    let n3 = LocalNode::generate();
+
+    let mut handles = handles::ServiceHandles::default();
+    let (in_sender, _in_receiver) = futures::channel::mpsc::channel(1);
+    let (_out_sender, out_receiver) = futures::channel::mpsc::channel(1);
+    handles.push_handle(
+        n1_cell.cell().clone(),
+        ServiceType::Chain,
+        in_sender,
+        out_receiver,
+    );
+
+    let n2_addr: libp2p::Multiaddr = "/ip4/127.0.0.1/tcp/3016".parse()?;
+    let n3_addr: libp2p::Multiaddr = "/ip4/127.0.0.1/tcp/3017".parse()?;
+    let mut addresses = transport::DiscoveredAddresses::default();
+    let discovered = addresses.discovered(
+        &handles,
+        vec![
+            (*n2.peer_id(), n2_addr.clone()),
+            (*n3.peer_id(), n3_addr.clone()),
+            (*n1.peer_id(), n2_addr.clone()),
+        ],
+    );
+
+    // only n2 is a member of the cell (local node is excluded)
+    assert_eq!(discovered.len(), 1);
+    assert_eq!(discovered[0].id(), n2.id());
+    assert_eq!(addresses.node_addresses(n2.id()), vec![n2_addr.clone()]);
+    assert!(addresses.node_addresses(n3.id()).is_empty());
+
+    // discovering the same address again doesn't change the node
+    let discovered = addresses.discovered(&handles, vec![(*n2.peer_id(), n2_addr.clone())]);
+    assert!(discovered.is_empty());
+
+    // expired addresses are forgotten
+    let expired = addresses.expired(
+        &handles,
+        vec![(*n2.peer_id(), n2_addr), (*n3.peer_id(), n3_addr)],
+    );
+    assert_eq!(expired.len(), 1);
+    assert!(addresses.node_addresses(n2.id()).is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn addresses_gossip() -> anyhow::Result<()> {
+    // only n2 has a known address, n1 and n3 need to learn each other's addresses
+    // via announces forwarded by n2
+    let n1 = LocalNode::generate();
+    let n1_cell = FullCell::generate(n1.clone())?;
+
+    let n2 = LocalNode::generate();
+    n2.add_p2p_address("/ip4/127.0.0.1/tcp/3019".parse()?);
+    let n2_cell = n1_cell.clone().with_local_node(n2.clone());
+
+    let n3 = LocalNode::generate();
+    let n3_cell = n1_cell.clone().with_local_node(n3.clone());
+
+    for cell in [&n1_cell, &n2_cell, &n3_cell] {
+        let mut nodes = cell.cell().nodes_mut();
+        for node in [&n1, &n2, &n3] {
+            if node.id() != cell.cell().local_node().id() {
+                nodes.add(node.node().clone());
+            }
+        }
+    }
+
+    let gossip_config = |listen_address: Option<&str>| Libp2pTransportConfig {
+        listen_addresses: listen_address
+            .map(|addr| vec![addr.parse().unwrap()])
+            .unwrap_or_default(),
+        addresses_gossip_interval: Duration::from_millis(100),
+        ..Default::default()
+    };
+
+    // n2 is started first so that others can connect to it right away
+    let mut handles = Vec::new();
+    for (node, cell, listen_address) in [
+        (&n2, &n2_cell, None),
+        (&n1, &n1_cell, Some("/ip4/127.0.0.1/tcp/3018")),
+        (&n3, &n3_cell, Some("/ip4/127.0.0.1/tcp/3020")),
+    ] {
+        let mut transport =
+            Libp2pTransport::new(node.clone(), gossip_config(listen_address), Clock::new());
+        let handle = transport.get_handle(cell.cell().clone(), ServiceType::Chain)?;
+        handles.push(TestableTransportHandle::new(handle, cell.cell().clone()));
+        spawn_future(async {
+            let res = transport.run().await;
+            info!("Transport done: {:?}", res);
+        });
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    async_expect_eventually(|| async {
+        assert_equal_res(
+            handles[1].node_status(n3.id()).await,
+            Some(ConnectionStatus::Connected),
+        )
+    })
+    .await;
+
+    handles[1].send_rdv(n3.node().clone(), 1).await;
+    handles[2].recv_rdv(1).await;
+
+    // announced addresses are persisted in the cell's address book
+    let book = exocore_core::cell::AddressBook::load(n1_cell.cell())?;
+    let n3_address: libp2p::Multiaddr = "/ip4/127.0.0.1/tcp/3020".parse()?;
+    assert!(book.node_addresses(n3.id()).contains(&n3_address));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn relay_through_relay_node() -> anyhow::Result<()> {
+    // only n2 has a known address and the relay role. n1 and n3 don't listen on
+    // any address, so they can't dial each other and need to go through n2
+    let n1 = LocalNode::generate();
+    let n1_cell = FullCell::generate(n1.clone())?;
+
+    let n2 = LocalNode::generate();
+    n2.add_p2p_address("/ip4/127.0.0.1/tcp/3021".parse()?);
+    let n2_cell = n1_cell.clone().with_local_node(n2.clone());
+
+    let n3 = LocalNode::generate();
+    let n3_cell = n1_cell.clone().with_local_node(n3.clone());
+
+    for cell in [&n1_cell, &n2_cell, &n3_cell] {
+        let mut nodes = cell.cell().nodes_mut();
+        for node in [&n1, &n2, &n3] {
+            if node.id() != cell.cell().local_node().id() {
+                nodes.add(node.node().clone());
+            }
+        }
+
+        let relay_node = nodes.get_mut(n2.id()).unwrap();
+        relay_node.add_role(exocore_core::cell::CellNodeRole::Relay);
+    }
+
+    let mut handles = Vec::new();
+    for (node, cell) in [(&n2, &n2_cell), (&n1, &n1_cell), (&n3, &n3_cell)] {
+        let mut transport =
+            Libp2pTransport::new(node.clone(), Libp2pTransportConfig::default(), Clock::new());
+        let handle = transport.get_handle(cell.cell().clone(), ServiceType::Chain)?;
+        handles.push(TestableTransportHandle::new(handle, cell.cell().clone()));
+        spawn_future(async {
+            let res = transport.run().await;
+            info!("Transport done: {:?}", res);
+        });
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    for handle in &handles[1..] {
+        async_expect_eventually(|| async {
+            assert_equal_res(
+                handle.node_status(n2.id()).await,
+                Some(ConnectionStatus::Connected),
+            )
+        })
+        .await;
+    }
+
+    // n1 -> n3 gets relayed by n2, and is seen by n3 as coming from n1
+    handles[1].send_rdv(n3.node().clone(), 1).await;
+    let msg = handles[2].recv_rdv(1).await;
+    assert_eq!(msg.source.id(), n1.id());
+    assert!(msg.connection.is_none());
+
+    // reply from n3 to n1 gets relayed back
+    let reply = msg.to_response_message(
+        n3_cell.cell(),
+        TestableTransportHandle::empty_message_frame(),
+    )?;
+    handles[2].send_message(reply).await;
+    let msg = handles[1].recv_rdv(1).await;
+    assert_eq!(msg.source.id(), n3.id());
+
+    Ok(())
+}
+
+#[test]
+fn unspecified_addresses_not_dialable() -> anyhow::Result<()> {
+    assert!(!addresses::is_dialable(&"/ip4/0.0.0.0/tcp/3000".parse()?));
+    assert!(!addresses::is_dialable(&"/ip6/::/tcp/3000".parse()?));
+    assert!(addresses::is_dialable(
+        &"/ip4/192.168.1.2/tcp/3000".parse()?
+    ));
+    assert!(addresses::is_dialable(
+        &"/dns4/example.com/tcp/3000/ws".parse()?
+    ));
+
+    Ok(())
+}
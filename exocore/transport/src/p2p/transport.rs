@@ -1,34 +1,45 @@
 use std::{
+    collections::HashMap,
     num::NonZeroU8,
     sync::{Arc, RwLock},
     task::{Context, Poll},
 };
 
+use bytes::Bytes;
 use exocore_core::{
     cell::{Cell, CellId, CellNodes, LocalNode, Node, NodeId},
     framing::{FrameBuilder, TypedCapnpFrame},
+    time::{Clock, Instant},
     utils::handle_set::HandleSet,
 };
 use exocore_protos::generated::common_capnp::envelope;
 use futures::{channel::mpsc, prelude::*, FutureExt, SinkExt, StreamExt};
 use libp2p::{
     ping,
-    swarm::{NetworkBehaviour, Swarm},
-    PeerId, Transport,
+    swarm::{behaviour::toggle::Toggle, NetworkBehaviour, Swarm},
+    Multiaddr, PeerId, Transport,
 };
 
 use super::{
+    addresses::{AddressesGossip, ADDRESSES_ANNOUNCE_MESSAGE_TYPE},
     behaviour::{ExocoreBehaviour, ExocoreBehaviourEvent, ExocoreBehaviourMessage, PeerStatus},
     handles::ServiceHandles,
+    relay::{
+        build_relayed_envelope, find_relay_node, handle_relayed_message, RelayedMessageAction,
+        RELAYED_MESSAGE_TYPE,
+    },
     Libp2pTransportConfig,
 };
 use crate::{
-    messages::InMessage,
+    messages::{InMessage, MessageStream, OutMessage},
     p2p::protocol::MessageData,
     transport::{ConnectionId, ConnectionStatus, InEvent, OutEvent},
     Error, Libp2pTransportServiceHandle, ServiceType,
 };
 
+/// Expiration of addresses announces that couldn't be sent right away.
+const ADDRESSES_ANNOUNCE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
 /// Libp2p transport used by all services of Exocore through handles. There is
 /// one handle per cell per service.
 ///
@@ -38,6 +49,7 @@ use crate::{
 pub struct Libp2pTransport {
     local_node: LocalNode,
     config: Libp2pTransportConfig,
+    clock: Clock,
     service_handles: Arc<RwLock<ServiceHandles>>,
     handle_set: HandleSet,
 }
@@ -46,10 +58,18 @@ impl Libp2pTransport {
     /// Creates a new transport for given node and config. The node is important
     /// here since all messages are authenticated using the node's private
     /// key thanks to secio.
-    pub fn new(local_node: LocalNode, config: Libp2pTransportConfig) -> Libp2pTransport {
+    ///
+    /// The clock is used to date the announces of our addresses, which
+    /// determines which announces are the most recent ones.
+    pub fn new(
+        local_node: LocalNode,
+        config: Libp2pTransportConfig,
+        clock: Clock,
+    ) -> Libp2pTransport {
         Libp2pTransport {
             local_node,
             config,
+            clock,
             service_handles: Default::default(),
             handle_set: Default::default(),
         }
@@ -94,6 +114,7 @@ impl Libp2pTransport {
             // service_handles: Arc::clone(&self.service_handles),
             exocore: ExocoreBehaviour::default(),
             ping: ping::Behaviour::default(),
+            mdns: self.build_mdns_behaviour()?,
         };
 
         const DIAL_CONCURRENCY_FACTOR: u8 = 5;
@@ -153,16 +174,26 @@ impl Libp2pTransport {
         let (out_sender, mut out_receiver) =
             mpsc::channel::<OutEvent>(self.config.handles_to_behaviour_channel_size);
 
-        // Add initial nodes to swarm
-        {
+        // Add initial nodes to swarm, with the addresses they last announced
+        let mut discovered_addresses = DiscoveredAddresses::default();
+        let mut addresses_gossip = {
             let inner = self.service_handles.read()?;
+            let addresses_gossip = AddressesGossip::new(&inner, self.clock.clone());
             for node in inner.all_peer_nodes().values() {
-                swarm.behaviour_mut().exocore.add_node(node);
+                let recent_addresses =
+                    recent_addresses(&addresses_gossip, &discovered_addresses, node.id());
+                swarm
+                    .behaviour_mut()
+                    .exocore
+                    .add_node(node, &recent_addresses);
             }
-        }
+            addresses_gossip
+        };
 
         let mut nodes_update_interval =
             exocore_core::futures::interval(self.config.swarm_nodes_update_interval);
+        let mut addresses_gossip_interval =
+            exocore_core::futures::interval(self.config.addresses_gossip_interval);
 
         // Spawn the main Future which will take care of the swarm
         let service_handles = Arc::clone(&self.service_handles);
@@ -173,38 +204,25 @@ impl Libp2pTransport {
             if nodes_update_interval.poll_tick(cx).is_ready() {
                 let inner = inner.read().expect("Couldn't get inner lock");
                 for node in inner.all_peer_nodes().values() {
-                    swarm.behaviour_mut().exocore.add_node(node);
+                    let recent_addresses =
+                        recent_addresses(&addresses_gossip, &discovered_addresses, node.id());
+                    swarm
+                        .behaviour_mut()
+                        .exocore
+                        .add_node(node, &recent_addresses);
                 }
             }
 
+            // At interval, announce our addresses to connected nodes
+            if addresses_gossip_interval.poll_tick(cx).is_ready() {
+                send_addresses_announces(&mut swarm, &addresses_gossip, None);
+            }
+
             // Drain all messages coming from handles that need to be sent to other nodes
             while let Poll::Ready(Some(event)) = out_receiver.poll_next_unpin(cx) {
                 match event {
                     OutEvent::Message(msg) => {
-                        let frame_data = msg.envelope_builder.as_bytes();
-
-                        let connection =
-                            if let Some(ConnectionId::Libp2p(connection)) = msg.connection {
-                                Some(connection)
-                            } else {
-                                None
-                            };
-
-                        if let Some(dest) = msg.destination {
-                            let msg_data = MessageData {
-                                message: frame_data,
-                                stream: msg.stream,
-                            };
-
-                            swarm.behaviour_mut().exocore.send_message(
-                                *dest.peer_id(),
-                                msg.expiration,
-                                connection,
-                                msg_data,
-                            );
-                        } else {
-                            error!("Got a message to send to behaviour without destination node");
-                        }
+                        send_message(&mut swarm, &service_handles, msg);
                     }
                     OutEvent::Reset => {
                         info!("Resetting connections to peers...");
@@ -225,17 +243,76 @@ impl Libp2pTransport {
                     )) => {
                         trace!("Got message from {}", msg.source);
 
-                        if let Err(err) = dispatch_message(&service_handles, msg) {
-                            warn!("Couldn't dispatch message: {}", err);
+                        match dispatch_message(&service_handles, &mut addresses_gossip, msg) {
+                            Ok(DispatchAction::AddressesAnnounced(announced_node_id)) => {
+                                // node announced new addresses, which may need to be dialed
+                                let inner = inner.read().expect("Couldn't get inner lock");
+                                if let Some(node) = inner.all_peer_nodes().get(&announced_node_id) {
+                                    let recent_addresses = recent_addresses(
+                                        &addresses_gossip,
+                                        &discovered_addresses,
+                                        node.id(),
+                                    );
+                                    swarm
+                                        .behaviour_mut()
+                                        .exocore
+                                        .add_node(node, &recent_addresses);
+                                }
+                            }
+                            Ok(DispatchAction::Relay(destination, envelope)) => {
+                                trace!("Relaying message to {}", destination);
+                                swarm.behaviour_mut().exocore.send_message(
+                                    *destination.peer_id(),
+                                    None,
+                                    None,
+                                    MessageData {
+                                        message: envelope,
+                                        stream: None,
+                                    },
+                                );
+                            }
+                            Ok(DispatchAction::None) => {}
+                            Err(err) => {
+                                warn!("Couldn't dispatch message: {}", err);
+                            }
                         }
                     }
                     libp2p::swarm::SwarmEvent::Behaviour(CombinedEvent::Exocore(
                         ExocoreBehaviourEvent::PeerStatus(peer_id, status),
                     )) => {
+                        if status == PeerStatus::Connected {
+                            send_addresses_announces(&mut swarm, &addresses_gossip, Some(peer_id));
+                        }
+
                         if let Err(err) = dispatch_node_status(&service_handles, peer_id, status) {
                             warn!("Couldn't dispatch node status: {}", err);
                         }
                     }
+                    #[cfg(feature = "p2p-full")]
+                    libp2p::swarm::SwarmEvent::Behaviour(CombinedEvent::Mdns(event)) => {
+                        let inner = inner.read().expect("Couldn't get inner lock");
+                        let nodes = match event {
+                            libp2p::mdns::Event::Discovered(discovered) => {
+                                discovered_addresses.discovered(&inner, discovered)
+                            }
+                            libp2p::mdns::Event::Expired(expired) => {
+                                discovered_addresses.expired(&inner, expired)
+                            }
+                        };
+
+                        // addresses of the nodes changed, which may need to be dialed
+                        for node in nodes {
+                            let recent_addresses = recent_addresses(
+                                &addresses_gossip,
+                                &discovered_addresses,
+                                node.id(),
+                            );
+                            swarm
+                                .behaviour_mut()
+                                .exocore
+                                .add_node(&node, &recent_addresses);
+                        }
+                    }
                     libp2p::swarm::SwarmEvent::Behaviour(CombinedEvent::Ping(event)) => {
                         match event.result {
                             Ok(rtt) => {
@@ -290,19 +367,58 @@ impl Libp2pTransport {
 
         Ok(())
     }
+
+    #[cfg(feature = "p2p-full")]
+    fn build_mdns_behaviour(&self) -> Result<Toggle<MdnsBehaviour>, Error> {
+        if !self.config.mdns_discovery {
+            return Ok(Toggle::from(None));
+        }
+
+        info!("Enabling mDNS discovery of cell nodes");
+        let mdns = MdnsBehaviour::new(Default::default(), *self.local_node.peer_id())?;
+        Ok(Toggle::from(Some(mdns)))
+    }
+
+    #[cfg(not(feature = "p2p-full"))]
+    fn build_mdns_behaviour(&self) -> Result<Toggle<MdnsBehaviour>, Error> {
+        if self.config.mdns_discovery {
+            warn!("mDNS discovery is not supported on this target");
+        }
+
+        Ok(Toggle::from(None))
+    }
 }
 
-/// Behaviour that combines exocore and ping behaviours.
+/// Behaviour that combines exocore, ping and mDNS discovery behaviours.
 #[derive(NetworkBehaviour)]
 #[behaviour(to_swarm = "CombinedEvent")]
 struct CombinedBehaviour {
     exocore: ExocoreBehaviour,
     ping: ping::Behaviour,
+    mdns: Toggle<MdnsBehaviour>,
 }
 
+/// mDNS advertises the node's peer id (from which its node id is derived) and
+/// its listening addresses on the local network.
+#[cfg(feature = "p2p-full")]
+type MdnsBehaviour = libp2p::mdns::tokio::Behaviour;
+
+/// mDNS isn't available on wasm, hence replaced by a behaviour that does
+/// nothing.
+#[cfg(not(feature = "p2p-full"))]
+type MdnsBehaviour = libp2p::swarm::dummy::Behaviour;
+
+#[cfg(feature = "p2p-full")]
+type MdnsEvent = libp2p::mdns::Event;
+
+#[cfg(not(feature = "p2p-full"))]
+type MdnsEvent = void::Void;
+
 enum CombinedEvent {
     Exocore(ExocoreBehaviourEvent),
     Ping(ping::Event),
+    #[cfg_attr(not(feature = "p2p-full"), allow(dead_code))]
+    Mdns(MdnsEvent),
 }
 
 impl From<ExocoreBehaviourEvent> for CombinedEvent {
@@ -317,17 +433,238 @@ impl From<ping::Event> for CombinedEvent {
     }
 }
 
-/// Dispatches a received message from libp2p to corresponding handle
+impl From<MdnsEvent> for CombinedEvent {
+    fn from(event: MdnsEvent) -> Self {
+        CombinedEvent::Mdns(event)
+    }
+}
+
+/// Addresses of the nodes of our cells discovered on the local network via
+/// mDNS.
+///
+/// Since mDNS records only advertise peer ids, discovered peers are matched
+/// against the members of our cells and ignored if they aren't part of any.
+/// Addresses are kept apart from the nodes' configured addresses so that they
+/// can be forgotten once their records expire.
+#[derive(Default)]
+pub(super) struct DiscoveredAddresses {
+    addresses: HashMap<NodeId, Vec<Multiaddr>>,
+}
+
+#[cfg_attr(not(feature = "p2p-full"), allow(dead_code))]
+impl DiscoveredAddresses {
+    /// Adds discovered addresses and returns the nodes of our cells for which
+    /// new addresses were discovered.
+    pub(super) fn discovered(
+        &mut self,
+        inner: &ServiceHandles,
+        discovered: impl IntoIterator<Item = (PeerId, Multiaddr)>,
+    ) -> Vec<Node> {
+        let nodes = inner.all_peer_nodes();
+
+        let mut changed_nodes = HashMap::new();
+        for (peer_id, address) in discovered {
+            let node_id = NodeId::from_peer_id(peer_id);
+            let Some(node) = nodes.get(&node_id) else {
+                trace!(
+                    "Ignoring discovered peer {} that isn't in our cells",
+                    peer_id
+                );
+                continue;
+            };
+
+            let addresses = self.addresses.entry(node_id.clone()).or_default();
+            if !addresses.contains(&address) {
+                debug!("Discovered address {} for node {}", address, node);
+                addresses.push(address);
+                changed_nodes.insert(node_id, node.clone());
+            }
+        }
+
+        changed_nodes.into_values().collect()
+    }
+
+    /// Removes addresses for which the mDNS records expired and returns the
+    /// nodes of our cells that had any of them.
+    pub(super) fn expired(
+        &mut self,
+        inner: &ServiceHandles,
+        expired: impl IntoIterator<Item = (PeerId, Multiaddr)>,
+    ) -> Vec<Node> {
+        let nodes = inner.all_peer_nodes();
+
+        let mut changed_nodes = HashMap::new();
+        for (peer_id, address) in expired {
+            let node_id = NodeId::from_peer_id(peer_id);
+            let Some(addresses) = self.addresses.get_mut(&node_id) else {
+                continue;
+            };
+
+            if let Some(position) = addresses.iter().position(|addr| *addr == address) {
+                addresses.remove(position);
+                if let Some(node) = nodes.get(&node_id) {
+                    debug!("Discovered address {} for node {} expired", address, node);
+                    changed_nodes.insert(node_id.clone(), node.clone());
+                }
+            }
+
+            if addresses.is_empty() {
+                self.addresses.remove(&node_id);
+            }
+        }
+
+        changed_nodes.into_values().collect()
+    }
+
+    pub(super) fn node_addresses(&self, node_id: &NodeId) -> &[Multiaddr] {
+        self.addresses.get(node_id).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Returns the addresses of a node that aren't part of its config, either
+/// announced by the node or discovered on the local network.
+fn recent_addresses(
+    addresses_gossip: &AddressesGossip,
+    discovered_addresses: &DiscoveredAddresses,
+    node_id: &NodeId,
+) -> Vec<Multiaddr> {
+    let mut addresses = addresses_gossip.node_addresses(node_id);
+    for address in discovered_addresses.node_addresses(node_id) {
+        if !addresses.contains(address) {
+            addresses.push(address.clone());
+        }
+    }
+    addresses
+}
+
+/// Sends announces of our addresses and the latest ones we got from other
+/// nodes to the given peer, or to all connected peers if none is given.
+fn send_addresses_announces(
+    swarm: &mut Swarm<CombinedBehaviour>,
+    addresses_gossip: &AddressesGossip,
+    peer_id: Option<PeerId>,
+) {
+    let listen_addresses: Vec<Multiaddr> = swarm
+        .listeners()
+        .chain(swarm.external_addresses())
+        .cloned()
+        .collect();
+
+    let messages = {
+        let behaviour = &swarm.behaviour().exocore;
+        addresses_gossip.announce_messages(&listen_addresses, |node| match peer_id {
+            Some(peer_id) => node.peer_id() == &peer_id,
+            None => behaviour.is_peer_connected(node.peer_id()),
+        })
+    };
+
+    for (node, message) in messages {
+        swarm.behaviour_mut().exocore.send_message(
+            *node.peer_id(),
+            Some(Instant::now() + ADDRESSES_ANNOUNCE_TIMEOUT),
+            None,
+            MessageData {
+                message,
+                stream: None,
+            },
+        );
+    }
+}
+
+/// Sends a message coming from a handle to its destination node.
+///
+/// If the destination node can't be reached directly, the message is sent
+/// through a node of the cell having the relay role to which we are connected.
+/// Messages having a stream can't be relayed.
+fn send_message(
+    swarm: &mut Swarm<CombinedBehaviour>,
+    inner: &RwLock<ServiceHandles>,
+    msg: OutMessage,
+) {
+    let Some(dest) = msg.destination else {
+        error!("Got a message to send to behaviour without destination node");
+        return;
+    };
+
+    let frame_data = msg.envelope_builder.as_bytes();
+    let connection = if let Some(ConnectionId::Libp2p(connection)) = msg.connection {
+        Some(connection)
+    } else {
+        None
+    };
+
+    let behaviour = &swarm.behaviour().exocore;
+    if !behaviour.is_peer_reachable(dest.peer_id()) {
+        let inner = inner.read().expect("Couldn't get inner lock");
+        let relay = find_relay_node(&inner, dest.id(), |peer_id| {
+            behaviour.is_peer_connected(peer_id)
+        });
+
+        match relay {
+            Some((_cell, relay_node)) if msg.stream.is_some() => {
+                warn!(
+                    "Node {} isn't reachable, but message has a stream and can't be relayed through {}",
+                    dest, relay_node
+                );
+            }
+            Some((cell, relay_node)) => {
+                debug!(
+                    "Node {} isn't reachable. Relaying message through {}",
+                    dest, relay_node
+                );
+                let envelope = build_relayed_envelope(&cell, dest.id(), None, &frame_data);
+                swarm.behaviour_mut().exocore.send_message(
+                    *relay_node.peer_id(),
+                    msg.expiration,
+                    None,
+                    MessageData {
+                        message: envelope,
+                        stream: None,
+                    },
+                );
+                return;
+            }
+            None => {}
+        }
+    }
+
+    swarm.behaviour_mut().exocore.send_message(
+        *dest.peer_id(),
+        msg.expiration,
+        connection,
+        MessageData {
+            message: frame_data,
+            stream: msg.stream,
+        },
+    );
+}
+
+/// Action to be taken by the transport once a received message got dispatched.
+enum DispatchAction {
+    None,
+
+    /// A node announced new addresses, which may need to be dialed.
+    AddressesAnnounced(NodeId),
+
+    /// We are a relay node and the given envelope needs to be forwarded to the
+    /// given node.
+    Relay(Node, Bytes),
+}
+
+/// Dispatches a received message from libp2p to corresponding handle.
+///
+/// Messages of the `Meta` service are handled by the transport itself, and may
+/// require further action from the transport (ex: dialing a node that
+/// announced new addresses, or forwarding a relayed message).
 fn dispatch_message(
     inner: &RwLock<ServiceHandles>,
+    addresses_gossip: &mut AddressesGossip,
     message: ExocoreBehaviourMessage,
-) -> Result<(), Error> {
+) -> Result<DispatchAction, Error> {
     let frame = TypedCapnpFrame::<_, envelope::Owned>::new(message.message.message)?;
     let frame_reader: envelope::Reader = frame.get_reader()?;
     let cell_id_bytes = frame_reader.get_cell_id()?;
 
-    let mut inner = inner.write()?;
-
     let cell_id = CellId::from_bytes(cell_id_bytes);
     let service_type = ServiceType::from_code(frame_reader.get_service()).ok_or_else(|| {
         Error::Other(format!(
@@ -336,6 +673,82 @@ fn dispatch_message(
         ))
     })?;
 
+    if service_type == ServiceType::Meta {
+        return match frame_reader.get_type() {
+            ADDRESSES_ANNOUNCE_MESSAGE_TYPE => Ok(addresses_gossip
+                .handle_announce(&cell_id, frame_reader.get_data()?)?
+                .map_or(DispatchAction::None, DispatchAction::AddressesAnnounced)),
+            RELAYED_MESSAGE_TYPE => {
+                let action = {
+                    let inner = inner.read()?;
+                    handle_relayed_message(
+                        &inner,
+                        &cell_id,
+                        message.source,
+                        frame_reader.get_data()?,
+                    )?
+                };
+
+                match action {
+                    RelayedMessageAction::Forward(destination, envelope) => {
+                        Ok(DispatchAction::Relay(destination, envelope))
+                    }
+                    RelayedMessageAction::Deliver(source, envelope) => {
+                        let frame = TypedCapnpFrame::<_, envelope::Owned>::new(envelope)?;
+                        let relayed_cell_id =
+                            CellId::from_bytes(frame.get_reader()?.get_cell_id()?);
+                        if relayed_cell_id != cell_id {
+                            return Err(Error::Other(format!(
+                                "Relayed message is for cell {} instead of {}",
+                                relayed_cell_id, cell_id
+                            )));
+                        }
+
+                        // replies can't use the connection on which we got the message since
+                        // it's the relay's, and will get relayed back if needed
+                        dispatch_to_handle(inner, frame, *source.peer_id(), None, None)?;
+                        Ok(DispatchAction::None)
+                    }
+                }
+            }
+            other => Err(Error::Other(format!(
+                "Got meta message with unknown type {}",
+                other
+            ))),
+        };
+    }
+
+    dispatch_to_handle(
+        inner,
+        frame,
+        message.source,
+        Some(ConnectionId::Libp2p(message.connection)),
+        message.message.stream,
+    )?;
+
+    Ok(DispatchAction::None)
+}
+
+/// Dispatches a message sent by the given peer to the handle of the cell and
+/// service it is for.
+fn dispatch_to_handle(
+    inner: &RwLock<ServiceHandles>,
+    frame: TypedCapnpFrame<Bytes, envelope::Owned>,
+    source: PeerId,
+    connection: Option<ConnectionId>,
+    stream: Option<MessageStream>,
+) -> Result<(), Error> {
+    let frame_reader: envelope::Reader = frame.get_reader()?;
+    let cell_id = CellId::from_bytes(frame_reader.get_cell_id()?);
+    let service_type = ServiceType::from_code(frame_reader.get_service()).ok_or_else(|| {
+        Error::Other(format!(
+            "Message has invalid service_type {}",
+            frame_reader.get_service()
+        ))
+    })?;
+
+    let mut inner = inner.write()?;
+
     let key = (cell_id, service_type);
     let Some(service_handle) = inner.service_handles.get_mut(&key) else {
         return Err(Error::Other(format!(
@@ -344,15 +757,17 @@ fn dispatch_message(
         )));
     };
 
-    let source_node = get_node_by_peer(&service_handle.cell, message.source)?;
+    let source_node = get_node_by_peer(&service_handle.cell, source)?;
     let mut msg = InMessage::from_node_and_frame(source_node, frame.to_owned())?;
-    msg.connection = Some(ConnectionId::Libp2p(message.connection));
-    msg.stream = message.message.stream;
+    msg.connection = connection;
+    msg.stream = stream;
 
     service_handle
         .in_sender
         .try_send(InEvent::Message(msg))
-        .map_err(|err| Error::Other(format!("Couldn't send message to cell service: {}", err)))
+        .map_err(|err| Error::Other(format!("Couldn't send message to cell service: {}", err)))?;
+
+    Ok(())
 }
 
 /// Dispatches a node status change.
@@ -6,12 +6,14 @@ use std::{
         Arc, Mutex, Weak,
     },
     task::{Context, Poll},
+    time::Duration,
 };
 
 use exocore_core::{
     cell::{Cell, LocalNode, Node, NodeId},
     framing::CapnpFrameBuilder,
-    futures::{owned_spawn, spawn_future, OwnedSpawn},
+    futures::{interval, owned_spawn, spawn_future, OwnedSpawn},
+    time::Clock,
     utils::handle_set::{Handle, HandleSet},
 };
 use exocore_protos::generated::data_chain_capnp::block_operation_header;
@@ -23,27 +25,47 @@ use crate::{
     Error, InEvent, InMessage, OutEvent, OutMessage, ServiceType, TransportServiceHandle,
 };
 
+mod network;
+use network::SimulatedNetwork;
+pub use network::{NetworkConditions, NetworkStats};
+
 const CHANNELS_SIZE: usize = 1000;
 
+/// Interval at which messages delayed by the simulated network are checked for
+/// delivery.
+const NETWORK_DELIVERY_INTERVAL: Duration = Duration::from_millis(5);
+
 type HandleKey = (NodeId, ServiceType);
 
 /// In memory transport used by all services of Exocore through handles. There
 /// is one handle per cell per service.
+///
+/// Messages go through a simulated network on which latency, jitter, loss,
+/// duplication, reordering and partitions can be configured at any time. By
+/// default, the network is perfect and messages are delivered instantly.
 pub struct MockTransport {
     service_sinks: Arc<Mutex<HashMap<HandleKey, ServiceSink>>>,
+    network: Arc<SimulatedNetwork>,
     handle_set: HandleSet,
 }
 
 impl Default for MockTransport {
     fn default() -> MockTransport {
+        MockTransport::new_simulated(Clock::new(), 0)
+    }
+}
+
+impl MockTransport {
+    /// Creates a transport for which message delays of the simulated network
+    /// are driven by the given clock, and random decisions by the given seed.
+    pub fn new_simulated(clock: Clock, seed: u64) -> MockTransport {
         MockTransport {
             service_sinks: Arc::new(Mutex::new(HashMap::new())),
+            network: Arc::new(SimulatedNetwork::new(clock, seed)),
             handle_set: HandleSet::new(),
         }
     }
-}
 
-impl MockTransport {
     pub fn get_transport(
         &self,
         node: LocalNode,
@@ -69,6 +91,7 @@ impl MockTransport {
             service_type,
             started: false,
             service_sinks: Arc::downgrade(&self.service_sinks),
+            network: Arc::downgrade(&self.network),
             incoming_stream: Some(incoming_receiver),
             outgoing_stream: None,
         }
@@ -86,6 +109,66 @@ impl MockTransport {
                 .try_send(InEvent::NodeStatus(node_id.clone(), connection_status));
         }
     }
+
+    /// Changes the conditions of the simulated network. Messages already in
+    /// flight keep the delivery time they were given when sent.
+    pub fn set_network_conditions(&self, conditions: NetworkConditions) {
+        self.network.set_conditions(conditions);
+    }
+
+    /// Partitions the network so that nodes on one side can't reach nodes on
+    /// the other side, including messages that are currently in flight. Nodes
+    /// on each side get notified that the nodes of the other side got
+    /// disconnected.
+    pub fn partition(&self, side_a: &[NodeId], side_b: &[NodeId]) {
+        self.network.add_partition(side_a, side_b);
+
+        let pairs = side_a
+            .iter()
+            .flat_map(|a| side_b.iter().map(move |b| (a.clone(), b.clone())))
+            .collect::<Vec<_>>();
+        self.notify_pairs_status(&pairs, ConnectionStatus::Disconnected);
+    }
+
+    /// Removes all partitions of the network, and notifies nodes that were
+    /// partitioned that the nodes they couldn't reach are connected again.
+    /// Nodes that weren't partitioned don't get notified.
+    pub fn heal_partitions(&self) {
+        let pairs = self.network.heal_partitions();
+        self.notify_pairs_status(&pairs, ConnectionStatus::Connected);
+    }
+
+    /// Delivers the messages of the simulated network for which the delivery
+    /// time has been reached. This is also done periodically once a handle is
+    /// started, but can be called by tests right after moving the clock
+    /// forward.
+    pub fn deliver_due_messages(&self) {
+        let mut service_sinks = self.service_sinks.lock().unwrap();
+        self.network.deliver_due(&mut service_sinks);
+    }
+
+    pub fn network_stats(&self) -> NetworkStats {
+        self.network.stats()
+    }
+
+    fn notify_pairs_status(&self, pairs: &[(NodeId, NodeId)], status: ConnectionStatus) {
+        let mut service_sinks = self.service_sinks.lock().unwrap();
+        for ((handle_node, _service_type), sink) in service_sinks.iter_mut() {
+            for (a, b) in pairs {
+                let other = if handle_node == a {
+                    b
+                } else if handle_node == b {
+                    a
+                } else {
+                    continue;
+                };
+
+                let _ = sink
+                    .sender
+                    .try_send(InEvent::NodeStatus(other.clone(), status));
+            }
+        }
+    }
 }
 
 /// Handle taken by a Cell service to receive and send message for a given node
@@ -95,6 +178,7 @@ pub struct MockTransportServiceHandle {
     service_type: ServiceType,
     started: bool,
     service_sinks: Weak<Mutex<HashMap<HandleKey, ServiceSink>>>,
+    network: Weak<SimulatedNetwork>,
     incoming_stream: Option<mpsc::Receiver<InEvent>>,
     outgoing_stream: Option<mpsc::Receiver<OutEvent>>,
 }
@@ -144,31 +228,43 @@ impl Future for MockTransportServiceHandle {
             let node = self.node.clone();
             let service_type = self.service_type;
             let handles_sink_weak = Weak::clone(&self.service_sinks);
+            let network_weak = Weak::clone(&self.network);
             spawn_future(async move {
                 while let Some(OutEvent::Message(msg)) = outgoing_stream.next().await {
-                    let Some(handles_sink) = handles_sink_weak.upgrade() else {
+                    let (Some(handles_sink), Some(network)) =
+                        (handles_sink_weak.upgrade(), network_weak.upgrade())
+                    else {
                         return;
                     };
                     let mut handles_sink = handles_sink.lock().unwrap();
 
-                    let in_message = msg
-                        .to_in_message(node.clone())
-                        .expect("Couldn't get InMessage from OutMessage");
-                    let dest_node = msg
-                        .destination
-                        .expect("Message didn't have a destination node");
-                    let key = (dest_node.id().clone(), service_type);
-                    if let Some(node_sink) = handles_sink.get_mut(&key) {
-                        let _ = node_sink.sender.try_send(InEvent::Message(in_message));
-                    } else {
-                        warn!(
-                            "Couldn't send message to node {} since it's not in the hub anymore",
-                            dest_node.id()
-                        );
-                    }
+                    network.send(&node, service_type, &msg);
+                    network.deliver_due(&mut handles_sink);
                 }
             });
 
+            // messages delayed by the simulated network get delivered by the
+            // transport's first started handle
+            let network = self.network.upgrade();
+            if network.is_some_and(|network| network.start_delivery()) {
+                let handles_sink_weak = Weak::clone(&self.service_sinks);
+                let network_weak = Weak::clone(&self.network);
+                spawn_future(async move {
+                    let mut interval = interval(NETWORK_DELIVERY_INTERVAL);
+                    loop {
+                        interval.tick().await;
+
+                        let (Some(handles_sink), Some(network)) =
+                            (handles_sink_weak.upgrade(), network_weak.upgrade())
+                        else {
+                            return;
+                        };
+                        let mut handles_sink = handles_sink.lock().unwrap();
+                        network.deliver_due(&mut handles_sink);
+                    }
+                });
+            }
+
             self.started = true;
         }
 
@@ -372,7 +468,10 @@ impl TestableTransportHandle {
 
 #[cfg(test)]
 mod test {
-    use exocore_core::cell::{FullCell, LocalNode};
+    use exocore_core::{
+        cell::{FullCell, LocalNode},
+        futures::sleep,
+    };
 
     use super::*;
 
@@ -419,4 +518,140 @@ mod test {
         assert_eq!(&msg_node, node0.id());
         assert_eq!(status, ConnectionStatus::Connected);
     }
+
+    #[tokio::test]
+    async fn simulated_latency_driven_by_clock() {
+        let clock = Clock::new_fixed_mocked(exocore_core::time::Instant::now());
+        let hub = MockTransport::new_simulated(clock.clone(), 1);
+        hub.set_network_conditions(NetworkConditions {
+            latency: Duration::from_millis(100),
+            ..Default::default()
+        });
+
+        let (node0, mut t0) = testable_handle(&hub);
+        let (_node1, mut t1) = testable_handle(&hub);
+
+        t0.send_rdv(t1.cell().local_node().node().clone(), 100)
+            .await;
+        sleep(Duration::from_millis(50)).await;
+        assert!(!t1.has_msg().await.unwrap());
+
+        clock.add_fixed_instant_duration(Duration::from_millis(100));
+        hub.deliver_due_messages();
+
+        let msg = t1.recv_msg().await;
+        assert_eq!(msg.source.id(), node0.id());
+        assert_eq!(msg.rendez_vous_id, Some(100.into()));
+
+        assert!(!t0.has_msg().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn simulated_partition() {
+        let hub = MockTransport::default();
+
+        let (node0, mut t0) = testable_handle(&hub);
+        let (node1, mut t1) = testable_handle(&hub);
+
+        hub.partition(&[node0.id().clone()], &[node1.id().clone()]);
+        let (status_node, status) = t0.recv_status().await;
+        assert_eq!(&status_node, node1.id());
+        assert_eq!(status, ConnectionStatus::Disconnected);
+
+        t0.send_rdv(node1.node().clone(), 100).await;
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(hub.network_stats().partitioned, 1);
+
+        let (_, status) = t1.recv_status().await;
+        assert_eq!(status, ConnectionStatus::Disconnected);
+
+        hub.heal_partitions();
+        let (status_node, status) = t1.recv_status().await;
+        assert_eq!(&status_node, node0.id());
+        assert_eq!(status, ConnectionStatus::Connected);
+
+        t0.send_rdv(node1.node().clone(), 101).await;
+        let msg = t1.recv_msg().await;
+        assert_eq!(msg.rendez_vous_id, Some(101.into()));
+    }
+
+    #[tokio::test]
+    async fn simulated_partition_heal_only_partitioned() {
+        let hub = MockTransport::default();
+
+        let (node0, t0) = testable_handle(&hub);
+        let (node1, t1) = testable_handle(&hub);
+        let (_node2, t2) = testable_handle(&hub);
+
+        // same pair partitioned twice
+        hub.partition(&[node0.id().clone()], &[node1.id().clone()]);
+        hub.partition(&[node1.id().clone()], &[node0.id().clone()]);
+        hub.heal_partitions();
+        sleep(Duration::from_millis(50)).await;
+
+        // 2 disconnections, but a single reconnection
+        assert_eq!(t0.received_count().await, 3);
+        assert_eq!(t1.received_count().await, 3);
+        assert_eq!(
+            t0.node_status(node1.id()).await,
+            Some(ConnectionStatus::Connected)
+        );
+
+        // node 2 wasn't partitioned, so it doesn't get notified
+        assert_eq!(t2.received_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn simulated_loss_duplication_and_reordering() {
+        let clock = Clock::new_fixed_mocked(exocore_core::time::Instant::now());
+        let hub = MockTransport::new_simulated(clock.clone(), 1);
+
+        let (_node0, mut t0) = testable_handle(&hub);
+        let (node1, mut t1) = testable_handle(&hub);
+
+        hub.set_network_conditions(NetworkConditions {
+            loss: 1.0,
+            ..Default::default()
+        });
+        t0.send_rdv(node1.node().clone(), 100).await;
+        sleep(Duration::from_millis(50)).await;
+
+        hub.set_network_conditions(NetworkConditions {
+            duplication: 1.0,
+            ..Default::default()
+        });
+        t0.send_rdv(node1.node().clone(), 101).await;
+        assert_eq!(t1.recv_msg().await.rendez_vous_id, Some(101.into()));
+        assert_eq!(t1.recv_msg().await.rendez_vous_id, Some(101.into()));
+
+        hub.set_network_conditions(NetworkConditions {
+            reordering: 1.0,
+            ..Default::default()
+        });
+        t0.send_rdv(node1.node().clone(), 102).await;
+        sleep(Duration::from_millis(50)).await;
+
+        hub.set_network_conditions(NetworkConditions::default());
+        t0.send_rdv(node1.node().clone(), 103).await;
+        assert_eq!(t1.recv_msg().await.rendez_vous_id, Some(103.into()));
+
+        clock.add_fixed_instant_duration(Duration::from_secs(1));
+        hub.deliver_due_messages();
+        assert_eq!(t1.recv_msg().await.rendez_vous_id, Some(102.into()));
+
+        let stats = hub.network_stats();
+        assert_eq!(stats.sent, 4);
+        assert_eq!(stats.lost, 1);
+        assert_eq!(stats.duplicated, 1);
+        assert_eq!(stats.reordered, 1);
+        assert_eq!(stats.delivered, 4);
+    }
+
+    fn testable_handle(hub: &MockTransport) -> (LocalNode, TestableTransportHandle) {
+        let node = LocalNode::generate();
+        let cell = FullCell::generate(node.clone()).unwrap();
+        let handle = hub.get_transport(node.clone(), ServiceType::Chain);
+        let handle = TestableTransportHandle::new(handle, cell.cell().clone());
+        (node, handle)
+    }
 }
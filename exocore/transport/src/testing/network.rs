@@ -0,0 +1,258 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use exocore_core::{
+    cell::{Node, NodeId},
+    time::{Clock, Instant},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{HandleKey, ServiceSink};
+use crate::{InEvent, InMessage, OutMessage, ServiceType};
+
+/// Minimum extra delay applied to a message that gets reordered, so that
+/// messages sent after it get delivered first even on a network without
+/// latency.
+const REORDERING_MIN_DELAY: Duration = Duration::from_millis(10);
+
+/// Conditions of the network simulated by the `MockTransport`. The default
+/// conditions are a perfect network on which messages are delivered instantly
+/// and reliably.
+#[derive(Clone, Debug, Default)]
+pub struct NetworkConditions {
+    /// Delay before a message gets delivered.
+    pub latency: Duration,
+
+    /// Maximum random delay added to the latency of each message.
+    pub jitter: Duration,
+
+    /// Probability, between 0.0 and 1.0, that a message gets lost.
+    pub loss: f64,
+
+    /// Probability that a message gets delivered twice.
+    pub duplication: f64,
+
+    /// Probability that a message gets held back long enough for messages
+    /// sent after it to be delivered first.
+    pub reordering: f64,
+}
+
+/// Counters of what happened to the messages sent through the simulated
+/// network.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NetworkStats {
+    pub sent: usize,
+    pub delivered: usize,
+    pub lost: usize,
+    pub duplicated: usize,
+    pub reordered: usize,
+    pub partitioned: usize,
+}
+
+/// Network simulated between the handles of a `MockTransport`.
+///
+/// Messages are queued with a delivery time based on the network conditions
+/// and the network's clock, and get delivered once the clock reaches it. With
+/// a mocked clock, tests control when delayed messages get delivered by moving
+/// the clock forward.
+pub(super) struct SimulatedNetwork {
+    clock: Clock,
+    state: Mutex<NetworkState>,
+    delivery_started: AtomicBool,
+}
+
+struct NetworkState {
+    conditions: NetworkConditions,
+    partitions: Vec<(HashSet<NodeId>, HashSet<NodeId>)>,
+    rng: StdRng,
+    queue: BTreeMap<QueueKey, QueuedMessage>,
+    next_sequence: u64,
+    stats: NetworkStats,
+}
+
+/// Messages are ordered by delivery time, then by a random number drawn from
+/// the seeded generator so that messages due at the same time get delivered in
+/// an order that varies with the seed, but that is the same for a given seed.
+/// The sequence number only makes keys unique.
+type QueueKey = (Instant, u64, u64);
+
+struct QueuedMessage {
+    source: NodeId,
+    destination: HandleKey,
+    message: InMessage,
+}
+
+impl SimulatedNetwork {
+    pub(super) fn new(clock: Clock, seed: u64) -> SimulatedNetwork {
+        SimulatedNetwork {
+            clock,
+            state: Mutex::new(NetworkState {
+                conditions: NetworkConditions::default(),
+                partitions: Vec::new(),
+                rng: StdRng::seed_from_u64(seed),
+                queue: BTreeMap::new(),
+                next_sequence: 0,
+                stats: NetworkStats::default(),
+            }),
+            delivery_started: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns `true` if the periodic delivery of delayed messages wasn't
+    /// started yet and needs to be started by the caller.
+    pub(super) fn start_delivery(&self) -> bool {
+        !self.delivery_started.swap(true, Ordering::SeqCst)
+    }
+
+    pub(super) fn set_conditions(&self, conditions: NetworkConditions) {
+        let mut state = self.state.lock().unwrap();
+        state.conditions = conditions;
+    }
+
+    pub(super) fn add_partition(&self, side_a: &[NodeId], side_b: &[NodeId]) {
+        let mut state = self.state.lock().unwrap();
+        state.partitions.push((
+            side_a.iter().cloned().collect(),
+            side_b.iter().cloned().collect(),
+        ));
+    }
+
+    /// Removes all partitions, returning the pairs of nodes that were
+    /// partitioned and that can now reach each other again. Each pair is only
+    /// returned once, even if it was part of multiple partitions.
+    pub(super) fn heal_partitions(&self) -> Vec<(NodeId, NodeId)> {
+        let mut state = self.state.lock().unwrap();
+
+        let mut healed = Vec::<(NodeId, NodeId)>::new();
+        for (side_a, side_b) in state.partitions.drain(..) {
+            for a in &side_a {
+                for b in &side_b {
+                    let already_healed = healed
+                        .iter()
+                        .any(|(x, y)| (x == a && y == b) || (x == b && y == a));
+                    if a != b && !already_healed {
+                        healed.push((a.clone(), b.clone()));
+                    }
+                }
+            }
+        }
+
+        healed
+    }
+
+    pub(super) fn stats(&self) -> NetworkStats {
+        let state = self.state.lock().unwrap();
+        state.stats
+    }
+
+    /// Queues a message sent by the given node according to the current network
+    /// conditions.
+    pub(super) fn send(&self, source: &Node, service_type: ServiceType, msg: &OutMessage) {
+        let destination = msg
+            .destination
+            .as_ref()
+            .expect("Message didn't have a destination node")
+            .id()
+            .clone();
+
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        state.stats.sent += 1;
+
+        if state.sample(state.conditions.loss) {
+            state.stats.lost += 1;
+            return;
+        }
+
+        let copies = if state.sample(state.conditions.duplication) {
+            state.stats.duplicated += 1;
+            2
+        } else {
+            1
+        };
+
+        let now = self.clock.instant();
+        for _ in 0..copies {
+            let mut delay = state.delay();
+            if state.sample(state.conditions.reordering) {
+                state.stats.reordered += 1;
+                let conditions = &state.conditions;
+                delay += (conditions.latency + conditions.jitter).max(REORDERING_MIN_DELAY);
+            }
+
+            let message = msg
+                .to_in_message(source.clone())
+                .expect("Couldn't get InMessage from OutMessage");
+            let order = state.rng.gen();
+            let sequence = state.next_sequence;
+            state.next_sequence += 1;
+            state.queue.insert(
+                (now + delay, order, sequence),
+                QueuedMessage {
+                    source: source.id().clone(),
+                    destination: (destination.clone(), service_type),
+                    message,
+                },
+            );
+        }
+    }
+
+    /// Delivers the queued messages for which the delivery time has been
+    /// reached. Messages between partitioned nodes get dropped, even if they
+    /// were sent before the partition happened.
+    pub(super) fn deliver_due(&self, sinks: &mut HashMap<HandleKey, ServiceSink>) {
+        let now = self.clock.instant();
+        let mut state = self.state.lock().unwrap();
+
+        while let Some(entry) = state.queue.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+
+            let queued = entry.remove();
+            if state.is_partitioned(&queued.source, &queued.destination.0) {
+                state.stats.partitioned += 1;
+                continue;
+            }
+
+            if let Some(sink) = sinks.get_mut(&queued.destination) {
+                state.stats.delivered += 1;
+                let _ = sink.sender.try_send(InEvent::Message(queued.message));
+            } else {
+                warn!(
+                    "Couldn't send message to node {} since it's not in the hub anymore",
+                    queued.destination.0
+                );
+            }
+        }
+    }
+}
+
+impl NetworkState {
+    fn sample(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.rng.gen_bool(probability.min(1.0))
+    }
+
+    fn delay(&mut self) -> Duration {
+        let jitter = self.conditions.jitter.as_micros() as u64;
+        let jitter = if jitter > 0 {
+            Duration::from_micros(self.rng.gen_range(0..=jitter))
+        } else {
+            Duration::ZERO
+        };
+
+        self.conditions.latency + jitter
+    }
+
+    fn is_partitioned(&self, a: &NodeId, b: &NodeId) -> bool {
+        self.partitions.iter().any(|(side_a, side_b)| {
+            (side_a.contains(a) && side_b.contains(b)) || (side_a.contains(b) && side_b.contains(a))
+        })
+    }
+}
@@ -25,6 +25,7 @@ pub enum ServiceType {
     Chain = 3,
     Store = 4,
     Client = 5,
+    AppHost = 6,
 }
 
 impl ServiceType {
@@ -36,6 +37,7 @@ impl ServiceType {
             3 => Some(ServiceType::Chain),
             4 => Some(ServiceType::Store),
             5 => Some(ServiceType::Client),
+            6 => Some(ServiceType::AppHost),
             _ => None,
         }
     }
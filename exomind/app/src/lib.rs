@@ -30,12 +30,23 @@ impl App for ExomindApp {
         info!("Application initialized");
 
         let store = exocore.store.clone();
+        let scheduler = exocore.scheduler.clone();
         spawn(async move {
             create_base_entities(&store)
                 .await
                 .expect("error in check_base_entities");
 
-            spawn(check_snoozed_loop(store.clone()));
+            let res = scheduler.schedule(
+                "check_snoozed",
+                Schedule::Interval(Duration::from_secs(60)),
+                move || {
+                    let store = store.clone();
+                    async move { check_snoozed(&store).await }
+                },
+            );
+            if let Err(err) = res {
+                error!("Error scheduling snoozed entities check: {}", err);
+            }
         });
 
         Ok(())
@@ -77,16 +88,6 @@ async fn create_base_entities(store: &Arc<Store>) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn check_snoozed_loop(store: Arc<Store>) {
-    loop {
-        if let Err(err) = check_snoozed(&store).await {
-            error!("Error checking for snoozed entity: {}", err);
-        }
-
-        sleep(Duration::from_secs(60)).await;
-    }
-}
-
 async fn check_snoozed(store: &Arc<Store>) -> anyhow::Result<()> {
     let snoozed_list = get_snoozed(store).await?;
     debug!("Found {} entities to moved to inbox", snoozed_list.len());
@@ -0,0 +1,100 @@
+//! Tests the application's logic by running its module against an in-memory
+//! store. The module needs to be built to `app.wasm` first (see
+//! `tools/build.sh`), which is why tests are ignored by default. Run them with
+//! `cargo test -- --ignored` once the module has been built.
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use exocore::{
+    protos::{
+        prost::{ProstAnyPackMessageExt, Timestamp},
+        store::{Entity, Trait},
+    },
+    store::{entity::EntityExt, mutation::MutationBuilder, query::QueryBuilder},
+};
+use exocore_apps_host::testing::AppTestHarness;
+use exomind_protos::base::{Collection, CollectionChild, Snoozed};
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires the app's module to be built with tools/build.sh"]
+async fn snoozed_entities_moved_to_inbox() -> anyhow::Result<()> {
+    let app_dir = app_dir_with_module()?;
+    let mut harness = AppTestHarness::from_directory(app_dir).await?;
+
+    let now = harness.clock().unix_elapsed();
+    let hour = Duration::from_secs(3600);
+    harness
+        .seed(
+            MutationBuilder::new()
+                .put_trait("past", snoozed_trait(now - hour))
+                .put_trait("future", snoozed_trait(now + hour)),
+        )
+        .await?;
+
+    // first tick creates base entities and moves entities whose snooze has expired
+    harness.tick().await?;
+    let inbox = get_entity(&mut harness, "inbox").await?.unwrap();
+    assert!(inbox.trait_of_type::<Collection>().is_some());
+
+    let past = get_entity(&mut harness, "past").await?.unwrap();
+    assert!(past.trait_of_type::<Snoozed>().is_none());
+    let child = past.trait_of_type::<CollectionChild>().unwrap();
+    assert_eq!(child.instance.collection.unwrap().entity_id, "inbox");
+
+    let future = get_entity(&mut harness, "future").await?.unwrap();
+    assert!(future.trait_of_type::<Snoozed>().is_some());
+    assert!(future.trait_of_type::<CollectionChild>().is_none());
+
+    // snoozed entities are checked every minute, other entity should be moved once expired
+    harness.clear_requests();
+    harness.advance(hour + Duration::from_secs(60)).await?;
+    assert_eq!(harness.mutations().len(), 1);
+
+    let future = get_entity(&mut harness, "future").await?.unwrap();
+    assert!(future.trait_of_type::<Snoozed>().is_none());
+    assert!(future.trait_of_type::<CollectionChild>().is_some());
+
+    Ok(())
+}
+
+/// Returns the application's directory, failing if its module hasn't been
+/// built.
+fn app_dir_with_module() -> anyhow::Result<PathBuf> {
+    let app_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let module_path = app_dir.join("app.wasm");
+    if !module_path.exists() {
+        anyhow::bail!(
+            "Module {} doesn't exist. Build it with `tools/build.sh` first.",
+            module_path.display()
+        );
+    }
+
+    Ok(app_dir)
+}
+
+fn snoozed_trait(until: Duration) -> Trait {
+    let until_date = Timestamp {
+        seconds: until.as_secs() as i64,
+        nanos: until.subsec_nanos() as i32,
+    };
+
+    Trait {
+        id: "snoozed".to_string(),
+        message: Some(
+            Snoozed {
+                until_date: Some(until_date),
+            }
+            .pack_to_any()
+            .unwrap(),
+        ),
+        ..Default::default()
+    }
+}
+
+async fn get_entity(harness: &mut AppTestHarness, id: &str) -> anyhow::Result<Option<Entity>> {
+    let results = harness.query(QueryBuilder::with_id(id).build()).await?;
+    Ok(results.entities.into_iter().find_map(|res| res.entity))
+}
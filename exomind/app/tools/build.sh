@@ -11,6 +11,10 @@ cargo build --target wasm32-wasip1 --release
 cp $REPO_ROOT/target/wasm32-wasip1/release/exomind_app.wasm $EXOMIND_ROOT/app.wasm
 popd
 
+# the package is signed with the app keypair, expected in `EXO_APP_KEYPAIR`.
+# the signed manifest, which contains the module's multihash, then replaces the
+# unsigned one so that it gets committed with the release.
 pushd $EXOMIND_ROOT
 exo app package
+unzip -o -p exomind.zip app.yaml > app.yaml
 popd
\ No newline at end of file
@@ -0,0 +1,10 @@
+#!/usr/bin/env bash
+set -e
+CUR_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+
+APP_DIR="$CUR_DIR/../"
+EXOMIND_ROOT="$APP_DIR/../"
+REPO_ROOT="$EXOMIND_ROOT/../"
+
+# runs the app in a local development cell, rebuilding and reloading it when its files change
+exo app dev $EXOMIND_ROOT --build "cd $APP_DIR && cargo build --target wasm32-wasip1 --release && cp $REPO_ROOT/target/wasm32-wasip1/release/exomind_app.wasm $EXOMIND_ROOT/app.wasm"
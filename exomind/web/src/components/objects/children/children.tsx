@@ -60,6 +60,7 @@ export class Children extends React.Component<IProps, IState> {
             .withTrait(exomind.base.v1.CollectionChild, traitQuery)
             .count(30)
             .orderByField('weight', false)
+            .thenOrderByUpdatedAt(false)
             .project(
                 new exocore.store.Projection({
                     fieldGroupIds: [1],